@@ -11,7 +11,7 @@ use lumalla_ipc::{
     DrmDeviceInfo, KeyBindingInfo, LayoutOutputInfo, LayoutSpacesInfo, ModsInfo, OutputConfigInfo,
     OutputInfo, WindowManagerProxy, WindowRuleInfo, ZoneInfo,
 };
-use lumalla_shared::{CallbackRef, GlobalArgs, Mods, Output};
+use lumalla_shared::{CallbackRef, GlobalArgs, Mods, Output, Transform, WindowRule};
 use mlua::{
     Error as LuaError, FromLua, Function as LuaFunction, IntoLua, Lua, Result as LuaResult,
     Table as LuaTable, Value as LuaValue,
@@ -48,6 +48,7 @@ impl DbusConfigClient {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn init_dbus_module(
     lua: &Lua,
     client: DbusConfigClient,
@@ -55,6 +56,8 @@ pub(crate) fn init_dbus_module(
     on_startup: Rc<RefCell<Option<CallbackRef>>>,
     on_connector_change: Rc<RefCell<Option<CallbackRef>>>,
     on_drm_devices_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_layout_space_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_workspace_change: Rc<RefCell<Option<CallbackRef>>>,
 ) -> LuaResult<LuaTable> {
     let module = lua.create_table()?;
 
@@ -91,6 +94,28 @@ pub(crate) fn init_dbus_module(
         })?,
     )?;
 
+    let cb_state = callback_state.clone();
+    let on_layout_space_change_cb = on_layout_space_change.clone();
+    module.set(
+        "on_layout_space_change",
+        lua.create_function(move |_, callback: LuaFunction| {
+            let callback = cb_state.register_callback(callback);
+            *on_layout_space_change_cb.borrow_mut() = Some(callback);
+            Ok(())
+        })?,
+    )?;
+
+    let cb_state = callback_state.clone();
+    let on_workspace_change_cb = on_workspace_change.clone();
+    module.set(
+        "on_workspace_change",
+        lua.create_function(move |_, callback: LuaFunction| {
+            let callback = cb_state.register_callback(callback);
+            *on_workspace_change_cb.borrow_mut() = Some(callback);
+            Ok(())
+        })?,
+    )?;
+
     module.set("quit", create_quit_callback(lua, client.clone())?)?;
     module.set("shutdown", create_quit_callback(lua, client.clone())?)?;
 
@@ -117,11 +142,13 @@ pub(crate) fn init_dbus_module(
     init_dbus_drm(lua, &module, client.clone())?;
     init_dbus_spawn(lua, &module, client.clone())?;
     init_dbus_zone(lua, &module, client.clone())?;
+    init_dbus_workspace(lua, &module, client.clone())?;
     init_dbus_window(lua, &module, client)?;
 
     Ok(module)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn register_dbus_module(
     lua: &Lua,
     client: DbusConfigClient,
@@ -129,6 +156,8 @@ pub(crate) fn register_dbus_module(
     on_startup: Rc<RefCell<Option<CallbackRef>>>,
     on_connector_change: Rc<RefCell<Option<CallbackRef>>>,
     on_drm_devices_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_layout_space_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_workspace_change: Rc<RefCell<Option<CallbackRef>>>,
 ) -> anyhow::Result<()> {
     lua.register_module(
         LUA_MODULE_NAME,
@@ -139,6 +168,8 @@ pub(crate) fn register_dbus_module(
             on_startup,
             on_connector_change,
             on_drm_devices_change,
+            on_layout_space_change,
+            on_workspace_change,
         )
         .map_err(|err| anyhow::anyhow!("Unable to create D-Bus config module: {err}"))?,
     )
@@ -239,6 +270,8 @@ fn init_dbus_drm(lua: &Lua, module: &LuaTable, client: DbusConfigClient) -> LuaR
                     name: c.name,
                     enabled: c.enabled,
                     mode_name: c.mode.unwrap_or_default(),
+                    scale: c.scale.unwrap_or_default(),
+                    transform: c.transform.to_wayland(),
                 })
                 .collect();
             dbus_result(configs_client.proxy.set_output_configs(infos))?;
@@ -286,6 +319,10 @@ struct ConfigOutputSetting {
     name: String,
     enabled: bool,
     mode: Option<String>,
+    /// Scale in percent, e.g. `150`.
+    scale: Option<u32>,
+    /// Rotation and flipping, e.g. `"90"` for portrait monitors.
+    transform: Transform,
 }
 
 impl FromLua for ConfigOutputSetting {
@@ -299,6 +336,8 @@ impl FromLua for ConfigOutputSetting {
             name: table.get("name")?,
             enabled: table.get("enabled").unwrap_or(true),
             mode: table.get::<Option<String>>("mode").unwrap_or(None),
+            scale: table.get::<Option<u32>>("scale")?,
+            transform: transform_from_lua(table)?,
         })
     }
 }
@@ -353,6 +392,50 @@ fn init_dbus_zone(lua: &Lua, module: &LuaTable, client: DbusConfigClient) -> Lua
     Ok(())
 }
 
+fn init_dbus_workspace(lua: &Lua, module: &LuaTable, client: DbusConfigClient) -> LuaResult<()> {
+    let switch_client = client.clone();
+    module.set(
+        "switch_workspace",
+        lua.create_function(move |_, name: String| {
+            dbus_result(switch_client.proxy.switch_workspace(&name))?;
+            Ok(())
+        })?,
+    )?;
+
+    let move_window_client = client.clone();
+    module.set(
+        "move_current_window_to_workspace",
+        lua.create_function(move |_, name: String| {
+            dbus_result(
+                move_window_client
+                    .proxy
+                    .move_current_window_to_workspace(&name),
+            )?;
+            Ok(())
+        })?,
+    )?;
+
+    let move_workspace_client = client.clone();
+    module.set(
+        "move_workspace_to_output",
+        lua.create_function(move |_, (name, output): (String, String)| {
+            dbus_result(
+                move_workspace_client
+                    .proxy
+                    .move_workspace_to_output(&name, &output),
+            )?;
+            Ok(())
+        })?,
+    )?;
+
+    let get_client = client;
+    module.set(
+        "get_active_workspaces",
+        lua.create_function(move |_, ()| dbus_result(get_client.proxy.get_active_workspaces()))?,
+    )?;
+    Ok(())
+}
+
 fn init_dbus_window(lua: &Lua, module: &LuaTable, client: DbusConfigClient) -> LuaResult<()> {
     let close_client = client.clone();
     module.set(
@@ -363,20 +446,91 @@ fn init_dbus_window(lua: &Lua, module: &LuaTable, client: DbusConfigClient) -> L
         })?,
     )?;
 
-    let rules_client = client;
+    let kill_timeout_client = client.clone();
+    module.set(
+        "set_kill_timeout",
+        lua.create_function(move |_, timeout_ms: u32| {
+            dbus_result(kill_timeout_client.proxy.set_kill_timeout(timeout_ms))?;
+            Ok(())
+        })?,
+    )?;
+
+    let add_client = client.clone();
     module.set(
         "add_window_rule",
         lua.create_function(move |_, window_rule: ConfigWindowRule| {
-            dbus_result(rules_client.proxy.add_window_rule(WindowRuleInfo {
-                app_id: window_rule.app_id,
-                zone: window_rule.zone,
-            }))?;
+            dbus_result(
+                add_client
+                    .proxy
+                    .add_window_rule(WindowRuleInfo::from(window_rule.0)),
+            )
+        })?,
+    )?;
+
+    let get_client = client.clone();
+    module.set(
+        "get_window_rules",
+        lua.create_function(move |lua, ()| {
+            let rules = dbus_result(get_client.proxy.get_window_rules())?;
+            window_rules_to_lua(lua, rules)
+        })?,
+    )?;
+
+    let remove_client = client.clone();
+    module.set(
+        "remove_window_rule",
+        lua.create_function(move |_, id: u32| {
+            dbus_result(remove_client.proxy.remove_window_rule(id))?;
+            Ok(())
+        })?,
+    )?;
+
+    let focus_mode_client = client.clone();
+    module.set(
+        "set_focus_mode",
+        lua.create_function(move |_, mode: String| {
+            dbus_result(focus_mode_client.proxy.set_focus_mode(&mode))?;
+            Ok(())
+        })?,
+    )?;
+
+    let tab_client = client;
+    module.set(
+        "tab",
+        lua.create_function(move |_, index: u32| {
+            dbus_result(tab_client.proxy.tab(index))?;
             Ok(())
         })?,
     )?;
     Ok(())
 }
 
+/// Lists window rules as tables shaped like `add_window_rule` arguments,
+/// with their `id` added.
+fn window_rules_to_lua(lua: &Lua, rules: Vec<(u32, WindowRuleInfo)>) -> LuaResult<LuaValue> {
+    let lua_rules = lua.create_table()?;
+    for (index, (id, info)) in rules.into_iter().enumerate() {
+        let rule = WindowRule::from(info);
+        let lua_rule = lua.create_table()?;
+        lua_rule.set("id", id)?;
+        lua_rule.set("app_id", rule.app_id)?;
+        lua_rule.set("title", rule.title)?;
+        lua_rule.set("transient", rule.transient)?;
+        lua_rule.set("zone", rule.zone)?;
+        lua_rule.set("output", rule.output)?;
+        lua_rule.set("workspace", rule.workspace)?;
+        lua_rule.set("floating", rule.floating)?;
+        lua_rule.set("fullscreen", rule.fullscreen)?;
+        if let Some((width, height)) = rule.size {
+            lua_rule.set("width", width)?;
+            lua_rule.set("height", height)?;
+        }
+        lua_rule.set("opacity", rule.opacity)?;
+        lua_rules.set(index + 1, lua_rule)?;
+    }
+    lua_rules.into_lua(lua)
+}
+
 pub(crate) fn set_default_keymaps(
     lua: &Lua,
     client: &DbusConfigClient,
@@ -582,18 +736,25 @@ pub(crate) struct ConfigOutput {
     name: String,
     x: i32,
     y: i32,
+    /// Size in the layout, after scaling.
     width: i32,
     height: i32,
+    /// Scale in percent.
+    scale: u32,
+    transform: Transform,
 }
 
 impl From<&Output> for ConfigOutput {
     fn from(value: &Output) -> Self {
+        let (width, height) = value.logical_size();
         Self {
             name: value.name.clone(),
             x: value.location.0,
             y: value.location.1,
-            width: value.size.0,
-            height: value.size.1,
+            width,
+            height,
+            scale: value.scale,
+            transform: value.transform,
         }
     }
 }
@@ -611,6 +772,8 @@ impl FromLua for ConfigOutput {
             y: table.get("y")?,
             width: table.get("width")?,
             height: table.get("height")?,
+            scale: table.get::<Option<u32>>("scale")?.unwrap_or(100),
+            transform: transform_from_lua(table)?,
         })
     }
 }
@@ -623,10 +786,25 @@ impl IntoLua for ConfigOutput {
         lua_output.set("y", self.y)?;
         lua_output.set("width", self.width)?;
         lua_output.set("height", self.height)?;
+        lua_output.set("scale", self.scale)?;
+        lua_output.set("transform", self.transform.name())?;
         lua_output.into_lua(lua)
     }
 }
 
+/// Reads the optional `transform` name of an output table; see
+/// [`Transform::from_name`].
+fn transform_from_lua(table: &LuaTable) -> LuaResult<Transform> {
+    let Some(name) = table.get::<Option<String>>("transform")? else {
+        return Ok(Transform::Normal);
+    };
+    Transform::from_name(&name).ok_or_else(|| LuaError::FromLuaConversionError {
+        from: "string",
+        to: String::from("Transform"),
+        message: Some(format!("Unknown output transform '{name}'")),
+    })
+}
+
 struct ConfigSpawn {
     command: String,
     args: Vec<String>,
@@ -686,10 +864,7 @@ impl From<ConfigZone> for ZoneInfo {
     }
 }
 
-struct ConfigWindowRule {
-    app_id: String,
-    zone: String,
-}
+struct ConfigWindowRule(WindowRule);
 
 impl FromLua for ConfigWindowRule {
     fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
@@ -698,10 +873,20 @@ impl FromLua for ConfigWindowRule {
             to: String::from("ConfigWindowRule"),
             message: Some(String::from("Expected a Lua table for the ConfigWindowRule")),
         })?;
-        Ok(Self {
+        let width: Option<i32> = table.get("width")?;
+        let height: Option<i32> = table.get("height")?;
+        Ok(Self(WindowRule {
             app_id: table.get("app_id")?,
+            title: table.get("title")?,
+            transient: table.get("transient")?,
             zone: table.get("zone")?,
-        })
+            output: table.get("output")?,
+            workspace: table.get("workspace")?,
+            floating: table.get("floating")?,
+            fullscreen: table.get("fullscreen")?,
+            size: width.zip(height),
+            opacity: table.get("opacity")?,
+        }))
     }
 }
 
@@ -28,6 +28,8 @@ pub struct ExternalConfig {
     on_startup: Rc<RefCell<Option<CallbackRef>>>,
     on_connector_change: Rc<RefCell<Option<CallbackRef>>>,
     on_drm_devices_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_layout_space_change: Rc<RefCell<Option<CallbackRef>>>,
+    on_workspace_change: Rc<RefCell<Option<CallbackRef>>>,
     outputs: HashMap<String, Output>,
     config_watcher: ConfigWatcher,
     reload_receiver: mpsc::Receiver<PathBuf>,
@@ -43,6 +45,8 @@ impl ExternalConfig {
         let on_startup = Rc::new(RefCell::new(None));
         let on_connector_change = Rc::new(RefCell::new(None));
         let on_drm_devices_change = Rc::new(RefCell::new(None));
+        let on_layout_space_change = Rc::new(RefCell::new(None));
+        let on_workspace_change = Rc::new(RefCell::new(None));
         let (reload_tx, reload_receiver) = mpsc::channel();
         let config_watcher = ConfigWatcher::new(reload_tx)?;
 
@@ -53,6 +57,8 @@ impl ExternalConfig {
             on_startup.clone(),
             on_connector_change.clone(),
             on_drm_devices_change.clone(),
+            on_layout_space_change.clone(),
+            on_workspace_change.clone(),
         )?;
 
         let mut state = Self {
@@ -62,6 +68,8 @@ impl ExternalConfig {
             on_startup,
             on_connector_change,
             on_drm_devices_change,
+            on_layout_space_change,
+            on_workspace_change,
             outputs: HashMap::new(),
             config_watcher,
             reload_receiver,
@@ -90,6 +98,8 @@ impl ExternalConfig {
         let mut output_changed = proxy.receive_output_changed()?;
         let mut drm_devices_changed = proxy.receive_drm_devices_changed()?;
         let mut binding_activated = proxy.receive_binding_activated()?;
+        let mut layout_space_activated = proxy.receive_layout_space_activated()?;
+        let mut workspace_activated = proxy.receive_workspace_activated()?;
 
         info!("External config connected to compositor");
 
@@ -123,6 +133,16 @@ impl ExternalConfig {
                 self.handle_binding_activated(&args.binding_id)?;
             }
 
+            if let Some(signal) = layout_space_activated.next() {
+                let args = signal.args()?;
+                self.handle_layout_space_activated(args.name)?;
+            }
+
+            if let Some(signal) = workspace_activated.next() {
+                let args = signal.args()?;
+                self.handle_workspace_activated(args.output, args.workspace)?;
+            }
+
             std::thread::sleep(Duration::from_millis(50));
         }
 
@@ -168,6 +188,26 @@ impl ExternalConfig {
         Ok(())
     }
 
+    fn handle_layout_space_activated(&mut self, name: &str) -> anyhow::Result<()> {
+        info!("Layout space {name} activated");
+        if let Some(on_layout_space_change) = *self.on_layout_space_change.borrow() {
+            self.callback_state
+                .run_callback::<String, ()>(on_layout_space_change, name.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn handle_workspace_activated(&mut self, output: &str, workspace: &str) -> anyhow::Result<()> {
+        info!("Workspace {workspace} shown on {output}");
+        if let Some(on_workspace_change) = *self.on_workspace_change.borrow() {
+            self.callback_state.run_callback::<(String, String), ()>(
+                on_workspace_change,
+                (output.to_string(), workspace.to_string()),
+            )?;
+        }
+        Ok(())
+    }
+
     fn on_connector_change(&mut self) -> anyhow::Result<()> {
         if let Some(on_connector_change) = *self.on_connector_change.borrow() {
             let outputs: Vec<ConfigOutput> = self
@@ -4,6 +4,7 @@ use std::{
     collections::HashMap,
     process::Command,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use log::{error, info};
@@ -13,7 +14,8 @@ use lumalla_ipc::{
         DrmDeviceInfo, LayoutSpacesInfo, OutputConfigInfo, OutputInfo, WindowRuleInfo, ZoneInfo,
     },
 };
-use lumalla_shared::{Comms, MainMessage, Mods, Output};
+use lumalla_shared::{Comms, FocusMode, MainMessage, Mods};
+use regex::Regex;
 use std::path::PathBuf;
 use zbus::blocking::Connection;
 
@@ -36,13 +38,34 @@ fn key_name_to_keycode(key_name: &str) -> Option<u32> {
     }
 }
 
+/// Window rules by id, in the order they apply.
+#[derive(Default)]
+pub(crate) struct WindowRules {
+    last_id: u32,
+    rules: Vec<(u32, WindowRuleInfo)>,
+}
+
+impl WindowRules {
+    /// Sends the rules to the compositor.
+    fn publish(&self, comms: &Comms) {
+        comms.main(MainMessage::SetWindowRules(
+            self.rules
+                .iter()
+                .map(|(_, rule)| rule.clone().into())
+                .collect(),
+        ));
+    }
+}
+
 pub(crate) struct ServiceState {
     pub comms: Comms,
     pub outputs: Arc<Mutex<Vec<OutputInfo>>>,
-    pub output_lookup: Arc<Mutex<HashMap<String, Output>>>,
     pub drm_devices: Arc<Mutex<Vec<DrmDeviceInfo>>>,
     pub extra_env: Arc<Mutex<HashMap<String, String>>>,
     pub keymaps: Arc<Mutex<Vec<KeyBindingInfo>>>,
+    pub window_rules: Arc<Mutex<WindowRules>>,
+    /// Workspace shown on every output, by output name.
+    pub active_workspaces: Arc<Mutex<HashMap<String, String>>>,
 }
 
 pub(crate) struct CompositorHandler {
@@ -84,62 +107,112 @@ impl WindowManagerHandler for CompositorHandler {
     }
 
     fn set_zones(&mut self, zones: Vec<ZoneInfo>) -> zbus::fdo::Result<()> {
-        let _ = zones;
-        // self.state.comms.display(DisplayMessage::SetZones(
-        //     zones.into_iter().map(Into::into).collect(),
-        // ));
+        info!("Set zones over D-Bus: {} zones", zones.len());
+        self.state.comms.main(MainMessage::SetZones(
+            zones.into_iter().map(Into::into).collect(),
+        ));
         Ok(())
     }
 
     fn set_layout(&mut self, spaces: LayoutSpacesInfo) -> zbus::fdo::Result<()> {
-        let _outputs = self.state.output_lookup.lock().unwrap();
-        let _ = spaces;
-        // self.state.comms.display(DisplayMessage::SetLayout {
-        //     spaces: spaces
-        //         .into_iter()
-        //         .map(|(name, layout_outputs)| {
-        //             (
-        //                 name,
-        //                 layout_outputs
-        //                     .into_iter()
-        //                     .filter_map(|layout_output| {
-        //                         let Some(output) = outputs.get(&layout_output.name) else {
-        //                             warn!("Output not found: {}", layout_output.name);
-        //                             return None;
-        //                         };
-        //                         let mut output = output.clone();
-        //                         output.set_location(layout_output.x, layout_output.y);
-        //                         Some(output)
-        //                     })
-        //                     .collect(),
-        //             )
-        //         })
-        //         .collect(),
-        // });
-        Ok(())
-    }
-
-    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<()> {
-        let _ = rule;
-        // self.state
-        //     .comms
-        //     .display(DisplayMessage::AddWindowRule(rule.into()));
+        info!("Set layout over D-Bus: {} spaces", spaces.len());
+        self.state.comms.main(MainMessage::SetLayout(
+            spaces
+                .into_iter()
+                .map(|(name, outputs)| (name, outputs.into_iter().map(Into::into).collect()))
+                .collect(),
+        ));
+        Ok(())
+    }
+
+    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<u32> {
+        if let Err(err) = Regex::new(&rule.title) {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Invalid title pattern: {err}"
+            )));
+        }
+        let mut rules = self.state.window_rules.lock().unwrap();
+        rules.last_id += 1;
+        let id = rules.last_id;
+        rules.rules.push((id, rule));
+        info!("Added window rule {id} over D-Bus");
+        rules.publish(&self.state.comms);
+        Ok(id)
+    }
+
+    fn get_window_rules(&self) -> zbus::fdo::Result<Vec<(u32, WindowRuleInfo)>> {
+        Ok(self.state.window_rules.lock().unwrap().rules.clone())
+    }
+
+    fn remove_window_rule(&mut self, id: u32) -> zbus::fdo::Result<()> {
+        let mut rules = self.state.window_rules.lock().unwrap();
+        let Some(index) = rules.rules.iter().position(|(rule_id, _)| *rule_id == id) else {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "Unknown window rule {id}"
+            )));
+        };
+        rules.rules.remove(index);
+        info!("Removed window rule {id} over D-Bus");
+        rules.publish(&self.state.comms);
         Ok(())
     }
 
     fn close_current_window(&mut self) -> zbus::fdo::Result<()> {
-        // self.state.comms.display(DisplayMessage::CloseCurrentWindow);
+        self.state.comms.main(MainMessage::CloseCurrentWindow);
+        Ok(())
+    }
+
+    fn set_kill_timeout(&mut self, timeout_ms: u32) -> zbus::fdo::Result<()> {
+        let timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms.into()));
+        self.state.comms.main(MainMessage::SetKillTimeout(timeout));
         Ok(())
     }
 
     fn move_current_window_to_zone(&mut self, zone: &str) -> zbus::fdo::Result<()> {
-        let _ = zone;
-        // self.state
-        //     .comms
-        //     .display(DisplayMessage::MoveCurrentWindowToZone(zone.to_string()));
+        self.state
+            .comms
+            .main(MainMessage::MoveCurrentWindowToZone(zone.to_string()));
+        Ok(())
+    }
+
+    fn set_focus_mode(&mut self, mode: &str) -> zbus::fdo::Result<()> {
+        let focus_mode = FocusMode::from_name(mode)
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("Unknown focus mode '{mode}'")))?;
+        self.state.comms.main(MainMessage::SetFocusMode(focus_mode));
+        Ok(())
+    }
+
+    fn tab(&mut self, index: u32) -> zbus::fdo::Result<()> {
+        self.state.comms.main(MainMessage::Tab(index as usize));
+        Ok(())
+    }
+
+    fn switch_workspace(&mut self, name: &str) -> zbus::fdo::Result<()> {
+        self.state
+            .comms
+            .main(MainMessage::SwitchWorkspace(name.to_string()));
+        Ok(())
+    }
+
+    fn move_current_window_to_workspace(&mut self, name: &str) -> zbus::fdo::Result<()> {
+        self.state
+            .comms
+            .main(MainMessage::MoveCurrentWindowToWorkspace(name.to_string()));
+        Ok(())
+    }
+
+    fn move_workspace_to_output(&mut self, workspace: &str, output: &str) -> zbus::fdo::Result<()> {
+        self.state.comms.main(MainMessage::MoveWorkspaceToOutput {
+            workspace: workspace.to_string(),
+            output: output.to_string(),
+        });
         Ok(())
     }
 
+    fn get_active_workspaces(&self) -> zbus::fdo::Result<HashMap<String, String>> {
+        Ok(self.state.active_workspaces.lock().unwrap().clone())
+    }
+
     fn spawn(&mut self, command: &str, args: Vec<String>) -> zbus::fdo::Result<()> {
         spawn_process(command, &args, &self.state.extra_env);
         Ok(())
@@ -151,12 +224,11 @@ impl WindowManagerHandler for CompositorHandler {
         command: &str,
         args: Vec<String>,
     ) -> zbus::fdo::Result<()> {
-        let _ = (app_id, command, args);
-        // self.state.comms.display(DisplayMessage::FocusOrSpawn {
-        //     app_id: app_id.to_string(),
-        //     command: command.to_string(),
-        //     args,
-        // });
+        self.state.comms.main(MainMessage::FocusOrSpawn {
+            app_id: app_id.to_string(),
+            command: command.to_string(),
+            args,
+        });
         Ok(())
     }
 
@@ -205,7 +277,11 @@ impl WindowManagerHandler for CompositorHandler {
     }
 }
 
-fn spawn_process(command: &str, args: &[String], extra_env: &Arc<Mutex<HashMap<String, String>>>) {
+pub(crate) fn spawn_process(
+    command: &str,
+    args: &[String],
+    extra_env: &Arc<Mutex<HashMap<String, String>>>,
+) {
     info!("Starting program: {command} {args:?}");
     if let Err(e) = Command::new(command)
         .args(args)
@@ -224,3 +300,39 @@ where
         .emit_signal(None::<()>, OBJECT_PATH, INTERFACE_NAME, member, body)
         .map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumalla_shared::{DbusMessage, WindowRule, message_loop_with_channel};
+
+    #[test]
+    fn window_rules_with_invalid_title_patterns_are_rejected() {
+        let (_main_poll, _main_rx, to_main) = message_loop_with_channel::<MainMessage>().unwrap();
+        let (_dbus_poll, _dbus_rx, to_dbus) = message_loop_with_channel::<DbusMessage>().unwrap();
+        let mut handler = CompositorHandler {
+            state: Arc::new(ServiceState {
+                comms: Comms::new(to_main, to_dbus),
+                outputs: Arc::default(),
+                drm_devices: Arc::default(),
+                extra_env: Arc::default(),
+                keymaps: Arc::default(),
+                window_rules: Arc::default(),
+                active_workspaces: Arc::default(),
+            }),
+        };
+        let rule = |title: &str| WindowRuleInfo {
+            title: title.to_string(),
+            ..WindowRule::default().into()
+        };
+
+        assert!(matches!(
+            handler.add_window_rule(rule("(unclosed")),
+            Err(zbus::fdo::Error::InvalidArgs(_))
+        ));
+        assert!(handler.get_window_rules().unwrap().is_empty());
+
+        assert_eq!(handler.add_window_rule(rule("^Firefox")).unwrap(), 1);
+        assert_eq!(handler.get_window_rules().unwrap().len(), 1);
+    }
+}
@@ -12,7 +12,7 @@ use std::{
 };
 
 use anyhow::Context;
-use iface::{CompositorHandler, ServiceState, emit_signal};
+use iface::{CompositorHandler, ServiceState, WindowRules, emit_signal, spawn_process};
 use log::{error, info};
 use lumalla_ipc::{
     BUS_NAME, OBJECT_PATH, WindowManager, signals,
@@ -26,23 +26,26 @@ use zbus::{Error as ZbusError, blocking::connection};
 pub struct DbusService {
     connection: zbus::blocking::Connection,
     outputs: Arc<Mutex<Vec<OutputInfo>>>,
-    output_lookup: Arc<Mutex<HashMap<String, Output>>>,
     drm_devices: Arc<Mutex<Vec<DrmDeviceInfo>>>,
+    extra_env: Arc<Mutex<HashMap<String, String>>>,
+    active_workspaces: Arc<Mutex<HashMap<String, String>>>,
 }
 
 impl DbusService {
     /// Connect to the session bus and acquire `org.lumalla.wm`.
     pub fn register(comms: Comms) -> anyhow::Result<Self> {
         let outputs = Arc::new(Mutex::new(Vec::new()));
-        let output_lookup = Arc::new(Mutex::new(HashMap::new()));
         let drm_devices = Arc::new(Mutex::new(Vec::new()));
+        let extra_env = Arc::new(Mutex::new(HashMap::new()));
+        let active_workspaces = Arc::new(Mutex::new(HashMap::new()));
         let state = Arc::new(ServiceState {
             comms: comms.clone(),
             outputs: Arc::clone(&outputs),
-            output_lookup: Arc::clone(&output_lookup),
             drm_devices: Arc::clone(&drm_devices),
-            extra_env: Arc::new(Mutex::new(HashMap::new())),
+            extra_env: Arc::clone(&extra_env),
             keymaps: Arc::new(Mutex::new(Vec::new())),
+            window_rules: Arc::new(Mutex::new(WindowRules::default())),
+            active_workspaces: Arc::clone(&active_workspaces),
         });
         let connection = connection::Builder::session()
             .context("Failed to connect to session bus")?
@@ -70,8 +73,9 @@ impl DbusService {
         Ok(Self {
             connection,
             outputs,
-            output_lookup,
             drm_devices,
+            extra_env,
+            active_workspaces,
         })
     }
 
@@ -87,8 +91,9 @@ struct DbusState {
     shutting_down: bool,
     connection: zbus::blocking::Connection,
     outputs: Arc<Mutex<Vec<OutputInfo>>>,
-    output_lookup: Arc<Mutex<HashMap<String, Output>>>,
     drm_devices: Arc<Mutex<Vec<DrmDeviceInfo>>>,
+    extra_env: Arc<Mutex<HashMap<String, String>>>,
+    active_workspaces: Arc<Mutex<HashMap<String, String>>>,
 }
 
 impl DbusState {
@@ -99,8 +104,9 @@ impl DbusState {
             shutting_down: false,
             connection: service.connection,
             outputs: service.outputs,
-            output_lookup: service.output_lookup,
             drm_devices: service.drm_devices,
+            extra_env: service.extra_env,
+            active_workspaces: service.active_workspaces,
         }
     }
 
@@ -138,7 +144,7 @@ impl DbusState {
                 self.shutting_down = true;
             }
             DbusMessage::SetOutputs(outputs) => {
-                self.update_outputs(outputs);
+                self.update_outputs(&outputs);
             }
             DbusMessage::SetDrmDevices(devices) => {
                 self.update_drm_devices(devices);
@@ -147,7 +153,7 @@ impl DbusState {
                 emit_signal(&self.connection, signals::READY, &())?;
             }
             DbusMessage::EmitOutputChanged(outputs) => {
-                let infos = self.update_outputs(outputs);
+                let infos = self.update_outputs(&outputs);
                 emit_signal(&self.connection, signals::OUTPUT_CHANGED, &(&infos,))?;
             }
             DbusMessage::EmitDrmDevicesChanged(devices) => {
@@ -161,22 +167,48 @@ impl DbusState {
                     &(&binding_id,),
                 )?;
             }
+            DbusMessage::EmitLayoutSpaceActivated(name) => {
+                emit_signal(&self.connection, signals::LAYOUT_SPACE_ACTIVATED, &(&name,))?;
+            }
+            DbusMessage::SetActiveWorkspaces(workspaces) => {
+                let changed = self.update_active_workspaces(workspaces);
+                for (output, workspace) in changed {
+                    emit_signal(
+                        &self.connection,
+                        signals::WORKSPACE_ACTIVATED,
+                        &(&output, &workspace),
+                    )?;
+                }
+            }
+            DbusMessage::Spawn { command, args } => {
+                spawn_process(&command, &args, &self.extra_env);
+            }
         }
 
         Ok(())
     }
 
-    fn update_outputs(&self, outputs: Vec<Output>) -> Vec<OutputInfo> {
+    fn update_outputs(&self, outputs: &[Output]) -> Vec<OutputInfo> {
         let infos: Vec<OutputInfo> = outputs.iter().map(OutputInfo::from).collect();
         *self.outputs.lock().unwrap() = infos.clone();
-        let mut lookup = self.output_lookup.lock().unwrap();
-        lookup.clear();
-        for output in outputs {
-            lookup.insert(output.name.clone(), output);
-        }
         infos
     }
 
+    /// Stores the workspace every output shows and returns the outputs
+    /// that show another workspace than before.
+    fn update_active_workspaces(&self, workspaces: Vec<(String, String)>) -> Vec<(String, String)> {
+        let workspaces: HashMap<_, _> = workspaces.into_iter().collect();
+        let mut active = self.active_workspaces.lock().unwrap();
+        let mut changed: Vec<_> = workspaces
+            .iter()
+            .filter(|(output, workspace)| active.get(*output) != Some(*workspace))
+            .map(|(output, workspace)| (output.clone(), workspace.clone()))
+            .collect();
+        changed.sort();
+        *active = workspaces;
+        changed
+    }
+
     fn update_drm_devices(&self, devices: Vec<DrmDeviceState>) -> Vec<DrmDeviceInfo> {
         let infos: Vec<DrmDeviceInfo> = devices.iter().map(DrmDeviceInfo::from).collect();
         *self.drm_devices.lock().unwrap() = infos.clone();
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+/// How long an app started by focus-or-spawn may take to show a window
+/// before pressing the binding again starts another instance.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mapped toplevels by app_id, and apps started but not shown yet.
+#[derive(Debug, Default)]
+pub struct AppRegistry {
+    /// Mapped toplevels of every app_id, in the order they were mapped.
+    windows: HashMap<String, Vec<(ClientId, ObjectId)>>,
+    app_ids: HashMap<(ClientId, ObjectId), String>,
+    /// When each app without a window yet was started.
+    launching: HashMap<String, Instant>,
+}
+
+impl AppRegistry {
+    /// Mapped toplevels with the app_id `app_id`.
+    pub fn windows(&self, app_id: &str) -> &[(ClientId, ObjectId)] {
+        self.windows.get(app_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Records a shown toplevel, which also ends the launch of its app.
+    pub fn window_mapped(&mut self, client_id: ClientId, surface_id: ObjectId, app_id: &str) {
+        self.window_unmapped(client_id, surface_id);
+        self.launching.remove(app_id);
+        self.windows
+            .entry(app_id.to_string())
+            .or_default()
+            .push((client_id, surface_id));
+        self.app_ids
+            .insert((client_id, surface_id), app_id.to_string());
+    }
+
+    /// Moves a mapped toplevel to its new app_id; unmapped ones are ignored.
+    pub fn set_app_id(&mut self, client_id: ClientId, surface_id: ObjectId, app_id: &str) {
+        if self
+            .app_ids
+            .get(&(client_id, surface_id))
+            .is_some_and(|current| current != app_id)
+        {
+            self.window_mapped(client_id, surface_id, app_id);
+        }
+    }
+
+    pub fn window_unmapped(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        let key = (client_id, surface_id);
+        let Some(app_id) = self.app_ids.remove(&key) else {
+            return;
+        };
+        if let Some(windows) = self.windows.get_mut(&app_id) {
+            windows.retain(|window| *window != key);
+            if windows.is_empty() {
+                self.windows.remove(&app_id);
+            }
+        }
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        let keys: Vec<_> = self
+            .app_ids
+            .keys()
+            .filter(|(owner, _)| *owner == client_id)
+            .copied()
+            .collect();
+        for (client_id, surface_id) in keys {
+            self.window_unmapped(client_id, surface_id);
+        }
+    }
+
+    /// Records that `app_id` is being started at `now`.
+    ///
+    /// Returns `false` if it was started within the launch timeout and has
+    /// not shown a window yet, so it should not be started again.
+    pub fn launch(&mut self, app_id: &str, now: Instant) -> bool {
+        if self
+            .launching
+            .get(app_id)
+            .is_some_and(|started| now.duration_since(*started) < LAUNCH_TIMEOUT)
+        {
+            return false;
+        }
+        self.launching.insert(app_id.to_string(), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ids::window;
+
+    #[test]
+    fn tracks_windows_by_app_id_and_pending_launches() {
+        let mut apps = AppRegistry::default();
+        let start = Instant::now();
+        assert!(apps.launch("firefox", start));
+        assert!(!apps.launch("firefox", start + Duration::from_secs(1)));
+
+        let (client, surface) = window(1, 2);
+        apps.window_mapped(client, surface, "firefox");
+        assert_eq!(apps.windows("firefox"), [window(1, 2)]);
+        assert!(apps.launch("firefox", start + Duration::from_secs(2)));
+        assert!(apps.launch("firefox", start + LAUNCH_TIMEOUT * 2));
+
+        apps.set_app_id(client, surface, "org.mozilla.firefox");
+        assert!(apps.windows("firefox").is_empty());
+        let (other, surface) = window(2, 2);
+        apps.set_app_id(other, surface, "firefox");
+        assert!(apps.windows("firefox").is_empty());
+
+        apps.delete_client(client);
+        assert!(apps.windows("org.mozilla.firefox").is_empty());
+    }
+
+    #[test]
+    fn launches_without_a_window_expire_after_the_timeout() {
+        let mut apps = AppRegistry::default();
+        let start = Instant::now();
+        assert!(apps.launch("foot", start));
+        assert!(!apps.launch("foot", start + LAUNCH_TIMEOUT - Duration::from_millis(1)));
+        assert!(apps.launch("kitty", start));
+
+        let retry = start + LAUNCH_TIMEOUT;
+        assert!(apps.launch("foot", retry));
+        assert!(!apps.launch("foot", retry + Duration::from_secs(1)));
+        assert!(apps.windows("foot").is_empty());
+    }
+}
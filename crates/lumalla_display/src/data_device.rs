@@ -0,0 +1,981 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    os::fd::{AsRawFd, OwnedFd},
+};
+
+use lumalla_wayland_protocol::{
+    ClientId, Ctx, ObjectId,
+    protocols::wayland::{
+        WL_DATA_DEVICE_MANAGER_DND_ACTION_ASK, WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY,
+        WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE, WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE,
+    },
+    registry::InterfaceIndex,
+};
+
+use crate::{GlobalId, seat::Serial};
+
+type ResourceKey = (ClientId, ObjectId);
+
+const ALL_DND_ACTIONS: u32 = WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY
+    | WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE
+    | WL_DATA_DEVICE_MANAGER_DND_ACTION_ASK;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDeviceError {
+    UnknownSource,
+    UnknownDevice,
+    InvalidActionMask,
+    InvalidAction,
+    InvalidSource,
+    UsedSource,
+    InvalidFinish,
+    InvalidOffer,
+}
+
+/// Surface under the pointer while a drag is in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DragTarget {
+    pub client_id: ClientId,
+    pub surface_id: ObjectId,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Clipboard selections and drag-and-drop sessions.
+///
+/// Requests often produce events for other clients; those are queued and
+/// delivered through [`DataDeviceManager::deliver`] with the receiving
+/// client's context.
+#[derive(Default)]
+pub struct DataDeviceManager {
+    sources: HashMap<ResourceKey, DataSource>,
+    devices: Vec<DataDevice>,
+    offers: HashMap<ResourceKey, DataOffer>,
+    /// Current selection source per seat global.
+    selections: HashMap<GlobalId, ResourceKey>,
+    drag: Option<Drag>,
+    events: VecDeque<(ClientId, DataDeviceEvent)>,
+    serial: Option<Serial>,
+}
+
+struct DataSource {
+    version: u32,
+    mime_types: Vec<String>,
+    actions: Option<u32>,
+    used: bool,
+}
+
+struct DataDevice {
+    client_id: ClientId,
+    id: ObjectId,
+    version: u32,
+    seat: GlobalId,
+}
+
+struct DataOffer {
+    source: ResourceKey,
+    version: u32,
+    drag: Option<DragOffer>,
+}
+
+#[derive(Default)]
+struct DragOffer {
+    accepted: Option<String>,
+    actions: u32,
+    preferred_action: u32,
+    action: u32,
+    dropped: bool,
+}
+
+struct Drag {
+    seat: GlobalId,
+    source: Option<ResourceKey>,
+    origin: ResourceKey,
+    icon: Option<ObjectId>,
+    focus: Option<DragFocus>,
+}
+
+#[derive(Clone, Copy)]
+struct DragFocus {
+    client_id: ClientId,
+    surface_id: ObjectId,
+    offer: Option<ObjectId>,
+}
+
+pub enum DataDeviceEvent {
+    /// Announce the seat's current selection to one or all devices of a client.
+    Selection {
+        device: Option<ObjectId>,
+    },
+    Send {
+        source: ObjectId,
+        mime_type: String,
+        fd: OwnedFd,
+    },
+    Cancelled {
+        source: ObjectId,
+    },
+    Target {
+        source: ObjectId,
+        mime_type: Option<String>,
+    },
+    SourceAction {
+        source: ObjectId,
+        action: u32,
+    },
+    DropPerformed {
+        source: ObjectId,
+    },
+    Finished {
+        source: ObjectId,
+    },
+    OfferAction {
+        offer: ObjectId,
+        action: u32,
+    },
+    Enter {
+        target: DragTarget,
+    },
+    Motion {
+        time_msec: u32,
+        x: f32,
+        y: f32,
+    },
+    Leave,
+    Drop,
+}
+
+impl DataDeviceManager {
+    pub fn create_source(&mut self, client_id: ClientId, id: ObjectId, version: u32) {
+        self.sources.insert(
+            (client_id, id),
+            DataSource {
+                version,
+                mime_types: Vec::new(),
+                actions: None,
+                used: false,
+            },
+        );
+    }
+
+    pub fn offer_mime_type(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        mime_type: String,
+    ) -> Result<(), DataDeviceError> {
+        let source = self
+            .sources
+            .get_mut(&(client_id, id))
+            .ok_or(DataDeviceError::UnknownSource)?;
+        if !source.mime_types.contains(&mime_type) {
+            source.mime_types.push(mime_type);
+        }
+        Ok(())
+    }
+
+    pub fn set_source_actions(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        actions: u32,
+    ) -> Result<(), DataDeviceError> {
+        let source = self
+            .sources
+            .get_mut(&(client_id, id))
+            .ok_or(DataDeviceError::UnknownSource)?;
+        if actions & !ALL_DND_ACTIONS != 0 {
+            return Err(DataDeviceError::InvalidActionMask);
+        }
+        if source.used {
+            return Err(DataDeviceError::InvalidSource);
+        }
+        source.actions = Some(actions);
+        Ok(())
+    }
+
+    /// Destroys a source, clearing the selection or cancelling the drag it backs.
+    pub fn destroy_source(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        focused_clients: &[ClientId],
+    ) {
+        let key = (client_id, id);
+        if self.sources.remove(&key).is_none() {
+            return;
+        }
+        let seats: Vec<GlobalId> = self
+            .selections
+            .iter()
+            .filter(|(_, source)| **source == key)
+            .map(|(seat, _)| *seat)
+            .collect();
+        for seat in seats {
+            self.selections.remove(&seat);
+            self.announce_selection(focused_clients);
+        }
+        if self
+            .drag
+            .as_ref()
+            .is_some_and(|drag| drag.source == Some(key))
+        {
+            self.leave_drag_focus();
+            self.drag = None;
+        }
+    }
+
+    pub fn create_device(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        version: u32,
+        seat: GlobalId,
+        focused: bool,
+    ) {
+        self.devices.push(DataDevice {
+            client_id,
+            id,
+            version,
+            seat,
+        });
+        if focused {
+            self.events
+                .push_back((client_id, DataDeviceEvent::Selection { device: Some(id) }));
+        }
+    }
+
+    pub fn destroy_device(&mut self, client_id: ClientId, id: ObjectId) {
+        self.devices
+            .retain(|device| !(device.client_id == client_id && device.id == id));
+    }
+
+    pub fn set_selection(
+        &mut self,
+        client_id: ClientId,
+        device_id: ObjectId,
+        source_id: Option<ObjectId>,
+        focused_clients: &[ClientId],
+    ) -> Result<(), DataDeviceError> {
+        let seat = self.device(client_id, device_id)?.seat;
+        if let Some(source_id) = source_id {
+            let source = self
+                .sources
+                .get_mut(&(client_id, source_id))
+                .ok_or(DataDeviceError::UnknownSource)?;
+            if source.used {
+                return Err(DataDeviceError::UsedSource);
+            }
+            source.used = true;
+        }
+        let new_source = source_id.map(|id| (client_id, id));
+        let old_source = match new_source {
+            Some(source) => self.selections.insert(seat, source),
+            None => self.selections.remove(&seat),
+        };
+        if let Some((owner, source)) = old_source
+            && old_source != new_source
+        {
+            self.events
+                .push_back((owner, DataDeviceEvent::Cancelled { source }));
+        }
+        self.announce_selection(focused_clients);
+        Ok(())
+    }
+
+    /// Queues the current selection for a client whose keyboard just gained focus.
+    pub fn focus_changed(&mut self, client_id: ClientId) {
+        self.events
+            .push_back((client_id, DataDeviceEvent::Selection { device: None }));
+    }
+
+    pub fn start_drag(
+        &mut self,
+        client_id: ClientId,
+        device_id: ObjectId,
+        source_id: Option<ObjectId>,
+        origin: ObjectId,
+        icon: Option<ObjectId>,
+    ) -> Result<(), DataDeviceError> {
+        let seat = self.device(client_id, device_id)?.seat;
+        if let Some(source_id) = source_id {
+            let source = self
+                .sources
+                .get_mut(&(client_id, source_id))
+                .ok_or(DataDeviceError::UnknownSource)?;
+            if source.used {
+                return Err(DataDeviceError::UsedSource);
+            }
+            source.used = true;
+        }
+        if let Some(drag) = self.drag.take() {
+            self.leave_drag_focus_of(&drag);
+            if let Some((owner, source)) = drag.source {
+                self.events
+                    .push_back((owner, DataDeviceEvent::Cancelled { source }));
+            }
+        }
+        self.drag = Some(Drag {
+            seat,
+            source: source_id.map(|id| (client_id, id)),
+            origin: (client_id, origin),
+            icon,
+            focus: None,
+        });
+        Ok(())
+    }
+
+    /// Icon surface of the drag in progress, if any.
+    pub fn drag_icon(&self) -> Option<ResourceKey> {
+        let drag = self.drag.as_ref()?;
+        Some((drag.origin.0, drag.icon?))
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Moves the drag to `target`, sending leave/enter on focus changes and
+    /// motion otherwise.
+    ///
+    /// Without `time_msec`, only focus changes are sent.
+    pub fn drag_motion(&mut self, time_msec: Option<u32>, target: Option<DragTarget>) {
+        let Some(drag) = self.drag.as_ref() else {
+            return;
+        };
+        // Drags without a source stay within the originating client.
+        let target =
+            target.filter(|target| drag.source.is_some() || target.client_id == drag.origin.0);
+        let current = drag.focus.map(|focus| (focus.client_id, focus.surface_id));
+        match target {
+            Some(target) if current == Some((target.client_id, target.surface_id)) => {
+                if let Some(time_msec) = time_msec {
+                    self.events.push_back((
+                        target.client_id,
+                        DataDeviceEvent::Motion {
+                            time_msec,
+                            x: target.x,
+                            y: target.y,
+                        },
+                    ));
+                }
+            }
+            _ => {
+                self.leave_drag_focus();
+                if let Some(target) = target {
+                    if let Some(drag) = self.drag.as_mut() {
+                        drag.focus = Some(DragFocus {
+                            client_id: target.client_id,
+                            surface_id: target.surface_id,
+                            offer: None,
+                        });
+                    }
+                    self.events
+                        .push_back((target.client_id, DataDeviceEvent::Enter { target }));
+                }
+            }
+        }
+    }
+
+    /// Ends the drag, dropping onto the focused surface if it accepted the data.
+    pub fn drop_drag(&mut self) {
+        let Some(drag) = self.drag.take() else {
+            return;
+        };
+        let focus_offer = drag
+            .focus
+            .and_then(|focus| Some((focus.client_id, focus.offer?)));
+        let accepted = focus_offer
+            .and_then(|key| self.offers.get(&key))
+            .and_then(|offer| offer.drag.as_ref())
+            .is_some_and(|drag_offer| {
+                drag_offer.accepted.is_some()
+                    && drag_offer.action != WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE
+            });
+        match (drag.focus, focus_offer) {
+            (Some(focus), Some(key)) if accepted => {
+                if let Some(drag_offer) = self
+                    .offers
+                    .get_mut(&key)
+                    .and_then(|offer| offer.drag.as_mut())
+                {
+                    drag_offer.dropped = true;
+                }
+                self.events
+                    .push_back((focus.client_id, DataDeviceEvent::Drop));
+                if let Some((owner, source)) = drag.source {
+                    self.events
+                        .push_back((owner, DataDeviceEvent::DropPerformed { source }));
+                }
+            }
+            _ => {
+                self.leave_drag_focus_of(&drag);
+                if let Some((owner, source)) = drag.source {
+                    self.events
+                        .push_back((owner, DataDeviceEvent::Cancelled { source }));
+                }
+            }
+        }
+    }
+
+    pub fn accept(
+        &mut self,
+        client_id: ClientId,
+        offer_id: ObjectId,
+        mime_type: Option<String>,
+    ) -> Result<(), DataDeviceError> {
+        let offer = self
+            .offers
+            .get_mut(&(client_id, offer_id))
+            .ok_or(DataDeviceError::InvalidOffer)?;
+        // Selection offers ignore accept.
+        let Some(drag_offer) = offer.drag.as_mut() else {
+            return Ok(());
+        };
+        if drag_offer.dropped {
+            return Err(DataDeviceError::InvalidOffer);
+        }
+        drag_offer.accepted.clone_from(&mime_type);
+        let (owner, source) = offer.source;
+        self.events
+            .push_back((owner, DataDeviceEvent::Target { source, mime_type }));
+        Ok(())
+    }
+
+    /// Forwards a receive request to the source client. The descriptor is
+    /// closed right away if the source no longer exists.
+    pub fn receive(
+        &mut self,
+        client_id: ClientId,
+        offer_id: ObjectId,
+        mime_type: String,
+        fd: OwnedFd,
+    ) -> Result<(), DataDeviceError> {
+        let offer = self
+            .offers
+            .get(&(client_id, offer_id))
+            .ok_or(DataDeviceError::InvalidOffer)?;
+        let (owner, source) = offer.source;
+        if self.sources.contains_key(&(owner, source)) {
+            self.events.push_back((
+                owner,
+                DataDeviceEvent::Send {
+                    source,
+                    mime_type,
+                    fd,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_offer_actions(
+        &mut self,
+        client_id: ClientId,
+        offer_id: ObjectId,
+        actions: u32,
+        preferred_action: u32,
+    ) -> Result<(), DataDeviceError> {
+        if actions & !ALL_DND_ACTIONS != 0 {
+            return Err(DataDeviceError::InvalidActionMask);
+        }
+        if preferred_action & !ALL_DND_ACTIONS != 0 || preferred_action.count_ones() > 1 {
+            return Err(DataDeviceError::InvalidAction);
+        }
+        let offer = self
+            .offers
+            .get_mut(&(client_id, offer_id))
+            .ok_or(DataDeviceError::InvalidOffer)?;
+        let Some(drag_offer) = offer.drag.as_mut() else {
+            return Err(DataDeviceError::InvalidOffer);
+        };
+        if drag_offer.dropped {
+            return Err(DataDeviceError::InvalidOffer);
+        }
+        drag_offer.actions = actions;
+        drag_offer.preferred_action = preferred_action;
+        let source_key = offer.source;
+        self.negotiate_action(client_id, offer_id, source_key);
+        Ok(())
+    }
+
+    pub fn finish(
+        &mut self,
+        client_id: ClientId,
+        offer_id: ObjectId,
+    ) -> Result<(), DataDeviceError> {
+        let offer = self
+            .offers
+            .get_mut(&(client_id, offer_id))
+            .ok_or(DataDeviceError::InvalidFinish)?;
+        let Some(drag_offer) = offer.drag.as_mut() else {
+            return Err(DataDeviceError::InvalidFinish);
+        };
+        if !drag_offer.dropped
+            || drag_offer.accepted.is_none()
+            || drag_offer.action == WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE
+        {
+            return Err(DataDeviceError::InvalidFinish);
+        }
+        // A finished offer no longer cancels its source when destroyed.
+        offer.drag = None;
+        let (owner, source) = offer.source;
+        self.events
+            .push_back((owner, DataDeviceEvent::Finished { source }));
+        Ok(())
+    }
+
+    pub fn destroy_offer(&mut self, client_id: ClientId, offer_id: ObjectId) {
+        let Some(offer) = self.offers.remove(&(client_id, offer_id)) else {
+            return;
+        };
+        if offer.drag.is_some_and(|drag_offer| drag_offer.dropped) {
+            let (owner, source) = offer.source;
+            self.events
+                .push_back((owner, DataDeviceEvent::Cancelled { source }));
+        }
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId, focused_clients: &[ClientId]) {
+        let sources: Vec<ObjectId> = self
+            .sources
+            .keys()
+            .filter(|(owner, _)| *owner == client_id)
+            .map(|(_, id)| *id)
+            .collect();
+        for source in sources {
+            self.destroy_source(client_id, source, focused_clients);
+        }
+        if self
+            .drag
+            .as_ref()
+            .is_some_and(|drag| drag.origin.0 == client_id)
+        {
+            self.drop_drag();
+        }
+        self.devices.retain(|device| device.client_id != client_id);
+        self.offers.retain(|(owner, _), _| *owner != client_id);
+        self.events.retain(|(owner, _)| *owner != client_id);
+    }
+
+    /// Takes the queued events for all clients.
+    pub fn take_events(&mut self) -> VecDeque<(ClientId, DataDeviceEvent)> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Writes one queued event using the receiving client's context.
+    pub fn deliver(&mut self, ctx: &mut Ctx, event: DataDeviceEvent) {
+        match event {
+            DataDeviceEvent::Selection { device } => self.send_selection(ctx, device),
+            DataDeviceEvent::Send {
+                source,
+                mime_type,
+                fd,
+            } => {
+                ctx.writer
+                    .wl_data_source_send(source)
+                    .mime_type(&mime_type)
+                    .fd(fd.as_raw_fd());
+                ctx.writer.close_fd_after_flush(fd);
+            }
+            DataDeviceEvent::Cancelled { source } => {
+                ctx.writer.wl_data_source_cancelled(source);
+            }
+            DataDeviceEvent::Target { source, mime_type } => {
+                ctx.writer
+                    .wl_data_source_target(source)
+                    .mime_type(mime_type.as_deref());
+            }
+            DataDeviceEvent::SourceAction { source, action } => {
+                if self.source_version(ctx.client_id, source) >= 3 {
+                    ctx.writer.wl_data_source_action(source).dnd_action(action);
+                }
+            }
+            DataDeviceEvent::DropPerformed { source } => {
+                if self.source_version(ctx.client_id, source) >= 3 {
+                    ctx.writer.wl_data_source_dnd_drop_performed(source);
+                }
+            }
+            DataDeviceEvent::Finished { source } => {
+                if self.source_version(ctx.client_id, source) >= 3 {
+                    ctx.writer.wl_data_source_dnd_finished(source);
+                }
+            }
+            DataDeviceEvent::OfferAction { offer, action } => {
+                if self
+                    .offers
+                    .get(&(ctx.client_id, offer))
+                    .is_some_and(|offer| offer.version >= 3)
+                {
+                    ctx.writer.wl_data_offer_action(offer).dnd_action(action);
+                }
+            }
+            DataDeviceEvent::Enter { target } => self.send_drag_enter(ctx, target),
+            DataDeviceEvent::Motion { time_msec, x, y } => {
+                for device in self.drag_devices(ctx.client_id) {
+                    ctx.writer
+                        .wl_data_device_motion(device)
+                        .time(time_msec)
+                        .x(x)
+                        .y(y);
+                }
+            }
+            DataDeviceEvent::Leave => {
+                for device in self.drag_devices(ctx.client_id) {
+                    ctx.writer.wl_data_device_leave(device);
+                }
+            }
+            DataDeviceEvent::Drop => {
+                for device in self.drag_devices(ctx.client_id) {
+                    ctx.writer.wl_data_device_drop(device);
+                }
+            }
+        }
+    }
+
+    fn send_selection(&mut self, ctx: &mut Ctx, device_id: Option<ObjectId>) {
+        let devices: Vec<(ObjectId, u32, GlobalId)> = self
+            .devices
+            .iter()
+            .filter(|device| {
+                device.client_id == ctx.client_id && device_id.is_none_or(|id| id == device.id)
+            })
+            .map(|device| (device.id, device.version, device.seat))
+            .collect();
+        for (device, version, seat) in devices {
+            let source = self
+                .selections
+                .get(&seat)
+                .copied()
+                .filter(|key| self.sources.contains_key(key));
+            let offer = source.and_then(|source| self.create_offer(ctx, device, version, source));
+            ctx.writer.wl_data_device_selection(device).id(offer);
+        }
+    }
+
+    fn send_drag_enter(&mut self, ctx: &mut Ctx, target: DragTarget) {
+        let Some(drag) = self.drag.as_ref() else {
+            return;
+        };
+        let source = drag.source;
+        let devices = self.drag_devices(ctx.client_id);
+        let serial = self.serial.get_or_insert_with(Serial::new).next_serial();
+        let mut entered_offer = None;
+        for device in devices {
+            let version = self
+                .devices
+                .iter()
+                .find(|candidate| candidate.client_id == ctx.client_id && candidate.id == device)
+                .map_or(1, |device| device.version);
+            let offer = source.and_then(|source| {
+                let offer = self.create_offer(ctx, device, version, source)?;
+                if let Some(offer_state) = self.offers.get_mut(&(ctx.client_id, offer)) {
+                    offer_state.drag = Some(DragOffer::default());
+                }
+                let source_actions = self
+                    .sources
+                    .get(&source)
+                    .and_then(|source| source.actions)
+                    .unwrap_or(WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE);
+                if version >= 3 {
+                    ctx.writer
+                        .wl_data_offer_source_actions(offer)
+                        .source_actions(source_actions);
+                }
+                Some(offer)
+            });
+            ctx.writer
+                .wl_data_device_enter(device)
+                .serial(serial)
+                .surface(target.surface_id)
+                .x(target.x)
+                .y(target.y)
+                .id(offer);
+            entered_offer = entered_offer.or(offer);
+        }
+        let focus = self.drag.as_mut().and_then(|drag| drag.focus.as_mut());
+        match focus {
+            Some(focus)
+                if focus.client_id == ctx.client_id && focus.surface_id == target.surface_id =>
+            {
+                focus.offer = entered_offer;
+            }
+            // Focus moved on before the enter was delivered.
+            _ => {
+                if let Some(offer) =
+                    entered_offer.and_then(|offer| self.offers.get_mut(&(ctx.client_id, offer)))
+                {
+                    offer.drag = None;
+                }
+            }
+        }
+    }
+
+    /// Creates a wl_data_offer for `source` on the client's device and
+    /// advertises its mime types.
+    fn create_offer(
+        &mut self,
+        ctx: &mut Ctx,
+        device: ObjectId,
+        version: u32,
+        source: ResourceKey,
+    ) -> Option<ObjectId> {
+        let mime_types = self.sources.get(&source)?.mime_types.clone();
+        let offer = match ctx
+            .registry
+            .create_object(InterfaceIndex::WlDataOffer, version)
+        {
+            Ok(offer) => offer,
+            Err(err) => {
+                log::error!("Failed to create wl_data_offer: {err:#}");
+                return None;
+            }
+        };
+        ctx.writer.wl_data_device_data_offer(device).id(offer);
+        for mime_type in &mime_types {
+            ctx.writer.wl_data_offer_offer(offer).mime_type(mime_type);
+        }
+        self.offers.insert(
+            (ctx.client_id, offer),
+            DataOffer {
+                source,
+                version,
+                drag: None,
+            },
+        );
+        Some(offer)
+    }
+
+    fn negotiate_action(&mut self, client_id: ClientId, offer_id: ObjectId, source: ResourceKey) {
+        let source_actions = self
+            .sources
+            .get(&source)
+            .and_then(|source| source.actions)
+            .unwrap_or(WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE);
+        let Some(drag_offer) = self
+            .offers
+            .get_mut(&(client_id, offer_id))
+            .and_then(|offer| offer.drag.as_mut())
+        else {
+            return;
+        };
+        let action = choose_action(
+            source_actions,
+            drag_offer.actions,
+            drag_offer.preferred_action,
+        );
+        if action == drag_offer.action {
+            return;
+        }
+        drag_offer.action = action;
+        self.events.push_back((
+            client_id,
+            DataDeviceEvent::OfferAction {
+                offer: offer_id,
+                action,
+            },
+        ));
+        let (owner, source) = source;
+        self.events
+            .push_back((owner, DataDeviceEvent::SourceAction { source, action }));
+    }
+
+    fn announce_selection(&mut self, focused_clients: &[ClientId]) {
+        for client_id in focused_clients {
+            self.events
+                .push_back((*client_id, DataDeviceEvent::Selection { device: None }));
+        }
+    }
+
+    fn leave_drag_focus(&mut self) {
+        let Some(focus) = self.drag.as_mut().and_then(|drag| drag.focus.take()) else {
+            return;
+        };
+        self.retire_drag_offer(focus);
+    }
+
+    fn leave_drag_focus_of(&mut self, drag: &Drag) {
+        if let Some(focus) = drag.focus {
+            self.retire_drag_offer(focus);
+        }
+    }
+
+    /// Sends leave to the focused client; its offer stops being a drag offer.
+    fn retire_drag_offer(&mut self, focus: DragFocus) {
+        if let Some(offer) = focus
+            .offer
+            .and_then(|offer| self.offers.get_mut(&(focus.client_id, offer)))
+        {
+            offer.drag = None;
+        }
+        self.events
+            .push_back((focus.client_id, DataDeviceEvent::Leave));
+    }
+
+    /// Data devices of the client on the seat of the drag in progress.
+    fn drag_devices(&self, client_id: ClientId) -> Vec<ObjectId> {
+        let Some(seat) = self.drag.as_ref().map(|drag| drag.seat) else {
+            return Vec::new();
+        };
+        self.devices
+            .iter()
+            .filter(|device| device.client_id == client_id && device.seat == seat)
+            .map(|device| device.id)
+            .collect()
+    }
+
+    fn device(&self, client_id: ClientId, id: ObjectId) -> Result<&DataDevice, DataDeviceError> {
+        self.devices
+            .iter()
+            .find(|device| device.client_id == client_id && device.id == id)
+            .ok_or(DataDeviceError::UnknownDevice)
+    }
+
+    fn source_version(&self, client_id: ClientId, id: ObjectId) -> u32 {
+        self.sources
+            .get(&(client_id, id))
+            .map_or(1, |source| source.version)
+    }
+}
+
+/// Picks the destination's preferred action if both sides support it, and
+/// otherwise the first common action in copy, move, ask order.
+fn choose_action(source_actions: u32, destination_actions: u32, preferred_action: u32) -> u32 {
+    let common = source_actions & destination_actions;
+    if common & preferred_action != 0 {
+        return preferred_action;
+    }
+    [
+        WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY,
+        WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE,
+        WL_DATA_DEVICE_MANAGER_DND_ACTION_ASK,
+    ]
+    .into_iter()
+    .find(|action| common & action != 0)
+    .unwrap_or(WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    /// Creates a source with one mime type and a device on seat 1.
+    fn manager_with_source(owner: ClientId, actions: Option<u32>) -> DataDeviceManager {
+        let mut manager = DataDeviceManager::default();
+        manager.create_source(owner, object(2), 3);
+        manager
+            .offer_mime_type(owner, object(2), "text/plain".to_owned())
+            .unwrap();
+        if let Some(actions) = actions {
+            manager
+                .set_source_actions(owner, object(2), actions)
+                .unwrap();
+        }
+        manager.create_device(owner, object(3), 3, 1, false);
+        manager
+    }
+
+    #[test]
+    fn replacing_selection_cancels_previous_source() {
+        let owner = client(1);
+        let mut manager = manager_with_source(owner, None);
+        manager.create_source(owner, object(4), 3);
+        manager
+            .set_selection(owner, object(3), Some(object(2)), &[owner])
+            .unwrap();
+        manager.take_events();
+
+        manager
+            .set_selection(owner, object(3), Some(object(4)), &[owner])
+            .unwrap();
+
+        let events: Vec<_> = manager.take_events().into_iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                (_, DataDeviceEvent::Cancelled { source }),
+                (_, DataDeviceEvent::Selection { device: None }),
+            ] if *source == object(2)
+        ));
+        assert_eq!(
+            manager.set_selection(owner, object(3), Some(object(2)), &[]),
+            Err(DataDeviceError::UsedSource)
+        );
+    }
+
+    #[test]
+    fn receive_forwards_fd_to_source_client() {
+        let owner = client(1);
+        let receiver = client(2);
+        let mut manager = manager_with_source(owner, None);
+        manager
+            .set_selection(owner, object(3), Some(object(2)), &[])
+            .unwrap();
+        manager.offers.insert(
+            (receiver, object(10)),
+            DataOffer {
+                source: (owner, object(2)),
+                version: 3,
+                drag: None,
+            },
+        );
+        let (pipe, _) = UnixStream::pair().unwrap();
+
+        manager
+            .receive(receiver, object(10), "text/plain".to_owned(), pipe.into())
+            .unwrap();
+
+        let events: Vec<_> = manager.take_events().into_iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [(client_id, DataDeviceEvent::Send { source, mime_type, .. })]
+                if *client_id == owner && *source == object(2) && mime_type == "text/plain"
+        ));
+    }
+
+    #[test]
+    fn drop_without_accepted_action_cancels_source() {
+        let owner = client(1);
+        let mut manager = manager_with_source(owner, Some(WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY));
+        manager
+            .start_drag(owner, object(3), Some(object(2)), object(5), None)
+            .unwrap();
+
+        manager.drop_drag();
+
+        assert!(!manager.is_dragging());
+        let events: Vec<_> = manager.take_events().into_iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [(_, DataDeviceEvent::Cancelled { source })] if *source == object(2)
+        ));
+    }
+
+    #[test]
+    fn preferred_action_wins_when_both_sides_support_it() {
+        let all = ALL_DND_ACTIONS;
+        assert_eq!(
+            choose_action(all, all, WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE),
+            WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE
+        );
+        assert_eq!(
+            choose_action(
+                WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY,
+                all,
+                WL_DATA_DEVICE_MANAGER_DND_ACTION_MOVE
+            ),
+            WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY
+        );
+        assert_eq!(
+            choose_action(
+                WL_DATA_DEVICE_MANAGER_DND_ACTION_ASK,
+                WL_DATA_DEVICE_MANAGER_DND_ACTION_COPY,
+                WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE
+            ),
+            WL_DATA_DEVICE_MANAGER_DND_ACTION_NONE
+        );
+    }
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::Write,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::Arc,
+};
+
+use log::warn;
+use lumalla_shared::{DmabufAttributes, DmabufFormats, DmabufPlane, Output};
+use lumalla_wayland_protocol::{
+    ClientConnection, ClientId, ObjectId, buffer::Writer,
+    protocols::linux_dmabuf_v1::ZWP_LINUX_BUFFER_PARAMS_V1_FLAGS_Y_INVERT,
+    registry::InterfaceIndex,
+};
+
+use crate::{GlobalId, Globals};
+
+type ResourceKey = (ClientId, ObjectId);
+
+/// Planes a buffer may be made of.
+const MAX_PLANES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmabufError {
+    UnknownParams,
+    AlreadyUsed,
+    PlaneIdx,
+    PlaneSet,
+    Incomplete,
+    InvalidFormat,
+    InvalidDimensions,
+    OutOfBounds,
+    /// The buffer is valid, but cannot be shown.
+    Unsupported,
+}
+
+/// Planes collected by a zwp_linux_buffer_params_v1 object.
+#[derive(Debug, Default)]
+struct BufferParams {
+    planes: [Option<DmabufPlane>; MAX_PLANES],
+    modifier: Option<u64>,
+    used: bool,
+}
+
+/// Client buffers backed by dmabufs, created through the
+/// zwp_linux_dmabuf_v1 global while the render device can import them.
+#[derive(Debug, Default)]
+pub struct DmabufManager {
+    formats: Option<DmabufFormats>,
+    /// Sealed memfd of the format table sent with feedback, and its size.
+    format_table: Option<(OwnedFd, u32)>,
+    global_id: Option<GlobalId>,
+    params: HashMap<ResourceKey, BufferParams>,
+    buffers: HashMap<ResourceKey, Arc<DmabufAttributes>>,
+    /// Buffer shown by each surface; it is released when replaced.
+    held: HashMap<ResourceKey, ObjectId>,
+    /// Buffer each output scans out directly, by output name.
+    scanned_out: HashMap<String, ResourceKey>,
+    /// Replaced buffers that are released once no output scans them out.
+    pending_release: HashSet<ResourceKey>,
+    /// zwp_linux_dmabuf_feedback_v1 objects, resent when the formats change.
+    feedback: Vec<ResourceKey>,
+}
+
+impl DmabufManager {
+    /// Advertises the global while the render device imports dmabufs, and
+    /// sends changed formats to the feedback objects.
+    pub fn set_formats(
+        &mut self,
+        formats: Option<DmabufFormats>,
+        globals: &mut Globals,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        let formats = formats.filter(|formats| !formats.formats.is_empty());
+        if formats == self.formats {
+            return;
+        }
+        self.format_table = formats.as_ref().and_then(|formats| {
+            create_format_table(formats)
+                .inspect_err(|err| warn!("Failed to create dmabuf format table: {err}"))
+                .ok()
+        });
+        self.formats = formats;
+        match (&self.formats, self.global_id) {
+            (Some(_), None) => {
+                self.global_id =
+                    Some(globals.register(InterfaceIndex::ZwpLinuxDmabufV1, clients.values_mut()));
+            }
+            (None, Some(global_id)) => {
+                globals.remove(global_id, clients.values_mut());
+                self.global_id = None;
+            }
+            _ => {}
+        }
+        for &(client_id, feedback_id) in &self.feedback {
+            if let Some(client) = clients.get_mut(&client_id) {
+                self.send_feedback(client.writer_mut(), feedback_id);
+            }
+        }
+    }
+
+    /// Announces the formats to a global bound with a version predating
+    /// feedback objects.
+    pub fn bind(&self, writer: &mut Writer, id: ObjectId, version: u32) {
+        if version >= 4 {
+            return;
+        }
+        let Some(formats) = &self.formats else {
+            return;
+        };
+        if version >= 3 {
+            for format in &formats.formats {
+                writer
+                    .zwp_linux_dmabuf_v1_modifier(id)
+                    .format(format.format)
+                    .modifier_hi((format.modifier >> 32) as u32)
+                    .modifier_lo(format.modifier as u32);
+            }
+            return;
+        }
+        let mut fourccs: Vec<u32> = formats.formats.iter().map(|format| format.format).collect();
+        fourccs.dedup();
+        for fourcc in fourccs {
+            writer.zwp_linux_dmabuf_v1_format(id).format(fourcc);
+        }
+    }
+
+    pub fn create_params(&mut self, client_id: ClientId, params_id: ObjectId) {
+        self.params
+            .insert((client_id, params_id), BufferParams::default());
+    }
+
+    pub fn delete_params(&mut self, client_id: ClientId, params_id: ObjectId) {
+        self.params.remove(&(client_id, params_id));
+    }
+
+    pub fn add_plane(
+        &mut self,
+        client_id: ClientId,
+        params_id: ObjectId,
+        plane_idx: u32,
+        plane: DmabufPlane,
+        modifier: u64,
+    ) -> Result<(), DmabufError> {
+        let params = self
+            .params
+            .get_mut(&(client_id, params_id))
+            .ok_or(DmabufError::UnknownParams)?;
+        if params.used {
+            return Err(DmabufError::AlreadyUsed);
+        }
+        let slot = params
+            .planes
+            .get_mut(plane_idx as usize)
+            .ok_or(DmabufError::PlaneIdx)?;
+        if slot.is_some() {
+            return Err(DmabufError::PlaneSet);
+        }
+        if params.modifier.is_some_and(|current| current != modifier) {
+            return Err(DmabufError::InvalidFormat);
+        }
+        *slot = Some(plane);
+        params.modifier = Some(modifier);
+        Ok(())
+    }
+
+    /// Turns the planes added to a params object into a buffer.
+    ///
+    /// The params object can only be used once, even if this fails.
+    pub fn create_buffer(
+        &mut self,
+        client_id: ClientId,
+        params_id: ObjectId,
+        buffer_id: ObjectId,
+        (width, height): (i32, i32),
+        format: u32,
+        flags: u32,
+    ) -> Result<(), DmabufError> {
+        let params = self
+            .params
+            .get_mut(&(client_id, params_id))
+            .ok_or(DmabufError::UnknownParams)?;
+        if params.used {
+            return Err(DmabufError::AlreadyUsed);
+        }
+        params.used = true;
+        let plane_count = params.planes.iter().take_while(|p| p.is_some()).count();
+        if plane_count == 0 || params.planes[plane_count..].iter().any(Option::is_some) {
+            return Err(DmabufError::Incomplete);
+        }
+        if width <= 0 || height <= 0 {
+            return Err(DmabufError::InvalidDimensions);
+        }
+        let modifier = params.modifier.unwrap_or_default();
+        let Some(supported) = self
+            .formats
+            .as_ref()
+            .and_then(|formats| formats.get(format, modifier))
+        else {
+            return Err(DmabufError::InvalidFormat);
+        };
+        if supported.planes as usize != plane_count {
+            return Err(DmabufError::Incomplete);
+        }
+        if flags & !ZWP_LINUX_BUFFER_PARAMS_V1_FLAGS_Y_INVERT != 0 {
+            return Err(DmabufError::Unsupported);
+        }
+
+        let planes: Vec<DmabufPlane> = params.planes.iter_mut().map_while(Option::take).collect();
+        for (index, plane) in planes.iter().enumerate() {
+            let size = dmabuf_size(&plane.fd);
+            // Only the layout of the first plane is known for every modifier.
+            let end = if index == 0 {
+                u64::from(plane.stride)
+                    .checked_mul(height as u64)
+                    .map(|rows| rows + u64::from(plane.offset))
+            } else {
+                Some(u64::from(plane.offset))
+            };
+            if size.is_some_and(|size| end.is_none_or(|end| end > size)) {
+                return Err(DmabufError::OutOfBounds);
+            }
+        }
+
+        self.buffers.insert(
+            (client_id, buffer_id),
+            Arc::new(DmabufAttributes {
+                width: width as u32,
+                height: height as u32,
+                format,
+                modifier,
+                planes,
+                y_invert: flags & ZWP_LINUX_BUFFER_PARAMS_V1_FLAGS_Y_INVERT != 0,
+            }),
+        );
+        Ok(())
+    }
+
+    pub fn buffer(
+        &self,
+        client_id: ClientId,
+        buffer_id: ObjectId,
+    ) -> Option<Arc<DmabufAttributes>> {
+        self.buffers.get(&(client_id, buffer_id)).cloned()
+    }
+
+    /// Returns whether the buffer was a dmabuf.
+    pub fn delete_buffer(&mut self, client_id: ClientId, buffer_id: ObjectId) -> bool {
+        self.held
+            .retain(|&(owner, _), held| !(owner == client_id && *held == buffer_id));
+        self.pending_release.remove(&(client_id, buffer_id));
+        self.buffers.remove(&(client_id, buffer_id)).is_some()
+    }
+
+    /// Keeps a buffer until the surface shows another one.
+    ///
+    /// Returns the buffer the surface showed before, which can be released.
+    pub fn hold(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        buffer_id: ObjectId,
+    ) -> Option<ObjectId> {
+        self.pending_release.remove(&(client_id, buffer_id));
+        let previous = self
+            .held
+            .insert((client_id, surface_id), buffer_id)
+            .filter(|previous| *previous != buffer_id)?;
+        self.release(client_id, previous)
+    }
+
+    /// Returns the buffer a surface no longer shows, which can be released.
+    pub fn release_surface(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Option<ObjectId> {
+        let buffer_id = self.held.remove(&(client_id, surface_id))?;
+        self.release(client_id, buffer_id)
+    }
+
+    /// Whether a surface shows the buffer.
+    pub fn shows(&self, client_id: ClientId, surface_id: ObjectId, buffer_id: ObjectId) -> bool {
+        self.held.get(&(client_id, surface_id)) == Some(&buffer_id)
+    }
+
+    /// Records the buffer an output scans out after a page flip.
+    ///
+    /// Returns the replaced buffers that no output scans out anymore, which
+    /// can be released.
+    pub fn frame_presented(
+        &mut self,
+        output: &str,
+        scanned_out: Option<ResourceKey>,
+    ) -> Vec<ResourceKey> {
+        match scanned_out {
+            Some(buffer) => self.scanned_out.insert(output.to_owned(), buffer),
+            None => self.scanned_out.remove(output),
+        };
+        self.take_released()
+    }
+
+    /// Forgets the buffers scanned out by outputs that are gone.
+    ///
+    /// Returns the replaced buffers that can be released now.
+    pub fn retain_outputs(&mut self, outputs: &[Output]) -> Vec<ResourceKey> {
+        self.scanned_out
+            .retain(|name, _| outputs.iter().any(|output| output.name == *name));
+        self.take_released()
+    }
+
+    fn take_released(&mut self) -> Vec<ResourceKey> {
+        let mut released = Vec::new();
+        self.pending_release.retain(|buffer| {
+            let shown = self.scanned_out.values().any(|shown| shown == buffer);
+            if !shown {
+                released.push(*buffer);
+            }
+            shown
+        });
+        released
+    }
+
+    /// Defers the release of a buffer while an output scans it out.
+    fn release(&mut self, client_id: ClientId, buffer_id: ObjectId) -> Option<ObjectId> {
+        let buffer = (client_id, buffer_id);
+        if self.scanned_out.values().any(|shown| *shown == buffer) {
+            self.pending_release.insert(buffer);
+            return None;
+        }
+        Some(buffer_id)
+    }
+
+    pub fn add_feedback(
+        &mut self,
+        client_id: ClientId,
+        feedback_id: ObjectId,
+        writer: &mut Writer,
+    ) {
+        self.feedback.push((client_id, feedback_id));
+        self.send_feedback(writer, feedback_id);
+    }
+
+    pub fn delete_feedback(&mut self, client_id: ClientId, feedback_id: ObjectId) {
+        self.feedback
+            .retain(|feedback| *feedback != (client_id, feedback_id));
+    }
+
+    /// Sends the formats as a single tranche targeting the render device.
+    fn send_feedback(&self, writer: &mut Writer, feedback_id: ObjectId) {
+        let (Some(formats), Some((table, table_size))) = (&self.formats, &self.format_table) else {
+            return;
+        };
+        let device = formats.main_device.to_ne_bytes();
+        let indices: Vec<u8> = (0..formats.formats.len() as u16)
+            .flat_map(u16::to_ne_bytes)
+            .collect();
+        writer
+            .zwp_linux_dmabuf_feedback_v1_format_table(feedback_id)
+            .fd(table.as_raw_fd())
+            .size(*table_size);
+        writer
+            .zwp_linux_dmabuf_feedback_v1_main_device(feedback_id)
+            .device(&device);
+        writer
+            .zwp_linux_dmabuf_feedback_v1_tranche_target_device(feedback_id)
+            .device(&device);
+        writer
+            .zwp_linux_dmabuf_feedback_v1_tranche_flags(feedback_id)
+            .flags(0);
+        writer
+            .zwp_linux_dmabuf_feedback_v1_tranche_formats(feedback_id)
+            .indices(&indices);
+        writer.zwp_linux_dmabuf_feedback_v1_tranche_done(feedback_id);
+        writer.zwp_linux_dmabuf_feedback_v1_done(feedback_id);
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.params.retain(|(owner, _), _| *owner != client_id);
+        self.buffers.retain(|(owner, _), _| *owner != client_id);
+        self.held.retain(|(owner, _), _| *owner != client_id);
+        self.scanned_out.retain(|_, (owner, _)| *owner != client_id);
+        self.pending_release
+            .retain(|(owner, _)| *owner != client_id);
+        self.feedback.retain(|(owner, _)| *owner != client_id);
+    }
+}
+
+/// Writes the format and modifier pairs into a sealed memfd, 16 bytes each.
+fn create_format_table(formats: &DmabufFormats) -> std::io::Result<(OwnedFd, u32)> {
+    let fd = unsafe {
+        libc::memfd_create(
+            c"lumalla-dmabuf-formats".as_ptr(),
+            libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    let table: Vec<u8> = formats
+        .formats
+        .iter()
+        .flat_map(|format| {
+            let mut entry = [0; 16];
+            entry[..4].copy_from_slice(&format.format.to_ne_bytes());
+            entry[8..].copy_from_slice(&format.modifier.to_ne_bytes());
+            entry
+        })
+        .collect();
+    file.write_all(&table)?;
+    let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((OwnedFd::from(file), table.len() as u32))
+}
+
+/// Size of a dmabuf, if the exporter reports one.
+fn dmabuf_size(fd: &OwnedFd) -> Option<u64> {
+    let size = unsafe { libc::lseek(fd.as_raw_fd(), 0, libc::SEEK_END) };
+    (size > 0).then_some(size as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use lumalla_shared::DmabufFormat;
+
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    const ARGB8888: u32 = u32::from_le_bytes(*b"AR24");
+
+    fn plane(size: usize, stride: u32) -> DmabufPlane {
+        let fd = unsafe { libc::memfd_create(c"lumalla-dmabuf-test".as_ptr(), libc::MFD_CLOEXEC) };
+        assert!(fd >= 0);
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_len(size as u64).unwrap();
+        DmabufPlane {
+            fd: file.into(),
+            offset: 0,
+            stride,
+        }
+    }
+
+    fn manager() -> DmabufManager {
+        DmabufManager {
+            formats: Some(DmabufFormats {
+                main_device: 0,
+                formats: vec![DmabufFormat {
+                    format: ARGB8888,
+                    modifier: 0,
+                    planes: 1,
+                }],
+            }),
+            ..DmabufManager::default()
+        }
+    }
+
+    #[test]
+    fn validates_params_before_creating_buffers() {
+        let mut manager = manager();
+        manager.create_params(client(1), object(2));
+        assert_eq!(
+            manager.add_plane(client(1), object(2), 4, plane(64, 16), 0),
+            Err(DmabufError::PlaneIdx)
+        );
+        assert_eq!(
+            manager.add_plane(client(1), object(2), 1, plane(64, 16), 0),
+            Ok(())
+        );
+        assert_eq!(
+            manager.add_plane(client(1), object(2), 1, plane(64, 16), 0),
+            Err(DmabufError::PlaneSet)
+        );
+        assert_eq!(
+            manager.create_buffer(client(1), object(2), object(3), (4, 4), ARGB8888, 0),
+            Err(DmabufError::Incomplete)
+        );
+        assert_eq!(
+            manager.create_buffer(client(1), object(2), object(3), (4, 4), ARGB8888, 0),
+            Err(DmabufError::AlreadyUsed)
+        );
+
+        manager.create_params(client(1), object(4));
+        manager
+            .add_plane(client(1), object(4), 0, plane(64, 16), 0)
+            .unwrap();
+        assert_eq!(
+            manager.create_buffer(client(1), object(4), object(5), (4, 5), ARGB8888, 0),
+            Err(DmabufError::OutOfBounds)
+        );
+
+        manager.create_params(client(1), object(6));
+        manager
+            .add_plane(client(1), object(6), 0, plane(64, 16), 0)
+            .unwrap();
+        assert_eq!(
+            manager.create_buffer(
+                client(1),
+                object(6),
+                object(7),
+                (4, 4),
+                ARGB8888,
+                ZWP_LINUX_BUFFER_PARAMS_V1_FLAGS_Y_INVERT
+            ),
+            Ok(())
+        );
+        let buffer = manager.buffer(client(1), object(7)).unwrap();
+        assert_eq!((buffer.width, buffer.height), (4, 4));
+        assert!(buffer.y_invert);
+    }
+
+    #[test]
+    fn releases_held_buffers_once_replaced() {
+        let mut manager = manager();
+        assert_eq!(manager.hold(client(1), object(2), object(10)), None);
+        assert_eq!(manager.hold(client(1), object(2), object(10)), None);
+        assert_eq!(
+            manager.hold(client(1), object(2), object(11)),
+            Some(object(10))
+        );
+        // Destroyed buffers are never released.
+        manager.delete_buffer(client(1), object(11));
+        assert_eq!(manager.release_surface(client(1), object(2)), None);
+    }
+
+    #[test]
+    fn defers_release_while_scanned_out() {
+        let mut manager = manager();
+        manager.hold(client(1), object(2), object(10));
+        assert!(
+            manager
+                .frame_presented("DP-1", Some((client(1), object(10))))
+                .is_empty()
+        );
+        assert_eq!(manager.hold(client(1), object(2), object(11)), None);
+        assert!(
+            manager
+                .frame_presented("DP-1", Some((client(1), object(10))))
+                .is_empty()
+        );
+        assert_eq!(
+            manager.frame_presented("DP-1", None),
+            [(client(1), object(10))]
+        );
+    }
+}
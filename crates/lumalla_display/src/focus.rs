@@ -0,0 +1,175 @@
+use lumalla_shared::FocusMode;
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+/// Pointer input that may move the keyboard focus to the window under the
+/// pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusTrigger {
+    /// The pointer moved over a window.
+    Motion,
+    /// A button was pressed over a window.
+    Press,
+}
+
+/// Toplevels of the main seat in the order they were focused.
+///
+/// The keyboard follows the top of the stack, so closing the focused window
+/// returns focus to the one focused before it.
+#[derive(Debug, Default)]
+pub struct FocusStack {
+    mode: FocusMode,
+    /// Least recently focused first.
+    windows: Vec<(ClientId, ObjectId)>,
+}
+
+impl FocusStack {
+    pub fn set_mode(&mut self, mode: FocusMode) {
+        self.mode = mode;
+    }
+
+    /// Whether `trigger` focuses the window under the pointer in the
+    /// current mode.
+    pub fn focuses_on(&self, trigger: FocusTrigger) -> bool {
+        matches!(
+            (self.mode, trigger),
+            (FocusMode::Click, FocusTrigger::Press)
+                | (FocusMode::FollowsMouse, FocusTrigger::Motion)
+        )
+    }
+
+    /// The window keyboards should be entered on: the most recently focused
+    /// one that is `shown`.
+    pub fn focused(
+        &self,
+        shown: impl Fn(ClientId, ObjectId) -> bool,
+    ) -> Option<(ClientId, ObjectId)> {
+        self.windows
+            .iter()
+            .rev()
+            .find(|(client_id, surface_id)| shown(*client_id, *surface_id))
+            .copied()
+    }
+
+    /// Moves a window to the top of the stack, adding it if needed.
+    ///
+    /// Returns `false` if it was on top already.
+    pub fn focus(&mut self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        let key = (client_id, surface_id);
+        if self.windows.last() == Some(&key) {
+            return false;
+        }
+        self.windows.retain(|window| *window != key);
+        self.windows.push(key);
+        true
+    }
+
+    /// Focuses the `shown` window `index` places down the stack, wrapping
+    /// around, so `1` switches back and forth between the two latest windows.
+    pub fn cycle(
+        &mut self,
+        index: usize,
+        shown: impl Fn(ClientId, ObjectId) -> bool,
+    ) -> Option<(ClientId, ObjectId)> {
+        let windows: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|(client_id, surface_id)| shown(*client_id, *surface_id))
+            .copied()
+            .collect();
+        let len = windows.len();
+        if len == 0 {
+            return None;
+        }
+        let (client_id, surface_id) = windows[len - 1 - index % len];
+        self.focus(client_id, surface_id);
+        Some((client_id, surface_id))
+    }
+
+    /// The most recently focused of `candidates`.
+    pub fn most_recent(&self, candidates: &[(ClientId, ObjectId)]) -> Option<(ClientId, ObjectId)> {
+        self.windows
+            .iter()
+            .rev()
+            .find(|window| candidates.contains(window))
+            .copied()
+    }
+
+    pub fn remove(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        self.windows
+            .retain(|window| *window != (client_id, surface_id));
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.windows.retain(|(owner, _)| *owner != client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ids::window;
+
+    #[test]
+    fn cycles_down_the_stack_and_restores_focus_on_close() {
+        let mut stack = FocusStack::default();
+        for (client, surface) in [window(1, 2), window(2, 2), window(3, 2)] {
+            assert!(stack.focus(client, surface));
+        }
+        assert!(!stack.focus(window(3, 2).0, window(3, 2).1));
+
+        let all = |_, _| true;
+        assert_eq!(stack.cycle(1, all), Some(window(2, 2)));
+        assert_eq!(stack.cycle(1, all), Some(window(3, 2)));
+        assert_eq!(stack.cycle(2, all), Some(window(1, 2)));
+        assert_eq!(stack.cycle(3, all), Some(window(1, 2)));
+        let (hidden, _) = window(3, 2);
+        let shown = |client_id, _| client_id != hidden;
+        assert_eq!(stack.cycle(1, shown), Some(window(2, 2)));
+        assert_eq!(stack.focused(shown), Some(window(2, 2)));
+        assert_eq!(stack.cycle(1, all), Some(window(1, 2)));
+        assert_eq!(
+            stack.most_recent(&[window(2, 2), window(3, 2)]),
+            Some(window(2, 2))
+        );
+
+        let (client, surface) = window(1, 2);
+        stack.remove(client, surface);
+        assert_eq!(stack.focused(all), Some(window(2, 2)));
+        stack.delete_client(window(2, 2).0);
+        assert_eq!(stack.focused(all), Some(window(3, 2)));
+    }
+
+    #[test]
+    fn closed_windows_leave_the_stack() {
+        let mut stack = FocusStack::default();
+        for (client, surface) in [window(1, 2), window(2, 2), window(3, 2)] {
+            stack.focus(client, surface);
+        }
+        let all = |_, _| true;
+
+        let (client, surface) = window(1, 2);
+        stack.remove(client, surface);
+        assert_eq!(stack.focused(all), Some(window(3, 2)));
+        assert_eq!(stack.cycle(2, all), Some(window(3, 2)));
+
+        let (client, surface) = window(3, 2);
+        stack.remove(client, surface);
+        assert_eq!(stack.focused(all), Some(window(2, 2)));
+        let (client, surface) = window(2, 2);
+        stack.remove(client, surface);
+        assert_eq!(stack.focused(all), None);
+        assert_eq!(stack.cycle(1, all), None);
+        assert!(stack.focus(client, surface));
+    }
+
+    #[test]
+    fn focus_follows_the_pointer_only_in_follows_mouse_mode() {
+        let mut stack = FocusStack::default();
+        assert!(stack.focuses_on(FocusTrigger::Press));
+        assert!(!stack.focuses_on(FocusTrigger::Motion));
+
+        stack.set_mode(FocusMode::FollowsMouse);
+        assert!(stack.focuses_on(FocusTrigger::Motion));
+        assert!(!stack.focuses_on(FocusTrigger::Press));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use lumalla_shared::Output;
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+/// Scale wp_fractional_scale_v1 expresses preferred scales in; 120 is 1.0.
+const SCALE_DENOMINATOR: u32 = 120;
+
+/// wp_fractional_scale_v1 objects by the surface they were created for, and
+/// the scales of the outputs surfaces are preferred to be drawn at.
+#[derive(Debug, Default)]
+pub struct FractionalScaleManager {
+    objects: HashMap<(ClientId, ObjectId), FractionalScale>,
+    /// Scale in percent of every output by name.
+    output_scales: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FractionalScale {
+    id: ObjectId,
+    /// Preferred scale in percent last sent to the object.
+    scale: u32,
+}
+
+impl FractionalScaleManager {
+    /// Adds the fractional scale object of a surface, which was sent the
+    /// preferred scale `scale`.
+    ///
+    /// Returns `false` if the surface already has one.
+    pub fn add(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        id: ObjectId,
+        scale: u32,
+    ) -> bool {
+        if self.objects.contains_key(&(client_id, surface_id)) {
+            return false;
+        }
+        self.objects
+            .insert((client_id, surface_id), FractionalScale { id, scale });
+        true
+    }
+
+    pub fn remove(&mut self, client_id: ClientId, id: ObjectId) {
+        self.objects
+            .retain(|(owner, _), object| *owner != client_id || object.id != id);
+    }
+
+    /// Forgets the fractional scale object of a destroyed surface; the object
+    /// stays alive until the client destroys it, but no longer gets events.
+    pub fn surface_destroyed(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        self.objects.remove(&(client_id, surface_id));
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.objects.retain(|(owner, _), _| *owner != client_id);
+    }
+
+    /// Replaces the scales of the outputs.
+    pub fn set_outputs(&mut self, outputs: &[Output]) {
+        self.output_scales = outputs
+            .iter()
+            .map(|output| (output.name.clone(), output.scale))
+            .collect();
+    }
+
+    /// Scale in percent of the output `output`. Surfaces on no known output
+    /// may be shown on any of them, so they get the largest scale.
+    pub fn output_scale(&self, output: Option<&str>) -> u32 {
+        output
+            .and_then(|name| self.output_scales.get(name))
+            .or_else(|| self.output_scales.values().max())
+            .copied()
+            .unwrap_or(100)
+    }
+
+    /// Surfaces of a client with a fractional scale object.
+    pub fn surfaces(&self, client_id: ClientId) -> Vec<ObjectId> {
+        self.objects
+            .keys()
+            .filter(|(owner, _)| *owner == client_id)
+            .map(|(_, surface_id)| *surface_id)
+            .collect()
+    }
+
+    /// Sets the preferred scale of a surface in percent.
+    ///
+    /// Returns the object to send the new scale to; `None` if the surface
+    /// has none or the scale is unchanged.
+    pub fn set_scale(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        scale: u32,
+    ) -> Option<ObjectId> {
+        let object = self.objects.get_mut(&(client_id, surface_id))?;
+        if object.scale == scale {
+            return None;
+        }
+        object.scale = scale;
+        Some(object.id)
+    }
+}
+
+/// A scale in percent in the units of `wp_fractional_scale_v1.preferred_scale`.
+pub fn preferred_scale(scale: u32) -> u32 {
+    scale * SCALE_DENOMINATOR / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use lumalla_shared::Transform;
+
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    fn output(name: &str, scale: u32) -> Output {
+        Output {
+            name: String::from(name),
+            description: String::new(),
+            location: (0, 0),
+            size: (1920, 1080),
+            scale,
+            transform: Transform::Normal,
+        }
+    }
+
+    #[test]
+    fn preferred_scale_changes_reach_live_objects() {
+        let mut manager = FractionalScaleManager::default();
+        assert_eq!(preferred_scale(manager.output_scale(None)), 120);
+        assert!(manager.add(client(1), object(2), object(10), 100));
+        assert!(!manager.add(client(1), object(2), object(11), 100));
+        assert!(manager.add(client(1), object(3), object(12), 100));
+        manager.surface_destroyed(client(1), object(3));
+        assert_eq!(manager.surfaces(client(1)), [object(2)]);
+
+        assert_eq!(
+            manager.set_scale(client(1), object(2), 150),
+            Some(object(10))
+        );
+        assert_eq!(preferred_scale(150), 180);
+        assert_eq!(manager.set_scale(client(1), object(2), 150), None);
+
+        manager.remove(client(1), object(10));
+        assert_eq!(manager.set_scale(client(1), object(2), 100), None);
+    }
+
+    #[test]
+    fn surfaces_get_the_scale_of_their_output() {
+        let mut manager = FractionalScaleManager::default();
+        manager.set_outputs(&[output("eDP-1", 200), output("DP-1", 100)]);
+        assert_eq!(manager.output_scale(Some("eDP-1")), 200);
+        assert_eq!(manager.output_scale(Some("DP-1")), 100);
+        assert_eq!(manager.output_scale(Some("HDMI-A-1")), 200);
+        assert_eq!(manager.output_scale(None), 200);
+    }
+}
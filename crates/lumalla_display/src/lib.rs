@@ -1,18 +1,56 @@
 use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Context;
-use lumalla_shared::Comms;
-use lumalla_wayland_protocol::registry::InterfaceIndex;
+use lumalla_shared::{
+    Comms, DmabufAttributes, DmabufFormats, DrmDeviceState, FocusMode, Output, PresentedFrame,
+    Transform, Viewport, WindowRule, Zone,
+};
+use lumalla_wayland_protocol::{
+    Ctx,
+    protocols::presentation_time::{
+        WP_PRESENTATION_FEEDBACK_KIND_HW_CLOCK, WP_PRESENTATION_FEEDBACK_KIND_HW_COMPLETION,
+        WP_PRESENTATION_FEEDBACK_KIND_VSYNC, WP_PRESENTATION_FEEDBACK_KIND_ZERO_COPY,
+    },
+    registry::InterfaceIndex,
+};
 
-use crate::{seat::SeatManager, shm::ShmManager, surface::SurfaceManager};
+use crate::{
+    apps::AppRegistry,
+    data_device::{DataDeviceManager, DragTarget},
+    dmabuf::DmabufManager,
+    focus::{FocusStack, FocusTrigger},
+    fractional_scale::{FractionalScaleManager, preferred_scale},
+    output::{OutputManager, absolute_input_output},
+    presentation::PresentationManager,
+    seat::{PointerTarget, SeatManager},
+    shm::ShmManager,
+    surface::SurfaceManager,
+    window_manager::{WindowChanges, WindowManager},
+};
 
+mod apps;
+mod data_device;
+mod dmabuf;
+mod focus;
+mod fractional_scale;
+mod output;
+mod presentation;
 mod protocols;
 mod seat;
 mod shm;
 mod surface;
+#[cfg(test)]
+mod test_ids;
+mod window_manager;
+mod window_rules;
+mod workspace;
 
-pub use lumalla_wayland_protocol::{ClientConnection, ClientId, Wayland};
-pub use seat::KeyboardModifiers;
+pub use lumalla_wayland_protocol::{ClientConnection, ClientId, ObjectId, Wayland};
+pub use seat::{AxisSource, AxisValue, CursorImage, KeyboardModifiers, PointerAxis};
+pub use surface::{Rectangle, SurfaceLayer};
 
 pub struct DisplayMessage;
 
@@ -20,21 +58,95 @@ pub struct DisplayMessage;
 pub struct CommittedFrame {
     pub client_id: ClientId,
     pub surface_id: lumalla_wayland_protocol::ObjectId,
+    /// Main surface of the sub-surface tree; equal to `surface_id` for main surfaces.
+    pub root_id: lumalla_wayland_protocol::ObjectId,
     pub buffer_id: lumalla_wayland_protocol::ObjectId,
     pub pixels: Vec<u8>,
     pub width: usize,
     pub height: usize,
     pub stride: usize,
     pub format: u32,
+    /// Parts of the buffer that changed since the previous commit, in
+    /// buffer coordinates.
+    pub damage: Vec<Rectangle>,
+    /// Scale the buffer is drawn at.
+    pub scale: i32,
+    /// Transform the client drew the buffer with.
+    pub transform: Transform,
+    /// How the surface crops and scales the buffer.
+    pub viewport: Viewport,
+}
+
+/// A client dmabuf committed to a surface; it is shown without copying.
+#[derive(Debug)]
+pub struct CommittedDmabuf {
+    pub client_id: ClientId,
+    pub surface_id: lumalla_wayland_protocol::ObjectId,
+    /// Main surface of the sub-surface tree; equal to `surface_id` for main surfaces.
+    pub root_id: lumalla_wayland_protocol::ObjectId,
+    pub buffer_id: lumalla_wayland_protocol::ObjectId,
+    pub attributes: Arc<DmabufAttributes>,
+    /// Parts of the buffer that changed since the previous commit, in
+    /// buffer coordinates.
+    pub damage: Vec<Rectangle>,
+    /// Scale the buffer is drawn at.
+    pub scale: i32,
+    /// Transform the client drew the buffer with.
+    pub transform: Transform,
+    /// How the surface crops and scales the buffer.
+    pub viewport: Viewport,
 }
 
 #[derive(Debug)]
 pub enum SurfaceUpdate {
     Frame(CommittedFrame),
+    /// New contents of a surface with the cursor role.
+    CursorFrame(CommittedFrame),
+    Dmabuf(CommittedDmabuf),
+    /// A dmabuf buffer was destroyed; it is dropped once no surface shows it.
+    BufferDestroyed {
+        client_id: ClientId,
+        buffer_id: lumalla_wayland_protocol::ObjectId,
+    },
     Unmapped {
         client_id: ClientId,
         surface_id: lumalla_wayland_protocol::ObjectId,
     },
+    /// Stacking order and placement of a main surface and its sub-surfaces.
+    Tree {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+        layers: Vec<SurfaceLayer>,
+    },
+    /// A main surface was placed at `(x, y)` in the global layout.
+    Moved {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+        x: i32,
+        y: i32,
+    },
+    /// A main surface is drawn with `opacity` percent opacity.
+    Opacity {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+        opacity: u32,
+    },
+    /// A main surface was stacked above all others.
+    Raised {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+    },
+    /// A main surface's workspace was shown or hidden.
+    Visibility {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+        visible: bool,
+    },
+    /// A tree is waiting for a frame callback without having new contents.
+    FrameRequested {
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+    },
 }
 
 pub struct DisplayState {
@@ -42,8 +154,22 @@ pub struct DisplayState {
     globals: Globals,
     surface_manager: SurfaceManager,
     shm_manager: ShmManager,
+    dmabuf_manager: DmabufManager,
     seat_manager: SeatManager,
+    data_device_manager: DataDeviceManager,
+    output_manager: OutputManager,
+    presentation_manager: PresentationManager,
+    fractional_scale_manager: FractionalScaleManager,
+    window_manager: WindowManager,
+    focus_stack: FocusStack,
+    apps: AppRegistry,
     surface_updates: VecDeque<SurfaceUpdate>,
+    /// Mapped main surfaces from bottom to top, stacked like the renderer's
+    /// scene; pointer input goes to the topmost one under the cursor.
+    stacking: Vec<(ClientId, lumalla_wayland_protocol::ObjectId)>,
+    /// xdg_popup surfaces holding an explicit grab, from bottom to top. They
+    /// all belong to one client.
+    popup_grab: Vec<(ClientId, lumalla_wayland_protocol::ObjectId)>,
 }
 
 impl DisplayState {
@@ -53,8 +179,18 @@ impl DisplayState {
             globals: Globals::default(),
             surface_manager: SurfaceManager::default(),
             shm_manager: ShmManager::default(),
+            dmabuf_manager: DmabufManager::default(),
             seat_manager: SeatManager::default(),
+            data_device_manager: DataDeviceManager::default(),
+            output_manager: OutputManager::default(),
+            presentation_manager: PresentationManager::default(),
+            fractional_scale_manager: FractionalScaleManager::default(),
+            window_manager: WindowManager::default(),
+            focus_stack: FocusStack::default(),
+            apps: AppRegistry::default(),
             surface_updates: VecDeque::new(),
+            stacking: Vec::new(),
+            popup_grab: Vec::new(),
         })
     }
 
@@ -87,15 +223,907 @@ impl DisplayState {
 
     pub fn remove_client(&mut self, client_id: ClientId) {
         self.shm_manager.delete_client(client_id);
+        self.dmabuf_manager.delete_client(client_id);
         self.surface_manager.delete_client(client_id);
+        self.seat_manager.delete_client(client_id);
+        self.output_manager.delete_client(client_id);
+        self.presentation_manager.delete_client(client_id);
+        self.fractional_scale_manager.delete_client(client_id);
+        self.window_manager.delete_client(client_id);
+        self.focus_stack.delete_client(client_id);
+        self.apps.delete_client(client_id);
+        self.stacking.retain(|(owner, _)| *owner != client_id);
+        self.popup_grab.retain(|(owner, _)| *owner != client_id);
+        let focused_clients = self.seat_manager.focused_clients();
+        self.data_device_manager
+            .delete_client(client_id, &focused_clients);
         self.surface_updates.retain(|update| match update {
-            SurfaceUpdate::Frame(frame) => frame.client_id != client_id,
-            SurfaceUpdate::Unmapped {
+            SurfaceUpdate::Frame(frame) | SurfaceUpdate::CursorFrame(frame) => {
+                frame.client_id != client_id
+            }
+            SurfaceUpdate::Dmabuf(dmabuf) => dmabuf.client_id != client_id,
+            SurfaceUpdate::BufferDestroyed {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Unmapped {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Tree {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Moved {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Opacity {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Raised {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::Visibility {
+                client_id: owner, ..
+            }
+            | SurfaceUpdate::FrameRequested {
                 client_id: owner, ..
             } => *owner != client_id,
         });
     }
 
+    /// Advertises the scanned-out outputs as wl_output globals and confines
+    /// the cursor to them. Client buffers shown by removed outputs are released.
+    ///
+    /// Surfaces are asked to draw at the scale of the output their window is on.
+    pub fn set_outputs(
+        &mut self,
+        outputs: &[Output],
+        drm_devices: &[DrmDeviceState],
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        self.output_manager
+            .set_outputs(outputs, drm_devices, &mut self.globals, clients);
+        self.fractional_scale_manager.set_outputs(outputs);
+        let changes = self.window_manager.set_outputs(outputs);
+        self.apply_window_changes(clients, changes);
+        for (client_id, buffer_id) in self.dmabuf_manager.retain_outputs(outputs) {
+            if let Some(client) = clients.get_mut(&client_id) {
+                client.writer_mut().wl_buffer_release(buffer_id);
+            }
+        }
+        let layout = outputs
+            .iter()
+            .map(|output| Rectangle {
+                x: output.location.0,
+                y: output.location.1,
+                width: output.logical_size().0,
+                height: output.logical_size().1,
+            })
+            .collect();
+        let absolute_output = absolute_input_output(outputs, drm_devices).map(|output| {
+            let rectangle = Rectangle {
+                x: output.location.0,
+                y: output.location.1,
+                width: output.logical_size().0,
+                height: output.logical_size().1,
+            };
+            (rectangle, output.transform)
+        });
+        self.seat_manager.set_output_layout(layout, absolute_output);
+        for client in clients.values_mut() {
+            self.update_preferred_scales(&mut client.ctx());
+        }
+    }
+
+    /// Offers the formats and modifiers client dmabufs can be imported in;
+    /// `None` withdraws the zwp_linux_dmabuf_v1 global.
+    pub fn set_dmabuf_formats(
+        &mut self,
+        formats: Option<DmabufFormats>,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        self.dmabuf_manager
+            .set_formats(formats, &mut self.globals, clients);
+    }
+
+    pub fn cursor_position(&self) -> (f64, f64) {
+        self.seat_manager.pointer_position()
+    }
+
+    /// Cursor image the focused client set, if any; the default image is
+    /// shown otherwise.
+    pub fn cursor(&self) -> Option<CursorImage> {
+        self.seat_manager.cursor()
+    }
+
+    pub fn handle_pointer_motion(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        dx: f64,
+        dy: f64,
+    ) {
+        self.seat_manager.move_pointer(dx, dy);
+        self.update_pointer_focus(clients, Some(time_msec));
+    }
+
+    /// Moves the cursor to a position normalized to the tablet's output.
+    pub fn handle_pointer_motion_absolute(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        x: f64,
+        y: f64,
+    ) {
+        self.seat_manager.warp_pointer_normalized(x, y);
+        self.update_pointer_focus(clients, Some(time_msec));
+    }
+
+    pub fn handle_pointer_button(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        button: u32,
+        pressed: bool,
+    ) {
+        if self.data_device_manager.is_dragging() {
+            self.seat_manager.track_pointer_button(button, pressed);
+            if !self.seat_manager.is_pointer_grabbed() {
+                self.data_device_manager.drop_drag();
+                self.update_pointer_focus(clients, None);
+            }
+            return;
+        }
+        if pressed {
+            let grab_client = self.popup_grab.first().map(|(client_id, _)| *client_id);
+            let focus_client = self
+                .seat_manager
+                .pointer_focus()
+                .map(|(client_id, _)| client_id);
+            if grab_client.is_some() && grab_client != focus_client {
+                self.dismiss_popups(clients);
+            }
+            self.focus_window_under_pointer(clients, FocusTrigger::Press);
+        }
+        self.seat_manager
+            .handle_pointer_button(clients, time_msec, button, pressed);
+        if !pressed && !self.seat_manager.is_pointer_grabbed() {
+            self.update_pointer_focus(clients, None);
+        }
+    }
+
+    pub fn handle_pointer_axis(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        axis: PointerAxis,
+    ) {
+        if !self.data_device_manager.is_dragging() {
+            self.seat_manager
+                .handle_pointer_axis(clients, time_msec, axis);
+        }
+    }
+
+    /// Starts a touch point at a position normalized to the touchscreen's output.
+    pub fn handle_touch_down(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+        x: f64,
+        y: f64,
+    ) {
+        let Some(target) = self
+            .seat_manager
+            .layout_position(x, y)
+            .and_then(|position| self.surface_under(position))
+        else {
+            return;
+        };
+        self.seat_manager
+            .handle_touch_down(clients, time_msec, id, target);
+    }
+
+    /// Moves a touch point; it stays with the surface it went down on.
+    pub fn handle_touch_motion(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+        x: f64,
+        y: f64,
+    ) {
+        let Some((x, y)) = self.seat_manager.layout_position(x, y) else {
+            return;
+        };
+        let Some((surface_x, surface_y)) =
+            self.seat_manager
+                .touch_point(id)
+                .and_then(|(client_id, surface_id)| {
+                    self.global_surface_position(client_id, surface_id)
+                })
+        else {
+            return;
+        };
+        self.seat_manager.handle_touch_motion(
+            clients,
+            time_msec,
+            id,
+            (x - f64::from(surface_x), y - f64::from(surface_y)),
+        );
+    }
+
+    pub fn handle_touch_up(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+    ) {
+        self.seat_manager.handle_touch_up(clients, time_msec, id);
+    }
+
+    pub fn handle_touch_cancel(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        id: i32,
+    ) {
+        self.seat_manager.handle_touch_cancel(clients, id);
+    }
+
+    pub fn handle_touch_frame(&mut self, clients: &mut HashMap<ClientId, ClientConnection>) {
+        self.seat_manager.handle_touch_frame(clients);
+    }
+
+    /// Re-evaluates pointer focus after the scene under a resting cursor changed.
+    pub fn refresh_pointer_focus(&mut self, clients: &mut HashMap<ClientId, ClientConnection>) {
+        self.update_pointer_focus(clients, None);
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.data_device_manager.is_dragging()
+    }
+
+    /// Icon surface of the drag in progress.
+    pub fn drag_icon(&self) -> Option<(ClientId, lumalla_wayland_protocol::ObjectId)> {
+        self.data_device_manager.drag_icon()
+    }
+
+    /// Writes clipboard and drag-and-drop events queued for other clients.
+    pub fn dispatch_data_device_events(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        for (client_id, event) in self.data_device_manager.take_events() {
+            if let Some(client) = clients.get_mut(&client_id) {
+                self.data_device_manager.deliver(&mut client.ctx(), event);
+            }
+        }
+    }
+
+    fn update_pointer_focus(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: Option<u32>,
+    ) {
+        let (x, y) = self.seat_manager.pointer_position();
+        if self.data_device_manager.is_dragging() {
+            // The pointer leaves clients while a drag is in progress.
+            self.seat_manager.set_pointer_focus(clients, None, None);
+            let target = self.surface_under((x, y)).map(|target| DragTarget {
+                client_id: target.client_id,
+                surface_id: target.surface_id,
+                x: target.x as f32,
+                y: target.y as f32,
+            });
+            self.data_device_manager.drag_motion(time_msec, target);
+            return;
+        }
+        let target = if self.seat_manager.is_pointer_grabbed() {
+            // Focus stays on the pressed surface until all buttons are released.
+            self.seat_manager
+                .pointer_focus()
+                .and_then(|(client_id, surface_id)| {
+                    let (surface_x, surface_y) =
+                        self.global_surface_position(client_id, surface_id)?;
+                    Some(PointerTarget {
+                        client_id,
+                        surface_id,
+                        x: x - f64::from(surface_x),
+                        y: y - f64::from(surface_y),
+                    })
+                })
+        } else {
+            self.surface_under((x, y))
+        };
+        self.seat_manager
+            .set_pointer_focus(clients, time_msec, target);
+        if time_msec.is_some() && target.is_some() {
+            self.focus_window_under_pointer(clients, FocusTrigger::Motion);
+        }
+    }
+
+    /// Focuses and raises the window of the surface with pointer focus if
+    /// the focus mode reacts to `trigger`.
+    fn focus_window_under_pointer(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        trigger: FocusTrigger,
+    ) {
+        if !self.focus_stack.focuses_on(trigger) {
+            return;
+        }
+        let Some((client_id, surface_id)) = self.seat_manager.pointer_focus() else {
+            return;
+        };
+        let root_id = self.surface_manager.tree_root(client_id, surface_id);
+        if self.window_manager.is_mapped(client_id, root_id) {
+            self.focus_window(clients, client_id, root_id);
+        }
+    }
+
+    /// Moves a window to the top of the focus stack and of the scene, and
+    /// enters the keyboards on it.
+    fn focus_window(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+    ) {
+        self.focus_stack.focus(client_id, root_id);
+        if self.stacking.contains(&(client_id, root_id)) {
+            // Popups stay right above their window.
+            let mut raised = vec![(client_id, root_id)];
+            raised.extend(
+                self.window_popups(client_id, root_id)
+                    .into_iter()
+                    .map(|popup_id| (client_id, popup_id)),
+            );
+            if !self.stacking.ends_with(&raised) {
+                self.stacking.retain(|surface| !raised.contains(surface));
+                for (client_id, root_id) in raised {
+                    self.stacking.push((client_id, root_id));
+                    self.surface_updates
+                        .push_back(SurfaceUpdate::Raised { client_id, root_id });
+                }
+            }
+        }
+        self.refresh_keyboard_focus(clients);
+    }
+
+    /// Mapped xdg_popups of a window, including nested ones, in stacking
+    /// order.
+    fn window_popups(
+        &self,
+        client_id: ClientId,
+        window_id: lumalla_wayland_protocol::ObjectId,
+    ) -> Vec<lumalla_wayland_protocol::ObjectId> {
+        self.stacking
+            .iter()
+            .filter(|&&(owner, root_id)| {
+                owner == client_id
+                    && root_id != window_id
+                    && self.surface_window(client_id, root_id) == window_id
+            })
+            .map(|&(_, root_id)| root_id)
+            .collect()
+    }
+
+    /// Moves the popups of a window to their parents again.
+    fn move_popups(&mut self, client_id: ClientId, window_id: lumalla_wayland_protocol::ObjectId) {
+        for root_id in self.window_popups(client_id, window_id) {
+            let (x, y) = self.surface_location(client_id, root_id);
+            self.surface_updates.push_back(SurfaceUpdate::Moved {
+                client_id,
+                root_id,
+                x,
+                y,
+            });
+        }
+    }
+
+    /// Ends the popup grab, dismissing the grabbing popups from the top.
+    fn dismiss_popups(&mut self, clients: &mut HashMap<ClientId, ClientConnection>) {
+        for (client_id, surface_id) in std::mem::take(&mut self.popup_grab).into_iter().rev() {
+            let Some(client) = clients.get_mut(&client_id) else {
+                continue;
+            };
+            let mut ctx = client.ctx();
+            if let Some(popup_id) = self.surface_manager.xdg_popup(client_id, surface_id) {
+                ctx.writer.xdg_popup_popup_done(popup_id);
+            }
+            if self.stacking.contains(&(client_id, surface_id)) {
+                self.push_unmapped(&mut ctx, surface_id);
+            }
+        }
+    }
+
+    /// Enters the keyboards on the window at the top of the focus stack,
+    /// e.g. after windows were mapped or closed.
+    pub fn refresh_keyboard_focus(&mut self, clients: &mut HashMap<ClientId, ClientConnection>) {
+        let target = self.keyboard_target();
+        if self.seat_manager.set_keyboard_focus(clients, target)
+            && let Some((client_id, _)) = target
+        {
+            self.data_device_manager.focus_changed(client_id);
+        }
+    }
+
+    /// The surface the keyboards should be entered on: the topmost popup
+    /// holding a grab once it is shown, else the focused window.
+    fn keyboard_target(&self) -> Option<(ClientId, lumalla_wayland_protocol::ObjectId)> {
+        self.popup_grab
+            .last()
+            .copied()
+            .filter(|popup| self.stacking.contains(popup))
+            .or_else(|| self.focused_window())
+    }
+
+    /// The shown window the keyboards should be entered on.
+    fn focused_window(&self) -> Option<(ClientId, lumalla_wayland_protocol::ObjectId)> {
+        self.focus_stack
+            .focused(|client_id, root_id| self.window_manager.is_visible(client_id, root_id))
+    }
+
+    /// Changes how pointer input moves the keyboard focus.
+    pub fn set_focus_mode(&mut self, mode: FocusMode) {
+        self.focus_stack.set_mode(mode);
+    }
+
+    /// Focuses and raises the window `index` places down the focus stack.
+    ///
+    /// Returns `false` if no window is shown.
+    pub fn tab(&mut self, index: usize, clients: &mut HashMap<ClientId, ClientConnection>) -> bool {
+        let window_manager = &self.window_manager;
+        let Some((client_id, root_id)) = self.focus_stack.cycle(index, |client_id, root_id| {
+            window_manager.is_visible(client_id, root_id)
+        }) else {
+            return false;
+        };
+        self.focus_window(clients, client_id, root_id);
+        true
+    }
+
+    /// Asks the focused window to close.
+    ///
+    /// Returns the window, or `None` if no window is focused. wl_shell has no
+    /// close event, so those windows only go away when their client is killed.
+    pub fn close_focused_window(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> Option<(ClientId, lumalla_wayland_protocol::ObjectId)> {
+        let (client_id, root_id) = self.focused_window()?;
+        if let Some(toplevel_id) = self.surface_manager.xdg_toplevel(client_id, root_id)
+            && let Some(client) = clients.get_mut(&client_id)
+        {
+            client.writer_mut().xdg_toplevel_close(toplevel_id);
+        }
+        Some((client_id, root_id))
+    }
+
+    /// Whether a window is still shown, e.g. after it was asked to close.
+    pub fn is_window_mapped(
+        &self,
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+    ) -> bool {
+        self.window_manager.is_mapped(client_id, root_id)
+    }
+
+    /// Focuses and raises the most recently focused window of `app_id`,
+    /// placing it back in its zone or on its output and showing its
+    /// workspace.
+    ///
+    /// Returns `false` if the app shows no window.
+    pub fn focus_app(
+        &mut self,
+        app_id: &str,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> bool {
+        let Some((client_id, root_id)) = self.focus_stack.most_recent(self.apps.windows(app_id))
+        else {
+            return false;
+        };
+        let changes = self.window_manager.show_workspace_of(client_id, root_id);
+        self.apply_window_changes(clients, changes);
+        self.place_client_window(clients, client_id, root_id);
+        self.focus_window(clients, client_id, root_id);
+        true
+    }
+
+    /// Shows the workspace `name` on its output, creating it on the output
+    /// under the pointer if needed.
+    ///
+    /// Returns `false` while no output is connected.
+    pub fn switch_workspace(
+        &mut self,
+        name: &str,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> bool {
+        let Some(output) = self
+            .window_manager
+            .output_at(self.seat_manager.pointer_position())
+            .map(str::to_string)
+        else {
+            return false;
+        };
+        let changes = self.window_manager.switch_workspace(name, &output);
+        self.apply_window_changes(clients, changes);
+        true
+    }
+
+    /// Moves the focused window to the workspace `name`, creating it on the
+    /// window's output if needed.
+    ///
+    /// Returns `false` if no window is focused.
+    pub fn move_current_window_to_workspace(
+        &mut self,
+        name: &str,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> bool {
+        let Some((client_id, root_id)) = self.focused_window() else {
+            return false;
+        };
+        let Some(changes) = self
+            .window_manager
+            .move_window_to_workspace(client_id, root_id, name)
+        else {
+            return false;
+        };
+        self.apply_window_changes(clients, changes);
+        true
+    }
+
+    /// Shows the workspace `name` with its windows on `output`.
+    ///
+    /// Returns `false` if there is no such workspace or output.
+    pub fn move_workspace_to_output(
+        &mut self,
+        name: &str,
+        output: &str,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> bool {
+        let Some(changes) = self.window_manager.move_workspace_to_output(name, output) else {
+            return false;
+        };
+        self.apply_window_changes(clients, changes);
+        true
+    }
+
+    /// `(output, workspace)` of the workspace every output shows, sorted by
+    /// output.
+    pub fn shown_workspaces(&self) -> Vec<(String, String)> {
+        self.window_manager.shown_workspaces()
+    }
+
+    /// Places moved windows again, shows or hides the mapped windows whose
+    /// workspace changed visibility, and moves the keyboard focus off
+    /// hidden windows.
+    fn apply_window_changes(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        changes: WindowChanges,
+    ) {
+        for (client_id, surface_id) in changes.moved {
+            self.place_client_window(clients, client_id, surface_id);
+        }
+        for ((client_id, window_id), visible) in changes.visibility {
+            if self.window_manager.is_mapped(client_id, window_id) {
+                let popups = self.window_popups(client_id, window_id);
+                for root_id in std::iter::once(window_id).chain(popups) {
+                    self.surface_updates.push_back(SurfaceUpdate::Visibility {
+                        client_id,
+                        root_id,
+                        visible,
+                    });
+                }
+            }
+        }
+        self.refresh_keyboard_focus(clients);
+    }
+
+    /// Records that `app_id` is being started because it shows no window.
+    ///
+    /// Returns `false` if an earlier start has not shown a window yet.
+    pub fn launch_app(&mut self, app_id: &str) -> bool {
+        self.apps.launch(app_id, Instant::now())
+    }
+
+    /// Re-files a mapped toplevel under its app_id after it changed.
+    pub(crate) fn track_app_id(
+        &mut self,
+        client_id: ClientId,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) {
+        if let Some(properties) = self
+            .surface_manager
+            .window_properties(client_id, surface_id)
+        {
+            self.apps
+                .set_app_id(client_id, surface_id, properties.app_id);
+        }
+    }
+
+    /// Input-accepting surface under a global position, searching the
+    /// stacked main surfaces from the top.
+    fn surface_under(&self, (x, y): (f64, f64)) -> Option<PointerTarget> {
+        self.stacking
+            .iter()
+            .rev()
+            .filter(|&&(client_id, root_id)| {
+                let window_id = self.surface_window(client_id, root_id);
+                self.window_manager.is_visible(client_id, window_id)
+            })
+            .find_map(|&(client_id, root_id)| {
+                let (window_x, window_y) = self.surface_location(client_id, root_id);
+                let position = (x - f64::from(window_x), y - f64::from(window_y));
+                let (surface_id, x, y) = self
+                    .surface_manager
+                    .surface_at(client_id, root_id, position)?;
+                Some(PointerTarget {
+                    client_id,
+                    surface_id,
+                    x,
+                    y,
+                })
+            })
+    }
+
+    /// Position of a surface in the global layout, including the location of
+    /// the window it belongs to.
+    fn global_surface_position(
+        &self,
+        client_id: ClientId,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) -> Option<(i32, i32)> {
+        let (x, y) = self
+            .surface_manager
+            .surface_position(client_id, surface_id)?;
+        let root_id = self.surface_manager.tree_root(client_id, surface_id);
+        let (window_x, window_y) = self.surface_location(client_id, root_id);
+        Some((x + window_x, y + window_y))
+    }
+
+    /// Position of a main surface in the global layout: where its window
+    /// was placed, or where an xdg_popup's positioner puts it next to its
+    /// parent.
+    fn surface_location(
+        &self,
+        client_id: ClientId,
+        root_id: lumalla_wayland_protocol::ObjectId,
+    ) -> (i32, i32) {
+        let Some((parent_id, (x, y))) = self.surface_manager.popup_offset(client_id, root_id)
+        else {
+            return self.window_manager.location(client_id, root_id);
+        };
+        let (parent_x, parent_y) = self.surface_location(client_id, parent_id);
+        (parent_x + x, parent_y + y)
+    }
+
+    /// Replaces the zones toplevels are placed in. Windows of removed zones
+    /// move to the default zone.
+    pub fn set_zones(
+        &mut self,
+        zones: Vec<Zone>,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        for (client_id, surface_id) in self.window_manager.set_zones(zones) {
+            self.place_client_window(clients, client_id, surface_id);
+        }
+    }
+
+    /// Replaces the window rules. They apply to windows opened, renamed or
+    /// reparented from now on.
+    pub fn set_window_rules(&mut self, rules: Vec<WindowRule>) {
+        self.window_manager.set_rules(rules);
+    }
+
+    /// Moves the focused window to the zone `name`.
+    ///
+    /// Returns `false` if there is no such window or zone.
+    pub fn move_current_window_to_zone(
+        &mut self,
+        name: &str,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) -> bool {
+        let Some((client_id, surface_id)) = self.focused_window() else {
+            return false;
+        };
+        if !self.window_manager.move_window(client_id, surface_id, name) {
+            return false;
+        }
+        self.place_client_window(clients, client_id, surface_id);
+        true
+    }
+
+    /// Applies the window rules matching a toplevel's app_id, title and
+    /// parent, placing it again if their effects changed.
+    ///
+    /// Returns `false` if the window was left alone.
+    pub(crate) fn apply_window_rules(
+        &mut self,
+        ctx: &mut Ctx,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) -> bool {
+        let client_id = ctx.client_id;
+        let Some(properties) = self
+            .surface_manager
+            .window_properties(client_id, surface_id)
+        else {
+            return false;
+        };
+        if !self
+            .window_manager
+            .apply_rules(client_id, surface_id, &properties)
+        {
+            return false;
+        }
+        let fullscreen = self.window_manager.is_fullscreen(client_id, surface_id);
+        self.surface_manager
+            .set_fullscreen(client_id, surface_id, fullscreen);
+        self.place_window(ctx, surface_id);
+        if self.window_manager.is_mapped(client_id, surface_id) {
+            self.surface_updates.push_back(SurfaceUpdate::Opacity {
+                client_id,
+                root_id: surface_id,
+                opacity: self.window_manager.opacity(client_id, surface_id),
+            });
+            self.surface_updates.push_back(SurfaceUpdate::Visibility {
+                client_id,
+                root_id: surface_id,
+                visible: self.window_manager.is_visible(client_id, surface_id),
+            });
+        }
+        true
+    }
+
+    fn place_client_window(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        client_id: ClientId,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) {
+        if let Some(client) = clients.get_mut(&client_id) {
+            self.place_window(&mut client.ctx(), surface_id);
+        }
+    }
+
+    /// Resizes a window to its zone and moves it there once it is shown.
+    pub(crate) fn place_window(
+        &mut self,
+        ctx: &mut Ctx,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) {
+        self.update_preferred_scales(ctx);
+        self.configure_window(ctx, surface_id);
+        let client_id = ctx.client_id;
+        if self.window_manager.is_mapped(client_id, surface_id) {
+            let (x, y) = self.window_manager.location(client_id, surface_id);
+            self.surface_updates.push_back(SurfaceUpdate::Moved {
+                client_id,
+                root_id: surface_id,
+                x,
+                y,
+            });
+            self.move_popups(client_id, surface_id);
+        }
+    }
+
+    /// Toplevel a surface belongs to through sub-surfaces and popups.
+    fn surface_window(
+        &self,
+        client_id: ClientId,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) -> lumalla_wayland_protocol::ObjectId {
+        let mut root_id = self.surface_manager.tree_root(client_id, surface_id);
+        while let Some(parent_id) = self.surface_manager.xdg_popup_parent(client_id, root_id) {
+            root_id = self.surface_manager.tree_root(client_id, parent_id);
+        }
+        root_id
+    }
+
+    /// Scale in percent a surface is preferred to be drawn at: the scale of
+    /// the output its window is on.
+    pub(crate) fn preferred_scale(
+        &self,
+        client_id: ClientId,
+        surface_id: lumalla_wayland_protocol::ObjectId,
+    ) -> u32 {
+        let window_id = self.surface_window(client_id, surface_id);
+        let output = self.window_manager.window_output(client_id, window_id);
+        self.fractional_scale_manager.output_scale(output)
+    }
+
+    /// Sends the surfaces of a client whose window moved to an output with
+    /// another scale their new preferred scale.
+    pub(crate) fn update_preferred_scales(&mut self, ctx: &mut Ctx) {
+        for surface_id in self.fractional_scale_manager.surfaces(ctx.client_id) {
+            let scale = self.preferred_scale(ctx.client_id, surface_id);
+            if let Some(id) =
+                self.fractional_scale_manager
+                    .set_scale(ctx.client_id, surface_id, scale)
+            {
+                ctx.writer
+                    .wp_fractional_scale_v1_preferred_scale(id)
+                    .scale(preferred_scale(scale));
+            }
+        }
+    }
+
+    /// Answers the frame callbacks and presentation feedback of the trees
+    /// shown by a completed page flip, and releases the client buffers it
+    /// stopped scanning out.
+    pub fn frame_presented(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        frame: &PresentedFrame,
+    ) {
+        let resource_key = |(owner_id, object_id): (u32, u32)| {
+            Some((
+                ClientId::new(NonZeroU32::new(owner_id)?),
+                ObjectId::new(NonZeroU32::new(object_id)?),
+            ))
+        };
+        let presented: Vec<_> = frame
+            .windows
+            .iter()
+            .filter_map(|&window| resource_key(window))
+            .collect();
+        let scanned_out = frame.client_buffer.and_then(resource_key);
+
+        for (client_id, buffer_id) in self
+            .dmabuf_manager
+            .frame_presented(&frame.output, scanned_out)
+        {
+            if let Some(client) = clients.get_mut(&client_id) {
+                client.writer_mut().wl_buffer_release(buffer_id);
+            }
+        }
+
+        for (client_id, callback) in self
+            .surface_manager
+            .take_presented_frame_callbacks(&presented)
+        {
+            let Some(client) = clients.get_mut(&client_id) else {
+                continue;
+            };
+            let ctx = client.ctx();
+            ctx.writer
+                .wl_callback_done(callback)
+                .callback_data(frame.time_msec());
+            ctx.registry.free_object(callback, ctx.writer);
+        }
+
+        for (client_id, surface_id, feedback) in
+            self.presentation_manager.take_presented(&presented)
+        {
+            let Some(client) = clients.get_mut(&client_id) else {
+                continue;
+            };
+            let mut flags = WP_PRESENTATION_FEEDBACK_KIND_VSYNC
+                | WP_PRESENTATION_FEEDBACK_KIND_HW_CLOCK
+                | WP_PRESENTATION_FEEDBACK_KIND_HW_COMPLETION;
+            if scanned_out.is_some_and(|(owner, buffer_id)| {
+                owner == client_id && self.dmabuf_manager.shows(client_id, surface_id, buffer_id)
+            }) {
+                flags |= WP_PRESENTATION_FEEDBACK_KIND_ZERO_COPY;
+            }
+            let ctx = client.ctx();
+            for output_id in self.output_manager.bound_outputs(client_id, &frame.output) {
+                ctx.writer
+                    .wp_presentation_feedback_sync_output(feedback)
+                    .output(output_id);
+            }
+            ctx.writer
+                .wp_presentation_feedback_presented(feedback)
+                .tv_sec_hi((frame.tv_sec >> 32) as u32)
+                .tv_sec_lo(frame.tv_sec as u32)
+                .tv_nsec(frame.tv_nsec)
+                .refresh(frame.refresh_nsec)
+                .seq_hi((frame.sequence >> 32) as u32)
+                .seq_lo(frame.sequence as u32)
+                .flags(flags);
+            ctx.registry.free_object(feedback, ctx.writer);
+        }
+    }
+
     pub fn take_surface_updates(&mut self) -> impl Iterator<Item = SurfaceUpdate> + '_ {
         self.surface_updates.drain(..)
     }
@@ -140,6 +1168,9 @@ struct Global {
     name: &'static str,
     version: u32,
     interface_index: InterfaceIndex,
+    /// Removed globals are no longer advertised, but clients may still bind
+    /// them until they have processed `wl_registry.global_remove`.
+    removed: bool,
 }
 
 impl Default for Globals {
@@ -148,9 +1179,15 @@ impl Default for Globals {
             globals: HashMap::new(),
             next_id: 1,
         };
-        globals.register_version(InterfaceIndex::WlCompositor, 1, [].into_iter());
+        globals.register_version(InterfaceIndex::WlCompositor, 4, [].into_iter());
         globals.register_version(InterfaceIndex::WlShm, 1, [].into_iter());
         globals.register_version(InterfaceIndex::WlShell, 1, [].into_iter());
+        globals.register(InterfaceIndex::WlSubcompositor, [].into_iter());
+        globals.register(InterfaceIndex::WlDataDeviceManager, [].into_iter());
+        globals.register(InterfaceIndex::XdgWmBase, [].into_iter());
+        globals.register(InterfaceIndex::WpPresentation, [].into_iter());
+        globals.register(InterfaceIndex::WpViewporter, [].into_iter());
+        globals.register(InterfaceIndex::WpFractionalScaleManagerV1, [].into_iter());
         globals
     }
 }
@@ -185,6 +1222,7 @@ impl Globals {
                 name: interface_index.interface_name(),
                 version,
                 interface_index,
+                removed: false,
             },
         );
         for client in client_connections {
@@ -193,8 +1231,23 @@ impl Globals {
         id
     }
 
+    /// Withdraws a global and announces its removal to all connected clients.
+    fn remove<'connection>(
+        &mut self,
+        id: GlobalId,
+        client_connections: impl Iterator<Item = &'connection mut ClientConnection>,
+    ) {
+        let Some(global) = self.globals.get_mut(&id) else {
+            return;
+        };
+        global.removed = true;
+        for client in client_connections {
+            client.broadcast_global_remove(id);
+        }
+    }
+
     fn iter(&self) -> impl Iterator<Item = (&u32, &Global)> {
-        self.globals.iter()
+        self.globals.iter().filter(|(_, global)| !global.removed)
     }
 
     fn get(&self, id: u32) -> Option<&Global> {
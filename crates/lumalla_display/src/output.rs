@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use lumalla_shared::{DrmConnector, DrmDeviceState, DrmMode, Output, Transform};
+use lumalla_wayland_protocol::{
+    ClientConnection, ClientId, ObjectId,
+    buffer::Writer,
+    protocols::wayland::{
+        WL_OUTPUT_MODE_CURRENT, WL_OUTPUT_MODE_PREFERRED, WL_OUTPUT_SUBPIXEL_UNKNOWN,
+    },
+    registry::InterfaceIndex,
+};
+
+use crate::{GlobalId, Globals};
+
+/// Monitor state advertised through a wl_output global.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputHead {
+    /// Connector name, e.g. `HDMI-A-1`.
+    name: String,
+    description: String,
+    /// Connector type, e.g. `eDP`; used as the model since EDID is not parsed.
+    model: String,
+    location: (i32, i32),
+    /// Physical size in millimeters; zero when unknown.
+    physical_size: (i32, i32),
+    mode_size: (i32, i32),
+    /// Integer scale clients without fractional scaling draw at; the
+    /// output scale rounded up.
+    scale: i32,
+    /// Transform the output shows the layout with.
+    transform: Transform,
+    refresh_mhz: i32,
+    preferred: bool,
+}
+
+impl OutputHead {
+    fn new(output: &Output, drm_devices: &[DrmDeviceState]) -> Self {
+        let connector = drm_devices
+            .iter()
+            .flat_map(|device| &device.connectors)
+            .find(|connector| connector.name == output.name);
+        let (refresh_hz, preferred) = connector
+            .and_then(|connector| current_mode(connector, output.size))
+            .unwrap_or((0, false));
+        Self {
+            name: output.name.clone(),
+            description: output.description.clone(),
+            model: connector.map_or_else(String::new, |connector| connector.connector_type.clone()),
+            location: output.location,
+            physical_size: connector.map_or((0, 0), |connector| {
+                (connector.mm_width as i32, connector.mm_height as i32)
+            }),
+            mode_size: output.size,
+            scale: output.scale.max(1).div_ceil(100) as i32,
+            transform: output.transform,
+            refresh_mhz: refresh_hz as i32 * 1000,
+            preferred,
+        }
+    }
+
+    fn send(&self, writer: &mut Writer, output_id: ObjectId, version: u32) {
+        writer
+            .wl_output_geometry(output_id)
+            .x(self.location.0)
+            .y(self.location.1)
+            .physical_width(self.physical_size.0)
+            .physical_height(self.physical_size.1)
+            .subpixel(WL_OUTPUT_SUBPIXEL_UNKNOWN as i32)
+            .make("Unknown")
+            .model(&self.model)
+            .transform(self.transform.to_wayland());
+        let mut flags = WL_OUTPUT_MODE_CURRENT;
+        if self.preferred {
+            flags |= WL_OUTPUT_MODE_PREFERRED;
+        }
+        writer
+            .wl_output_mode(output_id)
+            .flags(flags)
+            .width(self.mode_size.0)
+            .height(self.mode_size.1)
+            .refresh(self.refresh_mhz);
+        if version >= 2 {
+            writer.wl_output_scale(output_id).factor(self.scale);
+        }
+        if version >= 4 {
+            writer.wl_output_name(output_id).name(&self.name);
+            writer
+                .wl_output_description(output_id)
+                .description(&self.description);
+        }
+        if version >= 2 {
+            writer.wl_output_done(output_id);
+        }
+    }
+}
+
+/// Refresh rate and preferred flag of the mode being scanned out.
+///
+/// The renderer scans out the preferred mode unless another mode is
+/// configured by name, in which case it takes the first mode of that name.
+fn current_mode(connector: &DrmConnector, (width, height): (i32, i32)) -> Option<(u32, bool)> {
+    let matches_size = |mode: &&DrmMode| mode.width as i32 == width && mode.height as i32 == height;
+    connector
+        .modes
+        .iter()
+        .filter(matches_size)
+        .find(|mode| mode.preferred)
+        .or_else(|| connector.modes.iter().find(matches_size))
+        .map(|mode| (mode.refresh_hz, mode.preferred))
+}
+
+/// Output touchscreens and tablets are mapped to: the built-in panel, or the
+/// first output if there is none.
+///
+/// Input events do not tell the devices apart, so all of them share it.
+pub(crate) fn absolute_input_output<'a>(
+    outputs: &'a [Output],
+    drm_devices: &[DrmDeviceState],
+) -> Option<&'a Output> {
+    let built_in = |output: &&Output| {
+        drm_devices
+            .iter()
+            .flat_map(|device| &device.connectors)
+            .find(|connector| connector.name == output.name)
+            .is_some_and(|connector| {
+                matches!(connector.connector_type.as_str(), "eDP" | "LVDS" | "DSI")
+            })
+    };
+    outputs.iter().find(built_in).or_else(|| outputs.first())
+}
+
+struct BoundOutput {
+    client_id: ClientId,
+    id: ObjectId,
+    version: u32,
+    global_id: GlobalId,
+}
+
+/// One wl_output global per output that is being scanned out.
+#[derive(Default)]
+pub struct OutputManager {
+    heads: HashMap<GlobalId, OutputHead>,
+    bound: Vec<BoundOutput>,
+}
+
+impl OutputManager {
+    /// Registers globals for new outputs and removes those of vanished ones.
+    /// Clients bound to an output whose state changed receive it again.
+    pub fn set_outputs(
+        &mut self,
+        outputs: &[Output],
+        drm_devices: &[DrmDeviceState],
+        globals: &mut Globals,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+    ) {
+        let heads: Vec<OutputHead> = outputs
+            .iter()
+            .map(|output| OutputHead::new(output, drm_devices))
+            .collect();
+        let removed: Vec<GlobalId> = self
+            .heads
+            .iter()
+            .filter(|(_, current)| !heads.iter().any(|head| head.name == current.name))
+            .map(|(global_id, _)| *global_id)
+            .collect();
+        for global_id in removed {
+            self.heads.remove(&global_id);
+            self.bound.retain(|bound| bound.global_id != global_id);
+            globals.remove(global_id, clients.values_mut());
+        }
+        for head in heads {
+            let existing = self
+                .heads
+                .iter()
+                .find(|(_, current)| current.name == head.name)
+                .map(|(global_id, current)| (*global_id, *current == head));
+            match existing {
+                Some((_, true)) => {}
+                Some((global_id, false)) => {
+                    for bound in self
+                        .bound
+                        .iter()
+                        .filter(|bound| bound.global_id == global_id)
+                    {
+                        if let Some(client) = clients.get_mut(&bound.client_id) {
+                            head.send(client.writer_mut(), bound.id, bound.version);
+                        }
+                    }
+                    self.heads.insert(global_id, head);
+                }
+                None => {
+                    let global_id =
+                        globals.register(InterfaceIndex::WlOutput, clients.values_mut());
+                    self.heads.insert(global_id, head);
+                }
+            }
+        }
+    }
+
+    /// Sends the output state to a newly bound wl_output. Binds to outputs
+    /// that were removed in the meantime yield an inert object.
+    pub fn bind_output(
+        &mut self,
+        client_id: ClientId,
+        output_id: ObjectId,
+        version: u32,
+        global_id: GlobalId,
+        writer: &mut Writer,
+    ) {
+        let Some(head) = self.heads.get(&global_id) else {
+            return;
+        };
+        head.send(writer, output_id, version);
+        self.bound.push(BoundOutput {
+            client_id,
+            id: output_id,
+            version,
+            global_id,
+        });
+    }
+
+    /// wl_output objects a client bound for the output with the given name.
+    pub fn bound_outputs<'a>(
+        &'a self,
+        client_id: ClientId,
+        name: &'a str,
+    ) -> impl Iterator<Item = ObjectId> + 'a {
+        self.bound
+            .iter()
+            .filter(move |bound| {
+                bound.client_id == client_id
+                    && self
+                        .heads
+                        .get(&bound.global_id)
+                        .is_some_and(|head| head.name == name)
+            })
+            .map(|bound| bound.id)
+    }
+
+    pub fn release_output(&mut self, client_id: ClientId, output_id: ObjectId) {
+        self.bound
+            .retain(|bound| !(bound.client_id == client_id && bound.id == output_id));
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.bound.retain(|bound| bound.client_id != client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn mode(width: u32, height: u32, refresh_hz: u32, preferred: bool) -> DrmMode {
+        DrmMode {
+            width,
+            height,
+            refresh_hz,
+            name: format!("{width}x{height}"),
+            preferred,
+        }
+    }
+
+    fn drm_device(modes: Vec<DrmMode>) -> DrmDeviceState {
+        DrmDeviceState {
+            path: PathBuf::from("/dev/dri/card0"),
+            connectors: vec![DrmConnector {
+                name: String::from("eDP-1"),
+                connector_id: 40,
+                connector_type: String::from("eDP"),
+                connected: true,
+                mm_width: 310,
+                mm_height: 170,
+                modes,
+            }],
+            selected_render_device: true,
+        }
+    }
+
+    fn output(name: &str, size: (i32, i32)) -> Output {
+        Output {
+            name: String::from(name),
+            description: String::from("/dev/dri/card0"),
+            location: (0, 0),
+            size,
+            scale: 100,
+            transform: Transform::Normal,
+        }
+    }
+
+    #[test]
+    fn head_uses_connector_size_and_current_mode() {
+        let devices = [drm_device(vec![
+            mode(1920, 1080, 60, false),
+            mode(1920, 1080, 144, true),
+            mode(1280, 720, 60, false),
+        ])];
+
+        let head = OutputHead::new(&output("eDP-1", (1920, 1080)), &devices);
+        assert_eq!(head.physical_size, (310, 170));
+        assert_eq!(head.model, "eDP");
+        assert_eq!((head.refresh_mhz, head.preferred), (144_000, true));
+
+        let head = OutputHead::new(&output("eDP-1", (1280, 720)), &devices);
+        assert_eq!((head.refresh_mhz, head.preferred), (60_000, false));
+        assert_eq!(head.scale, 1);
+
+        let mut scaled = output("eDP-1", (1920, 1080));
+        scaled.scale = 150;
+        assert_eq!(OutputHead::new(&scaled, &devices).scale, 2);
+    }
+
+    #[test]
+    fn absolute_input_prefers_the_built_in_panel() {
+        let devices = [drm_device(vec![mode(1920, 1080, 60, true)])];
+        let monitor = output("HDMI-A-1", (2560, 1440));
+        let panel = output("eDP-1", (1920, 1080));
+
+        let outputs = [monitor.clone(), panel.clone()];
+        assert_eq!(absolute_input_output(&outputs, &devices), Some(&panel));
+        let outputs = [monitor.clone()];
+        assert_eq!(absolute_input_output(&outputs, &devices), Some(&monitor));
+        assert_eq!(absolute_input_output(&[], &devices), None);
+    }
+
+    #[test]
+    fn outputs_are_added_and_removed_as_globals() {
+        let devices = [drm_device(vec![mode(1920, 1080, 60, true)])];
+        let mut manager = OutputManager::default();
+        let mut globals = Globals::default();
+        let mut clients = HashMap::new();
+        let advertised_outputs = |globals: &Globals| {
+            globals
+                .iter()
+                .filter(|(_, global)| global.interface_index == InterfaceIndex::WlOutput)
+                .count()
+        };
+
+        manager.set_outputs(
+            &[
+                output("eDP-1", (1920, 1080)),
+                output("HDMI-A-1", (1920, 1080)),
+            ],
+            &devices,
+            &mut globals,
+            &mut clients,
+        );
+        assert_eq!(advertised_outputs(&globals), 2);
+
+        manager.set_outputs(
+            &[output("eDP-1", (1920, 1080))],
+            &devices,
+            &mut globals,
+            &mut clients,
+        );
+        assert_eq!(advertised_outputs(&globals), 1);
+        assert_eq!(manager.heads.len(), 1);
+    }
+}
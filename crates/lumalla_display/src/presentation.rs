@@ -0,0 +1,134 @@
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+/// A wp_presentation_feedback object and the surface it was requested for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Feedback {
+    client_id: ClientId,
+    id: ObjectId,
+    surface_id: ObjectId,
+    /// Main surface of the tree; set once the feedback's commit was applied.
+    root_id: Option<ObjectId>,
+}
+
+/// Presentation feedback requested through wp_presentation, from the
+/// request until the content update is presented or discarded.
+#[derive(Debug, Default)]
+pub struct PresentationManager {
+    feedback: Vec<Feedback>,
+}
+
+impl PresentationManager {
+    /// Requests feedback for the next content update of a surface.
+    pub fn add_feedback(&mut self, client_id: ClientId, surface_id: ObjectId, id: ObjectId) {
+        self.feedback.push(Feedback {
+            client_id,
+            id,
+            surface_id,
+            root_id: None,
+        });
+    }
+
+    /// Attaches the requested feedback to an applied commit of a surface.
+    ///
+    /// Returns the feedback of earlier content updates of the surface that
+    /// were superseded before reaching the screen.
+    pub fn commit(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        root_id: ObjectId,
+    ) -> Vec<ObjectId> {
+        let mut discarded = Vec::new();
+        self.feedback.retain_mut(|feedback| {
+            if feedback.client_id != client_id || feedback.surface_id != surface_id {
+                return true;
+            }
+            if feedback.root_id.is_some() {
+                discarded.push(feedback.id);
+                return false;
+            }
+            feedback.root_id = Some(root_id);
+            true
+        });
+        discarded
+    }
+
+    /// Removes and returns the feedback of the trees whose main surface is in
+    /// `presented`, with the surface it was requested for.
+    pub fn take_presented(
+        &mut self,
+        presented: &[(ClientId, ObjectId)],
+    ) -> Vec<(ClientId, ObjectId, ObjectId)> {
+        let mut done = Vec::new();
+        self.feedback.retain(|feedback| {
+            let is_presented = feedback
+                .root_id
+                .is_some_and(|root_id| presented.contains(&(feedback.client_id, root_id)));
+            if is_presented {
+                done.push((feedback.client_id, feedback.surface_id, feedback.id));
+            }
+            !is_presented
+        });
+        done
+    }
+
+    /// Removes and returns the feedback of a destroyed surface, which is discarded.
+    pub fn surface_destroyed(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Vec<ObjectId> {
+        let mut discarded = Vec::new();
+        self.feedback.retain(|feedback| {
+            let destroyed = feedback.client_id == client_id && feedback.surface_id == surface_id;
+            if destroyed {
+                discarded.push(feedback.id);
+            }
+            !destroyed
+        });
+        discarded
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.feedback
+            .retain(|feedback| feedback.client_id != client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    #[test]
+    fn feedback_is_presented_with_its_committed_tree() {
+        let mut manager = PresentationManager::default();
+        manager.add_feedback(client(1), object(3), object(10));
+
+        // Not committed yet, so not part of the presented frame.
+        assert!(manager.take_presented(&[(client(1), object(2))]).is_empty());
+        assert!(manager.commit(client(1), object(3), object(2)).is_empty());
+        assert_eq!(
+            manager.take_presented(&[(client(1), object(2))]),
+            [(client(1), object(3), object(10))]
+        );
+    }
+
+    #[test]
+    fn superseded_and_destroyed_feedback_is_discarded() {
+        let mut manager = PresentationManager::default();
+        manager.add_feedback(client(1), object(2), object(10));
+        manager.commit(client(1), object(2), object(2));
+        manager.add_feedback(client(1), object(2), object(11));
+
+        assert_eq!(
+            manager.commit(client(1), object(2), object(2)),
+            [object(10)]
+        );
+        assert_eq!(
+            manager.surface_destroyed(client(1), object(2)),
+            [object(11)]
+        );
+        assert!(manager.take_presented(&[(client(1), object(2))]).is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use lumalla_wayland_protocol::{
+    Ctx, ObjectId,
+    protocols::{
+        FractionalScaleV1Protocol, fractional_scale_v1::*, wayland::WL_DISPLAY_ERROR_INVALID_OBJECT,
+    },
+    registry::{DISPLAY_OBJECT_ID, InterfaceIndex},
+};
+
+use super::wayland::register_object;
+use crate::{DisplayState, fractional_scale::preferred_scale};
+
+impl FractionalScaleV1Protocol for DisplayState {}
+
+impl WpFractionalScaleManagerV1 for DisplayState {
+    fn destroy(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &WpFractionalScaleManagerV1Destroy<'_>,
+    ) {
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn get_fractional_scale(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WpFractionalScaleManagerV1GetFractionalScale<'_>,
+    ) {
+        if !register_object(ctx, params.id(), InterfaceIndex::WpFractionalScaleV1, 1) {
+            return;
+        }
+        let id = *params.id();
+        if ctx.registry.interface_index(params.surface()) != Some(InterfaceIndex::WlSurface) {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(params.surface())
+                .code(WL_DISPLAY_ERROR_INVALID_OBJECT)
+                .message("Unknown surface");
+            return;
+        }
+        let scale = self.preferred_scale(ctx.client_id, params.surface());
+        if !self
+            .fractional_scale_manager
+            .add(ctx.client_id, params.surface(), id, scale)
+        {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(object_id)
+                .code(WP_FRACTIONAL_SCALE_MANAGER_V1_ERROR_FRACTIONAL_SCALE_EXISTS)
+                .message("Surface already has a fractional scale object");
+            return;
+        }
+        ctx.writer
+            .wp_fractional_scale_v1_preferred_scale(id)
+            .scale(preferred_scale(scale));
+    }
+}
+
+impl WpFractionalScaleV1 for DisplayState {
+    fn destroy(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &WpFractionalScaleV1Destroy<'_>,
+    ) {
+        self.fractional_scale_manager
+            .remove(ctx.client_id, object_id);
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+}
@@ -0,0 +1,236 @@
+use std::os::fd::{FromRawFd, OwnedFd};
+
+use log::debug;
+use lumalla_shared::DmabufPlane;
+use lumalla_wayland_protocol::{
+    Ctx, ObjectId,
+    protocols::{
+        LinuxDmabufV1Protocol, linux_dmabuf_v1::*, wayland::WL_DISPLAY_ERROR_INVALID_OBJECT,
+    },
+    registry::{DISPLAY_OBJECT_ID, InterfaceIndex},
+};
+
+use super::wayland::register_object;
+use crate::{DisplayState, dmabuf::DmabufError};
+
+impl LinuxDmabufV1Protocol for DisplayState {}
+
+fn report_dmabuf_error(ctx: &mut Ctx, object_id: ObjectId, error: DmabufError) {
+    let (code, message) = match error {
+        DmabufError::UnknownParams => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown buffer params"),
+        DmabufError::AlreadyUsed => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_ALREADY_USED,
+            "Buffer params were already used",
+        ),
+        DmabufError::PlaneIdx => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_PLANE_IDX,
+            "Plane index out of bounds",
+        ),
+        DmabufError::PlaneSet => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_PLANE_SET,
+            "Plane was already set",
+        ),
+        DmabufError::Incomplete => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_INCOMPLETE,
+            "Missing or extra planes",
+        ),
+        DmabufError::InvalidFormat => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_INVALID_FORMAT,
+            "Unsupported format and modifier",
+        ),
+        DmabufError::InvalidDimensions => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_INVALID_DIMENSIONS,
+            "Buffer dimensions must be positive",
+        ),
+        DmabufError::OutOfBounds => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_OUT_OF_BOUNDS,
+            "Plane exceeds its dmabuf",
+        ),
+        DmabufError::Unsupported => (
+            ZWP_LINUX_BUFFER_PARAMS_V1_ERROR_INVALID_WL_BUFFER,
+            "Buffer cannot be shown",
+        ),
+    };
+    debug!("Linux dmabuf protocol error: {message}");
+    ctx.writer
+        .wl_display_error(DISPLAY_OBJECT_ID)
+        .object_id(object_id)
+        .code(code)
+        .message(message);
+}
+
+impl ZwpLinuxDmabufV1 for DisplayState {
+    fn destroy(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &ZwpLinuxDmabufV1Destroy<'_>,
+    ) {
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn create_params(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &ZwpLinuxDmabufV1CreateParams<'_>,
+    ) {
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version);
+        if !register_object(
+            ctx,
+            params.params_id(),
+            InterfaceIndex::ZwpLinuxBufferParamsV1,
+            version,
+        ) {
+            return;
+        }
+        self.dmabuf_manager
+            .create_params(ctx.client_id, *params.params_id());
+    }
+
+    fn get_default_feedback(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &ZwpLinuxDmabufV1GetDefaultFeedback<'_>,
+    ) {
+        self.create_feedback(ctx, object_id, params.id());
+    }
+
+    fn get_surface_feedback(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &ZwpLinuxDmabufV1GetSurfaceFeedback<'_>,
+    ) {
+        // Every surface is drawn by the render device, so the default
+        // feedback applies to all of them.
+        self.create_feedback(ctx, object_id, params.id());
+    }
+}
+
+impl ZwpLinuxBufferParamsV1 for DisplayState {
+    fn destroy(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &ZwpLinuxBufferParamsV1Destroy<'_>,
+    ) {
+        self.dmabuf_manager.delete_params(ctx.client_id, object_id);
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn add(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &ZwpLinuxBufferParamsV1Add<'_>) {
+        // SAFETY: The descriptor was received with this request and is owned by us.
+        let fd = unsafe { OwnedFd::from_raw_fd(params.fd()) };
+        let plane = DmabufPlane {
+            fd,
+            offset: params.offset(),
+            stride: params.stride(),
+        };
+        let modifier = (u64::from(params.modifier_hi()) << 32) | u64::from(params.modifier_lo());
+        if let Err(error) = self.dmabuf_manager.add_plane(
+            ctx.client_id,
+            object_id,
+            params.plane_idx(),
+            plane,
+            modifier,
+        ) {
+            report_dmabuf_error(ctx, object_id, error);
+        }
+    }
+
+    fn create(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &ZwpLinuxBufferParamsV1Create<'_>,
+    ) {
+        let buffer_id = match ctx.registry.create_object(InterfaceIndex::WlBuffer, 1) {
+            Ok(buffer_id) => buffer_id,
+            Err(err) => {
+                debug!("Failed to create wl_buffer for dmabuf: {err}");
+                ctx.writer.zwp_linux_buffer_params_v1_failed(object_id);
+                return;
+            }
+        };
+        match self.dmabuf_manager.create_buffer(
+            ctx.client_id,
+            object_id,
+            buffer_id,
+            (params.width(), params.height()),
+            params.format(),
+            params.flags(),
+        ) {
+            Ok(()) => {
+                ctx.writer
+                    .zwp_linux_buffer_params_v1_created(object_id)
+                    .buffer(buffer_id);
+            }
+            Err(DmabufError::Unsupported) => {
+                ctx.registry.free_object(buffer_id, ctx.writer);
+                ctx.writer.zwp_linux_buffer_params_v1_failed(object_id);
+            }
+            Err(error) => {
+                ctx.registry.free_object(buffer_id, ctx.writer);
+                report_dmabuf_error(ctx, object_id, error);
+            }
+        }
+    }
+
+    fn create_immed(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &ZwpLinuxBufferParamsV1CreateImmed<'_>,
+    ) {
+        if !register_object(ctx, params.buffer_id(), InterfaceIndex::WlBuffer, 1) {
+            return;
+        }
+        if let Err(error) = self.dmabuf_manager.create_buffer(
+            ctx.client_id,
+            object_id,
+            *params.buffer_id(),
+            (params.width(), params.height()),
+            params.format(),
+            params.flags(),
+        ) {
+            report_dmabuf_error(ctx, object_id, error);
+        }
+    }
+}
+
+impl ZwpLinuxDmabufFeedbackV1 for DisplayState {
+    fn destroy(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &ZwpLinuxDmabufFeedbackV1Destroy<'_>,
+    ) {
+        self.dmabuf_manager
+            .delete_feedback(ctx.client_id, object_id);
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+}
+
+impl DisplayState {
+    fn create_feedback(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        id: lumalla_wayland_protocol::NewObjectId,
+    ) {
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version);
+        if !register_object(ctx, id, InterfaceIndex::ZwpLinuxDmabufFeedbackV1, version) {
+            return;
+        }
+        self.dmabuf_manager
+            .add_feedback(ctx.client_id, *id, ctx.writer);
+    }
+}
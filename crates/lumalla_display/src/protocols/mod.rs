@@ -1 +1,6 @@
+mod fractional_scale;
+mod linux_dmabuf;
+mod presentation_time;
+mod viewporter;
 mod wayland;
+mod xdg_shell;
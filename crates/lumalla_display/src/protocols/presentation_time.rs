@@ -0,0 +1,51 @@
+use lumalla_wayland_protocol::{
+    Ctx, ObjectId,
+    protocols::{PresentationTimeProtocol, presentation_time::*},
+    registry::InterfaceIndex,
+};
+
+use super::wayland::register_object;
+use crate::DisplayState;
+
+impl PresentationTimeProtocol for DisplayState {}
+
+impl WpPresentation for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WpPresentationDestroy<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn feedback(
+        &mut self,
+        ctx: &mut Ctx,
+        _object_id: ObjectId,
+        params: &WpPresentationFeedback<'_>,
+    ) {
+        if !register_object(
+            ctx,
+            params.callback(),
+            InterfaceIndex::WpPresentationFeedback,
+            1,
+        ) {
+            return;
+        }
+        let feedback_id = *params.callback();
+        if ctx.registry.interface_index(params.surface()) != Some(InterfaceIndex::WlSurface) {
+            ctx.writer.wp_presentation_feedback_discarded(feedback_id);
+            ctx.registry.free_object(feedback_id, ctx.writer);
+            return;
+        }
+        self.presentation_manager
+            .add_feedback(ctx.client_id, params.surface(), feedback_id);
+    }
+}
+
+impl DisplayState {
+    /// Tells the client that the content updates of the given feedback
+    /// objects never reached the screen.
+    pub(super) fn discard_presentation_feedback(&mut self, ctx: &mut Ctx, feedback: Vec<ObjectId>) {
+        for feedback_id in feedback {
+            ctx.writer.wp_presentation_feedback_discarded(feedback_id);
+            ctx.registry.free_object(feedback_id, ctx.writer);
+        }
+    }
+}
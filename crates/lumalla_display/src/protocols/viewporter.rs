@@ -0,0 +1,72 @@
+use lumalla_wayland_protocol::{
+    Ctx, ObjectId,
+    buffer::fixed_to_f32,
+    protocols::{ViewporterProtocol, viewporter::*},
+    registry::InterfaceIndex,
+};
+
+use super::wayland::{register_object, report_surface_error};
+use crate::DisplayState;
+
+impl ViewporterProtocol for DisplayState {}
+
+impl WpViewporter for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WpViewporterDestroy<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn get_viewport(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WpViewporterGetViewport<'_>,
+    ) {
+        if !register_object(ctx, params.id(), InterfaceIndex::WpViewport, 1) {
+            return;
+        }
+        if let Err(error) =
+            self.surface_manager
+                .create_viewport(ctx.client_id, *params.id(), params.surface())
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+}
+
+impl WpViewport for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WpViewportDestroy<'_>) {
+        self.surface_manager
+            .destroy_viewport(ctx.client_id, object_id);
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn set_source(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WpViewportSetSource<'_>) {
+        let source = (
+            fixed_to_f32(params.x()),
+            fixed_to_f32(params.y()),
+            fixed_to_f32(params.width()),
+            fixed_to_f32(params.height()),
+        );
+        if let Err(error) =
+            self.surface_manager
+                .set_viewport_source(ctx.client_id, object_id, source)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn set_destination(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WpViewportSetDestination<'_>,
+    ) {
+        let destination = (params.width(), params.height());
+        if let Err(error) =
+            self.surface_manager
+                .set_viewport_destination(ctx.client_id, object_id, destination)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+}
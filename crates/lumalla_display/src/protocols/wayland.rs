@@ -1,19 +1,22 @@
+use std::os::fd::{FromRawFd, OwnedFd};
+
 use log::debug;
 use lumalla_wayland_protocol::{
-    Ctx, NewObjectId, ObjectId,
-    protocols::{WaylandProtocol, WlDisplay, wayland::*},
+    ClientId, Ctx, NewObjectId, ObjectId,
+    protocols::{WaylandProtocol, WlDisplay, viewporter::*, wayland::*, xdg_shell::*},
     registry::{DISPLAY_OBJECT_ID, InterfaceIndex},
 };
 
 use crate::{
-    CommittedFrame, DisplayState, GlobalId, SurfaceUpdate,
+    CommittedDmabuf, CommittedFrame, DisplayState, GlobalId, SurfaceUpdate,
+    data_device::DataDeviceError,
     shm::{ShmError, ShmErrorKind},
-    surface::{Rectangle, ShellMode, SurfaceError},
+    surface::{Rectangle, ShellMode, SurfaceCommit, SurfaceError},
 };
 
 impl WaylandProtocol for DisplayState {}
 
-fn register_object(
+pub(super) fn register_object(
     ctx: &mut Ctx,
     id: NewObjectId,
     interface: InterfaceIndex,
@@ -49,8 +52,62 @@ fn report_shm_error(ctx: &mut Ctx, object_id: ObjectId, error: &ShmError) {
         .message(&error.to_string());
 }
 
-fn report_surface_error(ctx: &mut Ctx, object_id: ObjectId, error: SurfaceError) {
+fn report_data_device_error(ctx: &mut Ctx, object_id: ObjectId, error: DataDeviceError) {
+    let interface = ctx.registry.interface_index(object_id);
     let (code, message) = match error {
+        DataDeviceError::UnknownSource => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown data source"),
+        DataDeviceError::UnknownDevice => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown data device"),
+        DataDeviceError::InvalidActionMask if interface == Some(InterfaceIndex::WlDataOffer) => (
+            WL_DATA_OFFER_ERROR_INVALID_ACTION_MASK,
+            "Invalid drag-and-drop action mask",
+        ),
+        DataDeviceError::InvalidActionMask => (
+            WL_DATA_SOURCE_ERROR_INVALID_ACTION_MASK,
+            "Invalid drag-and-drop action mask",
+        ),
+        DataDeviceError::InvalidAction => (
+            WL_DATA_OFFER_ERROR_INVALID_ACTION,
+            "Invalid preferred drag-and-drop action",
+        ),
+        DataDeviceError::InvalidSource => (
+            WL_DATA_SOURCE_ERROR_INVALID_SOURCE,
+            "Data source is already in use",
+        ),
+        DataDeviceError::UsedSource => (
+            WL_DATA_DEVICE_ERROR_USED_SOURCE,
+            "Data source was already used",
+        ),
+        DataDeviceError::InvalidFinish => (
+            WL_DATA_OFFER_ERROR_INVALID_FINISH,
+            "Offer is not an accepted and dropped drag",
+        ),
+        DataDeviceError::InvalidOffer => (
+            WL_DATA_OFFER_ERROR_INVALID_OFFER,
+            "Offer is no longer valid",
+        ),
+    };
+    ctx.writer
+        .wl_display_error(DISPLAY_OBJECT_ID)
+        .object_id(object_id)
+        .code(code)
+        .message(message);
+}
+
+pub(super) fn report_surface_error(ctx: &mut Ctx, object_id: ObjectId, error: SurfaceError) {
+    let interface = ctx.registry.interface_index(object_id);
+    let (code, message) = match error {
+        SurfaceError::RoleAlreadyAssigned if interface == Some(InterfaceIndex::WlSubcompositor) => {
+            (
+                WL_SUBCOMPOSITOR_ERROR_BAD_SURFACE,
+                "Surface already has a role",
+            )
+        }
+        SurfaceError::RoleAlreadyAssigned if interface == Some(InterfaceIndex::WlDataDevice) => {
+            (WL_DATA_DEVICE_ERROR_ROLE, "Surface already has a role")
+        }
+        SurfaceError::RoleAlreadyAssigned if interface == Some(InterfaceIndex::WlPointer) => {
+            (WL_POINTER_ERROR_ROLE, "Surface already has a role")
+        }
         SurfaceError::RoleAlreadyAssigned => (WL_SHELL_ERROR_ROLE, "Surface already has a role"),
         SurfaceError::UnknownSurface => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown surface"),
         SurfaceError::UnknownBuffer => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown buffer"),
@@ -58,6 +115,87 @@ fn report_surface_error(ctx: &mut Ctx, object_id: ObjectId, error: SurfaceError)
             (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown shell surface")
         }
         SurfaceError::UnknownRegion => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown region"),
+        SurfaceError::UnknownXdgSurface => (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown xdg_surface"),
+        SurfaceError::UnknownPositioner => {
+            (WL_DISPLAY_ERROR_INVALID_OBJECT, "Unknown xdg_positioner")
+        }
+        SurfaceError::AlreadyConstructed => (
+            XDG_SURFACE_ERROR_ALREADY_CONSTRUCTED,
+            "xdg_surface already has a role object",
+        ),
+        SurfaceError::NotConstructed => (
+            XDG_SURFACE_ERROR_NOT_CONSTRUCTED,
+            "xdg_surface has no role object",
+        ),
+        SurfaceError::InvalidSurfaceState => (
+            XDG_WM_BASE_ERROR_INVALID_SURFACE_STATE,
+            "Surface already has a buffer attached",
+        ),
+        SurfaceError::UnconfiguredBuffer => (
+            XDG_SURFACE_ERROR_UNCONFIGURED_BUFFER,
+            "Buffer attached before the first configure was acked",
+        ),
+        SurfaceError::InvalidSerial => (
+            XDG_SURFACE_ERROR_INVALID_SERIAL,
+            "Acked an unknown configure serial",
+        ),
+        SurfaceError::InvalidScale => (
+            WL_SURFACE_ERROR_INVALID_SCALE,
+            "Buffer scale must be positive",
+        ),
+        SurfaceError::InvalidTransform => (
+            WL_SURFACE_ERROR_INVALID_TRANSFORM,
+            "Unknown buffer transform",
+        ),
+        SurfaceError::InvalidSize if interface == Some(InterfaceIndex::WlSurface) => (
+            WL_SURFACE_ERROR_INVALID_SIZE,
+            "Buffer size is not a multiple of the buffer scale",
+        ),
+        SurfaceError::InvalidSize if interface == Some(InterfaceIndex::XdgToplevel) => {
+            (XDG_TOPLEVEL_ERROR_INVALID_SIZE, "Invalid min or max size")
+        }
+        SurfaceError::InvalidSize => (XDG_SURFACE_ERROR_INVALID_SIZE, "Invalid window geometry"),
+        SurfaceError::InvalidPositioner => (
+            XDG_WM_BASE_ERROR_INVALID_POSITIONER,
+            "Incomplete xdg_positioner",
+        ),
+        SurfaceError::InvalidParent if interface == Some(InterfaceIndex::WlSubcompositor) => (
+            WL_SUBCOMPOSITOR_ERROR_BAD_PARENT,
+            "Invalid sub-surface parent",
+        ),
+        SurfaceError::InvalidParent => (XDG_TOPLEVEL_ERROR_INVALID_PARENT, "Invalid parent"),
+        SurfaceError::InvalidSibling => (
+            WL_SUBSURFACE_ERROR_BAD_SURFACE,
+            "Surface is not a sibling or the parent",
+        ),
+        SurfaceError::InvalidPopupParent => (
+            XDG_WM_BASE_ERROR_INVALID_POPUP_PARENT,
+            "Invalid popup parent",
+        ),
+        SurfaceError::DefunctRoleObject => (
+            XDG_SURFACE_ERROR_DEFUNCT_ROLE_OBJECT,
+            "xdg_surface destroyed before its role object",
+        ),
+        SurfaceError::ViewportExists => (
+            WP_VIEWPORTER_ERROR_VIEWPORT_EXISTS,
+            "Surface already has a viewport",
+        ),
+        SurfaceError::InvalidViewport => (
+            WP_VIEWPORT_ERROR_BAD_VALUE,
+            "Invalid viewport source or destination",
+        ),
+        SurfaceError::InvalidViewportSize => (
+            WP_VIEWPORT_ERROR_BAD_SIZE,
+            "Viewport source size is not integer without a destination",
+        ),
+        SurfaceError::ViewportOutOfBuffer => (
+            WP_VIEWPORT_ERROR_OUT_OF_BUFFER,
+            "Viewport source extends beyond the buffer",
+        ),
+        SurfaceError::DefunctViewport => (
+            WP_VIEWPORT_ERROR_NO_SURFACE,
+            "Surface of the viewport was destroyed",
+        ),
     };
     ctx.writer
         .wl_display_error(DISPLAY_OBJECT_ID)
@@ -131,14 +269,34 @@ impl WlRegistry for DisplayState {
                 ctx.writer.wl_shm_format(*id).format(WL_SHM_FORMAT_XRGB8888);
             }
             _ if interface_name == InterfaceIndex::WlSeat.interface_name() => {
+                self.seat_manager.bind_seat(ctx.client_id, *id, global_id);
                 if requested_version >= 2 {
                     ctx.writer
                         .wl_seat_name(*id)
                         .name(self.seat_manager.get_name(global_id).unwrap_or_default());
                 }
+                ctx.writer.wl_seat_capabilities(*id).capabilities(
+                    WL_SEAT_CAPABILITY_KEYBOARD
+                        | WL_SEAT_CAPABILITY_POINTER
+                        | WL_SEAT_CAPABILITY_TOUCH,
+                );
+            }
+            _ if interface_name == InterfaceIndex::WpPresentation.interface_name() => {
                 ctx.writer
-                    .wl_seat_capabilities(*id)
-                    .capabilities(WL_SEAT_CAPABILITY_KEYBOARD);
+                    .wp_presentation_clock_id(*id)
+                    .clk_id(libc::CLOCK_MONOTONIC as u32);
+            }
+            _ if interface_name == InterfaceIndex::ZwpLinuxDmabufV1.interface_name() => {
+                self.dmabuf_manager.bind(ctx.writer, *id, requested_version);
+            }
+            _ if interface_name == InterfaceIndex::WlOutput.interface_name() => {
+                self.output_manager.bind_output(
+                    ctx.client_id,
+                    *id,
+                    requested_version,
+                    global_id,
+                    ctx.writer,
+                );
             }
             _ => {}
         }
@@ -246,96 +404,199 @@ impl WlBuffer for DisplayState {
     fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlBufferDestroy<'_>) {
         ctx.registry.free_object(object_id, &mut ctx.writer);
         self.shm_manager.delete_buffer(ctx.client_id, object_id);
+        if self.dmabuf_manager.delete_buffer(ctx.client_id, object_id) {
+            self.surface_updates
+                .push_back(SurfaceUpdate::BufferDestroyed {
+                    client_id: ctx.client_id,
+                    buffer_id: object_id,
+                });
+        }
     }
 }
 
 impl WlDataOffer for DisplayState {
-    fn accept(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataOfferAccept<'_>) {
-        todo!()
+    fn accept(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlDataOfferAccept<'_>) {
+        if let Err(error) = self.data_device_manager.accept(
+            ctx.client_id,
+            object_id,
+            params.mime_type().map(str::to_owned),
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
-    fn receive(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataOfferReceive<'_>) {
-        todo!()
+    fn receive(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlDataOfferReceive<'_>) {
+        // SAFETY: The descriptor was received with this request and is owned by us.
+        let fd = unsafe { OwnedFd::from_raw_fd(params.fd()) };
+        if let Err(error) = self.data_device_manager.receive(
+            ctx.client_id,
+            object_id,
+            params.mime_type().to_owned(),
+            fd,
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
-    fn destroy(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataOfferDestroy<'_>) {
-        todo!()
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlDataOfferDestroy<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        self.data_device_manager
+            .destroy_offer(ctx.client_id, object_id);
     }
 
-    fn finish(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataOfferFinish<'_>) {
-        todo!()
+    fn finish(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlDataOfferFinish<'_>) {
+        if let Err(error) = self.data_device_manager.finish(ctx.client_id, object_id) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
     fn set_actions(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataOfferSetActions<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataOfferSetActions<'_>,
     ) {
-        todo!()
+        if let Err(error) = self.data_device_manager.set_offer_actions(
+            ctx.client_id,
+            object_id,
+            params.dnd_actions(),
+            params.preferred_action(),
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 }
 
 impl WlDataSource for DisplayState {
-    fn offer(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataSourceOffer<'_>) {
-        todo!()
+    fn offer(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlDataSourceOffer<'_>) {
+        if let Err(error) = self.data_device_manager.offer_mime_type(
+            ctx.client_id,
+            object_id,
+            params.mime_type().to_owned(),
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
-    fn destroy(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataSourceDestroy<'_>) {
-        todo!()
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlDataSourceDestroy<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        let focused_clients = self.seat_manager.focused_clients();
+        self.data_device_manager
+            .destroy_source(ctx.client_id, object_id, &focused_clients);
     }
 
     fn set_actions(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataSourceSetActions<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataSourceSetActions<'_>,
     ) {
-        todo!()
+        if let Err(error) = self.data_device_manager.set_source_actions(
+            ctx.client_id,
+            object_id,
+            params.dnd_actions(),
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 }
 
 impl WlDataDevice for DisplayState {
     fn start_drag(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataDeviceStartDrag<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataDeviceStartDrag<'_>,
     ) {
-        todo!()
+        if !self
+            .seat_manager
+            .has_pointer_grab(ctx.client_id, params.serial())
+        {
+            debug!("Ignoring start_drag without a matching pointer grab");
+            return;
+        }
+        if let Some(icon) = params.icon()
+            && let Err(error) = self.surface_manager.set_drag_icon_role(ctx.client_id, icon)
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        if let Err(error) = self.data_device_manager.start_drag(
+            ctx.client_id,
+            object_id,
+            params.source(),
+            params.origin(),
+            params.icon(),
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
     fn set_selection(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataDeviceSetSelection<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataDeviceSetSelection<'_>,
     ) {
-        todo!()
+        let focused_clients = self.seat_manager.focused_clients();
+        if let Err(error) = self.data_device_manager.set_selection(
+            ctx.client_id,
+            object_id,
+            params.source(),
+            &focused_clients,
+        ) {
+            report_data_device_error(ctx, object_id, error);
+        }
     }
 
-    fn release(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlDataDeviceRelease<'_>) {
-        todo!()
+    fn release(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlDataDeviceRelease<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        self.data_device_manager
+            .destroy_device(ctx.client_id, object_id);
     }
 }
 
 impl WlDataDeviceManager for DisplayState {
     fn create_data_source(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataDeviceManagerCreateDataSource<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataDeviceManagerCreateDataSource<'_>,
     ) {
-        todo!()
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version.min(WL_DATA_SOURCE_VERSION));
+        if register_object(ctx, params.id(), InterfaceIndex::WlDataSource, version) {
+            self.data_device_manager
+                .create_source(ctx.client_id, *params.id(), version);
+        }
     }
 
     fn get_data_device(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlDataDeviceManagerGetDataDevice<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlDataDeviceManagerGetDataDevice<'_>,
     ) {
-        todo!()
+        let Some(seat) = self.seat_manager.seat_global(ctx.client_id, params.seat()) else {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(params.seat())
+                .code(WL_DISPLAY_ERROR_INVALID_OBJECT)
+                .message("Unknown wl_seat");
+            return;
+        };
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version.min(WL_DATA_DEVICE_VERSION));
+        if !register_object(ctx, params.id(), InterfaceIndex::WlDataDevice, version) {
+            return;
+        }
+        let focused = self.seat_manager.focused_clients().contains(&ctx.client_id);
+        self.data_device_manager
+            .create_device(ctx.client_id, *params.id(), version, seat, focused);
     }
 }
 
@@ -385,8 +646,11 @@ impl WlShellSurface for DisplayState {
             .surface_manager
             .surface_for_shell(ctx.client_id, object_id)
         {
-            self.seat_manager
-                .focus_keyboards_on_surface(ctx.client_id, surface_id, ctx.writer);
+            self.window_manager.add_window(ctx.client_id, surface_id);
+            if !self.apply_window_rules(ctx, surface_id) {
+                self.update_preferred_scales(ctx);
+                self.configure_window(ctx, surface_id);
+            }
         }
     }
 
@@ -458,7 +722,9 @@ impl WlShellSurface for DisplayState {
             params.title().to_owned(),
         ) {
             report_surface_error(ctx, object_id, error);
+            return;
         }
+        self.shell_surface_changed(ctx, object_id);
     }
 
     fn set_class(
@@ -473,12 +739,16 @@ impl WlShellSurface for DisplayState {
             params.class_().to_owned(),
         ) {
             report_surface_error(ctx, object_id, error);
+            return;
         }
+        self.shell_surface_changed(ctx, object_id);
     }
 }
 
 impl WlSurface for DisplayState {
     fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSurfaceDestroy<'_>) {
+        let root_id = self.surface_manager.tree_root(ctx.client_id, object_id);
+        let is_cursor = self.surface_manager.is_cursor(ctx.client_id, object_id);
         match self
             .surface_manager
             .destroy_surface(ctx.client_id, object_id)
@@ -487,14 +757,28 @@ impl WlSurface for DisplayState {
                 for callback in callbacks {
                     ctx.registry.free_object(callback, ctx.writer);
                 }
+                let feedback = self
+                    .presentation_manager
+                    .surface_destroyed(ctx.client_id, object_id);
+                self.discard_presentation_feedback(ctx, feedback);
                 if let Some(shell_id) = shell_id {
                     ctx.registry.free_object(shell_id, ctx.writer);
                 }
-                if was_mapped {
-                    self.surface_updates.push_back(SurfaceUpdate::Unmapped {
-                        client_id: ctx.client_id,
-                        surface_id: object_id,
-                    });
+                self.seat_manager
+                    .surface_destroyed(ctx.client_id, object_id);
+                self.fractional_scale_manager
+                    .surface_destroyed(ctx.client_id, object_id);
+                self.window_manager.remove_window(ctx.client_id, object_id);
+                if was_mapped || is_cursor {
+                    self.push_unmapped(ctx, object_id);
+                } else if let Some(buffer_id) = self
+                    .dmabuf_manager
+                    .release_surface(ctx.client_id, object_id)
+                {
+                    ctx.writer.wl_buffer_release(buffer_id);
+                }
+                if root_id != object_id {
+                    self.push_surface_tree(ctx.client_id, root_id);
                 }
             }
             Err(error) => {
@@ -597,87 +881,368 @@ impl WlSurface for DisplayState {
     fn commit(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSurfaceCommit<'_>) {
         let commit = match self.surface_manager.commit(ctx.client_id, object_id) {
             Ok(commit) => commit,
+            // Errors of the xdg role are raised on the xdg_surface.
+            Err(error @ (SurfaceError::NotConstructed | SurfaceError::UnconfiguredBuffer)) => {
+                let xdg_surface_id = self
+                    .surface_manager
+                    .xdg_surface(ctx.client_id, object_id)
+                    .unwrap_or(object_id);
+                report_surface_error(ctx, xdg_surface_id, error);
+                return;
+            }
             Err(error) => {
                 report_surface_error(ctx, object_id, error);
                 return;
             }
         };
+        if commit.cached {
+            return;
+        }
+        self.push_surface_tree(ctx.client_id, commit.root_id);
+        self.apply_surface_commit(ctx, commit);
+    }
+
+    fn set_buffer_transform(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSurfaceSetBufferTransform<'_>,
+    ) {
+        if let Err(error) =
+            self.surface_manager
+                .set_buffer_transform(ctx.client_id, object_id, params.transform())
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn set_buffer_scale(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSurfaceSetBufferScale<'_>,
+    ) {
+        if let Err(error) =
+            self.surface_manager
+                .set_buffer_scale(ctx.client_id, object_id, params.scale())
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn damage_buffer(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSurfaceDamageBuffer<'_>,
+    ) {
+        let rectangle = Rectangle {
+            x: params.x(),
+            y: params.y(),
+            width: params.width(),
+            height: params.height(),
+        };
+        if let Err(error) = self
+            .surface_manager
+            .damage_buffer(ctx.client_id, object_id, rectangle)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
 
-        if let Some(Some(buffer_id)) = commit.attached_buffer {
-            if commit.mapped {
+    fn offset(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlSurfaceOffset) {
+        todo!()
+    }
+}
+
+impl DisplayState {
+    /// Raises a buffer size error on the wp_viewport of the surface when the
+    /// viewport caused it.
+    fn report_buffer_size_error(&self, ctx: &mut Ctx, surface_id: ObjectId, error: SurfaceError) {
+        let viewport_id = match error {
+            SurfaceError::InvalidViewportSize | SurfaceError::ViewportOutOfBuffer => {
+                self.surface_manager.viewport(ctx.client_id, surface_id)
+            }
+            _ => None,
+        };
+        report_surface_error(ctx, viewport_id.unwrap_or(surface_id), error);
+    }
+
+    /// Forwards applied surface state, including sub-surface state applied
+    /// with it, to the renderer and the client.
+    ///
+    /// Returns `false` if a protocol error was raised.
+    fn apply_surface_commit(&mut self, ctx: &mut Ctx, commit: SurfaceCommit) -> bool {
+        // Sub-surface buffers are kept even while the parent is unmapped,
+        // because they are released right away.
+        let is_subsurface = commit.root_id != commit.surface_id;
+        let dmabuf = commit
+            .attached_buffer
+            .flatten()
+            .and_then(|buffer_id| self.dmabuf_manager.buffer(ctx.client_id, buffer_id));
+        if let (Some(Some(buffer_id)), Some(attributes)) = (commit.attached_buffer, dmabuf) {
+            // Dmabufs are sampled in place, so they are held until replaced.
+            if commit.mapped || is_subsurface {
+                let size = (attributes.width as i32, attributes.height as i32);
+                if let Err(error) =
+                    self.surface_manager
+                        .set_buffer_size(ctx.client_id, commit.surface_id, size)
+                {
+                    self.report_buffer_size_error(ctx, commit.surface_id, error);
+                    return false;
+                }
+                self.surface_updates
+                    .push_back(SurfaceUpdate::Dmabuf(CommittedDmabuf {
+                        client_id: ctx.client_id,
+                        surface_id: commit.surface_id,
+                        root_id: commit.root_id,
+                        buffer_id,
+                        attributes,
+                        damage: commit.damage_in_buffer(size),
+                        scale: commit.scale,
+                        transform: commit.transform,
+                        viewport: commit.viewport,
+                    }));
+                if !is_subsurface {
+                    self.show_window(ctx.client_id, commit.surface_id);
+                }
+                let released =
+                    self.dmabuf_manager
+                        .hold(ctx.client_id, commit.surface_id, buffer_id);
+                if let Some(released) = released {
+                    ctx.writer.wl_buffer_release(released);
+                }
+            } else {
+                ctx.writer.wl_buffer_release(buffer_id);
+            }
+        } else if let Some(Some(buffer_id)) = commit.attached_buffer {
+            if let Some(released) = self
+                .dmabuf_manager
+                .release_surface(ctx.client_id, commit.surface_id)
+            {
+                ctx.writer.wl_buffer_release(released);
+            }
+            let is_cursor = self
+                .surface_manager
+                .is_cursor(ctx.client_id, commit.surface_id);
+            if commit.mapped || is_subsurface || is_cursor {
                 match self.shm_manager.snapshot_buffer(ctx.client_id, buffer_id) {
                     Ok(snapshot) => {
-                        self.surface_updates
-                            .push_back(SurfaceUpdate::Frame(CommittedFrame {
-                                client_id: ctx.client_id,
-                                surface_id: commit.surface_id,
-                                buffer_id,
-                                pixels: snapshot.pixels,
-                                width: snapshot.width,
-                                height: snapshot.height,
-                                stride: snapshot.stride,
-                                format: snapshot.format,
-                            }));
+                        let size = (snapshot.width as i32, snapshot.height as i32);
+                        if let Err(error) = self.surface_manager.set_buffer_size(
+                            ctx.client_id,
+                            commit.surface_id,
+                            size,
+                        ) {
+                            self.report_buffer_size_error(ctx, commit.surface_id, error);
+                            return false;
+                        }
+                        let frame = CommittedFrame {
+                            client_id: ctx.client_id,
+                            surface_id: commit.surface_id,
+                            root_id: commit.root_id,
+                            buffer_id,
+                            pixels: snapshot.pixels,
+                            width: snapshot.width,
+                            height: snapshot.height,
+                            stride: snapshot.stride,
+                            format: snapshot.format,
+                            damage: commit.damage_in_buffer(size),
+                            scale: commit.scale,
+                            transform: commit.transform,
+                            viewport: commit.viewport,
+                        };
+                        if is_cursor {
+                            self.surface_updates
+                                .push_back(SurfaceUpdate::CursorFrame(frame));
+                        } else {
+                            self.surface_updates.push_back(SurfaceUpdate::Frame(frame));
+                            if !is_subsurface {
+                                self.show_window(ctx.client_id, commit.surface_id);
+                            }
+                        }
                     }
                     Err(error) => {
                         report_shm_error(ctx, buffer_id, &error);
-                        return;
+                        return false;
                     }
                 }
             }
             ctx.writer.wl_buffer_release(buffer_id);
         } else if commit.attached_buffer == Some(None) {
-            self.surface_updates.push_back(SurfaceUpdate::Unmapped {
-                client_id: ctx.client_id,
-                surface_id: commit.surface_id,
-            });
+            self.push_unmapped(ctx, commit.surface_id);
         }
 
-        for callback in commit.frame_callbacks {
-            ctx.writer.wl_callback_done(callback).callback_data(0);
-            ctx.registry.free_object(callback, ctx.writer);
+        // Commits without new contents still need a frame to answer their callbacks.
+        if commit.attached_buffer.is_none() && !commit.frame_callbacks.is_empty() {
+            self.surface_updates
+                .push_back(SurfaceUpdate::FrameRequested {
+                    client_id: ctx.client_id,
+                    root_id: commit.root_id,
+                });
+        }
+        self.surface_manager.queue_frame_callbacks(
+            ctx.client_id,
+            commit.surface_id,
+            commit.root_id,
+            commit.frame_callbacks,
+        );
+        let superseded =
+            self.presentation_manager
+                .commit(ctx.client_id, commit.surface_id, commit.root_id);
+        self.discard_presentation_feedback(ctx, superseded);
+
+        if commit.needs_configure {
+            self.configure_xdg_surface(ctx, commit.surface_id);
         }
+        commit
+            .subsurface_commits
+            .into_iter()
+            .all(|commit| self.apply_surface_commit(ctx, commit))
     }
 
-    fn set_buffer_transform(
-        &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSurfaceSetBufferTransform<'_>,
-    ) {
-        todo!()
+    /// Removes a surface from the scene and releases the dmabuf it showed.
+    pub(crate) fn push_unmapped(&mut self, ctx: &mut Ctx, surface_id: ObjectId) {
+        let client_id = ctx.client_id;
+        if let Some(buffer_id) = self.dmabuf_manager.release_surface(client_id, surface_id) {
+            ctx.writer.wl_buffer_release(buffer_id);
+        }
+        self.stacking
+            .retain(|window| *window != (client_id, surface_id));
+        self.popup_grab
+            .retain(|popup| *popup != (client_id, surface_id));
+        self.window_manager.window_unmapped(client_id, surface_id);
+        self.focus_stack.remove(client_id, surface_id);
+        self.apps.window_unmapped(client_id, surface_id);
+        self.surface_updates.push_back(SurfaceUpdate::Unmapped {
+            client_id,
+            surface_id,
+        });
     }
 
-    fn set_buffer_scale(
-        &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSurfaceSetBufferScale<'_>,
-    ) {
-        todo!()
+    /// Stacks a main surface with new contents on top when it was just
+    /// mapped, moving it to its window location, hiding it if its workspace
+    /// is not shown and focusing it.
+    fn show_window(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        if self
+            .surface_manager
+            .xdg_popup(client_id, surface_id)
+            .is_some()
+        {
+            self.show_popup(client_id, surface_id);
+            return;
+        }
+        if !self.stacking.contains(&(client_id, surface_id)) {
+            self.stacking.push((client_id, surface_id));
+        }
+        let Some((x, y)) = self.window_manager.window_mapped(client_id, surface_id) else {
+            return;
+        };
+        self.focus_stack.focus(client_id, surface_id);
+        if let Some(properties) = self
+            .surface_manager
+            .window_properties(client_id, surface_id)
+        {
+            self.apps
+                .window_mapped(client_id, surface_id, properties.app_id);
+        }
+        // Newly mapped surfaces start out opaque and visible at the origin of the layout.
+        if (x, y) != (0, 0) {
+            self.surface_updates.push_back(SurfaceUpdate::Moved {
+                client_id,
+                root_id: surface_id,
+                x,
+                y,
+            });
+        }
+        let opacity = self.window_manager.opacity(client_id, surface_id);
+        if opacity != 100 {
+            self.surface_updates.push_back(SurfaceUpdate::Opacity {
+                client_id,
+                root_id: surface_id,
+                opacity,
+            });
+        }
+        if !self.window_manager.is_visible(client_id, surface_id) {
+            self.surface_updates.push_back(SurfaceUpdate::Visibility {
+                client_id,
+                root_id: surface_id,
+                visible: false,
+            });
+        }
     }
 
-    fn damage_buffer(
-        &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSurfaceDamageBuffer<'_>,
-    ) {
-        todo!()
+    /// Stacks a newly mapped xdg_popup right above its window and the popups
+    /// already open on it, and moves it next to its parent.
+    fn show_popup(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        if self.stacking.contains(&(client_id, surface_id)) {
+            return;
+        }
+        let window_id = self.surface_window(client_id, surface_id);
+        let index = self
+            .stacking
+            .iter()
+            .rposition(|&(owner, root_id)| {
+                owner == client_id && self.surface_window(client_id, root_id) == window_id
+            })
+            .map_or(self.stacking.len(), |index| index + 1);
+        self.stacking.insert(index, (client_id, surface_id));
+        // New trees are drawn on top, so the surfaces stacked above the
+        // popup are raised over it again.
+        for &(client_id, root_id) in &self.stacking[index + 1..] {
+            self.surface_updates
+                .push_back(SurfaceUpdate::Raised { client_id, root_id });
+        }
+        let (x, y) = self.surface_location(client_id, surface_id);
+        self.surface_updates.push_back(SurfaceUpdate::Moved {
+            client_id,
+            root_id: surface_id,
+            x,
+            y,
+        });
+        if !self.window_manager.is_visible(client_id, window_id) {
+            self.surface_updates.push_back(SurfaceUpdate::Visibility {
+                client_id,
+                root_id: surface_id,
+                visible: false,
+            });
+        }
     }
 
-    fn offset(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlSurfaceOffset) {
-        todo!()
+    /// Re-applies the window rules after a wl_shell_surface's title or class
+    /// changed.
+    fn shell_surface_changed(&mut self, ctx: &mut Ctx, object_id: ObjectId) {
+        if let Ok(surface_id) = self
+            .surface_manager
+            .surface_for_shell(ctx.client_id, object_id)
+        {
+            self.apply_window_rules(ctx, surface_id);
+            self.track_app_id(ctx.client_id, surface_id);
+        }
+    }
+
+    fn push_surface_tree(&mut self, client_id: ClientId, root_id: ObjectId) {
+        if let Some(layers) = self.surface_manager.surface_tree(client_id, root_id) {
+            self.surface_updates.push_back(SurfaceUpdate::Tree {
+                client_id,
+                root_id,
+                layers,
+            });
+        }
     }
 }
 
 impl WlSeat for DisplayState {
-    fn get_pointer(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSeatGetPointer<'_>) {
-        ctx.writer
-            .wl_display_error(DISPLAY_OBJECT_ID)
-            .object_id(object_id)
-            .code(WL_SEAT_ERROR_MISSING_CAPABILITY)
-            .message("Seat has no pointer capability");
+    fn get_pointer(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlSeatGetPointer<'_>) {
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version.min(WL_POINTER_VERSION));
+        if !register_object(ctx, params.id(), InterfaceIndex::WlPointer, version) {
+            return;
+        }
+        self.seat_manager
+            .create_pointer(ctx.client_id, *params.id(), version, ctx.writer);
     }
 
     fn get_keyboard(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlSeatGetKeyboard<'_>) {
@@ -688,7 +1253,11 @@ impl WlSeat for DisplayState {
         if !register_object(ctx, params.id(), InterfaceIndex::WlKeyboard, version) {
             return;
         }
-        let focus = self.surface_manager.first_surface(ctx.client_id);
+        let focus = self
+            .seat_manager
+            .keyboard_focus()
+            .filter(|(owner, _)| *owner == ctx.client_id)
+            .map(|(_, surface_id)| surface_id);
         if let Err(err) = self.seat_manager.create_keyboard(
             ctx.client_id,
             *params.id(),
@@ -697,34 +1266,51 @@ impl WlSeat for DisplayState {
             focus,
         ) {
             log::error!("Failed to create wl_keyboard: {err:#}");
+        } else if focus.is_some() {
+            self.data_device_manager.focus_changed(ctx.client_id);
         }
     }
 
-    fn get_touch(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSeatGetTouch<'_>) {
-        ctx.writer
-            .wl_display_error(DISPLAY_OBJECT_ID)
-            .object_id(object_id)
-            .code(WL_SEAT_ERROR_MISSING_CAPABILITY)
-            .message("Seat has no touch capability");
+    fn get_touch(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlSeatGetTouch<'_>) {
+        let version = ctx
+            .registry
+            .object_metadata(object_id)
+            .map_or(1, |object| object.version.min(WL_TOUCH_VERSION));
+        if !register_object(ctx, params.id(), InterfaceIndex::WlTouch, version) {
+            return;
+        }
+        self.seat_manager.create_touch(ctx.client_id, *params.id());
     }
 
     fn release(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSeatRelease<'_>) {
         ctx.registry.free_object(object_id, ctx.writer);
+        self.seat_manager.release_seat(ctx.client_id, object_id);
     }
 }
 
 impl WlPointer for DisplayState {
-    fn set_cursor(
-        &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlPointerSetCursor<'_>,
-    ) {
-        todo!()
+    fn set_cursor(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &WlPointerSetCursor<'_>) {
+        if let Some(surface_id) = params.surface()
+            && let Err(error) = self
+                .surface_manager
+                .set_cursor_role(ctx.client_id, surface_id)
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        if !self.seat_manager.set_cursor(
+            ctx.client_id,
+            params.serial(),
+            params.surface(),
+            (params.hotspot_x(), params.hotspot_y()),
+        ) {
+            debug!("Ignoring set_cursor with stale serial {}", params.serial());
+        }
     }
 
-    fn release(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlPointerRelease<'_>) {
-        todo!()
+    fn release(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlPointerRelease<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        self.seat_manager.destroy_pointer(ctx.client_id, object_id);
     }
 }
 
@@ -736,14 +1322,16 @@ impl WlKeyboard for DisplayState {
 }
 
 impl WlTouch for DisplayState {
-    fn release(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlTouchRelease<'_>) {
-        todo!()
+    fn release(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlTouchRelease<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        self.seat_manager.destroy_touch(ctx.client_id, object_id);
     }
 }
 
 impl WlOutput for DisplayState {
-    fn release(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlOutputRelease<'_>) {
-        todo!()
+    fn release(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlOutputRelease<'_>) {
+        ctx.registry.free_object(object_id, ctx.writer);
+        self.output_manager.release_output(ctx.client_id, object_id);
     }
 }
 
@@ -793,71 +1381,106 @@ impl WlRegion for DisplayState {
 impl WlSubcompositor for DisplayState {
     fn destroy(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
         _params: &WlSubcompositorDestroy<'_>,
     ) {
-        todo!()
+        ctx.registry.free_object(object_id, ctx.writer);
     }
 
     fn get_subsurface(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSubcompositorGetSubsurface<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSubcompositorGetSubsurface<'_>,
     ) {
-        todo!()
+        for surface in [params.surface(), params.parent()] {
+            if ctx.registry.interface_index(surface) != Some(InterfaceIndex::WlSurface) {
+                report_surface_error(ctx, surface, SurfaceError::UnknownSurface);
+                return;
+            }
+        }
+        if !register_object(ctx, params.id(), InterfaceIndex::WlSubsurface, 1) {
+            return;
+        }
+        if let Err(error) = self.surface_manager.create_subsurface(
+            ctx.client_id,
+            *params.id(),
+            params.surface(),
+            params.parent(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+        }
     }
 }
 
 impl WlSubsurface for DisplayState {
-    fn destroy(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &WlSubsurfaceDestroy<'_>) {
-        todo!()
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSubsurfaceDestroy<'_>) {
+        if let Some((surface_id, root_id)) = self
+            .surface_manager
+            .destroy_subsurface(ctx.client_id, object_id)
+        {
+            self.push_unmapped(ctx, surface_id);
+            self.push_surface_tree(ctx.client_id, root_id);
+        }
+        ctx.registry.free_object(object_id, ctx.writer);
     }
 
     fn set_position(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSubsurfaceSetPosition<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSubsurfaceSetPosition<'_>,
     ) {
-        todo!()
+        self.surface_manager.set_subsurface_position(
+            ctx.client_id,
+            object_id,
+            params.x(),
+            params.y(),
+        );
     }
 
     fn place_above(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSubsurfacePlaceAbove<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSubsurfacePlaceAbove<'_>,
     ) {
-        todo!()
+        if let Err(error) =
+            self.surface_manager
+                .place_subsurface(ctx.client_id, object_id, params.sibling(), true)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
     }
 
     fn place_below(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSubsurfacePlaceBelow<'_>,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &WlSubsurfacePlaceBelow<'_>,
     ) {
-        todo!()
+        if let Err(error) =
+            self.surface_manager
+                .place_subsurface(ctx.client_id, object_id, params.sibling(), false)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
     }
 
-    fn set_sync(
-        &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
-        _params: &WlSubsurfaceSetSync<'_>,
-    ) {
-        todo!()
+    fn set_sync(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &WlSubsurfaceSetSync<'_>) {
+        self.surface_manager
+            .set_subsurface_sync(ctx.client_id, object_id, true);
     }
 
     fn set_desync(
         &mut self,
-        _ctx: &mut Ctx,
-        _object_id: ObjectId,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
         _params: &WlSubsurfaceSetDesync<'_>,
     ) {
-        todo!()
+        self.surface_manager
+            .set_subsurface_sync(ctx.client_id, object_id, false);
     }
 }
 
@@ -879,10 +1502,9 @@ impl WlFixes for DisplayState {
 #[cfg(test)]
 mod tests {
     use std::{
-        collections::VecDeque,
+        collections::{HashMap, VecDeque},
         fs::File,
         io::Write,
-        num::NonZeroU32,
         os::{
             fd::{AsRawFd, FromRawFd, IntoRawFd},
             unix::net::UnixStream,
@@ -893,16 +1515,13 @@ mod tests {
 
     use lumalla_shared::{DbusMessage, MainMessage, message_loop_with_channel};
     use lumalla_wayland_protocol::{
-        ClientId,
         buffer::Writer,
         registry::{InterfaceIndex, Registry},
     };
 
     use super::*;
-
-    fn object_id(id: u32) -> ObjectId {
-        ObjectId::new(NonZeroU32::new(id).unwrap())
-    }
+    use crate::test_ids::{client, object};
+    use crate::{data_device::DataDeviceEvent, surface::Positioner};
 
     fn bind_data(name: u32, interface: &str, version: u32, id: u32) -> Vec<u8> {
         let mut data = Vec::new();
@@ -987,9 +1606,75 @@ mod tests {
             .map(|(_, global)| (global.name, global.version))
             .collect();
 
-        assert!(globals.contains(&(WL_COMPOSITOR_NAME, 1)));
+        assert!(globals.contains(&(WL_COMPOSITOR_NAME, 4)));
         assert!(globals.contains(&(WL_SHM_NAME, 1)));
         assert!(globals.contains(&(WL_SHELL_NAME, 1)));
+        assert!(globals.contains(&(WL_SUBCOMPOSITOR_NAME, WL_SUBCOMPOSITOR_VERSION)));
+        assert!(globals.contains(&(WL_DATA_DEVICE_MANAGER_NAME, WL_DATA_DEVICE_MANAGER_VERSION)));
+        assert!(globals.contains(&(XDG_WM_BASE_NAME, XDG_WM_BASE_VERSION)));
+    }
+
+    #[test]
+    fn selection_offer_forwards_receive_to_source_client() {
+        let (_receiver, sender) = UnixStream::pair().unwrap();
+        let mut state = display_state();
+        let source_client = client(1);
+        let target_client = client(2);
+        let source_id = object(2);
+        let device_id = object(3);
+        state
+            .data_device_manager
+            .create_source(source_client, source_id, 3);
+        state
+            .data_device_manager
+            .offer_mime_type(source_client, source_id, "text/plain".to_owned())
+            .unwrap();
+        state
+            .data_device_manager
+            .create_device(source_client, device_id, 3, 1, false);
+        state
+            .data_device_manager
+            .create_device(target_client, device_id, 3, 1, false);
+        state
+            .data_device_manager
+            .set_selection(source_client, device_id, Some(source_id), &[target_client])
+            .unwrap();
+
+        let mut registry = Registry::new();
+        let mut writer = Writer::new(sender.as_raw_fd());
+        let mut ctx = Ctx {
+            registry: &mut registry,
+            writer: &mut writer,
+            client_id: target_client,
+        };
+        for (client_id, event) in state.data_device_manager.take_events() {
+            assert_eq!(client_id, target_client);
+            state.data_device_manager.deliver(&mut ctx, event);
+        }
+        let offer_id = object(0xFF00_0000);
+        assert_eq!(
+            ctx.registry.interface_index(offer_id),
+            Some(InterfaceIndex::WlDataOffer)
+        );
+
+        let (pipe, _) = UnixStream::pair().unwrap();
+        let mut data = Vec::new();
+        data.extend_from_slice(&11u32.to_ne_bytes());
+        data.extend_from_slice(b"text/plain\0\0");
+        let mut fds = VecDeque::from([pipe.into_raw_fd()]);
+        let params = WlDataOfferReceive::new(&data, &mut fds);
+        WlDataOffer::receive(&mut state, &mut ctx, offer_id, &params);
+
+        let events: Vec<_> = state
+            .data_device_manager
+            .take_events()
+            .into_iter()
+            .collect();
+        assert!(matches!(
+            events.as_slice(),
+            [(client_id, DataDeviceEvent::Send { source, mime_type, .. })]
+                if *client_id == source_client && *source == source_id && mime_type == "text/plain"
+        ));
     }
 
     #[test]
@@ -1001,15 +1686,15 @@ mod tests {
         let mut ctx = Ctx {
             registry: &mut registry,
             writer: &mut writer,
-            client_id: ClientId::new(NonZeroU32::new(1).unwrap()),
+            client_id: client(1),
         };
         let mut fds = VecDeque::new();
         let data = bind_data(1, "wl_compositor", 1, 2);
         let params = WlRegistryBind::new(&data, &mut fds);
 
-        WlRegistry::bind(&mut state, &mut ctx, object_id(10), &params);
+        WlRegistry::bind(&mut state, &mut ctx, object(10), &params);
 
-        let metadata = ctx.registry.object_metadata(object_id(2)).unwrap();
+        let metadata = ctx.registry.object_metadata(object(2)).unwrap();
         assert_eq!(metadata.interface_index, InterfaceIndex::WlCompositor);
         assert_eq!(metadata.version, 1);
     }
@@ -1028,14 +1713,14 @@ mod tests {
             let mut ctx = Ctx {
                 registry: &mut registry,
                 writer: &mut writer,
-                client_id: ClientId::new(NonZeroU32::new(1).unwrap()),
+                client_id: client(1),
             };
             let mut fds = VecDeque::new();
             let params = WlRegistryBind::new(&data, &mut fds);
 
-            WlRegistry::bind(&mut state, &mut ctx, object_id(10), &params);
+            WlRegistry::bind(&mut state, &mut ctx, object(10), &params);
 
-            assert!(ctx.registry.object_metadata(object_id(2)).is_none());
+            assert!(ctx.registry.object_metadata(object(2)).is_none());
         }
     }
 
@@ -1043,12 +1728,12 @@ mod tests {
     fn mapped_surface_commit_snapshots_and_releases_buffer() {
         let (_receiver, sender) = UnixStream::pair().unwrap();
         let mut state = display_state();
-        let client_id = ClientId::new(NonZeroU32::new(1).unwrap());
-        let surface_id = object_id(2);
-        let shell_id = object_id(3);
-        let pool_id = object_id(4);
-        let buffer_id = object_id(5);
-        let callback_id = object_id(6);
+        let client_id = client(1);
+        let surface_id = object(2);
+        let shell_id = object(3);
+        let pool_id = object(4);
+        let buffer_id = object(5);
+        let callback_id = object(6);
         state.surface_manager.create_surface(client_id, surface_id);
         state
             .surface_manager
@@ -1117,7 +1802,14 @@ mod tests {
 
         WlSurface::commit(&mut state, &mut ctx, surface_id, &params);
 
-        assert!(ctx.registry.object_metadata(callback_id).is_none());
+        // The frame callback waits until the surface is presented.
+        assert!(ctx.registry.object_metadata(callback_id).is_some());
+        assert_eq!(
+            state
+                .surface_manager
+                .take_presented_frame_callbacks(&[(client_id, surface_id)]),
+            [(client_id, callback_id)]
+        );
         let updates: Vec<_> = state.take_surface_updates().collect();
         assert_eq!(updates.len(), 1);
         let SurfaceUpdate::Frame(frame) = &updates[0] else {
@@ -1142,6 +1834,169 @@ mod tests {
         ));
     }
 
+    fn payload(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect()
+    }
+
+    /// Maps an xdg surface by acking its initial configure and committing
+    /// a 1x1 buffer of pool 50.
+    fn map_xdg_surface(
+        state: &mut DisplayState,
+        ctx: &mut Ctx,
+        surface_id: ObjectId,
+        buffer_id: ObjectId,
+    ) {
+        let mut fds = VecDeque::new();
+        let params = WlSurfaceCommit::new(&[], &mut fds);
+        WlSurface::commit(state, ctx, surface_id, &params);
+        let target = state
+            .surface_manager
+            .configure_xdg_surface(ctx.client_id, surface_id, (0, 0))
+            .unwrap();
+        state
+            .surface_manager
+            .ack_configure(ctx.client_id, target.xdg_surface_id, target.serial)
+            .unwrap();
+        state
+            .shm_manager
+            .create_buffer(
+                ctx.client_id,
+                object(50),
+                buffer_id,
+                0,
+                1,
+                1,
+                4,
+                WL_SHM_FORMAT_ARGB8888,
+            )
+            .unwrap();
+        state
+            .surface_manager
+            .attach(ctx.client_id, surface_id, Some(buffer_id), 0, 0)
+            .unwrap();
+        WlSurface::commit(state, ctx, surface_id, &params);
+    }
+
+    #[test]
+    fn xdg_popups_stay_above_their_window_next_to_their_parent() {
+        let (_receiver, sender) = UnixStream::pair().unwrap();
+        let mut state = display_state();
+        let client_id = client(1);
+        let mut registry = Registry::new();
+        registry
+            .register_client_object_with_version(
+                NewObjectId::new(object(3)),
+                InterfaceIndex::XdgWmBase,
+                XDG_WM_BASE_VERSION,
+            )
+            .unwrap();
+        for surface_id in [10, 20, 30] {
+            state
+                .surface_manager
+                .create_surface(client_id, object(surface_id));
+            registry
+                .register_client_object_with_version(
+                    NewObjectId::new(object(surface_id)),
+                    InterfaceIndex::WlSurface,
+                    1,
+                )
+                .unwrap();
+        }
+        state
+            .shm_manager
+            .create_pool(client_id, object(50), memory_file(&[0; 4]), 4)
+            .unwrap();
+        let mut writer = Writer::new(sender.as_raw_fd());
+        let mut ctx = Ctx {
+            registry: &mut registry,
+            writer: &mut writer,
+            client_id,
+        };
+        let mut fds = VecDeque::new();
+
+        // Two toplevels, the first one stacked below the second.
+        for window in [10, 20] {
+            let data = payload(&[window + 1, window]);
+            let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+            XdgWmBase::get_xdg_surface(&mut state, &mut ctx, object(3), &params);
+            let data = payload(&[window + 2]);
+            let params = XdgSurfaceGetToplevel::new(&data, &mut fds);
+            XdgSurface::get_toplevel(&mut state, &mut ctx, object(window + 1), &params);
+            map_xdg_surface(&mut state, &mut ctx, object(window), object(window + 3));
+        }
+        let window = (client_id, object(10));
+        let other_window = (client_id, object(20));
+        let popup = (client_id, object(30));
+        assert_eq!(state.stacking, [window, other_window]);
+
+        // A popup of the lower toplevel, anchored below a 30x10 rectangle.
+        let data = payload(&[40]);
+        let params = XdgWmBaseCreatePositioner::new(&data, &mut fds);
+        XdgWmBase::create_positioner(&mut state, &mut ctx, object(3), &params);
+        *state
+            .surface_manager
+            .positioner_mut(client_id, object(40))
+            .unwrap() = Positioner {
+            size: (50, 40),
+            anchor_rect: Rectangle {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 10,
+            },
+            anchor: XDG_POSITIONER_ANCHOR_BOTTOM_LEFT,
+            gravity: XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT,
+            ..Positioner::default()
+        };
+        let data = payload(&[31, 30]);
+        let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+        XdgWmBase::get_xdg_surface(&mut state, &mut ctx, object(3), &params);
+        let data = payload(&[32, 11, 40]);
+        let params = XdgSurfaceGetPopup::new(&data, &mut fds);
+        XdgSurface::get_popup(&mut state, &mut ctx, object(31), &params);
+        // Grabs without a button press of the client are denied.
+        let data = payload(&[0, 0]);
+        let params = XdgPopupGrab::new(&data, &mut fds);
+        XdgPopup::grab(&mut state, &mut ctx, object(32), &params);
+        assert!(state.popup_grab.is_empty());
+        state.take_surface_updates().for_each(drop);
+
+        map_xdg_surface(&mut state, &mut ctx, object(30), object(33));
+        assert_eq!(state.stacking, [window, popup, other_window]);
+        let (window_x, window_y) = state.window_manager.location(client_id, object(10));
+        let (x, y) = (window_x + 10, window_y + 30);
+        let placement: Vec<_> = state
+            .take_surface_updates()
+            .filter_map(|update| match update {
+                SurfaceUpdate::Raised { root_id, .. } => Some((root_id.get(), None)),
+                SurfaceUpdate::Moved { root_id, x, y, .. } => Some((root_id.get(), Some((x, y)))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(placement, [(20, None), (30, Some((x, y)))]);
+        let target = state
+            .surface_under((f64::from(x) + 0.5, f64::from(y) + 0.5))
+            .unwrap();
+        assert_eq!(target.surface_id, object(30));
+
+        state.focus_window(&mut HashMap::new(), client_id, object(10));
+        assert_eq!(state.stacking, [other_window, window, popup]);
+        let raised: Vec<_> = state
+            .take_surface_updates()
+            .filter_map(|update| match update {
+                SurfaceUpdate::Raised { root_id, .. } => Some(root_id.get()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(raised, [10, 30]);
+
+        state.popup_grab.push(popup);
+        assert_eq!(state.keyboard_target(), Some(popup));
+    }
+
     #[test]
     fn wire_client_can_commit_a_wl_shell_shm_surface() {
         static NEXT_SOCKET: AtomicU64 = AtomicU64::new(0);
@@ -1188,10 +2043,88 @@ mod tests {
             panic!("expected one committed frame");
         };
         assert_eq!(frame.client_id, client.client_id());
-        assert_eq!(frame.surface_id, object_id(6));
-        assert_eq!(frame.buffer_id, object_id(8));
+        assert_eq!(frame.surface_id, object(6));
+        assert_eq!(frame.buffer_id, object(8));
         assert_eq!(frame.pixels, [1, 2, 3, 0xff]);
         assert_eq!((frame.width, frame.height, frame.stride), (1, 1, 4));
         assert_eq!(frame.format, WL_SHM_FORMAT_XRGB8888);
     }
+
+    #[test]
+    fn wire_client_can_commit_a_synchronized_subsurface() {
+        static NEXT_SOCKET: AtomicU64 = AtomicU64::new(0);
+        let socket_path = std::env::temp_dir().join(format!(
+            "lumalla-subsurface-test-{}-{}",
+            std::process::id(),
+            NEXT_SOCKET.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut wayland =
+            lumalla_wayland_protocol::Wayland::new(socket_path.to_string_lossy().into_owned())
+                .unwrap();
+        let client_stream = UnixStream::connect(&socket_path).unwrap();
+        let mut client = wayland.next_client().unwrap();
+        let mut state = display_state();
+
+        let mut wire = Vec::new();
+        wire.extend(wire_message(1, WL_DISPLAY_GET_REGISTRY_OPCODE, &[2]));
+        wire.extend(wire_bind(1, "wl_compositor", 3));
+        wire.extend(wire_bind(2, "wl_shm", 4));
+        wire.extend(wire_bind(3, "wl_shell", 5));
+        wire.extend(wire_bind(4, "wl_subcompositor", 13));
+        wire.extend(wire_message(3, WL_COMPOSITOR_CREATE_SURFACE_OPCODE, &[6]));
+        wire.extend(wire_message(3, WL_COMPOSITOR_CREATE_SURFACE_OPCODE, &[11]));
+        wire.extend(wire_message(4, WL_SHM_CREATE_POOL_OPCODE, &[7, 8]));
+        wire.extend(wire_message(
+            7,
+            WL_SHM_POOL_CREATE_BUFFER_OPCODE,
+            &[8, 0, 1, 1, 4, WL_SHM_FORMAT_XRGB8888],
+        ));
+        wire.extend(wire_message(
+            7,
+            WL_SHM_POOL_CREATE_BUFFER_OPCODE,
+            &[10, 4, 1, 1, 4, WL_SHM_FORMAT_ARGB8888],
+        ));
+        wire.extend(wire_message(5, WL_SHELL_GET_SHELL_SURFACE_OPCODE, &[9, 6]));
+        wire.extend(wire_message(9, WL_SHELL_SURFACE_SET_TOPLEVEL_OPCODE, &[]));
+        wire.extend(wire_message(
+            13,
+            WL_SUBCOMPOSITOR_GET_SUBSURFACE_OPCODE,
+            &[12, 11, 6],
+        ));
+        wire.extend(wire_message(12, WL_SUBSURFACE_SET_POSITION_OPCODE, &[3, 4]));
+        wire.extend(wire_message(11, WL_SURFACE_ATTACH_OPCODE, &[10, 0, 0]));
+        wire.extend(wire_message(11, WL_SURFACE_COMMIT_OPCODE, &[]));
+        wire.extend(wire_message(6, WL_SURFACE_ATTACH_OPCODE, &[8, 0, 0]));
+        wire.extend(wire_message(6, WL_SURFACE_COMMIT_OPCODE, &[]));
+
+        let fd = memory_file(&[1, 2, 3, 0xff, 4, 5, 6, 0x80]);
+        send_wire_with_fd(&client_stream, &wire, fd);
+        unsafe {
+            libc::close(fd);
+        }
+        client.handle_messages(&mut state).unwrap();
+
+        let updates: Vec<_> = state.take_surface_updates().collect();
+        let [
+            SurfaceUpdate::Tree {
+                root_id, layers, ..
+            },
+            SurfaceUpdate::Frame(parent),
+            SurfaceUpdate::Frame(child),
+        ] = updates.as_slice()
+        else {
+            panic!("expected the composed tree and both frames, got {updates:?}");
+        };
+        assert_eq!(*root_id, object(6));
+        assert_eq!(
+            layers
+                .iter()
+                .map(|layer| (layer.surface_id, layer.x, layer.y))
+                .collect::<Vec<_>>(),
+            [(object(6), 0, 0), (object(11), 3, 4)]
+        );
+        assert_eq!((parent.surface_id, parent.root_id), (object(6), object(6)));
+        assert_eq!((child.surface_id, child.root_id), (object(11), object(6)));
+        assert_eq!(child.pixels, [4, 5, 6, 0x80]);
+    }
 }
@@ -0,0 +1,862 @@
+use lumalla_wayland_protocol::{
+    Ctx, ObjectId,
+    protocols::{XdgShellProtocol, xdg_shell::*},
+    registry::{DISPLAY_OBJECT_ID, InterfaceIndex},
+};
+
+use super::wayland::{register_object, report_surface_error};
+use crate::{
+    DisplayState,
+    surface::{Rectangle, ShellMode, SurfaceError, XdgConfigure},
+};
+
+impl XdgShellProtocol for DisplayState {}
+
+fn child_version(ctx: &Ctx, object_id: ObjectId, max_version: u32) -> u32 {
+    ctx.registry
+        .object_metadata(object_id)
+        .map_or(1, |object| object.version.min(max_version))
+}
+
+fn report_positioner_input_error(ctx: &mut Ctx, object_id: ObjectId, message: &str) {
+    ctx.writer
+        .wl_display_error(DISPLAY_OBJECT_ID)
+        .object_id(object_id)
+        .code(XDG_POSITIONER_ERROR_INVALID_INPUT)
+        .message(message);
+}
+
+impl DisplayState {
+    /// Sends a full configure sequence for the surface's xdg role.
+    pub(super) fn configure_xdg_surface(&mut self, ctx: &mut Ctx, surface_id: ObjectId) {
+        let size = self.window_manager.size(ctx.client_id, surface_id);
+        let Some(target) =
+            self.surface_manager
+                .configure_xdg_surface(ctx.client_id, surface_id, size)
+        else {
+            return;
+        };
+        match target.role {
+            XdgConfigure::Toplevel {
+                toplevel_id,
+                width,
+                height,
+                states,
+                ..
+            } => {
+                let states: Vec<u8> = states
+                    .iter()
+                    .flat_map(|state| state.to_ne_bytes())
+                    .collect();
+                ctx.writer
+                    .xdg_toplevel_configure(toplevel_id)
+                    .width(width)
+                    .height(height)
+                    .states(&states);
+            }
+            XdgConfigure::Popup { popup_id, geometry } => {
+                ctx.writer
+                    .xdg_popup_configure(popup_id)
+                    .x(geometry.x)
+                    .y(geometry.y)
+                    .width(geometry.width)
+                    .height(geometry.height);
+            }
+        }
+        ctx.writer
+            .xdg_surface_configure(target.xdg_surface_id)
+            .serial(target.serial);
+    }
+
+    /// Sends a toplevel the size of its zone through the shell it uses.
+    pub(crate) fn configure_window(&mut self, ctx: &mut Ctx, surface_id: ObjectId) {
+        let Some(shell_id) = self
+            .surface_manager
+            .wl_shell_surface(ctx.client_id, surface_id)
+        else {
+            self.configure_xdg_surface(ctx, surface_id);
+            return;
+        };
+        let (width, height) = self.window_manager.size(ctx.client_id, surface_id);
+        if width > 0 && height > 0 {
+            ctx.writer
+                .wl_shell_surface_configure(shell_id)
+                .edges(0)
+                .width(width)
+                .height(height);
+        }
+    }
+
+    fn destroy_xdg_role(&mut self, ctx: &mut Ctx, object_id: ObjectId) {
+        if let Some((surface_id, was_mapped)) = self
+            .surface_manager
+            .destroy_xdg_role(ctx.client_id, object_id)
+        {
+            if was_mapped {
+                self.push_unmapped(ctx, surface_id);
+            }
+            self.popup_grab
+                .retain(|popup| *popup != (ctx.client_id, surface_id));
+            self.window_manager.remove_window(ctx.client_id, surface_id);
+        }
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    /// Before the initial commit nothing is sent: the mode is kept and the
+    /// initial configure carries it.
+    fn set_toplevel_mode(&mut self, ctx: &mut Ctx, object_id: ObjectId, mode: ShellMode) {
+        if let Err(error) = self
+            .surface_manager
+            .set_shell_mode(ctx.client_id, object_id, mode)
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        if let Ok(surface_id) = self
+            .surface_manager
+            .surface_for_shell(ctx.client_id, object_id)
+        {
+            let fullscreen = mode == ShellMode::Fullscreen;
+            self.window_manager
+                .set_fullscreen(ctx.client_id, surface_id, fullscreen);
+            self.place_window(ctx, surface_id);
+        }
+    }
+
+    /// Re-applies the window rules after a toplevel property changed.
+    fn toplevel_changed(&mut self, ctx: &mut Ctx, object_id: ObjectId) {
+        if let Ok(surface_id) = self
+            .surface_manager
+            .surface_for_shell(ctx.client_id, object_id)
+        {
+            self.apply_window_rules(ctx, surface_id);
+            self.track_app_id(ctx.client_id, surface_id);
+        }
+    }
+}
+
+impl XdgWmBase for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &XdgWmBaseDestroy<'_>) {
+        if self
+            .surface_manager
+            .has_xdg_surfaces(ctx.client_id, object_id)
+        {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(object_id)
+                .code(XDG_WM_BASE_ERROR_DEFUNCT_SURFACES)
+                .message("xdg_wm_base destroyed before its xdg_surfaces");
+            return;
+        }
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn create_positioner(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgWmBaseCreatePositioner<'_>,
+    ) {
+        let version = child_version(ctx, object_id, XDG_POSITIONER_VERSION);
+        if register_object(ctx, params.id(), InterfaceIndex::XdgPositioner, version) {
+            self.surface_manager
+                .create_positioner(ctx.client_id, *params.id());
+        }
+    }
+
+    fn get_xdg_surface(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgWmBaseGetXdgSurface<'_>,
+    ) {
+        if ctx.registry.interface_index(params.surface()) != Some(InterfaceIndex::WlSurface) {
+            report_surface_error(ctx, params.surface(), SurfaceError::UnknownSurface);
+            return;
+        }
+        let version = child_version(ctx, object_id, XDG_SURFACE_VERSION);
+        if !register_object(ctx, params.id(), InterfaceIndex::XdgSurface, version) {
+            return;
+        }
+        if let Err(error) = self.surface_manager.create_xdg_surface(
+            ctx.client_id,
+            object_id,
+            *params.id(),
+            params.surface(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn pong(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &XdgWmBasePong<'_>) {}
+}
+
+impl XdgPositioner for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &XdgPositionerDestroy<'_>) {
+        self.surface_manager
+            .destroy_positioner(ctx.client_id, object_id);
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn set_size(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgPositionerSetSize<'_>) {
+        if params.width() <= 0 || params.height() <= 0 {
+            report_positioner_input_error(ctx, object_id, "Positioner size must be positive");
+            return;
+        }
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.size = (params.width(), params.height()),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_anchor_rect(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetAnchorRect<'_>,
+    ) {
+        if params.width() < 0 || params.height() < 0 {
+            report_positioner_input_error(ctx, object_id, "Anchor rectangle size is negative");
+            return;
+        }
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => {
+                positioner.anchor_rect = Rectangle {
+                    x: params.x(),
+                    y: params.y(),
+                    width: params.width(),
+                    height: params.height(),
+                }
+            }
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_anchor(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetAnchor<'_>,
+    ) {
+        if params.anchor() > XDG_POSITIONER_ANCHOR_BOTTOM_RIGHT {
+            report_positioner_input_error(ctx, object_id, "Invalid anchor");
+            return;
+        }
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.anchor = params.anchor(),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_gravity(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetGravity<'_>,
+    ) {
+        if params.gravity() > XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT {
+            report_positioner_input_error(ctx, object_id, "Invalid gravity");
+            return;
+        }
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.gravity = params.gravity(),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_constraint_adjustment(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetConstraintAdjustment<'_>,
+    ) {
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.constraint_adjustment = params.constraint_adjustment(),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_offset(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetOffset<'_>,
+    ) {
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.offset = (params.x(), params.y()),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_reactive(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &XdgPositionerSetReactive<'_>,
+    ) {
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.reactive = true,
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_parent_size(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetParentSize<'_>,
+    ) {
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => {
+                positioner.parent_size = Some((params.parent_width(), params.parent_height()))
+            }
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+
+    fn set_parent_configure(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgPositionerSetParentConfigure<'_>,
+    ) {
+        match self
+            .surface_manager
+            .positioner_mut(ctx.client_id, object_id)
+        {
+            Ok(positioner) => positioner.parent_configure = Some(params.serial()),
+            Err(error) => report_surface_error(ctx, object_id, error),
+        }
+    }
+}
+
+impl XdgSurface for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &XdgSurfaceDestroy<'_>) {
+        if let Err(error) = self
+            .surface_manager
+            .destroy_xdg_surface(ctx.client_id, object_id)
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        ctx.registry.free_object(object_id, ctx.writer);
+    }
+
+    fn get_toplevel(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgSurfaceGetToplevel<'_>,
+    ) {
+        let version = child_version(ctx, object_id, XDG_TOPLEVEL_VERSION);
+        if !register_object(ctx, params.id(), InterfaceIndex::XdgToplevel, version) {
+            return;
+        }
+        if let Err(error) = self.surface_manager.create_xdg_toplevel(
+            ctx.client_id,
+            object_id,
+            *params.id(),
+            version,
+        ) {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        if let Ok(surface_id) = self
+            .surface_manager
+            .surface_for_xdg_surface(ctx.client_id, object_id)
+        {
+            self.window_manager.add_window(ctx.client_id, surface_id);
+            self.apply_window_rules(ctx, surface_id);
+            self.update_preferred_scales(ctx);
+        }
+        if version >= 5 {
+            ctx.writer
+                .xdg_toplevel_wm_capabilities(*params.id())
+                .capabilities(&[]);
+        }
+    }
+
+    fn get_popup(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgSurfaceGetPopup<'_>) {
+        let version = child_version(ctx, object_id, XDG_POPUP_VERSION);
+        if !register_object(ctx, params.id(), InterfaceIndex::XdgPopup, version) {
+            return;
+        }
+        if let Err(error) = self.surface_manager.create_xdg_popup(
+            ctx.client_id,
+            object_id,
+            *params.id(),
+            params.parent(),
+            params.positioner(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        self.update_preferred_scales(ctx);
+    }
+
+    fn set_window_geometry(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgSurfaceSetWindowGeometry<'_>,
+    ) {
+        let geometry = Rectangle {
+            x: params.x(),
+            y: params.y(),
+            width: params.width(),
+            height: params.height(),
+        };
+        if let Err(error) =
+            self.surface_manager
+                .set_window_geometry(ctx.client_id, object_id, geometry)
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn ack_configure(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgSurfaceAckConfigure<'_>,
+    ) {
+        if let Err(error) =
+            self.surface_manager
+                .ack_configure(ctx.client_id, object_id, params.serial())
+        {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+}
+
+impl XdgToplevel for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &XdgToplevelDestroy<'_>) {
+        self.destroy_xdg_role(ctx, object_id);
+    }
+
+    fn set_parent(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgToplevelSetParent<'_>,
+    ) {
+        if let Err(error) =
+            self.surface_manager
+                .set_xdg_toplevel_parent(ctx.client_id, object_id, params.parent())
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        self.toplevel_changed(ctx, object_id);
+    }
+
+    fn set_title(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgToplevelSetTitle<'_>) {
+        if let Err(error) = self.surface_manager.set_shell_title(
+            ctx.client_id,
+            object_id,
+            params.title().to_owned(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        self.toplevel_changed(ctx, object_id);
+    }
+
+    fn set_app_id(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgToplevelSetAppId<'_>) {
+        if let Err(error) = self.surface_manager.set_shell_class(
+            ctx.client_id,
+            object_id,
+            params.app_id().to_owned(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        self.toplevel_changed(ctx, object_id);
+    }
+
+    fn show_window_menu(
+        &mut self,
+        _ctx: &mut Ctx,
+        _object_id: ObjectId,
+        _params: &XdgToplevelShowWindowMenu<'_>,
+    ) {
+    }
+
+    fn move_(&mut self, _ctx: &mut Ctx, _object_id: ObjectId, _params: &XdgToplevelMove<'_>) {}
+
+    fn resize(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgToplevelResize<'_>) {
+        if !matches!(
+            params.edges(),
+            XDG_TOPLEVEL_RESIZE_EDGE_NONE
+                | XDG_TOPLEVEL_RESIZE_EDGE_TOP
+                | XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM
+                | XDG_TOPLEVEL_RESIZE_EDGE_LEFT
+                | XDG_TOPLEVEL_RESIZE_EDGE_TOP_LEFT
+                | XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_LEFT
+                | XDG_TOPLEVEL_RESIZE_EDGE_RIGHT
+                | XDG_TOPLEVEL_RESIZE_EDGE_TOP_RIGHT
+                | XDG_TOPLEVEL_RESIZE_EDGE_BOTTOM_RIGHT
+        ) {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(object_id)
+                .code(XDG_TOPLEVEL_ERROR_INVALID_RESIZE_EDGE)
+                .message("Invalid resize edge");
+        }
+    }
+
+    fn set_max_size(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgToplevelSetMaxSize<'_>,
+    ) {
+        if let Err(error) = self.surface_manager.set_max_size(
+            ctx.client_id,
+            object_id,
+            params.width(),
+            params.height(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn set_min_size(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        params: &XdgToplevelSetMinSize<'_>,
+    ) {
+        if let Err(error) = self.surface_manager.set_min_size(
+            ctx.client_id,
+            object_id,
+            params.width(),
+            params.height(),
+        ) {
+            report_surface_error(ctx, object_id, error);
+        }
+    }
+
+    fn set_maximized(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &XdgToplevelSetMaximized<'_>,
+    ) {
+        self.set_toplevel_mode(ctx, object_id, ShellMode::Maximized);
+    }
+
+    fn unset_maximized(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &XdgToplevelUnsetMaximized<'_>,
+    ) {
+        self.set_toplevel_mode(ctx, object_id, ShellMode::Toplevel);
+    }
+
+    fn set_fullscreen(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &XdgToplevelSetFullscreen<'_>,
+    ) {
+        self.set_toplevel_mode(ctx, object_id, ShellMode::Fullscreen);
+    }
+
+    fn unset_fullscreen(
+        &mut self,
+        ctx: &mut Ctx,
+        object_id: ObjectId,
+        _params: &XdgToplevelUnsetFullscreen<'_>,
+    ) {
+        self.set_toplevel_mode(ctx, object_id, ShellMode::Toplevel);
+    }
+
+    fn set_minimized(
+        &mut self,
+        _ctx: &mut Ctx,
+        _object_id: ObjectId,
+        _params: &XdgToplevelSetMinimized<'_>,
+    ) {
+    }
+}
+
+impl XdgPopup for DisplayState {
+    fn destroy(&mut self, ctx: &mut Ctx, object_id: ObjectId, _params: &XdgPopupDestroy<'_>) {
+        self.destroy_xdg_role(ctx, object_id);
+    }
+
+    /// Gives the popup the keyboard focus until a click outside the client
+    /// dismisses it. Grabs without a pointer button press of the client are
+    /// denied by dismissing the popup right away.
+    fn grab(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgPopupGrab<'_>) {
+        let surface_id = match self
+            .surface_manager
+            .surface_for_shell(ctx.client_id, object_id)
+        {
+            Ok(surface_id) => surface_id,
+            Err(error) => {
+                report_surface_error(ctx, object_id, error);
+                return;
+            }
+        };
+        if self.stacking.contains(&(ctx.client_id, surface_id)) {
+            ctx.writer
+                .wl_display_error(DISPLAY_OBJECT_ID)
+                .object_id(object_id)
+                .code(XDG_POPUP_ERROR_INVALID_GRAB)
+                .message("xdg_popup grabbed after it was mapped");
+            return;
+        }
+        if !self
+            .seat_manager
+            .has_pointer_grab(ctx.client_id, params.serial())
+        {
+            ctx.writer.xdg_popup_popup_done(object_id);
+            return;
+        }
+        self.popup_grab.push((ctx.client_id, surface_id));
+    }
+
+    fn reposition(&mut self, ctx: &mut Ctx, object_id: ObjectId, params: &XdgPopupReposition<'_>) {
+        if let Err(error) =
+            self.surface_manager
+                .reposition_xdg_popup(ctx.client_id, object_id, params.positioner())
+        {
+            report_surface_error(ctx, object_id, error);
+            return;
+        }
+        ctx.writer
+            .xdg_popup_repositioned(object_id)
+            .token(params.token());
+        if let Ok(surface_id) = self
+            .surface_manager
+            .surface_for_shell(ctx.client_id, object_id)
+        {
+            self.configure_xdg_surface(ctx, surface_id);
+            let window_id = self.surface_window(ctx.client_id, surface_id);
+            self.move_popups(ctx.client_id, window_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        io::Read,
+        os::{fd::AsRawFd, unix::net::UnixStream},
+    };
+
+    use lumalla_shared::{DbusMessage, MainMessage, message_loop_with_channel};
+    use lumalla_wayland_protocol::{
+        NewObjectId,
+        buffer::Writer,
+        protocols::wayland::{WL_SURFACE_VERSION, WlSurface, WlSurfaceCommit},
+        registry::Registry,
+    };
+
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    fn display_state() -> DisplayState {
+        let (_main_poll, _main_rx, to_main) = message_loop_with_channel::<MainMessage>().unwrap();
+        let (_dbus_poll, _dbus_rx, to_dbus) = message_loop_with_channel::<DbusMessage>().unwrap();
+        DisplayState::new(lumalla_shared::Comms::new(to_main, to_dbus)).unwrap()
+    }
+
+    fn payload(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect()
+    }
+
+    /// Flushes the events sent to the client and reads them back as
+    /// `(object_id, opcode, body)`.
+    fn sent_events(ctx: &mut Ctx, receiver: &mut UnixStream) -> Vec<(u32, u16, Vec<u8>)> {
+        ctx.writer.flush().unwrap();
+        receiver.set_nonblocking(true).unwrap();
+        let mut bytes = Vec::new();
+        let _ = receiver.read_to_end(&mut bytes);
+        let mut events = Vec::new();
+        let mut rest = bytes.as_slice();
+        while rest.len() >= 8 {
+            let object = u32::from_ne_bytes(rest[0..4].try_into().unwrap());
+            let opcode = u16::from_ne_bytes(rest[4..6].try_into().unwrap());
+            let size = u16::from_ne_bytes(rest[6..8].try_into().unwrap()) as usize;
+            events.push((object, opcode, rest[8..size].to_vec()));
+            rest = &rest[size..];
+        }
+        events
+    }
+
+    fn opcodes(events: &[(u32, u16, Vec<u8>)]) -> Vec<(u32, u16)> {
+        events
+            .iter()
+            .map(|(object, opcode, _)| (*object, *opcode))
+            .collect()
+    }
+
+    /// Runs `test` for client 1 with wl_surface 2 and xdg_wm_base 3, and
+    /// the end of its connection the events it is sent arrive at.
+    fn with_client(test: impl FnOnce(&mut DisplayState, &mut Ctx, &mut UnixStream)) {
+        let (mut receiver, sender) = UnixStream::pair().unwrap();
+        let mut state = display_state();
+        state.surface_manager.create_surface(client(1), object(2));
+        let mut registry = Registry::new();
+        registry
+            .register_client_object_with_version(
+                NewObjectId::new(object(2)),
+                InterfaceIndex::WlSurface,
+                WL_SURFACE_VERSION,
+            )
+            .unwrap();
+        registry
+            .register_client_object_with_version(
+                NewObjectId::new(object(3)),
+                InterfaceIndex::XdgWmBase,
+                XDG_WM_BASE_VERSION,
+            )
+            .unwrap();
+        let mut writer = Writer::new(sender.as_raw_fd());
+        let mut ctx = Ctx {
+            registry: &mut registry,
+            writer: &mut writer,
+            client_id: client(1),
+        };
+        test(&mut state, &mut ctx, &mut receiver);
+    }
+
+    /// Code of the protocol error sent on `object`, if any.
+    fn protocol_error(events: &[(u32, u16, Vec<u8>)], object: u32) -> Option<u32> {
+        events.iter().find_map(|(id, opcode, body)| {
+            let error_object = u32::from_ne_bytes(body.get(0..4)?.try_into().unwrap());
+            (*id == 1 && *opcode == 0 && error_object == object)
+                .then(|| u32::from_ne_bytes(body[4..8].try_into().unwrap()))
+        })
+    }
+
+    #[test]
+    fn initial_toplevel_commit_sends_configure_sequence() {
+        with_client(|state, ctx, receiver| {
+            let mut fds = VecDeque::new();
+            let data = payload(&[4, 2]);
+            let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+            XdgWmBase::get_xdg_surface(state, ctx, object(3), &params);
+            let data = payload(&[5]);
+            let params = XdgSurfaceGetToplevel::new(&data, &mut fds);
+            XdgSurface::get_toplevel(state, ctx, object(4), &params);
+            let data = payload(&[]);
+            let params = WlSurfaceCommit::new(&data, &mut fds);
+            WlSurface::commit(state, ctx, object(2), &params);
+
+            assert_eq!(
+                ctx.registry.interface_index(object(5)),
+                Some(InterfaceIndex::XdgToplevel)
+            );
+            let events = sent_events(ctx, receiver);
+            // wm_capabilities, toplevel configure, xdg_surface configure
+            assert_eq!(opcodes(&events), [(5, 3), (5, 0), (4, 0)]);
+            let serial = u32::from_ne_bytes(events[2].2[0..4].try_into().unwrap());
+            state
+                .surface_manager
+                .ack_configure(client(1), object(4), serial)
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn toplevel_modes_set_before_the_initial_commit_come_with_its_configure() {
+        with_client(|state, ctx, receiver| {
+            let mut fds = VecDeque::new();
+            let data = payload(&[4, 2]);
+            let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+            XdgWmBase::get_xdg_surface(state, ctx, object(3), &params);
+            let data = payload(&[5]);
+            let params = XdgSurfaceGetToplevel::new(&data, &mut fds);
+            XdgSurface::get_toplevel(state, ctx, object(4), &params);
+            let data = payload(&[]);
+            let params = XdgToplevelSetMaximized::new(&data, &mut fds);
+            XdgToplevel::set_maximized(state, ctx, object(5), &params);
+            // Only wm_capabilities
+            assert_eq!(opcodes(&sent_events(ctx, receiver)), [(5, 3)]);
+
+            let params = WlSurfaceCommit::new(&data, &mut fds);
+            WlSurface::commit(state, ctx, object(2), &params);
+            let events = sent_events(ctx, receiver);
+            assert_eq!(opcodes(&events), [(5, 0), (4, 0)]);
+            // width, height, then the states array
+            assert_eq!(
+                events[0].2[8..],
+                payload(&[4, XDG_TOPLEVEL_STATE_MAXIMIZED])
+            );
+        });
+    }
+
+    #[test]
+    fn wm_base_outliving_its_xdg_surfaces_is_enforced() {
+        with_client(|state, ctx, receiver| {
+            let mut fds = VecDeque::new();
+            let data = payload(&[4, 2]);
+            let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+            XdgWmBase::get_xdg_surface(state, ctx, object(3), &params);
+            let data = payload(&[]);
+            let params = XdgWmBaseDestroy::new(&data, &mut fds);
+            XdgWmBase::destroy(state, ctx, object(3), &params);
+            assert_eq!(
+                protocol_error(&sent_events(ctx, receiver), 3),
+                Some(XDG_WM_BASE_ERROR_DEFUNCT_SURFACES)
+            );
+
+            let params = XdgSurfaceDestroy::new(&data, &mut fds);
+            XdgSurface::destroy(state, ctx, object(4), &params);
+            let params = XdgWmBaseDestroy::new(&data, &mut fds);
+            XdgWmBase::destroy(state, ctx, object(3), &params);
+            assert_eq!(protocol_error(&sent_events(ctx, receiver), 3), None);
+            assert_eq!(ctx.registry.interface_index(object(3)), None);
+        });
+    }
+
+    #[test]
+    fn committing_an_xdg_surface_without_role_is_an_error() {
+        with_client(|state, ctx, receiver| {
+            let mut fds = VecDeque::new();
+            let data = payload(&[4, 2]);
+            let params = XdgWmBaseGetXdgSurface::new(&data, &mut fds);
+            XdgWmBase::get_xdg_surface(state, ctx, object(3), &params);
+            let data = payload(&[]);
+            let params = WlSurfaceCommit::new(&data, &mut fds);
+            WlSurface::commit(state, ctx, object(2), &params);
+            assert_eq!(
+                protocol_error(&sent_events(ctx, receiver), 4),
+                Some(XDG_SURFACE_ERROR_NOT_CONSTRUCTED)
+            );
+        });
+    }
+}
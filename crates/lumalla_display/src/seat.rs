@@ -1,26 +1,40 @@
 use std::collections::{HashMap, HashSet};
 
-use lumalla_shared::KeymapMemfd;
+use lumalla_shared::{KeymapMemfd, Transform};
 use lumalla_wayland_protocol::{
     ClientConnection, ClientId, ObjectId,
     buffer::Writer,
     protocols::wayland::{
         WL_KEYBOARD_KEY_STATE_PRESSED, WL_KEYBOARD_KEY_STATE_RELEASED,
-        WL_KEYBOARD_KEYMAP_FORMAT_XKB_V1,
+        WL_KEYBOARD_KEYMAP_FORMAT_XKB_V1, WL_POINTER_AXIS_HORIZONTAL_SCROLL,
+        WL_POINTER_AXIS_SOURCE_CONTINUOUS, WL_POINTER_AXIS_SOURCE_FINGER,
+        WL_POINTER_AXIS_SOURCE_WHEEL, WL_POINTER_AXIS_VERTICAL_SCROLL,
+        WL_POINTER_BUTTON_STATE_PRESSED, WL_POINTER_BUTTON_STATE_RELEASED,
     },
     registry::InterfaceIndex,
 };
 
-use crate::{GlobalId, Globals};
+use crate::{GlobalId, Globals, surface::Rectangle};
 
 pub struct SeatManager {
     has_main_seat: bool,
     known_seats: HashSet<String>,
     id_to_name: HashMap<GlobalId, String>,
+    /// Bound wl_seat objects and the seat global they refer to.
+    seat_objects: HashMap<(ClientId, ObjectId), GlobalId>,
     /// Sealed memfd of the xkb TEXT_V1 keymap, shared with all clients via SCM_RIGHTS.
     keymap: Option<KeymapMemfd>,
     modifiers: KeyboardModifiers,
     keyboards: Vec<SeatKeyboard>,
+    /// Surface the keyboards of its client are entered on.
+    keyboard_focus: Option<(ClientId, ObjectId)>,
+    pointers: Vec<SeatPointer>,
+    pointer: PointerState,
+    touches: Vec<SeatTouch>,
+    /// Active touch points by id, each grabbed by the surface it went down on.
+    touch_points: HashMap<i32, TouchPoint>,
+    /// Clients that received touch events since the last frame.
+    touch_frame_clients: Vec<ClientId>,
     serial: Serial,
 }
 
@@ -38,15 +52,104 @@ struct SeatKeyboard {
     focus: Option<ObjectId>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisSource {
+    Wheel,
+    Finger,
+    Continuous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisValue {
+    /// Scroll distance in surface-local coordinates.
+    pub value: f64,
+    /// High-resolution wheel steps where 120 is one detent.
+    pub value120: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerAxis {
+    pub source: AxisSource,
+    pub horizontal: Option<AxisValue>,
+    pub vertical: Option<AxisValue>,
+}
+
+/// Surface under the pointer or a touch point with surface-local coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PointerTarget {
+    pub(crate) client_id: ClientId,
+    pub(crate) surface_id: ObjectId,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+}
+
+struct SeatPointer {
+    client_id: ClientId,
+    id: ObjectId,
+    version: u32,
+}
+
+struct SeatTouch {
+    client_id: ClientId,
+    id: ObjectId,
+}
+
+#[derive(Clone, Copy)]
+struct TouchPoint {
+    client_id: ClientId,
+    surface_id: ObjectId,
+}
+
+#[derive(Default)]
+struct PointerState {
+    /// Cursor position in the global output layout.
+    position: (f64, f64),
+    output_layout: Vec<Rectangle>,
+    /// Output absolute positions of touchscreens and tablets are mapped to,
+    /// and the transform it shows the layout with; absolute positions are
+    /// normalized to the transformed output.
+    absolute_output: Option<(Rectangle, Transform)>,
+    focus: Option<PointerFocus>,
+    pressed_buttons: Vec<u32>,
+    /// Serial of the button press that started the current implicit grab.
+    grab_serial: Option<u32>,
+    cursor: Option<CursorImage>,
+    /// Surface-local position of the last enter or motion.
+    local_position: (f64, f64),
+}
+
+#[derive(Clone, Copy)]
+struct PointerFocus {
+    client_id: ClientId,
+    surface_id: ObjectId,
+    enter_serial: u32,
+}
+
+/// Surface a client set as its cursor image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorImage {
+    pub client_id: ClientId,
+    /// `None` hides the cursor.
+    pub surface_id: Option<ObjectId>,
+    pub hotspot: (i32, i32),
+}
+
 impl Default for SeatManager {
     fn default() -> Self {
         Self {
             has_main_seat: false,
             known_seats: HashSet::new(),
             id_to_name: HashMap::new(),
+            seat_objects: HashMap::new(),
             keymap: None,
             modifiers: KeyboardModifiers::default(),
             keyboards: Vec::new(),
+            keyboard_focus: None,
+            pointers: Vec::new(),
+            pointer: PointerState::default(),
+            touches: Vec::new(),
+            touch_points: HashMap::new(),
+            touch_frame_clients: Vec::new(),
             serial: Serial::new(),
         }
     }
@@ -93,6 +196,56 @@ impl SeatManager {
         self.id_to_name.get(&id).map(|s| s.as_str())
     }
 
+    pub fn bind_seat(&mut self, client_id: ClientId, seat_id: ObjectId, global_id: GlobalId) {
+        self.seat_objects.insert((client_id, seat_id), global_id);
+    }
+
+    pub fn release_seat(&mut self, client_id: ClientId, seat_id: ObjectId) {
+        self.seat_objects.remove(&(client_id, seat_id));
+    }
+
+    /// The seat global a client's wl_seat object was bound to.
+    pub fn seat_global(&self, client_id: ClientId, seat_id: ObjectId) -> Option<GlobalId> {
+        self.seat_objects.get(&(client_id, seat_id)).copied()
+    }
+
+    /// Clients with at least one keyboard that has a focused surface.
+    pub fn focused_clients(&self) -> Vec<ClientId> {
+        let mut clients: Vec<ClientId> = Vec::new();
+        for keyboard in self.keyboards.iter().filter(|kb| kb.focus.is_some()) {
+            if !clients.contains(&keyboard.client_id) {
+                clients.push(keyboard.client_id);
+            }
+        }
+        clients
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.seat_objects
+            .retain(|(owner, _), _| *owner != client_id);
+        self.keyboards.retain(|kb| kb.client_id != client_id);
+        if self
+            .keyboard_focus
+            .is_some_and(|(owner, _)| owner == client_id)
+        {
+            self.keyboard_focus = None;
+        }
+        self.pointers
+            .retain(|pointer| pointer.client_id != client_id);
+        if self
+            .pointer
+            .focus
+            .is_some_and(|focus| focus.client_id == client_id)
+        {
+            self.pointer.focus = None;
+            self.pointer.cursor = None;
+        }
+        self.touches.retain(|touch| touch.client_id != client_id);
+        self.touch_points
+            .retain(|_, point| point.client_id != client_id);
+        self.touch_frame_clients.retain(|owner| *owner != client_id);
+    }
+
     pub fn create_keyboard(
         &mut self,
         client_id: ClientId,
@@ -126,41 +279,59 @@ impl SeatManager {
             .retain(|kb| !(kb.client_id == client_id && kb.id == keyboard_id));
     }
 
-    pub fn focus_keyboards_on_surface(
+    /// Surface keyboards are entered on.
+    pub fn keyboard_focus(&self) -> Option<(ClientId, ObjectId)> {
+        self.keyboard_focus
+    }
+
+    /// Moves keyboard focus to `target`, sending leave to the keyboards
+    /// entered on the previous surface and enter to those of its client.
+    ///
+    /// Returns whether focus changed.
+    pub fn set_keyboard_focus(
         &mut self,
-        client_id: ClientId,
-        surface: ObjectId,
-        writer: &mut Writer,
-    ) {
-        let modifiers = self.modifiers;
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        target: Option<(ClientId, ObjectId)>,
+    ) -> bool {
+        if self.keyboard_focus == target {
+            return false;
+        }
+        self.keyboard_focus = target;
+        let serial = self.serial.next_serial();
+        for keyboard in &mut self.keyboards {
+            let Some(surface) = keyboard.focus.take() else {
+                continue;
+            };
+            if let Some(client) = clients.get_mut(&keyboard.client_id) {
+                client
+                    .writer_mut()
+                    .wl_keyboard_leave(keyboard.id)
+                    .serial(serial)
+                    .surface(surface);
+            }
+        }
+        let Some((client_id, surface_id)) = target else {
+            return true;
+        };
+        let Some(client) = clients.get_mut(&client_id) else {
+            return true;
+        };
         let keyboards: Vec<ObjectId> = self
             .keyboards
             .iter()
-            .filter(|kb| kb.client_id == client_id && kb.focus.is_none())
+            .filter(|kb| kb.client_id == client_id)
             .map(|kb| kb.id)
             .collect();
         for keyboard_id in keyboards {
-            let serial = self.serial.next_serial();
-            writer
-                .wl_keyboard_enter(keyboard_id)
-                .serial(serial)
-                .surface(surface)
-                .keys(&[]);
-            writer
-                .wl_keyboard_modifiers(keyboard_id)
-                .serial(serial)
-                .mods_depressed(modifiers.depressed)
-                .mods_latched(modifiers.latched)
-                .mods_locked(modifiers.locked)
-                .group(modifiers.group);
-            if let Some(keyboard) = self
-                .keyboards
-                .iter_mut()
-                .find(|kb| kb.client_id == client_id && kb.id == keyboard_id)
-            {
-                keyboard.focus = Some(surface);
+            self.send_enter(client.writer_mut(), keyboard_id, surface_id);
+            self.send_modifiers(client.writer_mut(), keyboard_id);
+        }
+        for keyboard in &mut self.keyboards {
+            if keyboard.client_id == client_id {
+                keyboard.focus = Some(surface_id);
             }
         }
+        true
     }
 
     pub fn handle_key(
@@ -220,6 +391,503 @@ impl SeatManager {
         }
     }
 
+    pub fn create_pointer(
+        &mut self,
+        client_id: ClientId,
+        pointer_id: ObjectId,
+        version: u32,
+        writer: &mut Writer,
+    ) {
+        self.pointers.push(SeatPointer {
+            client_id,
+            id: pointer_id,
+            version,
+        });
+        let Some(focus) = self
+            .pointer
+            .focus
+            .filter(|focus| focus.client_id == client_id)
+        else {
+            return;
+        };
+        let (x, y) = self.pointer.local_position;
+        writer
+            .wl_pointer_enter(pointer_id)
+            .serial(focus.enter_serial)
+            .surface(focus.surface_id)
+            .surface_x(x as f32)
+            .surface_y(y as f32);
+        if version >= 5 {
+            writer.wl_pointer_frame(pointer_id);
+        }
+    }
+
+    pub fn destroy_pointer(&mut self, client_id: ClientId, pointer_id: ObjectId) {
+        self.pointers
+            .retain(|pointer| !(pointer.client_id == client_id && pointer.id == pointer_id));
+    }
+
+    /// Replaces the output rectangles the cursor is confined to, and the
+    /// output absolute input devices are mapped to.
+    pub fn set_output_layout(
+        &mut self,
+        layout: Vec<Rectangle>,
+        absolute_output: Option<(Rectangle, Transform)>,
+    ) {
+        self.pointer.output_layout = layout;
+        self.pointer.absolute_output = absolute_output;
+        self.pointer.position = self.clamp_to_layout(self.pointer.position);
+    }
+
+    pub fn pointer_position(&self) -> (f64, f64) {
+        self.pointer.position
+    }
+
+    pub fn move_pointer(&mut self, dx: f64, dy: f64) {
+        let (x, y) = self.pointer.position;
+        self.pointer.position = self.clamp_to_layout((x + dx, y + dy));
+    }
+
+    /// Moves the pointer to a position normalized to the absolute output.
+    pub fn warp_pointer_normalized(&mut self, x: f64, y: f64) {
+        if let Some(position) = self.layout_position(x, y) {
+            self.pointer.position = position;
+        }
+    }
+
+    /// Maps a position normalized to the absolute output, as shown by the
+    /// output, into the layout.
+    pub fn layout_position(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let (bounds, transform) = self.pointer.absolute_output?;
+        let (x, y) = transform.invert().apply_point((x, y), (1.0, 1.0));
+        let max_x = f64::from(bounds.x + bounds.width) - 1.0;
+        let max_y = f64::from(bounds.y + bounds.height) - 1.0;
+        Some((
+            (f64::from(bounds.x) + x * f64::from(bounds.width)).clamp(f64::from(bounds.x), max_x),
+            (f64::from(bounds.y) + y * f64::from(bounds.height)).clamp(f64::from(bounds.y), max_y),
+        ))
+    }
+
+    pub fn pointer_focus(&self) -> Option<(ClientId, ObjectId)> {
+        self.pointer
+            .focus
+            .map(|focus| (focus.client_id, focus.surface_id))
+    }
+
+    /// Whether a button is held, keeping pointer focus on the pressed surface.
+    pub fn is_pointer_grabbed(&self) -> bool {
+        !self.pointer.pressed_buttons.is_empty()
+    }
+
+    /// Whether `serial` is the button press of the client's current implicit grab.
+    pub fn has_pointer_grab(&self, client_id: ClientId, serial: u32) -> bool {
+        self.is_pointer_grabbed()
+            && self.pointer.grab_serial == Some(serial)
+            && self
+                .pointer
+                .focus
+                .is_some_and(|focus| focus.client_id == client_id)
+    }
+
+    /// Moves pointer focus to `target`, sending leave and enter on changes.
+    ///
+    /// Motion is only sent when `time_msec` is set, i.e. for actual pointer
+    /// movement rather than scene changes under a resting cursor.
+    pub(crate) fn set_pointer_focus(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: Option<u32>,
+        target: Option<PointerTarget>,
+    ) {
+        let current = self.pointer_focus();
+        let next = target.map(|target| (target.client_id, target.surface_id));
+        if let Some(target) = target {
+            self.pointer.local_position = (target.x, target.y);
+        }
+        if current == next {
+            if let (Some(target), Some(time_msec)) = (target, time_msec) {
+                for (pointer_id, version) in self.client_pointers(target.client_id) {
+                    let Some(client) = clients.get_mut(&target.client_id) else {
+                        continue;
+                    };
+                    let writer = client.writer_mut();
+                    writer
+                        .wl_pointer_motion(pointer_id)
+                        .time(time_msec)
+                        .surface_x(target.x as f32)
+                        .surface_y(target.y as f32);
+                    if version >= 5 {
+                        writer.wl_pointer_frame(pointer_id);
+                    }
+                }
+            }
+            return;
+        }
+
+        if let Some(focus) = self.pointer.focus.take() {
+            let serial = self.serial.next_serial();
+            for (pointer_id, version) in self.client_pointers(focus.client_id) {
+                let Some(client) = clients.get_mut(&focus.client_id) else {
+                    continue;
+                };
+                let writer = client.writer_mut();
+                writer
+                    .wl_pointer_leave(pointer_id)
+                    .serial(serial)
+                    .surface(focus.surface_id);
+                if version >= 5 {
+                    writer.wl_pointer_frame(pointer_id);
+                }
+            }
+            if target.is_none_or(|target| target.client_id != focus.client_id) {
+                self.pointer.cursor = None;
+            }
+        }
+        let Some(target) = target else {
+            return;
+        };
+        let serial = self.serial.next_serial();
+        for (pointer_id, version) in self.client_pointers(target.client_id) {
+            let Some(client) = clients.get_mut(&target.client_id) else {
+                continue;
+            };
+            let writer = client.writer_mut();
+            writer
+                .wl_pointer_enter(pointer_id)
+                .serial(serial)
+                .surface(target.surface_id)
+                .surface_x(target.x as f32)
+                .surface_y(target.y as f32);
+            if version >= 5 {
+                writer.wl_pointer_frame(pointer_id);
+            }
+        }
+        self.pointer.focus = Some(PointerFocus {
+            client_id: target.client_id,
+            surface_id: target.surface_id,
+            enter_serial: serial,
+        });
+    }
+
+    /// Updates the set of held buttons and forwards the button to the focused client.
+    pub fn handle_pointer_button(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        button: u32,
+        pressed: bool,
+    ) {
+        let serial = self.serial.next_serial();
+        if !self.track_pointer_button(button, pressed) {
+            return;
+        }
+        if pressed && self.pointer.pressed_buttons.len() == 1 {
+            self.pointer.grab_serial = Some(serial);
+        }
+        let Some(focus) = self.pointer.focus else {
+            return;
+        };
+        let state = if pressed {
+            WL_POINTER_BUTTON_STATE_PRESSED
+        } else {
+            WL_POINTER_BUTTON_STATE_RELEASED
+        };
+        for (pointer_id, version) in self.client_pointers(focus.client_id) {
+            let Some(client) = clients.get_mut(&focus.client_id) else {
+                continue;
+            };
+            let writer = client.writer_mut();
+            writer
+                .wl_pointer_button(pointer_id)
+                .serial(serial)
+                .time(time_msec)
+                .button(button)
+                .state(state);
+            if version >= 5 {
+                writer.wl_pointer_frame(pointer_id);
+            }
+        }
+    }
+
+    /// Records a button change without delivering it, e.g. during drag-and-drop.
+    ///
+    /// Returns `false` for releases of buttons that were never pressed.
+    pub fn track_pointer_button(&mut self, button: u32, pressed: bool) -> bool {
+        let buttons = &mut self.pointer.pressed_buttons;
+        if pressed {
+            if !buttons.contains(&button) {
+                buttons.push(button);
+            }
+            return true;
+        }
+        let held = buttons.len();
+        buttons.retain(|held_button| *held_button != button);
+        if buttons.is_empty() {
+            self.pointer.grab_serial = None;
+        }
+        held != buttons.len()
+    }
+
+    pub fn handle_pointer_axis(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        axis: PointerAxis,
+    ) {
+        let Some(focus) = self.pointer.focus else {
+            return;
+        };
+        let Some(client) = clients.get_mut(&focus.client_id) else {
+            return;
+        };
+        let writer = client.writer_mut();
+        let source = match axis.source {
+            AxisSource::Wheel => WL_POINTER_AXIS_SOURCE_WHEEL,
+            AxisSource::Finger => WL_POINTER_AXIS_SOURCE_FINGER,
+            AxisSource::Continuous => WL_POINTER_AXIS_SOURCE_CONTINUOUS,
+        };
+        for (pointer_id, version) in self.client_pointers(focus.client_id) {
+            if version >= 5 {
+                writer
+                    .wl_pointer_axis_source(pointer_id)
+                    .axis_source(source);
+            }
+            for (wl_axis, value) in [
+                (WL_POINTER_AXIS_VERTICAL_SCROLL, axis.vertical),
+                (WL_POINTER_AXIS_HORIZONTAL_SCROLL, axis.horizontal),
+            ] {
+                let Some(value) = value else {
+                    continue;
+                };
+                match value.value120 {
+                    Some(value120) if version >= 8 => {
+                        writer
+                            .wl_pointer_axis_value120(pointer_id)
+                            .axis(wl_axis)
+                            .value120(value120);
+                    }
+                    Some(value120) if version >= 5 && value120 / 120 != 0 => {
+                        writer
+                            .wl_pointer_axis_discrete(pointer_id)
+                            .axis(wl_axis)
+                            .discrete(value120 / 120);
+                    }
+                    _ => {}
+                }
+                if value.value != 0.0 {
+                    writer
+                        .wl_pointer_axis(pointer_id)
+                        .time(time_msec)
+                        .axis(wl_axis)
+                        .value(value.value as f32);
+                } else if axis.source == AxisSource::Finger && version >= 5 {
+                    // Zero finger scrolling marks the end of the gesture.
+                    writer
+                        .wl_pointer_axis_stop(pointer_id)
+                        .time(time_msec)
+                        .axis(wl_axis);
+                }
+            }
+            if version >= 5 {
+                writer.wl_pointer_frame(pointer_id);
+            }
+        }
+    }
+
+    /// Sets the cursor image if `serial` matches the client's latest pointer enter.
+    pub fn set_cursor(
+        &mut self,
+        client_id: ClientId,
+        serial: u32,
+        surface: Option<ObjectId>,
+        hotspot: (i32, i32),
+    ) -> bool {
+        let Some(focus) = self.pointer.focus else {
+            return false;
+        };
+        if focus.client_id != client_id || focus.enter_serial != serial {
+            return false;
+        }
+        self.pointer.cursor = Some(CursorImage {
+            client_id,
+            surface_id: surface,
+            hotspot,
+        });
+        true
+    }
+
+    pub fn cursor(&self) -> Option<CursorImage> {
+        self.pointer.cursor
+    }
+
+    /// Forgets keyboard and pointer focus, cursor image and touch points
+    /// referring to a destroyed surface.
+    pub fn surface_destroyed(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        // Keyboards leave destroyed surfaces implicitly.
+        if self.keyboard_focus == Some((client_id, surface_id)) {
+            self.keyboard_focus = None;
+            for keyboard in &mut self.keyboards {
+                if keyboard.client_id == client_id {
+                    keyboard.focus = None;
+                }
+            }
+        }
+        self.touch_points
+            .retain(|_, point| !(point.client_id == client_id && point.surface_id == surface_id));
+        if self
+            .pointer
+            .focus
+            .is_some_and(|focus| focus.client_id == client_id && focus.surface_id == surface_id)
+        {
+            self.pointer.focus = None;
+        }
+        if self.pointer.cursor.is_some_and(|cursor| {
+            cursor.client_id == client_id && cursor.surface_id == Some(surface_id)
+        }) {
+            self.pointer.cursor = None;
+        }
+    }
+
+    pub fn create_touch(&mut self, client_id: ClientId, touch_id: ObjectId) {
+        self.touches.push(SeatTouch {
+            client_id,
+            id: touch_id,
+        });
+    }
+
+    pub fn destroy_touch(&mut self, client_id: ClientId, touch_id: ObjectId) {
+        self.touches
+            .retain(|touch| !(touch.client_id == client_id && touch.id == touch_id));
+    }
+
+    /// Surface grabbing the touch point `id`.
+    pub fn touch_point(&self, id: i32) -> Option<(ClientId, ObjectId)> {
+        self.touch_points
+            .get(&id)
+            .map(|point| (point.client_id, point.surface_id))
+    }
+
+    /// Starts touch point `id` on `target`, which keeps it until it is lifted.
+    pub(crate) fn handle_touch_down(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+        target: PointerTarget,
+    ) {
+        self.touch_points.insert(
+            id,
+            TouchPoint {
+                client_id: target.client_id,
+                surface_id: target.surface_id,
+            },
+        );
+        let Some(client) = clients.get_mut(&target.client_id) else {
+            return;
+        };
+        let serial = self.serial.next_serial();
+        let writer = client.writer_mut();
+        for touch_id in client_touches(&self.touches, target.client_id) {
+            writer
+                .wl_touch_down(touch_id)
+                .serial(serial)
+                .time(time_msec)
+                .surface(target.surface_id)
+                .id(id)
+                .x(target.x as f32)
+                .y(target.y as f32);
+        }
+        self.touched(target.client_id);
+    }
+
+    /// Sends motion of touch point `id` in coordinates local to its surface.
+    pub fn handle_touch_motion(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+        (x, y): (f64, f64),
+    ) {
+        let Some(point) = self.touch_points.get(&id).copied() else {
+            return;
+        };
+        let Some(client) = clients.get_mut(&point.client_id) else {
+            return;
+        };
+        let writer = client.writer_mut();
+        for touch_id in client_touches(&self.touches, point.client_id) {
+            writer
+                .wl_touch_motion(touch_id)
+                .time(time_msec)
+                .id(id)
+                .x(x as f32)
+                .y(y as f32);
+        }
+        self.touched(point.client_id);
+    }
+
+    pub fn handle_touch_up(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        time_msec: u32,
+        id: i32,
+    ) {
+        let Some(point) = self.touch_points.remove(&id) else {
+            return;
+        };
+        let Some(client) = clients.get_mut(&point.client_id) else {
+            return;
+        };
+        let serial = self.serial.next_serial();
+        let writer = client.writer_mut();
+        for touch_id in client_touches(&self.touches, point.client_id) {
+            writer
+                .wl_touch_up(touch_id)
+                .serial(serial)
+                .time(time_msec)
+                .id(id);
+        }
+        self.touched(point.client_id);
+    }
+
+    /// Cancels the touch sequence of the client owning touch point `id`.
+    ///
+    /// `wl_touch.cancel` applies to all of the client's touch points, so they
+    /// are all dropped and no frame follows.
+    pub fn handle_touch_cancel(
+        &mut self,
+        clients: &mut HashMap<ClientId, ClientConnection>,
+        id: i32,
+    ) {
+        let Some(point) = self.touch_points.get(&id).copied() else {
+            return;
+        };
+        self.touch_points
+            .retain(|_, other| other.client_id != point.client_id);
+        self.touch_frame_clients
+            .retain(|owner| *owner != point.client_id);
+        let Some(client) = clients.get_mut(&point.client_id) else {
+            return;
+        };
+        let writer = client.writer_mut();
+        for touch_id in client_touches(&self.touches, point.client_id) {
+            writer.wl_touch_cancel(touch_id);
+        }
+    }
+
+    /// Sends `wl_touch.frame` to every client touched since the last frame.
+    pub fn handle_touch_frame(&mut self, clients: &mut HashMap<ClientId, ClientConnection>) {
+        for client_id in std::mem::take(&mut self.touch_frame_clients) {
+            let Some(client) = clients.get_mut(&client_id) else {
+                continue;
+            };
+            let writer = client.writer_mut();
+            for touch_id in client_touches(&self.touches, client_id) {
+                writer.wl_touch_frame(touch_id);
+            }
+        }
+    }
+
     fn send_keymap(&self, writer: &mut Writer, keyboard_id: ObjectId) -> anyhow::Result<()> {
         let Some(keymap) = self.keymap.as_ref() else {
             anyhow::bail!("Keyboard keymap has not been set");
@@ -256,18 +924,186 @@ impl SeatManager {
     }
 }
 
-struct Serial {
+impl SeatManager {
+    fn client_pointers(&self, client_id: ClientId) -> Vec<(ObjectId, u32)> {
+        self.pointers
+            .iter()
+            .filter(|pointer| pointer.client_id == client_id)
+            .map(|pointer| (pointer.id, pointer.version))
+            .collect()
+    }
+
+    fn touched(&mut self, client_id: ClientId) {
+        if !self.touch_frame_clients.contains(&client_id) {
+            self.touch_frame_clients.push(client_id);
+        }
+    }
+
+    /// Clamps `position` into the nearest output. Positions are left alone
+    /// while no outputs are known.
+    fn clamp_to_layout(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        self.pointer
+            .output_layout
+            .iter()
+            .filter(|output| output.width > 0 && output.height > 0)
+            .map(|output| {
+                let max_x = f64::from(output.x + output.width) - 1.0;
+                let max_y = f64::from(output.y + output.height) - 1.0;
+                (
+                    x.clamp(f64::from(output.x), max_x),
+                    y.clamp(f64::from(output.y), max_y),
+                )
+            })
+            .min_by(|a, b| {
+                let distance = |(cx, cy): (f64, f64)| (cx - x).powi(2) + (cy - y).powi(2);
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .unwrap_or((x, y))
+    }
+}
+
+fn client_touches(touches: &[SeatTouch], client_id: ClientId) -> Vec<ObjectId> {
+    touches
+        .iter()
+        .filter(|touch| touch.client_id == client_id)
+        .map(|touch| touch.id)
+        .collect()
+}
+
+pub(crate) struct Serial {
     next_serial: u32,
 }
 
 impl Serial {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self { next_serial: 1 }
     }
 
-    fn next_serial(&mut self) -> u32 {
+    pub(crate) fn next_serial(&mut self) -> u32 {
         let serial = self.next_serial;
         self.next_serial = self.next_serial.wrapping_add(1);
         serial
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    fn output(x: i32, y: i32, width: i32, height: i32) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn pointer_is_clamped_to_the_nearest_output() {
+        let mut seats = SeatManager::default();
+        seats.set_output_layout(
+            vec![output(0, 0, 100, 50), output(100, 0, 200, 100)],
+            Some((output(100, 0, 200, 100), Transform::Normal)),
+        );
+
+        seats.move_pointer(50.0, 80.0);
+        assert_eq!(seats.pointer_position(), (50.0, 49.0));
+
+        seats.move_pointer(100.0, 30.0);
+        assert_eq!(seats.pointer_position(), (150.0, 79.0));
+
+        seats.move_pointer(500.0, -500.0);
+        assert_eq!(seats.pointer_position(), (299.0, 0.0));
+
+        seats.warp_pointer_normalized(0.5, 0.5);
+        assert_eq!(seats.pointer_position(), (200.0, 50.0));
+    }
+
+    #[test]
+    fn absolute_positions_follow_the_output_transform() {
+        let mut seats = SeatManager::default();
+        let panel = output(0, 0, 100, 200);
+        seats.set_output_layout(vec![panel], Some((panel, Transform::Rotate90)));
+
+        // The top left of the rotated panel shows the top right of the layout.
+        assert_eq!(seats.layout_position(0.0, 0.0), Some((99.0, 0.0)));
+        assert_eq!(seats.layout_position(0.5, 0.25), Some((75.0, 100.0)));
+    }
+
+    #[test]
+    fn absolute_positions_map_to_one_output() {
+        let mut seats = SeatManager::default();
+        let laptop = output(0, 0, 1920, 1080);
+        let monitor = output(1920, 0, 2560, 1440);
+        seats.set_output_layout(vec![laptop, monitor], Some((laptop, Transform::Normal)));
+
+        assert_eq!(seats.layout_position(0.5, 0.5), Some((960.0, 540.0)));
+        assert_eq!(seats.layout_position(1.0, 1.0), Some((1919.0, 1079.0)));
+
+        seats.set_output_layout(vec![laptop, monitor], Some((monitor, Transform::Normal)));
+        assert_eq!(seats.layout_position(0.0, 0.5), Some((1920.0, 720.0)));
+
+        seats.set_output_layout(vec![laptop, monitor], None);
+        assert_eq!(seats.layout_position(0.5, 0.5), None);
+    }
+
+    #[test]
+    fn absolute_positions_follow_their_output_transform_only() {
+        let mut seats = SeatManager::default();
+        let laptop = output(0, 0, 1920, 1080);
+        let portrait = output(1920, 0, 1080, 1920);
+        let layout = vec![laptop, portrait];
+
+        seats.set_output_layout(layout.clone(), Some((laptop, Transform::Normal)));
+        assert_eq!(seats.layout_position(0.25, 0.5), Some((480.0, 540.0)));
+
+        // The top left of the rotated panel shows the top right of its output.
+        seats.set_output_layout(layout, Some((portrait, Transform::Rotate90)));
+        assert_eq!(seats.layout_position(0.0, 0.0), Some((2999.0, 0.0)));
+        assert_eq!(seats.layout_position(0.5, 0.25), Some((2730.0, 960.0)));
+    }
+
+    #[test]
+    fn releasing_the_last_button_ends_the_grab() {
+        let mut seats = SeatManager::default();
+        assert!(!seats.track_pointer_button(0x110, false));
+
+        assert!(seats.track_pointer_button(0x110, true));
+        assert!(seats.track_pointer_button(0x111, true));
+        assert!(seats.is_pointer_grabbed());
+
+        assert!(seats.track_pointer_button(0x110, false));
+        assert!(seats.is_pointer_grabbed());
+        assert!(seats.track_pointer_button(0x111, false));
+        assert!(!seats.is_pointer_grabbed());
+    }
+
+    #[test]
+    fn touch_points_stay_with_their_surface_until_cancelled() {
+        let mut seats = SeatManager::default();
+        let mut clients = HashMap::new();
+        let target = |client_id, surface_id| PointerTarget {
+            client_id,
+            surface_id,
+            x: 1.0,
+            y: 2.0,
+        };
+        seats.handle_touch_down(&mut clients, 0, 0, target(client(1), object(3)));
+        seats.handle_touch_down(&mut clients, 0, 1, target(client(1), object(4)));
+        seats.handle_touch_down(&mut clients, 0, 2, target(client(2), object(3)));
+        assert_eq!(seats.touch_point(1), Some((client(1), object(4))));
+
+        seats.handle_touch_up(&mut clients, 1, 1);
+        assert_eq!(seats.touch_point(1), None);
+
+        seats.surface_destroyed(client(2), object(3));
+        assert_eq!(seats.touch_point(2), None);
+
+        seats.handle_touch_down(&mut clients, 2, 1, target(client(1), object(4)));
+        seats.handle_touch_cancel(&mut clients, 0);
+        assert_eq!(seats.touch_point(0), None);
+        assert_eq!(seats.touch_point(1), None);
+    }
+}
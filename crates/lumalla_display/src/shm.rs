@@ -411,19 +411,11 @@ mod tests {
     use std::{
         fs::File,
         io::{Seek, SeekFrom, Write},
-        num::NonZeroU32,
         os::fd::{FromRawFd, IntoRawFd},
     };
 
     use super::*;
-
-    fn client(id: u32) -> ClientId {
-        ClientId::new(NonZeroU32::new(id).unwrap())
-    }
-
-    fn object(id: u32) -> ObjectId {
-        ObjectId::new(NonZeroU32::new(id).unwrap())
-    }
+    use crate::test_ids::{client, object};
 
     fn memory_file(bytes: &[u8], size: usize) -> RawFd {
         let fd = unsafe { libc::memfd_create(c"lumalla-shm-test".as_ptr(), libc::MFD_CLOEXEC) };
@@ -1,6 +1,21 @@
 use std::collections::HashMap;
 
-use lumalla_wayland_protocol::{ClientId, ObjectId};
+use lumalla_shared::{Transform, Viewport};
+use lumalla_wayland_protocol::{
+    ClientId, ObjectId,
+    protocols::xdg_shell::{
+        XDG_POSITIONER_ANCHOR_BOTTOM, XDG_POSITIONER_ANCHOR_BOTTOM_LEFT,
+        XDG_POSITIONER_ANCHOR_BOTTOM_RIGHT, XDG_POSITIONER_ANCHOR_LEFT,
+        XDG_POSITIONER_ANCHOR_RIGHT, XDG_POSITIONER_ANCHOR_TOP, XDG_POSITIONER_ANCHOR_TOP_LEFT,
+        XDG_POSITIONER_ANCHOR_TOP_RIGHT, XDG_POSITIONER_GRAVITY_BOTTOM,
+        XDG_POSITIONER_GRAVITY_BOTTOM_LEFT, XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT,
+        XDG_POSITIONER_GRAVITY_LEFT, XDG_POSITIONER_GRAVITY_RIGHT, XDG_POSITIONER_GRAVITY_TOP,
+        XDG_POSITIONER_GRAVITY_TOP_LEFT, XDG_POSITIONER_GRAVITY_TOP_RIGHT,
+        XDG_TOPLEVEL_STATE_FULLSCREEN, XDG_TOPLEVEL_STATE_MAXIMIZED,
+    },
+};
+
+use crate::window_rules::WindowProperties;
 
 type ResourceKey = (ClientId, ObjectId);
 
@@ -10,7 +25,32 @@ pub enum SurfaceError {
     UnknownBuffer,
     UnknownShellSurface,
     UnknownRegion,
+    UnknownXdgSurface,
+    UnknownPositioner,
     RoleAlreadyAssigned,
+    AlreadyConstructed,
+    NotConstructed,
+    InvalidSurfaceState,
+    InvalidScale,
+    InvalidTransform,
+    UnconfiguredBuffer,
+    InvalidSerial,
+    InvalidSize,
+    InvalidPositioner,
+    InvalidParent,
+    InvalidSibling,
+    /// An xdg_popup parent without role, or one the popup is an ancestor of.
+    InvalidPopupParent,
+    DefunctRoleObject,
+    ViewportExists,
+    /// A viewport source or destination with negative or zero values.
+    InvalidViewport,
+    /// A viewport without destination whose source has a fractional size.
+    InvalidViewportSize,
+    /// A viewport source extending beyond the buffer.
+    ViewportOutOfBuffer,
+    /// A viewport whose surface was destroyed.
+    DefunctViewport,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,7 +70,84 @@ pub struct SurfaceCommit {
     pub buffer: Option<ObjectId>,
     pub attached_buffer: Option<Option<ObjectId>>,
     pub mapped: bool,
+    /// Parts of the surface that changed with this commit, in surface coordinates.
+    pub damage: Vec<Rectangle>,
+    /// Parts of the buffer that changed with this commit, in buffer coordinates.
+    pub buffer_damage: Vec<Rectangle>,
+    /// Scale the buffer is drawn at.
+    pub scale: i32,
+    /// Transform the client drew the buffer with.
+    pub transform: Transform,
+    /// How the surface crops and scales the buffer.
+    pub viewport: Viewport,
     pub frame_callbacks: Vec<ObjectId>,
+    /// Set when an xdg surface needs its initial configure sequence.
+    pub needs_configure: bool,
+    /// Main surface of the sub-surface tree this surface belongs to.
+    pub root_id: ObjectId,
+    /// Set when the state was cached for a synchronized sub-surface instead
+    /// of being applied.
+    pub cached: bool,
+    /// Cached sub-surface state applied together with this commit.
+    pub subsurface_commits: Vec<SurfaceCommit>,
+}
+
+impl SurfaceCommit {
+    /// All parts that changed with this commit, in the coordinates of a
+    /// buffer of `buffer_size`.
+    pub fn damage_in_buffer(&self, buffer_size: (i32, i32)) -> Vec<Rectangle> {
+        let scale = self.scale.max(1);
+        let (width, height) = self.transform.apply_size(buffer_size);
+        let surface_size = (width / scale, height / scale);
+        let surface_damage = self.damage.iter().map(|rectangle| {
+            let source = self.viewport.source_of(
+                (rectangle.x, rectangle.y, rectangle.width, rectangle.height),
+                surface_size,
+            );
+            let (x, y, width, height) = self.transform.apply_rect(source, surface_size);
+            Rectangle {
+                x: x * scale,
+                y: y * scale,
+                width: width * scale,
+                height: height * scale,
+            }
+        });
+        surface_damage
+            .chain(self.buffer_damage.iter().copied())
+            .collect()
+    }
+}
+
+/// One surface of a composed sub-surface tree, in the main surface's coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceLayer {
+    pub surface_id: ObjectId,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Role-specific part of an xdg configure sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XdgConfigure {
+    Toplevel {
+        toplevel_id: ObjectId,
+        version: u32,
+        width: i32,
+        height: i32,
+        states: Vec<u32>,
+    },
+    Popup {
+        popup_id: ObjectId,
+        geometry: Rectangle,
+    },
+}
+
+/// Everything needed to send one configure sequence to an xdg surface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XdgConfigureTarget {
+    pub xdg_surface_id: ObjectId,
+    pub serial: u32,
+    pub role: XdgConfigure,
 }
 
 #[derive(Debug, Default)]
@@ -38,6 +155,23 @@ pub struct SurfaceManager {
     surfaces: HashMap<ResourceKey, Surface>,
     shell_surfaces: HashMap<ResourceKey, ObjectId>,
     regions: HashMap<ResourceKey, Region>,
+    xdg_surfaces: HashMap<ResourceKey, ObjectId>,
+    positioners: HashMap<ResourceKey, Positioner>,
+    subsurfaces: HashMap<ResourceKey, ObjectId>,
+    /// Surface of every wp_viewport; `None` once the surface was destroyed.
+    viewports: HashMap<ResourceKey, Option<ObjectId>>,
+    configure_serial: u32,
+    /// Frame callbacks of applied commits, waiting for their tree to be presented.
+    queued_frame_callbacks: Vec<QueuedFrameCallback>,
+}
+
+/// A frame callback answered once the tree of its surface reaches the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueuedFrameCallback {
+    client_id: ClientId,
+    surface_id: ObjectId,
+    root_id: ObjectId,
+    callback: ObjectId,
 }
 
 impl SurfaceManager {
@@ -50,26 +184,56 @@ impl SurfaceManager {
         client_id: ClientId,
         id: ObjectId,
     ) -> Result<(Option<ObjectId>, Vec<ObjectId>, bool), SurfaceError> {
+        let was_mapped = self.is_tree_mapped(client_id, id);
         let surface = self
             .surfaces
             .remove(&(client_id, id))
             .ok_or(SurfaceError::UnknownSurface)?;
-        let was_mapped = surface.is_mapped();
+        if let Some(parent) = surface.subsurface.as_ref().and_then(|sub| sub.parent) {
+            self.detach_subsurface(client_id, parent, id);
+        }
+        // Sub-surfaces of a destroyed parent stay around, unmapped.
+        for child_id in surface.pending_stack.iter().chain(&surface.stack) {
+            if let Some(subsurface) = self
+                .surfaces
+                .get_mut(&(client_id, *child_id))
+                .and_then(|child| child.subsurface.as_mut())
+            {
+                subsurface.parent = None;
+            }
+        }
+        if let Some(viewport_id) = surface.viewport {
+            self.viewports.insert((client_id, viewport_id), None);
+        }
+        let mut frame_callbacks = surface.pending.frame_callbacks;
+        if let Some(cached) = surface.subsurface.and_then(|sub| sub.cached) {
+            frame_callbacks.extend(cached.frame_callbacks);
+        }
+        self.queued_frame_callbacks.retain(|queued| {
+            let destroyed = queued.client_id == client_id && queued.surface_id == id;
+            if destroyed {
+                frame_callbacks.push(queued.callback);
+            }
+            !destroyed
+        });
         let shell_id = match surface.role {
             Some(Role::Shell(shell_id)) => {
                 self.shell_surfaces.remove(&(client_id, shell_id));
                 Some(shell_id)
             }
-            None => None,
+            // xdg objects outlive their wl_surface until the client destroys them.
+            Some(Role::Xdg) => {
+                if let Some(xdg) = surface.xdg {
+                    self.xdg_surfaces.remove(&(client_id, xdg.id));
+                    if let Some(role_id) = xdg.role.id() {
+                        self.shell_surfaces.remove(&(client_id, role_id));
+                    }
+                }
+                None
+            }
+            Some(Role::Subsurface | Role::DragIcon | Role::Cursor) | None => None,
         };
-        Ok((shell_id, surface.pending.frame_callbacks, was_mapped))
-    }
-
-    pub fn first_surface(&self, client_id: ClientId) -> Option<ObjectId> {
-        self.surfaces
-            .iter()
-            .find(|((owner, _), surface)| *owner == client_id && surface.is_mapped())
-            .map(|((_, id), _)| *id)
+        Ok((shell_id, frame_callbacks, was_mapped))
     }
 
     pub fn attach(
@@ -105,6 +269,54 @@ impl SurfaceManager {
         Ok(())
     }
 
+    pub fn damage_buffer(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        rectangle: Rectangle,
+    ) -> Result<(), SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if rectangle.width > 0 && rectangle.height > 0 {
+            surface.pending.buffer_damage.push(rectangle);
+        }
+        Ok(())
+    }
+
+    pub fn set_buffer_scale(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        scale: i32,
+    ) -> Result<(), SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if scale < 1 {
+            return Err(SurfaceError::InvalidScale);
+        }
+        surface.pending.scale = Some(scale);
+        Ok(())
+    }
+
+    pub fn set_buffer_transform(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        transform: i32,
+    ) -> Result<(), SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        surface.pending.transform =
+            Some(Transform::from_wayland(transform).ok_or(SurfaceError::InvalidTransform)?);
+        Ok(())
+    }
+
     pub fn add_frame_callback(
         &mut self,
         client_id: ClientId,
@@ -155,32 +367,51 @@ impl SurfaceManager {
         client_id: ClientId,
         id: ObjectId,
     ) -> Result<SurfaceCommit, SurfaceError> {
+        let synchronized = self.is_synchronized(client_id, id);
+        let root_id = self.tree_root(client_id, id);
         let surface = self
             .surfaces
             .get_mut(&(client_id, id))
             .ok_or(SurfaceError::UnknownSurface)?;
-        let attached_buffer = surface.pending.buffer.take();
-        if let Some(buffer) = attached_buffer {
-            surface.current.buffer = buffer;
+        if surface
+            .xdg
+            .as_ref()
+            .is_some_and(|xdg| xdg.role == XdgRole::None)
+        {
+            return Err(SurfaceError::NotConstructed);
         }
-        if let Some(offset) = surface.pending.offset.take() {
-            surface.current.offset = offset;
+        if surface.xdg.as_ref().is_some_and(|xdg| !xdg.configured)
+            && matches!(surface.pending.buffer, Some(Some(_)))
+        {
+            return Err(SurfaceError::UnconfiguredBuffer);
         }
-        if let Some(region) = surface.pending.opaque_region.take() {
-            surface.current.opaque_region = region;
-        }
-        if let Some(region) = surface.pending.input_region.take() {
-            surface.current.input_region = region;
+        let mut state = std::mem::take(&mut surface.pending);
+        if let Some(subsurface) = surface.subsurface.as_mut() {
+            if let Some(mut cached) = subsurface.cached.take() {
+                cached.merge(state);
+                state = cached;
+            }
+            if synchronized {
+                subsurface.cached = Some(state);
+                return Ok(SurfaceCommit {
+                    surface_id: id,
+                    buffer: surface.current.buffer,
+                    attached_buffer: None,
+                    mapped: false,
+                    damage: Vec::new(),
+                    buffer_damage: Vec::new(),
+                    scale: surface.current.scale,
+                    transform: surface.current.transform,
+                    viewport: surface.current.viewport,
+                    frame_callbacks: Vec::new(),
+                    needs_configure: false,
+                    root_id,
+                    cached: true,
+                    subsurface_commits: Vec::new(),
+                });
+            }
         }
-        surface.current.damage = std::mem::take(&mut surface.pending.damage);
-        let frame_callbacks = std::mem::take(&mut surface.pending.frame_callbacks);
-        Ok(SurfaceCommit {
-            surface_id: id,
-            buffer: surface.current.buffer,
-            attached_buffer,
-            mapped: surface.is_mapped(),
-            frame_callbacks,
-        })
+        self.apply_state(client_id, id, state)
     }
 
     pub fn create_shell_surface(
@@ -202,6 +433,31 @@ impl SurfaceManager {
         Ok(())
     }
 
+    /// Assigns the drag-and-drop icon role. A surface may be reused as an icon
+    /// for later drags.
+    pub fn set_drag_icon_role(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        self.set_reusable_role(client_id, surface_id, Role::DragIcon)
+    }
+
+    /// Assigns the cursor role. A surface may be set as the cursor repeatedly.
+    pub fn set_cursor_role(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        self.set_reusable_role(client_id, surface_id, Role::Cursor)
+    }
+
+    pub fn is_cursor(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        self.surfaces
+            .get(&(client_id, surface_id))
+            .is_some_and(|surface| surface.role == Some(Role::Cursor))
+    }
+
     pub fn set_shell_mode(
         &mut self,
         client_id: ClientId,
@@ -223,6 +479,119 @@ impl SurfaceManager {
             .ok_or(SurfaceError::UnknownShellSurface)
     }
 
+    /// wl_shell_surface of a surface with the wl_shell role.
+    pub fn wl_shell_surface(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        match self.surfaces.get(&(client_id, surface_id))?.role {
+            Some(Role::Shell(shell_id)) => Some(shell_id),
+            _ => None,
+        }
+    }
+
+    /// Properties window rules match a main surface by.
+    pub fn window_properties(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Option<WindowProperties<'_>> {
+        let surface = self.surfaces.get(&(client_id, surface_id))?;
+        let has_parent = matches!(
+            surface.xdg.as_ref().map(|xdg| &xdg.role),
+            Some(XdgRole::Toplevel {
+                parent: Some(_),
+                ..
+            })
+        );
+        Some(WindowProperties {
+            app_id: &surface.shell.class,
+            title: &surface.shell.title,
+            transient: has_parent || surface.shell.mode == ShellMode::Transient,
+        })
+    }
+
+    /// The xdg_toplevel giving a surface its role, if any.
+    pub fn xdg_toplevel(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        let xdg = self.surfaces.get(&(client_id, surface_id))?.xdg.as_ref()?;
+        match xdg.role {
+            XdgRole::Toplevel { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// wl_surface of the parent of an xdg_popup.
+    pub fn xdg_popup_parent(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        let xdg = self.surfaces.get(&(client_id, surface_id))?.xdg.as_ref()?;
+        match xdg.role {
+            XdgRole::Popup {
+                parent: Some(parent),
+                ..
+            } => self.surface_for_xdg_surface(client_id, parent).ok(),
+            _ => None,
+        }
+    }
+
+    /// The xdg_popup giving a surface its role, if any.
+    pub fn xdg_popup(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        let xdg = self.surfaces.get(&(client_id, surface_id))?.xdg.as_ref()?;
+        match xdg.role {
+            XdgRole::Popup { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// wl_surface of the parent of an xdg_popup and the position of the
+    /// popup's surface relative to it, which puts the popup's window
+    /// geometry where the positioner places it within the parent's.
+    pub fn popup_offset(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Option<(ObjectId, (i32, i32))> {
+        let xdg = self.surfaces.get(&(client_id, surface_id))?.xdg.as_ref()?;
+        let XdgRole::Popup {
+            parent: Some(parent),
+            positioner,
+            ..
+        } = &xdg.role
+        else {
+            return None;
+        };
+        let parent_id = self.surface_for_xdg_surface(client_id, *parent).ok()?;
+        let parent_geometry = self
+            .surfaces
+            .get(&(client_id, parent_id))?
+            .xdg
+            .as_ref()?
+            .current
+            .geometry
+            .map_or((0, 0), |geometry| (geometry.x, geometry.y));
+        let geometry = xdg
+            .current
+            .geometry
+            .map_or((0, 0), |geometry| (geometry.x, geometry.y));
+        let placed = positioner.geometry();
+        Some((
+            parent_id,
+            (
+                parent_geometry.0 + placed.x - geometry.0,
+                parent_geometry.1 + placed.y - geometry.1,
+            ),
+        ))
+    }
+
+    /// Makes a toplevel fullscreen, or a plain toplevel again.
+    pub fn set_fullscreen(&mut self, client_id: ClientId, surface_id: ObjectId, fullscreen: bool) {
+        let Some(surface) = self.surfaces.get_mut(&(client_id, surface_id)) else {
+            return;
+        };
+        match (surface.shell.mode, fullscreen) {
+            (ShellMode::Toplevel | ShellMode::Maximized, true) => {
+                surface.shell.mode = ShellMode::Fullscreen;
+            }
+            (ShellMode::Fullscreen, false) => surface.shell.mode = ShellMode::Toplevel,
+            _ => {}
+        }
+    }
+
     pub fn set_shell_title(
         &mut self,
         client_id: ClientId,
@@ -243,155 +612,1495 @@ impl SurfaceManager {
         Ok(())
     }
 
-    pub fn create_region(&mut self, client_id: ClientId, id: ObjectId) {
-        self.regions.insert((client_id, id), Region::default());
+    pub fn create_positioner(&mut self, client_id: ClientId, id: ObjectId) {
+        self.positioners
+            .insert((client_id, id), Positioner::default());
     }
 
-    pub fn destroy_region(
+    pub fn destroy_positioner(&mut self, client_id: ClientId, id: ObjectId) {
+        self.positioners.remove(&(client_id, id));
+    }
+
+    pub fn positioner_mut(
         &mut self,
         client_id: ClientId,
         id: ObjectId,
-    ) -> Result<(), SurfaceError> {
-        self.regions
-            .remove(&(client_id, id))
-            .map(|_| ())
-            .ok_or(SurfaceError::UnknownRegion)
+    ) -> Result<&mut Positioner, SurfaceError> {
+        self.positioners
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownPositioner)
     }
 
-    pub fn add_region(
+    pub fn create_xdg_surface(
         &mut self,
         client_id: ClientId,
-        id: ObjectId,
-        rectangle: Rectangle,
+        wm_base_id: ObjectId,
+        xdg_surface_id: ObjectId,
+        surface_id: ObjectId,
     ) -> Result<(), SurfaceError> {
-        let region = self
-            .regions
-            .get_mut(&(client_id, id))
-            .ok_or(SurfaceError::UnknownRegion)?;
-        if rectangle.width > 0 && rectangle.height > 0 {
-            region.operations.push(RegionOperation::Add(rectangle));
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface.role.is_some_and(|role| role != Role::Xdg) || surface.xdg.is_some() {
+            return Err(SurfaceError::RoleAlreadyAssigned);
+        }
+        if surface.current.buffer.is_some() || matches!(surface.pending.buffer, Some(Some(_))) {
+            return Err(SurfaceError::InvalidSurfaceState);
         }
+        surface.role = Some(Role::Xdg);
+        surface.xdg = Some(XdgState::new(wm_base_id, xdg_surface_id));
+        self.xdg_surfaces
+            .insert((client_id, xdg_surface_id), surface_id);
         Ok(())
     }
 
-    pub fn subtract_region(
+    pub fn destroy_xdg_surface(
         &mut self,
         client_id: ClientId,
-        id: ObjectId,
-        rectangle: Rectangle,
+        xdg_surface_id: ObjectId,
     ) -> Result<(), SurfaceError> {
-        let region = self
-            .regions
-            .get_mut(&(client_id, id))
-            .ok_or(SurfaceError::UnknownRegion)?;
-        if rectangle.width > 0 && rectangle.height > 0 {
-            region.operations.push(RegionOperation::Subtract(rectangle));
+        let Some(surface_id) = self.xdg_surfaces.get(&(client_id, xdg_surface_id)).copied() else {
+            // The wl_surface is already gone; nothing is left to tear down.
+            return Ok(());
+        };
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface
+            .xdg
+            .as_ref()
+            .is_some_and(|xdg| xdg.role != XdgRole::None)
+        {
+            return Err(SurfaceError::DefunctRoleObject);
         }
+        surface.xdg = None;
+        self.xdg_surfaces.remove(&(client_id, xdg_surface_id));
         Ok(())
     }
 
-    pub fn delete_client(&mut self, client_id: ClientId) {
-        self.surfaces.retain(|(owner, _), _| *owner != client_id);
-        self.shell_surfaces
-            .retain(|(owner, _), _| *owner != client_id);
-        self.regions.retain(|(owner, _), _| *owner != client_id);
+    /// Whether xdg_surfaces created through an xdg_wm_base are still alive.
+    pub fn has_xdg_surfaces(&self, client_id: ClientId, wm_base_id: ObjectId) -> bool {
+        self.surfaces.iter().any(|((owner, _), surface)| {
+            *owner == client_id
+                && surface
+                    .xdg
+                    .as_ref()
+                    .is_some_and(|xdg| xdg.wm_base_id == wm_base_id)
+        })
     }
 
-    fn copy_region(
+    /// The xdg_surface of a surface with the xdg role, if any.
+    pub fn xdg_surface(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        let xdg = self.surfaces.get(&(client_id, surface_id))?.xdg.as_ref()?;
+        Some(xdg.id)
+    }
+
+    pub fn surface_for_xdg_surface(
         &self,
         client_id: ClientId,
-        region_id: Option<ObjectId>,
-    ) -> Result<Option<Region>, SurfaceError> {
-        region_id
-            .map(|id| {
-                self.regions
-                    .get(&(client_id, id))
-                    .cloned()
-                    .ok_or(SurfaceError::UnknownRegion)
-            })
-            .transpose()
+        xdg_surface_id: ObjectId,
+    ) -> Result<ObjectId, SurfaceError> {
+        self.xdg_surfaces
+            .get(&(client_id, xdg_surface_id))
+            .copied()
+            .ok_or(SurfaceError::UnknownXdgSurface)
     }
 
-    fn shell_state_mut(
+    pub fn create_xdg_toplevel(
         &mut self,
         client_id: ClientId,
-        shell_id: ObjectId,
-    ) -> Result<&mut ShellState, SurfaceError> {
-        let surface_id = *self
-            .shell_surfaces
-            .get(&(client_id, shell_id))
-            .ok_or(SurfaceError::UnknownShellSurface)?;
-        let surface = self
-            .surfaces
-            .get_mut(&(client_id, surface_id))
-            .ok_or(SurfaceError::UnknownSurface)?;
-        Ok(&mut surface.shell)
+        xdg_surface_id: ObjectId,
+        toplevel_id: ObjectId,
+        version: u32,
+    ) -> Result<(), SurfaceError> {
+        let surface_id = self.surface_for_xdg_surface(client_id, xdg_surface_id)?;
+        let surface = self.xdg_surface_mut(client_id, surface_id)?;
+        let xdg = surface
+            .xdg
+            .as_mut()
+            .ok_or(SurfaceError::UnknownXdgSurface)?;
+        if xdg.role != XdgRole::None {
+            return Err(SurfaceError::AlreadyConstructed);
+        }
+        xdg.role = XdgRole::Toplevel {
+            id: toplevel_id,
+            version,
+            parent: None,
+        };
+        surface.shell.mode = ShellMode::Toplevel;
+        self.shell_surfaces
+            .insert((client_id, toplevel_id), surface_id);
+        Ok(())
     }
-}
-
-#[derive(Debug, Default)]
-struct Surface {
-    role: Option<Role>,
-    shell: ShellState,
-    current: SurfaceState,
-    pending: PendingState,
-}
 
-impl Surface {
-    fn is_mapped(&self) -> bool {
-        self.current.buffer.is_some()
-            && matches!(
-                self.shell.mode,
-                ShellMode::Toplevel
-                    | ShellMode::Transient
-                    | ShellMode::Fullscreen
-                    | ShellMode::Maximized
-            )
+    pub fn create_xdg_popup(
+        &mut self,
+        client_id: ClientId,
+        xdg_surface_id: ObjectId,
+        popup_id: ObjectId,
+        parent: Option<ObjectId>,
+        positioner_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        let positioner = self.complete_positioner(client_id, positioner_id)?;
+        let surface_id = self.surface_for_xdg_surface(client_id, xdg_surface_id)?;
+        if let Some(parent) = parent {
+            let parent_id = self.surface_for_xdg_surface(client_id, parent)?;
+            let has_role = self
+                .surfaces
+                .get(&(client_id, parent_id))
+                .and_then(|surface| surface.xdg.as_ref())
+                .is_some_and(|xdg| xdg.role != XdgRole::None);
+            if !has_role || self.is_popup_ancestor(client_id, surface_id, parent_id) {
+                return Err(SurfaceError::InvalidPopupParent);
+            }
+        }
+        let surface = self.xdg_surface_mut(client_id, surface_id)?;
+        let xdg = surface
+            .xdg
+            .as_mut()
+            .ok_or(SurfaceError::UnknownXdgSurface)?;
+        if xdg.role != XdgRole::None {
+            return Err(SurfaceError::AlreadyConstructed);
+        }
+        xdg.role = XdgRole::Popup {
+            id: popup_id,
+            parent,
+            positioner,
+        };
+        surface.shell.mode = ShellMode::Popup;
+        self.shell_surfaces
+            .insert((client_id, popup_id), surface_id);
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Role {
-    Shell(ObjectId),
-}
-
-#[derive(Debug)]
-struct ShellState {
-    mode: ShellMode,
-    title: String,
-    class: String,
-}
+    /// Destroys an xdg_toplevel or xdg_popup, unmapping its surface.
+    ///
+    /// Returns the wl_surface and whether it was mapped before.
+    pub fn destroy_xdg_role(
+        &mut self,
+        client_id: ClientId,
+        role_id: ObjectId,
+    ) -> Option<(ObjectId, bool)> {
+        let surface_id = self.shell_surfaces.remove(&(client_id, role_id))?;
+        let surface = self.surfaces.get_mut(&(client_id, surface_id))?;
+        let was_mapped = surface.is_mapped();
+        surface.current.buffer = None;
+        surface.shell = ShellState::default();
+        if let Some(xdg) = surface.xdg.as_mut() {
+            *xdg = XdgState::new(xdg.wm_base_id, xdg.id);
+        }
+        Some((surface_id, was_mapped))
+    }
 
-impl Default for ShellState {
-    fn default() -> Self {
-        Self {
-            mode: ShellMode::None,
-            title: String::new(),
-            class: String::new(),
+    pub fn reposition_xdg_popup(
+        &mut self,
+        client_id: ClientId,
+        popup_id: ObjectId,
+        positioner_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        let new_positioner = self.complete_positioner(client_id, positioner_id)?;
+        let surface_id = self.surface_for_shell(client_id, popup_id)?;
+        let surface = self.xdg_surface_mut(client_id, surface_id)?;
+        match surface.xdg.as_mut().map(|xdg| &mut xdg.role) {
+            Some(XdgRole::Popup { positioner, .. }) => {
+                *positioner = new_positioner;
+                Ok(())
+            }
+            _ => Err(SurfaceError::UnknownShellSurface),
         }
     }
-}
 
-#[derive(Debug, Default)]
-struct SurfaceState {
-    buffer: Option<ObjectId>,
-    offset: (i32, i32),
-    damage: Vec<Rectangle>,
+    pub fn set_xdg_toplevel_parent(
+        &mut self,
+        client_id: ClientId,
+        toplevel_id: ObjectId,
+        new_parent: Option<ObjectId>,
+    ) -> Result<(), SurfaceError> {
+        if let Some(parent_id) = new_parent {
+            if parent_id == toplevel_id
+                || self.is_toplevel_ancestor(client_id, toplevel_id, parent_id)
+            {
+                return Err(SurfaceError::InvalidParent);
+            }
+            self.surface_for_shell(client_id, parent_id)?;
+        }
+        let surface_id = self.surface_for_shell(client_id, toplevel_id)?;
+        let surface = self.xdg_surface_mut(client_id, surface_id)?;
+        match surface.xdg.as_mut().map(|xdg| &mut xdg.role) {
+            Some(XdgRole::Toplevel { parent, .. }) => {
+                *parent = new_parent;
+                Ok(())
+            }
+            _ => Err(SurfaceError::UnknownShellSurface),
+        }
+    }
+
+    pub fn set_window_geometry(
+        &mut self,
+        client_id: ClientId,
+        xdg_surface_id: ObjectId,
+        geometry: Rectangle,
+    ) -> Result<(), SurfaceError> {
+        if geometry.width <= 0 || geometry.height <= 0 {
+            return Err(SurfaceError::InvalidSize);
+        }
+        let surface_id = self.surface_for_xdg_surface(client_id, xdg_surface_id)?;
+        let xdg = self.xdg_state_mut(client_id, surface_id)?;
+        if xdg.role == XdgRole::None {
+            return Err(SurfaceError::NotConstructed);
+        }
+        xdg.pending.geometry = Some(geometry);
+        Ok(())
+    }
+
+    pub fn set_min_size(
+        &mut self,
+        client_id: ClientId,
+        toplevel_id: ObjectId,
+        width: i32,
+        height: i32,
+    ) -> Result<(), SurfaceError> {
+        if width < 0 || height < 0 {
+            return Err(SurfaceError::InvalidSize);
+        }
+        let surface_id = self.surface_for_shell(client_id, toplevel_id)?;
+        let xdg = self.xdg_state_mut(client_id, surface_id)?;
+        let max = xdg.pending.max_size.unwrap_or(xdg.current.max_size);
+        if exceeds((width, height), max) {
+            return Err(SurfaceError::InvalidSize);
+        }
+        xdg.pending.min_size = Some((width, height));
+        Ok(())
+    }
+
+    pub fn set_max_size(
+        &mut self,
+        client_id: ClientId,
+        toplevel_id: ObjectId,
+        width: i32,
+        height: i32,
+    ) -> Result<(), SurfaceError> {
+        if width < 0 || height < 0 {
+            return Err(SurfaceError::InvalidSize);
+        }
+        let surface_id = self.surface_for_shell(client_id, toplevel_id)?;
+        let xdg = self.xdg_state_mut(client_id, surface_id)?;
+        let min = xdg.pending.min_size.unwrap_or(xdg.current.min_size);
+        if exceeds(min, (width, height)) {
+            return Err(SurfaceError::InvalidSize);
+        }
+        xdg.pending.max_size = Some((width, height));
+        Ok(())
+    }
+
+    pub fn ack_configure(
+        &mut self,
+        client_id: ClientId,
+        xdg_surface_id: ObjectId,
+        serial: u32,
+    ) -> Result<(), SurfaceError> {
+        let surface_id = self.surface_for_xdg_surface(client_id, xdg_surface_id)?;
+        let xdg = self.xdg_state_mut(client_id, surface_id)?;
+        let Some(index) = xdg.sent_serials.iter().position(|sent| *sent == serial) else {
+            return Err(SurfaceError::InvalidSerial);
+        };
+        // Acking a configure implicitly acks every older one.
+        xdg.sent_serials.drain(..=index);
+        xdg.pending.acked_serial = Some(serial);
+        xdg.configured = true;
+        Ok(())
+    }
+
+    /// Allocates a configure serial for the surface's xdg role and returns
+    /// the events to send, or `None` if the surface has no xdg role yet or
+    /// its initial commit is still to come.
+    ///
+    /// Toplevels are asked for `size`; `(0, 0)` lets them pick their own.
+    pub fn configure_xdg_surface(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        (width, height): (i32, i32),
+    ) -> Option<XdgConfigureTarget> {
+        let surface = self.surfaces.get_mut(&(client_id, surface_id))?;
+        let mode = surface.shell.mode;
+        let xdg = surface
+            .xdg
+            .as_mut()
+            .filter(|xdg| xdg.initial_configure_sent)?;
+        self.configure_serial = self.configure_serial.wrapping_add(1);
+        let serial = self.configure_serial;
+        let role = match &xdg.role {
+            XdgRole::None => return None,
+            XdgRole::Toplevel { id, version, .. } => {
+                let mut states = Vec::new();
+                match mode {
+                    ShellMode::Maximized => states.push(XDG_TOPLEVEL_STATE_MAXIMIZED),
+                    ShellMode::Fullscreen => states.push(XDG_TOPLEVEL_STATE_FULLSCREEN),
+                    _ => {}
+                }
+                XdgConfigure::Toplevel {
+                    toplevel_id: *id,
+                    version: *version,
+                    width,
+                    height,
+                    states,
+                }
+            }
+            XdgRole::Popup { id, positioner, .. } => XdgConfigure::Popup {
+                popup_id: *id,
+                geometry: positioner.geometry(),
+            },
+        };
+        xdg.sent_serials.push(serial);
+        Some(XdgConfigureTarget {
+            xdg_surface_id: xdg.id,
+            serial,
+            role,
+        })
+    }
+
+    pub fn create_subsurface(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+        surface_id: ObjectId,
+        parent_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        if surface_id == parent_id || self.tree_contains(client_id, surface_id, parent_id) {
+            return Err(SurfaceError::InvalidParent);
+        }
+        if !self.surfaces.contains_key(&(client_id, parent_id)) {
+            return Err(SurfaceError::UnknownSurface);
+        }
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface.role.is_some_and(|role| role != Role::Subsurface) || surface.subsurface.is_some()
+        {
+            return Err(SurfaceError::RoleAlreadyAssigned);
+        }
+        surface.role = Some(Role::Subsurface);
+        surface.subsurface = Some(SubsurfaceState {
+            parent: Some(parent_id),
+            position: (0, 0),
+            pending_position: None,
+            sync: true,
+            cached: None,
+        });
+        self.subsurfaces
+            .insert((client_id, subsurface_id), surface_id);
+        // Adding a sub-surface is double-buffered on the parent.
+        if let Some(parent) = self.surfaces.get_mut(&(client_id, parent_id)) {
+            if parent.pending_stack.is_empty() {
+                parent.pending_stack.push(parent_id);
+            }
+            parent.pending_stack.push(surface_id);
+        }
+        Ok(())
+    }
+
+    /// Removes the sub-surface role object, unmapping its surface immediately.
+    ///
+    /// Returns the wl_surface and the main surface of the tree it was part of.
+    pub fn destroy_subsurface(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+    ) -> Option<(ObjectId, ObjectId)> {
+        let surface_id = self.subsurfaces.remove(&(client_id, subsurface_id))?;
+        let surface = self.surfaces.get_mut(&(client_id, surface_id))?;
+        let subsurface = surface.subsurface.take()?;
+        surface.current.buffer = None;
+        // Cached state is no longer tied to the parent; keep it for the next commit.
+        if let Some(mut cached) = subsurface.cached {
+            cached.merge(std::mem::take(&mut surface.pending));
+            surface.pending = cached;
+        }
+        let parent_id = subsurface.parent?;
+        self.detach_subsurface(client_id, parent_id, surface_id);
+        Some((surface_id, self.tree_root(client_id, parent_id)))
+    }
+
+    pub fn set_subsurface_position(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+        x: i32,
+        y: i32,
+    ) {
+        if let Some(subsurface) = self.subsurface_mut(client_id, subsurface_id) {
+            subsurface.pending_position = Some((x, y));
+        }
+    }
+
+    pub fn set_subsurface_sync(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+        sync: bool,
+    ) {
+        if let Some(subsurface) = self.subsurface_mut(client_id, subsurface_id) {
+            subsurface.sync = sync;
+        }
+    }
+
+    /// Moves a sub-surface directly above or below a sibling (or its parent)
+    /// in the parent's pending stacking order.
+    pub fn place_subsurface(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+        sibling_id: ObjectId,
+        above: bool,
+    ) -> Result<(), SurfaceError> {
+        let Some(surface_id) = self.subsurfaces.get(&(client_id, subsurface_id)).copied() else {
+            return Err(SurfaceError::InvalidSibling);
+        };
+        let parent_id = self
+            .subsurface_mut(client_id, subsurface_id)
+            .and_then(|subsurface| subsurface.parent)
+            .ok_or(SurfaceError::InvalidSibling)?;
+        if sibling_id == surface_id {
+            return Err(SurfaceError::InvalidSibling);
+        }
+        let parent = self
+            .surfaces
+            .get_mut(&(client_id, parent_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if !parent.pending_stack.contains(&sibling_id) {
+            return Err(SurfaceError::InvalidSibling);
+        }
+        parent.pending_stack.retain(|id| *id != surface_id);
+        let index = parent
+            .pending_stack
+            .iter()
+            .position(|id| *id == sibling_id)
+            .unwrap_or_default();
+        let index = if above { index + 1 } else { index };
+        parent.pending_stack.insert(index, surface_id);
+        Ok(())
+    }
+
+    /// Main surface of the sub-surface tree containing `surface_id`.
+    pub fn tree_root(&self, client_id: ClientId, surface_id: ObjectId) -> ObjectId {
+        let mut current = surface_id;
+        while let Some(parent) = self
+            .surfaces
+            .get(&(client_id, current))
+            .and_then(|surface| surface.subsurface.as_ref()?.parent)
+        {
+            current = parent;
+        }
+        current
+    }
+
+    /// The composed sub-surface tree of a mapped main surface, bottom to top.
+    ///
+    /// Returns `None` for surfaces that never had sub-surfaces.
+    pub fn surface_tree(
+        &self,
+        client_id: ClientId,
+        root_id: ObjectId,
+    ) -> Option<Vec<SurfaceLayer>> {
+        let root = self.surfaces.get(&(client_id, root_id))?;
+        if root.stack.is_empty() || !self.is_tree_mapped(client_id, root_id) {
+            return None;
+        }
+        let mut layers = Vec::new();
+        self.collect_layers(client_id, root_id, (0, 0), &mut layers);
+        Some(layers)
+    }
+
+    /// Records the size of the buffer a surface shows, which has to be a
+    /// multiple of its buffer scale and contain its viewport source.
+    pub fn set_buffer_size(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        (width, height): (i32, i32),
+    ) -> Result<(), SurfaceError> {
+        let Some(surface) = self.surfaces.get_mut(&(client_id, surface_id)) else {
+            return Ok(());
+        };
+        let scale = surface.current.scale;
+        if width % scale != 0 || height % scale != 0 {
+            return Err(SurfaceError::InvalidSize);
+        }
+        let size = surface
+            .current
+            .transform
+            .apply_size((width / scale, height / scale));
+        let viewport = surface.current.viewport;
+        if !viewport.fits(size) {
+            return Err(SurfaceError::ViewportOutOfBuffer);
+        }
+        surface.current.size = Some(
+            viewport
+                .surface_size(size)
+                .ok_or(SurfaceError::InvalidViewportSize)?,
+        );
+        Ok(())
+    }
+
+    /// Adds a wp_viewport to a surface, which may have only one.
+    pub fn create_viewport(
+        &mut self,
+        client_id: ClientId,
+        viewport_id: ObjectId,
+        surface_id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface.viewport.is_some() {
+            return Err(SurfaceError::ViewportExists);
+        }
+        surface.viewport = Some(viewport_id);
+        self.viewports
+            .insert((client_id, viewport_id), Some(surface_id));
+        Ok(())
+    }
+
+    /// Removes a wp_viewport; the crop and scale state is removed with the
+    /// next commit of its surface.
+    pub fn destroy_viewport(&mut self, client_id: ClientId, viewport_id: ObjectId) {
+        let Some(Some(surface_id)) = self.viewports.remove(&(client_id, viewport_id)) else {
+            return;
+        };
+        if let Some(surface) = self.surfaces.get_mut(&(client_id, surface_id)) {
+            surface.viewport = None;
+            surface.pending.viewport_source = Some(None);
+            surface.pending.viewport_destination = Some(None);
+        }
+    }
+
+    /// Sets the pending viewport source; all values being -1 unsets it.
+    pub fn set_viewport_source(
+        &mut self,
+        client_id: ClientId,
+        viewport_id: ObjectId,
+        (x, y, width, height): (f32, f32, f32, f32),
+    ) -> Result<(), SurfaceError> {
+        let source = if (x, y, width, height) == (-1.0, -1.0, -1.0, -1.0) {
+            None
+        } else if x < 0.0 || y < 0.0 || width <= 0.0 || height <= 0.0 {
+            return Err(SurfaceError::InvalidViewport);
+        } else {
+            Some((x, y, width, height))
+        };
+        self.viewport_surface(client_id, viewport_id)?
+            .pending
+            .viewport_source = Some(source);
+        Ok(())
+    }
+
+    /// Sets the pending viewport destination; both values being -1 unsets it.
+    pub fn set_viewport_destination(
+        &mut self,
+        client_id: ClientId,
+        viewport_id: ObjectId,
+        (width, height): (i32, i32),
+    ) -> Result<(), SurfaceError> {
+        let destination = if (width, height) == (-1, -1) {
+            None
+        } else if width <= 0 || height <= 0 {
+            return Err(SurfaceError::InvalidViewport);
+        } else {
+            Some((width, height))
+        };
+        self.viewport_surface(client_id, viewport_id)?
+            .pending
+            .viewport_destination = Some(destination);
+        Ok(())
+    }
+
+    /// The wp_viewport of a surface, if it has one.
+    pub fn viewport(&self, client_id: ClientId, surface_id: ObjectId) -> Option<ObjectId> {
+        self.surfaces.get(&(client_id, surface_id))?.viewport
+    }
+
+    fn viewport_surface(
+        &mut self,
+        client_id: ClientId,
+        viewport_id: ObjectId,
+    ) -> Result<&mut Surface, SurfaceError> {
+        let surface_id = self
+            .viewports
+            .get(&(client_id, viewport_id))
+            .copied()
+            .flatten()
+            .ok_or(SurfaceError::DefunctViewport)?;
+        self.surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::DefunctViewport)
+    }
+
+    /// Finds the topmost surface of the tree rooted at `root_id` that accepts
+    /// input at `(x, y)`, relative to the root. Returns the surface and the
+    /// surface-local position.
+    ///
+    /// Like rendering, sub-surfaces are clipped to the main surface.
+    pub fn surface_at(
+        &self,
+        client_id: ClientId,
+        root_id: ObjectId,
+        (x, y): (f64, f64),
+    ) -> Option<(ObjectId, f64, f64)> {
+        let (width, height) = self.surfaces.get(&(client_id, root_id))?.current.size?;
+        if x < 0.0 || y < 0.0 || x >= f64::from(width) || y >= f64::from(height) {
+            return None;
+        }
+        self.hit_test(client_id, root_id, (0, 0), (x, y))
+    }
+
+    /// Position of a surface relative to the main surface of its tree.
+    pub fn surface_position(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Option<(i32, i32)> {
+        let mut position = (0, 0);
+        let mut id = surface_id;
+        while let Some(subsurface) = self.surfaces.get(&(client_id, id))?.subsurface.as_ref() {
+            position.0 += subsurface.position.0;
+            position.1 += subsurface.position.1;
+            id = subsurface.parent?;
+        }
+        Some(position)
+    }
+
+    pub fn create_region(&mut self, client_id: ClientId, id: ObjectId) {
+        self.regions.insert((client_id, id), Region::default());
+    }
+
+    pub fn destroy_region(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+    ) -> Result<(), SurfaceError> {
+        self.regions
+            .remove(&(client_id, id))
+            .map(|_| ())
+            .ok_or(SurfaceError::UnknownRegion)
+    }
+
+    pub fn add_region(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        rectangle: Rectangle,
+    ) -> Result<(), SurfaceError> {
+        let region = self
+            .regions
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownRegion)?;
+        if rectangle.width > 0 && rectangle.height > 0 {
+            region.operations.push(RegionOperation::Add(rectangle));
+        }
+        Ok(())
+    }
+
+    pub fn subtract_region(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        rectangle: Rectangle,
+    ) -> Result<(), SurfaceError> {
+        let region = self
+            .regions
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownRegion)?;
+        if rectangle.width > 0 && rectangle.height > 0 {
+            region.operations.push(RegionOperation::Subtract(rectangle));
+        }
+        Ok(())
+    }
+
+    /// Holds the frame callbacks of an applied commit until
+    /// [`Self::take_presented_frame_callbacks`] reports its tree as presented.
+    pub fn queue_frame_callbacks(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        root_id: ObjectId,
+        callbacks: Vec<ObjectId>,
+    ) {
+        self.queued_frame_callbacks
+            .extend(callbacks.into_iter().map(|callback| QueuedFrameCallback {
+                client_id,
+                surface_id,
+                root_id,
+                callback,
+            }));
+    }
+
+    /// Removes and returns the queued callbacks of the trees whose main
+    /// surface is in `presented`.
+    pub fn take_presented_frame_callbacks(
+        &mut self,
+        presented: &[(ClientId, ObjectId)],
+    ) -> Vec<(ClientId, ObjectId)> {
+        let mut callbacks = Vec::new();
+        self.queued_frame_callbacks.retain(|queued| {
+            let done = presented.contains(&(queued.client_id, queued.root_id));
+            if done {
+                callbacks.push((queued.client_id, queued.callback));
+            }
+            !done
+        });
+        callbacks
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.queued_frame_callbacks
+            .retain(|queued| queued.client_id != client_id);
+        self.surfaces.retain(|(owner, _), _| *owner != client_id);
+        self.shell_surfaces
+            .retain(|(owner, _), _| *owner != client_id);
+        self.regions.retain(|(owner, _), _| *owner != client_id);
+        self.xdg_surfaces
+            .retain(|(owner, _), _| *owner != client_id);
+        self.positioners.retain(|(owner, _), _| *owner != client_id);
+        self.subsurfaces.retain(|(owner, _), _| *owner != client_id);
+        self.viewports.retain(|(owner, _), _| *owner != client_id);
+    }
+
+    fn apply_state(
+        &mut self,
+        client_id: ClientId,
+        id: ObjectId,
+        state: PendingState,
+    ) -> Result<SurfaceCommit, SurfaceError> {
+        let mut commit = self
+            .surfaces
+            .get_mut(&(client_id, id))
+            .ok_or(SurfaceError::UnknownSurface)?
+            .apply(id, state);
+        commit.root_id = self.tree_root(client_id, id);
+        commit.mapped = self.is_tree_mapped(client_id, id);
+        commit.subsurface_commits = self.apply_subsurface_state(client_id, id)?;
+        Ok(commit)
+    }
+
+    /// Applies the stacking order, positions and cached state of the
+    /// sub-surfaces of a surface whose own state was just applied.
+    fn apply_subsurface_state(
+        &mut self,
+        client_id: ClientId,
+        parent_id: ObjectId,
+    ) -> Result<Vec<SurfaceCommit>, SurfaceError> {
+        let Some(parent) = self.surfaces.get_mut(&(client_id, parent_id)) else {
+            return Ok(Vec::new());
+        };
+        parent.stack.clone_from(&parent.pending_stack);
+        let children: Vec<_> = parent
+            .stack
+            .iter()
+            .copied()
+            .filter(|id| *id != parent_id)
+            .collect();
+        let mut commits = Vec::new();
+        for child_id in children {
+            let Some(subsurface) = self
+                .surfaces
+                .get_mut(&(client_id, child_id))
+                .and_then(|child| child.subsurface.as_mut())
+            else {
+                continue;
+            };
+            if let Some(position) = subsurface.pending_position.take() {
+                subsurface.position = position;
+            }
+            if let Some(state) = subsurface.cached.take() {
+                commits.push(self.apply_state(client_id, child_id, state)?);
+            }
+        }
+        Ok(commits)
+    }
+
+    /// Whether commits on the surface are cached, either because it is a
+    /// synchronized sub-surface or because an ancestor is.
+    fn is_synchronized(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        let mut current = surface_id;
+        while let Some(subsurface) = self
+            .surfaces
+            .get(&(client_id, current))
+            .and_then(|surface| surface.subsurface.as_ref())
+        {
+            if subsurface.sync {
+                return true;
+            }
+            let Some(parent) = subsurface.parent else {
+                return false;
+            };
+            current = parent;
+        }
+        false
+    }
+
+    /// A sub-surface is mapped when it has a buffer and its parent is mapped
+    /// and has applied the sub-surface to its stacking order.
+    fn is_tree_mapped(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        let mut current = surface_id;
+        loop {
+            let Some(surface) = self.surfaces.get(&(client_id, current)) else {
+                return false;
+            };
+            let Some(subsurface) = surface.subsurface.as_ref() else {
+                return surface.is_mapped();
+            };
+            let Some(parent_id) = subsurface.parent else {
+                return false;
+            };
+            let added = self
+                .surfaces
+                .get(&(client_id, parent_id))
+                .is_some_and(|parent| parent.stack.contains(&current));
+            if surface.current.buffer.is_none() || !added {
+                return false;
+            }
+            current = parent_id;
+        }
+    }
+
+    /// Whether `candidate` is `surface_id` or one of its sub-surface descendants.
+    fn tree_contains(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        candidate: ObjectId,
+    ) -> bool {
+        let mut current = Some(candidate);
+        while let Some(id) = current {
+            if id == surface_id {
+                return true;
+            }
+            current = self
+                .surfaces
+                .get(&(client_id, id))
+                .and_then(|surface| surface.subsurface.as_ref()?.parent);
+        }
+        false
+    }
+
+    fn hit_test(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        (origin_x, origin_y): (i32, i32),
+        (x, y): (f64, f64),
+    ) -> Option<(ObjectId, f64, f64)> {
+        let surface = self.surfaces.get(&(client_id, surface_id))?;
+        surface.current.buffer.as_ref()?;
+        let local = (x - f64::from(origin_x), y - f64::from(origin_y));
+        let own_hit = || {
+            surface
+                .accepts_input(local)
+                .then_some((surface_id, local.0, local.1))
+        };
+        if surface.stack.is_empty() {
+            return own_hit();
+        }
+        for id in surface.stack.iter().rev() {
+            if *id == surface_id {
+                if let Some(hit) = own_hit() {
+                    return Some(hit);
+                }
+            } else if let Some(subsurface) = self
+                .surfaces
+                .get(&(client_id, *id))
+                .and_then(|child| child.subsurface.as_ref())
+            {
+                let (dx, dy) = subsurface.position;
+                let origin = (origin_x + dx, origin_y + dy);
+                if let Some(hit) = self.hit_test(client_id, *id, origin, (x, y)) {
+                    return Some(hit);
+                }
+            }
+        }
+        None
+    }
+
+    fn set_reusable_role(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        role: Role,
+    ) -> Result<(), SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface.role.is_some_and(|assigned| assigned != role) {
+            return Err(SurfaceError::RoleAlreadyAssigned);
+        }
+        surface.role = Some(role);
+        Ok(())
+    }
+
+    fn collect_layers(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        (x, y): (i32, i32),
+        layers: &mut Vec<SurfaceLayer>,
+    ) {
+        let Some(surface) = self.surfaces.get(&(client_id, surface_id)) else {
+            return;
+        };
+        if surface.current.buffer.is_none() {
+            return;
+        }
+        if surface.stack.is_empty() {
+            layers.push(SurfaceLayer { surface_id, x, y });
+            return;
+        }
+        for id in &surface.stack {
+            if *id == surface_id {
+                layers.push(SurfaceLayer { surface_id, x, y });
+            } else if let Some(subsurface) = self
+                .surfaces
+                .get(&(client_id, *id))
+                .and_then(|child| child.subsurface.as_ref())
+            {
+                let (dx, dy) = subsurface.position;
+                self.collect_layers(client_id, *id, (x + dx, y + dy), layers);
+            }
+        }
+    }
+
+    fn detach_subsurface(
+        &mut self,
+        client_id: ClientId,
+        parent_id: ObjectId,
+        surface_id: ObjectId,
+    ) {
+        if let Some(parent) = self.surfaces.get_mut(&(client_id, parent_id)) {
+            parent.stack.retain(|id| *id != surface_id);
+            parent.pending_stack.retain(|id| *id != surface_id);
+        }
+    }
+
+    fn subsurface_mut(
+        &mut self,
+        client_id: ClientId,
+        subsurface_id: ObjectId,
+    ) -> Option<&mut SubsurfaceState> {
+        let surface_id = self.subsurfaces.get(&(client_id, subsurface_id))?;
+        self.surfaces
+            .get_mut(&(client_id, *surface_id))?
+            .subsurface
+            .as_mut()
+    }
+
+    fn xdg_surface_mut(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Result<&mut Surface, SurfaceError> {
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        if surface.xdg.is_none() {
+            return Err(SurfaceError::UnknownXdgSurface);
+        }
+        Ok(surface)
+    }
+
+    fn xdg_state_mut(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Result<&mut XdgState, SurfaceError> {
+        self.surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?
+            .xdg
+            .as_mut()
+            .ok_or(SurfaceError::UnknownXdgSurface)
+    }
+
+    fn complete_positioner(
+        &self,
+        client_id: ClientId,
+        positioner_id: ObjectId,
+    ) -> Result<Positioner, SurfaceError> {
+        let positioner = self
+            .positioners
+            .get(&(client_id, positioner_id))
+            .ok_or(SurfaceError::UnknownPositioner)?;
+        if !positioner.is_complete() {
+            return Err(SurfaceError::InvalidPositioner);
+        }
+        Ok(positioner.clone())
+    }
+
+    /// Whether `candidate` is `toplevel_id` or one of its descendants.
+    fn is_toplevel_ancestor(
+        &self,
+        client_id: ClientId,
+        toplevel_id: ObjectId,
+        candidate: ObjectId,
+    ) -> bool {
+        let mut current = Some(candidate);
+        while let Some(id) = current {
+            if id == toplevel_id {
+                return true;
+            }
+            current = self
+                .shell_surfaces
+                .get(&(client_id, id))
+                .and_then(|surface_id| self.surfaces.get(&(client_id, *surface_id)))
+                .and_then(|surface| match surface.xdg.as_ref()?.role {
+                    XdgRole::Toplevel { parent, .. } => parent,
+                    _ => None,
+                });
+        }
+        false
+    }
+
+    fn is_popup_ancestor(
+        &self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        candidate: ObjectId,
+    ) -> bool {
+        let mut current = Some(candidate);
+        while let Some(id) = current {
+            if id == surface_id {
+                return true;
+            }
+            current = self.xdg_popup_parent(client_id, id);
+        }
+        false
+    }
+
+    fn copy_region(
+        &self,
+        client_id: ClientId,
+        region_id: Option<ObjectId>,
+    ) -> Result<Option<Region>, SurfaceError> {
+        region_id
+            .map(|id| {
+                self.regions
+                    .get(&(client_id, id))
+                    .cloned()
+                    .ok_or(SurfaceError::UnknownRegion)
+            })
+            .transpose()
+    }
+
+    fn shell_state_mut(
+        &mut self,
+        client_id: ClientId,
+        shell_id: ObjectId,
+    ) -> Result<&mut ShellState, SurfaceError> {
+        let surface_id = *self
+            .shell_surfaces
+            .get(&(client_id, shell_id))
+            .ok_or(SurfaceError::UnknownShellSurface)?;
+        let surface = self
+            .surfaces
+            .get_mut(&(client_id, surface_id))
+            .ok_or(SurfaceError::UnknownSurface)?;
+        Ok(&mut surface.shell)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Surface {
+    role: Option<Role>,
+    shell: ShellState,
+    xdg: Option<XdgState>,
+    subsurface: Option<SubsurfaceState>,
+    /// Stacking order of this surface and its sub-surfaces, bottom to top.
+    /// Empty until the first sub-surface is added.
+    stack: Vec<ObjectId>,
+    pending_stack: Vec<ObjectId>,
+    /// The wp_viewport of the surface, if any.
+    viewport: Option<ObjectId>,
+    current: SurfaceState,
+    pending: PendingState,
+}
+
+impl Surface {
+    fn is_mapped(&self) -> bool {
+        if self.xdg.as_ref().is_some_and(|xdg| !xdg.configured) {
+            return false;
+        }
+        self.current.buffer.is_some()
+            && match self.shell.mode {
+                ShellMode::Toplevel
+                | ShellMode::Transient
+                | ShellMode::Fullscreen
+                | ShellMode::Maximized => true,
+                // wl_shell popups are not placed next to their parent.
+                ShellMode::Popup => self.xdg.is_some(),
+                ShellMode::None => false,
+            }
+    }
+
+    /// Whether a surface-local point lies within the buffer and input region.
+    fn accepts_input(&self, (x, y): (f64, f64)) -> bool {
+        let Some((width, height)) = self.current.size else {
+            return false;
+        };
+        if x < 0.0 || y < 0.0 || x >= f64::from(width) || y >= f64::from(height) {
+            return false;
+        }
+        self.current
+            .input_region
+            .as_ref()
+            .is_none_or(|region| region.contains(x.floor() as i32, y.floor() as i32))
+    }
+
+    /// Applies committed state. Sub-surface fields of the result are filled
+    /// in by the manager.
+    fn apply(&mut self, id: ObjectId, state: PendingState) -> SurfaceCommit {
+        if let Some(xdg) = self.xdg.as_mut() {
+            if let Some(geometry) = xdg.pending.geometry.take() {
+                xdg.current.geometry = Some(geometry);
+            }
+            if let Some(size) = xdg.pending.min_size.take() {
+                xdg.current.min_size = size;
+            }
+            if let Some(size) = xdg.pending.max_size.take() {
+                xdg.current.max_size = size;
+            }
+            if let Some(serial) = xdg.pending.acked_serial.take() {
+                xdg.current.acked_serial = Some(serial);
+            }
+        }
+        let attached_buffer = state.buffer;
+        if let Some(buffer) = attached_buffer {
+            self.current.buffer = buffer;
+        }
+        if let Some(offset) = state.offset {
+            self.current.offset = offset;
+        }
+        if let Some(scale) = state.scale {
+            self.current.scale = scale;
+        }
+        if let Some(transform) = state.transform {
+            self.current.transform = transform;
+        }
+        if let Some(source) = state.viewport_source {
+            self.current.viewport.source = source;
+        }
+        if let Some(destination) = state.viewport_destination {
+            self.current.viewport.destination = destination;
+        }
+        if let Some(region) = state.opaque_region {
+            self.current.opaque_region = region;
+        }
+        if let Some(region) = state.input_region {
+            self.current.input_region = region;
+        }
+        let mut needs_configure = false;
+        if let Some(xdg) = self.xdg.as_mut() {
+            if attached_buffer == Some(None) {
+                // Unmapping resets the role to its pre-configure state.
+                xdg.configured = false;
+                xdg.initial_configure_sent = false;
+            }
+            if xdg.role != XdgRole::None && !xdg.initial_configure_sent {
+                xdg.initial_configure_sent = true;
+                needs_configure = true;
+            }
+        }
+        SurfaceCommit {
+            surface_id: id,
+            buffer: self.current.buffer,
+            attached_buffer,
+            mapped: self.is_mapped(),
+            damage: state.damage,
+            buffer_damage: state.buffer_damage,
+            scale: self.current.scale,
+            transform: self.current.transform,
+            viewport: self.current.viewport,
+            frame_callbacks: state.frame_callbacks,
+            needs_configure,
+            root_id: id,
+            cached: false,
+            subsurface_commits: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Shell(ObjectId),
+    Xdg,
+    Subsurface,
+    DragIcon,
+    Cursor,
+}
+
+#[derive(Debug)]
+struct ShellState {
+    mode: ShellMode,
+    title: String,
+    class: String,
+}
+
+impl Default for ShellState {
+    fn default() -> Self {
+        Self {
+            mode: ShellMode::None,
+            title: String::new(),
+            class: String::new(),
+        }
+    }
+}
+
+/// wl_subsurface state layered on top of a wl_surface.
+#[derive(Debug)]
+struct SubsurfaceState {
+    /// `None` once the parent wl_surface has been destroyed.
+    parent: Option<ObjectId>,
+    position: (i32, i32),
+    pending_position: Option<(i32, i32)>,
+    sync: bool,
+    /// State committed while synchronized, applied with the parent's state.
+    cached: Option<PendingState>,
+}
+
+/// xdg_surface state layered on top of a wl_surface.
+#[derive(Debug)]
+struct XdgState {
+    /// The xdg_wm_base the xdg_surface was created through.
+    wm_base_id: ObjectId,
+    id: ObjectId,
+    role: XdgRole,
+    /// Configure serials sent to the client that have not been acked yet.
+    sent_serials: Vec<u32>,
+    initial_configure_sent: bool,
+    configured: bool,
+    current: XdgCurrentState,
+    pending: XdgPendingState,
+}
+
+impl XdgState {
+    fn new(wm_base_id: ObjectId, id: ObjectId) -> Self {
+        Self {
+            wm_base_id,
+            id,
+            role: XdgRole::None,
+            sent_serials: Vec::new(),
+            initial_configure_sent: false,
+            configured: false,
+            current: XdgCurrentState::default(),
+            pending: XdgPendingState::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum XdgRole {
+    None,
+    Toplevel {
+        id: ObjectId,
+        version: u32,
+        parent: Option<ObjectId>,
+    },
+    Popup {
+        id: ObjectId,
+        parent: Option<ObjectId>,
+        positioner: Positioner,
+    },
+}
+
+impl XdgRole {
+    fn id(&self) -> Option<ObjectId> {
+        match self {
+            XdgRole::None => None,
+            XdgRole::Toplevel { id, .. } | XdgRole::Popup { id, .. } => Some(*id),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct XdgCurrentState {
+    geometry: Option<Rectangle>,
+    min_size: (i32, i32),
+    max_size: (i32, i32),
+    acked_serial: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct XdgPendingState {
+    geometry: Option<Rectangle>,
+    min_size: Option<(i32, i32)>,
+    max_size: Option<(i32, i32)>,
+    acked_serial: Option<u32>,
+}
+
+/// Whether a non-zero minimum size is larger than a non-zero maximum size.
+fn exceeds(min: (i32, i32), max: (i32, i32)) -> bool {
+    (min.0 > 0 && max.0 > 0 && min.0 > max.0) || (min.1 > 0 && max.1 > 0 && min.1 > max.1)
+}
+
+/// Placement rules copied from an xdg_positioner.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Positioner {
+    pub size: (i32, i32),
+    pub anchor_rect: Rectangle,
+    pub anchor: u32,
+    pub gravity: u32,
+    pub constraint_adjustment: u32,
+    pub offset: (i32, i32),
+    pub reactive: bool,
+    pub parent_size: Option<(i32, i32)>,
+    pub parent_configure: Option<u32>,
+}
+
+impl Positioner {
+    fn is_complete(&self) -> bool {
+        self.size.0 > 0
+            && self.size.1 > 0
+            && self.anchor_rect.width > 0
+            && self.anchor_rect.height > 0
+    }
+
+    /// Popup geometry relative to the parent's window geometry.
+    ///
+    /// Constraint adjustments are not applied yet; the popup is placed exactly
+    /// where the anchor, gravity and offset put it.
+    pub fn geometry(&self) -> Rectangle {
+        let rect = self.anchor_rect;
+        let (mut x, mut y) = (rect.x + rect.width / 2, rect.y + rect.height / 2);
+        match self.anchor {
+            XDG_POSITIONER_ANCHOR_TOP
+            | XDG_POSITIONER_ANCHOR_TOP_LEFT
+            | XDG_POSITIONER_ANCHOR_TOP_RIGHT => y = rect.y,
+            XDG_POSITIONER_ANCHOR_BOTTOM
+            | XDG_POSITIONER_ANCHOR_BOTTOM_LEFT
+            | XDG_POSITIONER_ANCHOR_BOTTOM_RIGHT => y = rect.y + rect.height,
+            _ => {}
+        }
+        match self.anchor {
+            XDG_POSITIONER_ANCHOR_LEFT
+            | XDG_POSITIONER_ANCHOR_TOP_LEFT
+            | XDG_POSITIONER_ANCHOR_BOTTOM_LEFT => x = rect.x,
+            XDG_POSITIONER_ANCHOR_RIGHT
+            | XDG_POSITIONER_ANCHOR_TOP_RIGHT
+            | XDG_POSITIONER_ANCHOR_BOTTOM_RIGHT => x = rect.x + rect.width,
+            _ => {}
+        }
+        let (width, height) = self.size;
+        match self.gravity {
+            XDG_POSITIONER_GRAVITY_TOP
+            | XDG_POSITIONER_GRAVITY_TOP_LEFT
+            | XDG_POSITIONER_GRAVITY_TOP_RIGHT => y -= height,
+            XDG_POSITIONER_GRAVITY_BOTTOM
+            | XDG_POSITIONER_GRAVITY_BOTTOM_LEFT
+            | XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT => {}
+            _ => y -= height / 2,
+        }
+        match self.gravity {
+            XDG_POSITIONER_GRAVITY_LEFT
+            | XDG_POSITIONER_GRAVITY_TOP_LEFT
+            | XDG_POSITIONER_GRAVITY_BOTTOM_LEFT => x -= width,
+            XDG_POSITIONER_GRAVITY_RIGHT
+            | XDG_POSITIONER_GRAVITY_TOP_RIGHT
+            | XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT => {}
+            _ => x -= width / 2,
+        }
+        Rectangle {
+            x: x + self.offset.0,
+            y: y + self.offset.1,
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SurfaceState {
+    buffer: Option<ObjectId>,
+    /// Size of the surface showing `buffer`, recorded when its contents were
+    /// snapshotted.
+    size: Option<(i32, i32)>,
+    offset: (i32, i32),
+    scale: i32,
+    transform: Transform,
+    viewport: Viewport,
     opaque_region: Option<Region>,
     input_region: Option<Region>,
 }
 
+impl Default for SurfaceState {
+    fn default() -> Self {
+        Self {
+            buffer: None,
+            size: None,
+            offset: (0, 0),
+            scale: 1,
+            transform: Transform::Normal,
+            viewport: Viewport::default(),
+            opaque_region: None,
+            input_region: None,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct PendingState {
     buffer: Option<Option<ObjectId>>,
     offset: Option<(i32, i32)>,
+    scale: Option<i32>,
+    transform: Option<Transform>,
+    viewport_source: Option<Option<(f32, f32, f32, f32)>>,
+    viewport_destination: Option<Option<(i32, i32)>>,
+    /// In surface coordinates.
     damage: Vec<Rectangle>,
+    /// In buffer coordinates.
+    buffer_damage: Vec<Rectangle>,
     frame_callbacks: Vec<ObjectId>,
     opaque_region: Option<Option<Region>>,
     input_region: Option<Option<Region>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl PendingState {
+    /// Layers newer pending state on top of this cached state.
+    fn merge(&mut self, newer: PendingState) {
+        if newer.buffer.is_some() {
+            self.buffer = newer.buffer;
+        }
+        if newer.offset.is_some() {
+            self.offset = newer.offset;
+        }
+        if newer.scale.is_some() {
+            self.scale = newer.scale;
+        }
+        if newer.transform.is_some() {
+            self.transform = newer.transform;
+        }
+        if newer.viewport_source.is_some() {
+            self.viewport_source = newer.viewport_source;
+        }
+        if newer.viewport_destination.is_some() {
+            self.viewport_destination = newer.viewport_destination;
+        }
+        if newer.opaque_region.is_some() {
+            self.opaque_region = newer.opaque_region;
+        }
+        if newer.input_region.is_some() {
+            self.input_region = newer.input_region;
+        }
+        self.damage.extend(newer.damage);
+        self.buffer_damage.extend(newer.buffer_damage);
+        self.frame_callbacks.extend(newer.frame_callbacks);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Rectangle {
     pub x: i32,
     pub y: i32,
@@ -410,47 +2119,263 @@ enum RegionOperation {
     Subtract(Rectangle),
 }
 
+impl Region {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.operations
+            .iter()
+            .fold(false, |inside, operation| match operation {
+                RegionOperation::Add(rect) if rect.contains(x, y) => true,
+                RegionOperation::Subtract(rect) if rect.contains(x, y) => false,
+                _ => inside,
+            })
+    }
+}
+
+impl Rectangle {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::num::NonZeroU32;
+    use lumalla_wayland_protocol::protocols::xdg_shell::{
+        XDG_POSITIONER_ANCHOR_NONE, XDG_POSITIONER_GRAVITY_NONE,
+    };
 
     use super::*;
+    use crate::test_ids::{client, object};
+
+    #[test]
+    fn commit_applies_pending_state_atomically() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_shell_surface(client(1), object(3), object(2))
+            .unwrap();
+        manager
+            .set_shell_mode(client(1), object(3), ShellMode::Toplevel)
+            .unwrap();
+        manager
+            .attach(client(1), object(2), Some(object(4)), 5, 6)
+            .unwrap();
+        manager
+            .add_frame_callback(client(1), object(2), object(5))
+            .unwrap();
+
+        let commit = manager.commit(client(1), object(2)).unwrap();
+
+        assert_eq!(commit.buffer, Some(object(4)));
+        assert_eq!(commit.attached_buffer, Some(Some(object(4))));
+        assert!(commit.mapped);
+        assert_eq!(commit.frame_callbacks, [object(5)]);
+        let second = manager.commit(client(1), object(2)).unwrap();
+        assert_eq!(second.buffer, Some(object(4)));
+        assert_eq!(second.attached_buffer, None);
+        assert!(second.frame_callbacks.is_empty());
+    }
+
+    #[test]
+    fn commit_reports_surface_and_buffer_damage() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        let rectangle = |x, width| Rectangle {
+            x,
+            y: 0,
+            width,
+            height: 4,
+        };
+        manager
+            .damage(client(1), object(2), rectangle(1, 2))
+            .unwrap();
+        manager
+            .damage_buffer(client(1), object(2), rectangle(5, 3))
+            .unwrap();
+        manager
+            .damage_buffer(client(1), object(2), rectangle(9, 0))
+            .unwrap();
+
+        let commit = manager.commit(client(1), object(2)).unwrap();
+        assert_eq!(
+            commit.damage_in_buffer((20, 4)),
+            [rectangle(1, 2), rectangle(5, 3)]
+        );
+        let second = manager.commit(client(1), object(2)).unwrap();
+        assert!(second.damage_in_buffer((20, 4)).is_empty());
+    }
+
+    #[test]
+    fn scales_and_transforms_surface_damage_into_the_buffer() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        assert_eq!(
+            manager.set_buffer_scale(client(1), object(2), 0),
+            Err(SurfaceError::InvalidScale)
+        );
+        assert_eq!(
+            manager.set_buffer_transform(client(1), object(2), 8),
+            Err(SurfaceError::InvalidTransform)
+        );
+        manager.set_buffer_scale(client(1), object(2), 2).unwrap();
+        manager
+            .set_buffer_transform(client(1), object(2), 1)
+            .unwrap();
+        let rectangle = Rectangle {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        manager.damage(client(1), object(2), rectangle).unwrap();
+        manager
+            .attach(client(1), object(2), Some(object(3)), 0, 0)
+            .unwrap();
+
+        // A 10x20 surface rotated by 90 degrees into a 40x20 buffer.
+        let commit = manager.commit(client(1), object(2)).unwrap();
+        assert_eq!(
+            commit.damage_in_buffer((40, 20)),
+            [Rectangle {
+                x: 0,
+                y: 18,
+                width: 2,
+                height: 2,
+            }]
+        );
+        assert_eq!(
+            manager.set_buffer_size(client(1), object(2), (41, 20)),
+            Err(SurfaceError::InvalidSize)
+        );
+        manager
+            .set_buffer_size(client(1), object(2), (40, 20))
+            .unwrap();
+        assert!(
+            manager
+                .surface_at(client(1), object(2), (9.0, 19.0))
+                .is_some()
+        );
+        assert!(
+            manager
+                .surface_at(client(1), object(2), (10.0, 0.0))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn viewport_crops_and_scales_the_buffer() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_viewport(client(1), object(3), object(2))
+            .unwrap();
+        assert_eq!(
+            manager.create_viewport(client(1), object(4), object(2)),
+            Err(SurfaceError::ViewportExists)
+        );
+        assert_eq!(
+            manager.set_viewport_source(client(1), object(3), (-1.0, 0.0, 4.0, 4.0)),
+            Err(SurfaceError::InvalidViewport)
+        );
+        manager
+            .set_viewport_source(client(1), object(3), (10.0, 0.0, 20.0, 10.5))
+            .unwrap();
+        manager
+            .attach(client(1), object(2), Some(object(5)), 0, 0)
+            .unwrap();
+        manager.commit(client(1), object(2)).unwrap();
+
+        // Without a destination the source has to be of integer size.
+        assert_eq!(
+            manager.set_buffer_size(client(1), object(2), (40, 20)),
+            Err(SurfaceError::InvalidViewportSize)
+        );
+        manager
+            .set_viewport_destination(client(1), object(3), (40, 20))
+            .unwrap();
+        let rectangle = Rectangle {
+            x: 0,
+            y: 0,
+            width: 10,
+            height: 10,
+        };
+        manager.damage(client(1), object(2), rectangle).unwrap();
+        let commit = manager.commit(client(1), object(2)).unwrap();
+        assert_eq!(
+            manager.set_buffer_size(client(1), object(2), (20, 10)),
+            Err(SurfaceError::ViewportOutOfBuffer)
+        );
+        manager
+            .set_buffer_size(client(1), object(2), (40, 20))
+            .unwrap();
+        assert!(
+            manager
+                .surface_at(client(1), object(2), (39.0, 19.0))
+                .is_some()
+        );
 
-    fn client(id: u32) -> ClientId {
-        ClientId::new(NonZeroU32::new(id).unwrap())
+        // The source is drawn at twice its size, so damage halves, rounded
+        // out to whole buffer pixels.
+        assert_eq!(
+            commit.damage_in_buffer((40, 20)),
+            [Rectangle {
+                x: 10,
+                y: 0,
+                width: 5,
+                height: 6,
+            }]
+        );
+
+        manager.destroy_viewport(client(1), object(3));
+        manager.commit(client(1), object(2)).unwrap();
+        manager
+            .set_buffer_size(client(1), object(2), (20, 10))
+            .unwrap();
+        assert_eq!(manager.viewport(client(1), object(2)), None);
     }
 
-    fn object(id: u32) -> ObjectId {
-        ObjectId::new(NonZeroU32::new(id).unwrap())
+    #[test]
+    fn viewport_of_destroyed_surface_is_defunct() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_viewport(client(1), object(3), object(2))
+            .unwrap();
+        manager.destroy_surface(client(1), object(2)).unwrap();
+
+        assert_eq!(
+            manager.set_viewport_destination(client(1), object(3), (10, 10)),
+            Err(SurfaceError::DefunctViewport)
+        );
+        manager.destroy_viewport(client(1), object(3));
     }
 
     #[test]
-    fn commit_applies_pending_state_atomically() {
+    fn frame_callbacks_wait_for_their_tree_to_be_presented() {
         let mut manager = SurfaceManager::default();
         manager.create_surface(client(1), object(2));
-        manager
-            .create_shell_surface(client(1), object(3), object(2))
-            .unwrap();
-        manager
-            .set_shell_mode(client(1), object(3), ShellMode::Toplevel)
-            .unwrap();
-        manager
-            .attach(client(1), object(2), Some(object(4)), 5, 6)
-            .unwrap();
-        manager
-            .add_frame_callback(client(1), object(2), object(5))
-            .unwrap();
+        manager.create_surface(client(1), object(3));
+        manager.queue_frame_callbacks(client(1), object(2), object(2), vec![object(4)]);
+        manager.queue_frame_callbacks(client(1), object(3), object(2), vec![object(5)]);
+        manager.queue_frame_callbacks(client(2), object(2), object(2), vec![object(6)]);
 
-        let commit = manager.commit(client(1), object(2)).unwrap();
+        assert!(
+            manager
+                .take_presented_frame_callbacks(&[(client(1), object(3))])
+                .is_empty()
+        );
+        let (_, destroyed_callbacks, _) = manager.destroy_surface(client(1), object(3)).unwrap();
+        assert_eq!(destroyed_callbacks, [object(5)]);
+        assert_eq!(
+            manager.take_presented_frame_callbacks(&[(client(1), object(2))]),
+            [(client(1), object(4))]
+        );
 
-        assert_eq!(commit.buffer, Some(object(4)));
-        assert_eq!(commit.attached_buffer, Some(Some(object(4))));
-        assert!(commit.mapped);
-        assert_eq!(commit.frame_callbacks, [object(5)]);
-        let second = manager.commit(client(1), object(2)).unwrap();
-        assert_eq!(second.buffer, Some(object(4)));
-        assert_eq!(second.attached_buffer, None);
-        assert!(second.frame_callbacks.is_empty());
+        manager.delete_client(client(2));
+        assert!(
+            manager
+                .take_presented_frame_callbacks(&[(client(2), object(2))])
+                .is_empty()
+        );
     }
 
     #[test]
@@ -524,4 +2449,446 @@ mod tests {
             [RegionOperation::Add(first)]
         );
     }
+
+    fn xdg_toplevel(manager: &mut SurfaceManager) {
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_xdg_surface(client(1), object(9), object(3), object(2))
+            .unwrap();
+        manager
+            .create_xdg_toplevel(client(1), object(3), object(4), 6)
+            .unwrap();
+    }
+
+    #[test]
+    fn xdg_toplevel_maps_after_configure_is_acked() {
+        let mut manager = SurfaceManager::default();
+        xdg_toplevel(&mut manager);
+
+        let initial = manager.commit(client(1), object(2)).unwrap();
+        assert!(initial.needs_configure);
+        assert!(!initial.mapped);
+        let target = manager
+            .configure_xdg_surface(client(1), object(2), (640, 480))
+            .unwrap();
+        assert_eq!(target.xdg_surface_id, object(3));
+        assert!(matches!(
+            target.role,
+            XdgConfigure::Toplevel { toplevel_id, width: 640, height: 480, .. }
+                if toplevel_id == object(4)
+        ));
+
+        manager
+            .attach(client(1), object(2), Some(object(5)), 0, 0)
+            .unwrap();
+        assert_eq!(
+            manager.commit(client(1), object(2)).unwrap_err(),
+            SurfaceError::UnconfiguredBuffer
+        );
+
+        manager
+            .ack_configure(client(1), object(3), target.serial)
+            .unwrap();
+        let commit = manager.commit(client(1), object(2)).unwrap();
+        assert!(commit.mapped);
+        assert!(!commit.needs_configure);
+    }
+
+    #[test]
+    fn ack_configure_rejects_unknown_serials() {
+        let mut manager = SurfaceManager::default();
+        xdg_toplevel(&mut manager);
+        manager.commit(client(1), object(2)).unwrap();
+        let first = manager
+            .configure_xdg_surface(client(1), object(2), (0, 0))
+            .unwrap();
+        let second = manager
+            .configure_xdg_surface(client(1), object(2), (0, 0))
+            .unwrap();
+
+        manager
+            .ack_configure(client(1), object(3), second.serial)
+            .unwrap();
+        assert_eq!(
+            manager
+                .ack_configure(client(1), object(3), first.serial)
+                .unwrap_err(),
+            SurfaceError::InvalidSerial
+        );
+    }
+
+    #[test]
+    fn xdg_popups_are_placed_by_their_positioner() {
+        let mut manager = SurfaceManager::default();
+        xdg_toplevel(&mut manager);
+        let parent_geometry = Rectangle {
+            x: 5,
+            y: 5,
+            width: 100,
+            height: 100,
+        };
+        manager
+            .set_window_geometry(client(1), object(3), parent_geometry)
+            .unwrap();
+        manager.commit(client(1), object(2)).unwrap();
+
+        manager.create_surface(client(1), object(6));
+        manager
+            .create_xdg_surface(client(1), object(9), object(7), object(6))
+            .unwrap();
+        manager.create_positioner(client(1), object(8));
+        *manager.positioner_mut(client(1), object(8)).unwrap() = Positioner {
+            size: (50, 40),
+            anchor_rect: Rectangle {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 10,
+            },
+            anchor: XDG_POSITIONER_ANCHOR_BOTTOM_LEFT,
+            gravity: XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT,
+            ..Positioner::default()
+        };
+        assert_eq!(
+            manager
+                .create_xdg_popup(client(1), object(7), object(10), Some(object(7)), object(8))
+                .unwrap_err(),
+            SurfaceError::InvalidPopupParent
+        );
+        manager
+            .create_xdg_popup(client(1), object(7), object(10), Some(object(3)), object(8))
+            .unwrap();
+
+        assert_eq!(manager.xdg_popup(client(1), object(6)), Some(object(10)));
+        assert_eq!(
+            manager.popup_offset(client(1), object(6)),
+            Some((object(2), (15, 35)))
+        );
+        assert_eq!(manager.popup_offset(client(1), object(2)), None);
+    }
+
+    #[test]
+    fn xdg_surface_requires_a_surface_without_role() {
+        let mut manager = SurfaceManager::default();
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_shell_surface(client(1), object(3), object(2))
+            .unwrap();
+        assert_eq!(
+            manager
+                .create_xdg_surface(client(1), object(9), object(4), object(2))
+                .unwrap_err(),
+            SurfaceError::RoleAlreadyAssigned
+        );
+
+        xdg_toplevel(&mut manager);
+        assert_eq!(
+            manager
+                .create_xdg_toplevel(client(1), object(3), object(5), 6)
+                .unwrap_err(),
+            SurfaceError::AlreadyConstructed
+        );
+        assert_eq!(
+            manager
+                .destroy_xdg_surface(client(1), object(3))
+                .unwrap_err(),
+            SurfaceError::DefunctRoleObject
+        );
+    }
+
+    #[test]
+    fn toplevel_size_limits_are_validated_and_double_buffered() {
+        let mut manager = SurfaceManager::default();
+        xdg_toplevel(&mut manager);
+
+        manager.set_max_size(client(1), object(4), 100, 80).unwrap();
+        assert_eq!(
+            manager
+                .set_min_size(client(1), object(4), 120, 0)
+                .unwrap_err(),
+            SurfaceError::InvalidSize
+        );
+        manager.set_min_size(client(1), object(4), 10, 0).unwrap();
+        manager
+            .set_shell_title(client(1), object(4), "editor".to_owned())
+            .unwrap();
+        manager.commit(client(1), object(2)).unwrap();
+
+        let surface = manager.surfaces.get(&(client(1), object(2))).unwrap();
+        let xdg = surface.xdg.as_ref().unwrap();
+        assert_eq!(xdg.current.min_size, (10, 0));
+        assert_eq!(xdg.current.max_size, (100, 80));
+        assert_eq!(surface.shell.title, "editor");
+    }
+
+    fn mapped_parent_with_subsurface(manager: &mut SurfaceManager) {
+        manager.create_surface(client(1), object(2));
+        manager
+            .create_shell_surface(client(1), object(3), object(2))
+            .unwrap();
+        manager
+            .set_shell_mode(client(1), object(3), ShellMode::Toplevel)
+            .unwrap();
+        manager
+            .attach(client(1), object(2), Some(object(4)), 0, 0)
+            .unwrap();
+        manager.commit(client(1), object(2)).unwrap();
+        manager.create_surface(client(1), object(5));
+        manager
+            .create_subsurface(client(1), object(6), object(5), object(2))
+            .unwrap();
+    }
+
+    #[test]
+    fn synchronized_subsurface_state_is_applied_with_parent() {
+        let mut manager = SurfaceManager::default();
+        mapped_parent_with_subsurface(&mut manager);
+        manager.set_subsurface_position(client(1), object(6), 10, 20);
+        manager
+            .attach(client(1), object(5), Some(object(7)), 0, 0)
+            .unwrap();
+        manager
+            .add_frame_callback(client(1), object(5), object(8))
+            .unwrap();
+
+        let cached = manager.commit(client(1), object(5)).unwrap();
+        assert!(cached.cached);
+        assert_eq!(cached.root_id, object(2));
+        assert_eq!(
+            manager.surface_tree(client(1), object(2)),
+            None,
+            "the sub-surface is only added with the parent's state"
+        );
+
+        let commit = manager.commit(client(1), object(2)).unwrap();
+        let [child] = commit.subsurface_commits.as_slice() else {
+            panic!("expected the cached sub-surface state to be applied");
+        };
+        assert_eq!(child.surface_id, object(5));
+        assert_eq!(child.root_id, object(2));
+        assert_eq!(child.attached_buffer, Some(Some(object(7))));
+        assert_eq!(child.frame_callbacks, [object(8)]);
+        assert!(child.mapped);
+        assert_eq!(
+            manager.surface_tree(client(1), object(2)).unwrap(),
+            [
+                SurfaceLayer {
+                    surface_id: object(2),
+                    x: 0,
+                    y: 0,
+                },
+                SurfaceLayer {
+                    surface_id: object(5),
+                    x: 10,
+                    y: 20,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hit_testing_respects_input_region_and_subsurfaces() {
+        let mut manager = SurfaceManager::default();
+        mapped_parent_with_subsurface(&mut manager);
+        manager.set_subsurface_position(client(1), object(6), 10, 20);
+        manager
+            .attach(client(1), object(5), Some(object(7)), 0, 0)
+            .unwrap();
+        manager.commit(client(1), object(5)).unwrap();
+        manager.create_region(client(1), object(9));
+        manager
+            .add_region(
+                client(1),
+                object(9),
+                Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: 100,
+                    height: 100,
+                },
+            )
+            .unwrap();
+        manager
+            .subtract_region(
+                client(1),
+                object(9),
+                Rectangle {
+                    x: 50,
+                    y: 50,
+                    width: 50,
+                    height: 50,
+                },
+            )
+            .unwrap();
+        manager
+            .set_input_region(client(1), object(2), Some(object(9)))
+            .unwrap();
+        manager.commit(client(1), object(2)).unwrap();
+        manager
+            .set_buffer_size(client(1), object(2), (100, 100))
+            .unwrap();
+        manager
+            .set_buffer_size(client(1), object(5), (20, 20))
+            .unwrap();
+
+        assert_eq!(
+            manager.surface_at(client(1), object(2), (15.0, 25.5)),
+            Some((object(5), 5.0, 5.5))
+        );
+        assert_eq!(
+            manager.surface_at(client(1), object(2), (5.0, 5.0)),
+            Some((object(2), 5.0, 5.0))
+        );
+        assert_eq!(
+            manager.surface_at(client(1), object(2), (60.0, 60.0)),
+            None,
+            "outside the input region"
+        );
+        assert_eq!(manager.surface_at(client(1), object(2), (150.0, 5.0)), None);
+        assert_eq!(
+            manager.surface_position(client(1), object(5)),
+            Some((10, 20))
+        );
+    }
+
+    #[test]
+    fn desynchronized_subsurface_commits_apply_immediately() {
+        let mut manager = SurfaceManager::default();
+        mapped_parent_with_subsurface(&mut manager);
+        manager.create_surface(client(1), object(9));
+        manager
+            .create_subsurface(client(1), object(10), object(9), object(5))
+            .unwrap();
+        manager.set_subsurface_sync(client(1), object(6), false);
+        manager
+            .attach(client(1), object(9), Some(object(11)), 0, 0)
+            .unwrap();
+
+        // The grandchild is desynchronized but its parent is not.
+        manager.set_subsurface_sync(client(1), object(10), false);
+        manager.set_subsurface_sync(client(1), object(6), true);
+        assert!(manager.commit(client(1), object(9)).unwrap().cached);
+
+        manager.set_subsurface_sync(client(1), object(6), false);
+        manager
+            .attach(client(1), object(5), Some(object(7)), 0, 0)
+            .unwrap();
+        let commit = manager.commit(client(1), object(5)).unwrap();
+        assert!(!commit.cached);
+        assert!(
+            !commit.mapped,
+            "the parent has not added the sub-surface yet"
+        );
+        let [grandchild] = commit.subsurface_commits.as_slice() else {
+            panic!("expected the cached grandchild state to be applied");
+        };
+        assert_eq!(grandchild.root_id, object(2));
+        assert_eq!(grandchild.attached_buffer, Some(Some(object(11))));
+    }
+
+    #[test]
+    fn subsurface_stacking_is_double_buffered() {
+        let mut manager = SurfaceManager::default();
+        mapped_parent_with_subsurface(&mut manager);
+        manager.set_subsurface_sync(client(1), object(6), false);
+        manager
+            .attach(client(1), object(5), Some(object(7)), 0, 0)
+            .unwrap();
+        manager.commit(client(1), object(5)).unwrap();
+        manager
+            .place_subsurface(client(1), object(6), object(2), false)
+            .unwrap();
+        assert_eq!(
+            manager
+                .place_subsurface(client(1), object(6), object(5), true)
+                .unwrap_err(),
+            SurfaceError::InvalidSibling
+        );
+
+        let layers = |manager: &SurfaceManager| -> Vec<_> {
+            manager
+                .surface_tree(client(1), object(2))
+                .unwrap_or_default()
+                .iter()
+                .map(|layer| layer.surface_id)
+                .collect()
+        };
+        assert!(layers(&manager).is_empty());
+        manager.commit(client(1), object(2)).unwrap();
+        assert_eq!(layers(&manager), [object(5), object(2)]);
+
+        assert_eq!(
+            manager.destroy_subsurface(client(1), object(6)),
+            Some((object(5), object(2)))
+        );
+        assert_eq!(layers(&manager), [object(2)]);
+    }
+
+    #[test]
+    fn subsurface_parent_must_not_be_a_descendant() {
+        let mut manager = SurfaceManager::default();
+        mapped_parent_with_subsurface(&mut manager);
+
+        assert_eq!(
+            manager
+                .create_subsurface(client(1), object(9), object(2), object(5))
+                .unwrap_err(),
+            SurfaceError::InvalidParent
+        );
+        assert_eq!(
+            manager
+                .create_subsurface(client(1), object(9), object(5), object(5))
+                .unwrap_err(),
+            SurfaceError::InvalidParent
+        );
+        assert_eq!(
+            manager
+                .create_subsurface(client(1), object(9), object(5), object(2))
+                .unwrap_err(),
+            SurfaceError::RoleAlreadyAssigned
+        );
+    }
+
+    #[test]
+    fn positioner_places_popup_from_anchor_and_gravity() {
+        let positioner = Positioner {
+            size: (40, 20),
+            anchor_rect: Rectangle {
+                x: 10,
+                y: 10,
+                width: 30,
+                height: 10,
+            },
+            anchor: XDG_POSITIONER_ANCHOR_BOTTOM_LEFT,
+            gravity: XDG_POSITIONER_GRAVITY_BOTTOM_RIGHT,
+            offset: (2, 3),
+            ..Positioner::default()
+        };
+
+        assert_eq!(
+            positioner.geometry(),
+            Rectangle {
+                x: 12,
+                y: 23,
+                width: 40,
+                height: 20,
+            }
+        );
+
+        let centered = Positioner {
+            anchor: XDG_POSITIONER_ANCHOR_NONE,
+            gravity: XDG_POSITIONER_GRAVITY_NONE,
+            offset: (0, 0),
+            ..positioner
+        };
+        assert_eq!(
+            centered.geometry(),
+            Rectangle {
+                x: 5,
+                y: 5,
+                width: 40,
+                height: 20,
+            }
+        );
+    }
 }
@@ -0,0 +1,16 @@
+use std::num::NonZeroU32;
+
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+pub(crate) fn client(id: u32) -> ClientId {
+    ClientId::new(NonZeroU32::new(id).unwrap())
+}
+
+pub(crate) fn object(id: u32) -> ObjectId {
+    ObjectId::new(NonZeroU32::new(id).unwrap())
+}
+
+/// The toplevel with main surface `surface` of client `client`.
+pub(crate) fn window(client: u32, surface: u32) -> (ClientId, ObjectId) {
+    (self::client(client), object(surface))
+}
@@ -0,0 +1,728 @@
+use lumalla_shared::{Output, WindowRule, Zone};
+use lumalla_wayland_protocol::{ClientId, ObjectId};
+
+use crate::{
+    window_rules::{WindowProperties, WindowRules},
+    workspace::Workspaces,
+};
+
+/// `(x, y, width, height)` in the global layout.
+type Geometry = (i32, i32, i32, i32);
+
+/// Windows whose placement an update changed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct WindowChanges {
+    /// Windows with a new location or size.
+    pub moved: Vec<(ClientId, ObjectId)>,
+    /// Windows that were shown (`true`) or hidden (`false`).
+    pub visibility: Vec<((ClientId, ObjectId), bool)>,
+}
+
+/// A toplevel placed in a zone or on an output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Window {
+    client_id: ClientId,
+    surface_id: ObjectId,
+    /// `None` while no zone is configured.
+    zone: Option<String>,
+    /// Output the window fills instead of its zone.
+    output: Option<String>,
+    /// Workspace the window is on; it is hidden while its output shows
+    /// another one. `None` while no output is connected.
+    workspace: Option<String>,
+    /// Floating windows keep their own size, centered on their zone.
+    floating: bool,
+    fullscreen: bool,
+    /// Size floating windows are configured with.
+    size: Option<(i32, i32)>,
+    /// Opacity in percent.
+    opacity: u32,
+    /// Effects of the window rules last applied to the window.
+    rule: WindowRule,
+    /// Whether the window has contents on screen.
+    mapped: bool,
+}
+
+/// Zones in the logical layout and the toplevels placed in them.
+#[derive(Debug, Default)]
+pub struct WindowManager {
+    zones: Vec<Zone>,
+    /// Logical geometry of the outputs by name.
+    outputs: Vec<(String, Geometry)>,
+    workspaces: Workspaces,
+    rules: WindowRules,
+    /// Least recently mapped first; the last mapped window is the current one.
+    windows: Vec<Window>,
+}
+
+impl WindowManager {
+    /// Replaces the zones. Windows whose zone is gone move to the default zone.
+    ///
+    /// Returns the windows whose geometry changed.
+    pub fn set_zones(&mut self, zones: Vec<Zone>) -> Vec<(ClientId, ObjectId)> {
+        self.track_geometry(|manager| {
+            manager.zones = zones;
+            let default_zone = manager.default_zone();
+            for window in &mut manager.windows {
+                let known = window
+                    .zone
+                    .as_deref()
+                    .is_some_and(|name| manager.zones.iter().any(|zone| zone.name == name));
+                if !known {
+                    window.zone = default_zone.clone();
+                }
+            }
+        })
+    }
+
+    /// Updates the outputs windows can be placed on, giving each of them a
+    /// workspace to show.
+    pub fn set_outputs(&mut self, outputs: &[Output]) -> WindowChanges {
+        self.track_changes(|manager| {
+            manager.outputs = outputs
+                .iter()
+                .map(|output| {
+                    let (width, height) = output.logical_size();
+                    let (x, y) = output.location;
+                    (output.name.clone(), (x, y, width, height))
+                })
+                .collect();
+            let names: Vec<_> = outputs.iter().map(|output| output.name.as_str()).collect();
+            manager.workspaces.set_outputs(&names);
+            // Windows opened without outputs go to the workspaces shown now.
+            for index in 0..manager.windows.len() {
+                let window = &manager.windows[index];
+                let Some(output) = manager.output_around(Some(manager.area_geometry(window)))
+                else {
+                    continue;
+                };
+                let output = output.0.to_string();
+                match window.workspace.clone() {
+                    Some(name) => manager.workspaces.ensure(&name, &output),
+                    None => {
+                        manager.windows[index].workspace =
+                            manager.workspaces.active(&output).map(str::to_string);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Replaces the window rules; they apply to windows whose properties
+    /// change from now on.
+    pub fn set_rules(&mut self, rules: Vec<WindowRule>) {
+        self.rules.set(rules);
+    }
+
+    /// Places a new toplevel in the default zone, on the workspace shown
+    /// on its output.
+    pub fn add_window(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        if self.window(client_id, surface_id).is_some() {
+            return;
+        }
+        let zone = self.default_zone();
+        let workspace = self.shown_workspace_around(self.zone_geometry(zone.as_deref()));
+        self.windows.push(Window {
+            client_id,
+            surface_id,
+            zone,
+            output: None,
+            workspace,
+            floating: false,
+            fullscreen: false,
+            size: None,
+            opacity: 100,
+            rule: WindowRule::default(),
+            mapped: false,
+        });
+    }
+
+    /// Applies the rules matching a window's current properties.
+    ///
+    /// Returns `false` if their effects did not change since they were last
+    /// applied, so windows moved since then stay where they are.
+    pub fn apply_rules(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        properties: &WindowProperties,
+    ) -> bool {
+        let effects = self.rules.effects(properties);
+        let zone = effects
+            .zone
+            .clone()
+            .filter(|name| self.zones.iter().any(|zone| zone.name == *name));
+        let Some(window) = self.window_mut(client_id, surface_id) else {
+            return false;
+        };
+        if window.rule == effects {
+            return false;
+        }
+        let placed = zone.is_some() || effects.output.is_some();
+        if zone.is_some() {
+            window.zone = zone;
+            window.output = None;
+        } else if effects.output.is_some() {
+            window.output.clone_from(&effects.output);
+        }
+        window.floating = effects.floating.unwrap_or(false);
+        if let Some(fullscreen) = effects.fullscreen {
+            window.fullscreen = fullscreen;
+        }
+        window.size = effects.size;
+        window.opacity = effects.opacity.unwrap_or(100).min(100);
+        let workspace = effects.workspace.clone();
+        window.rule = effects;
+        if let Some(name) = workspace {
+            self.set_workspace(client_id, surface_id, &name);
+        } else if placed {
+            self.settle_workspace(client_id, surface_id);
+        }
+        true
+    }
+
+    pub fn remove_window(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        self.windows
+            .retain(|window| window.client_id != client_id || window.surface_id != surface_id);
+    }
+
+    pub fn delete_client(&mut self, client_id: ClientId) {
+        self.windows.retain(|window| window.client_id != client_id);
+    }
+
+    /// Marks a window as shown and makes it the current window.
+    ///
+    /// Returns its location if it was hidden before; shown windows and
+    /// unmanaged surfaces yield `None`.
+    pub fn window_mapped(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> Option<(i32, i32)> {
+        let index = self.windows.iter().position(|window| {
+            window.client_id == client_id && window.surface_id == surface_id && !window.mapped
+        })?;
+        let mut window = self.windows.remove(index);
+        window.mapped = true;
+        self.windows.push(window);
+        Some(self.location(client_id, surface_id))
+    }
+
+    pub fn window_unmapped(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        if let Some(window) = self.window_mut(client_id, surface_id) {
+            window.mapped = false;
+        }
+    }
+
+    pub fn is_mapped(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        self.window(client_id, surface_id)
+            .is_some_and(|window| window.mapped)
+    }
+
+    /// Makes a window cover its output, or returns it to its zone.
+    ///
+    /// Returns `false` for unmanaged surfaces.
+    pub fn set_fullscreen(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        fullscreen: bool,
+    ) -> bool {
+        let Some(window) = self.window_mut(client_id, surface_id) else {
+            return false;
+        };
+        window.fullscreen = fullscreen;
+        true
+    }
+
+    pub fn is_fullscreen(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        self.window(client_id, surface_id)
+            .is_some_and(|window| window.fullscreen)
+    }
+
+    /// Moves a window to the zone `name`, tiling it there.
+    ///
+    /// Returns `false` if there is no such window or zone.
+    pub fn move_window(&mut self, client_id: ClientId, surface_id: ObjectId, name: &str) -> bool {
+        if !self.zones.iter().any(|zone| zone.name == name) {
+            return false;
+        }
+        let Some(window) = self.window_mut(client_id, surface_id) else {
+            return false;
+        };
+        window.zone = Some(name.to_string());
+        window.output = None;
+        window.floating = false;
+        self.settle_workspace(client_id, surface_id);
+        true
+    }
+
+    /// Whether a window's workspace is shown; unmanaged surfaces always are.
+    pub fn is_visible(&self, client_id: ClientId, surface_id: ObjectId) -> bool {
+        self.window(client_id, surface_id)
+            .is_none_or(|window| self.is_window_visible(window))
+    }
+
+    /// Shows the workspace `name`, creating it on `output` if needed.
+    pub fn switch_workspace(&mut self, name: &str, output: &str) -> WindowChanges {
+        self.track_changes(|manager| manager.workspaces.activate(name, output))
+    }
+
+    /// Moves a window to the workspace `name`, which is created on the
+    /// window's output if needed.
+    ///
+    /// Returns `None` for unmanaged surfaces.
+    pub fn move_window_to_workspace(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        name: &str,
+    ) -> Option<WindowChanges> {
+        self.window(client_id, surface_id)?;
+        Some(self.track_changes(|manager| manager.set_workspace(client_id, surface_id, name)))
+    }
+
+    /// Attaches the workspace `name` with its windows to `output` and shows
+    /// it there.
+    ///
+    /// Returns `None` if there is no such workspace or output.
+    pub fn move_workspace_to_output(&mut self, name: &str, output: &str) -> Option<WindowChanges> {
+        self.output_geometry(output)?;
+        self.workspaces.output(name)?;
+        Some(self.track_changes(|manager| {
+            manager.workspaces.move_to_output(name, output);
+        }))
+    }
+
+    /// Shows the workspace a window is on.
+    pub fn show_workspace_of(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+    ) -> WindowChanges {
+        let Some(name) = self
+            .window(client_id, surface_id)
+            .and_then(|window| window.workspace.clone())
+        else {
+            return WindowChanges::default();
+        };
+        self.track_changes(|manager| {
+            if let Some(output) = manager.workspaces.output(&name).map(str::to_string) {
+                manager.workspaces.activate(&name, &output);
+            }
+        })
+    }
+
+    /// `(output, workspace)` of the workspace every output shows.
+    pub fn shown_workspaces(&self) -> Vec<(String, String)> {
+        self.workspaces.shown()
+    }
+
+    /// The output a window is shown on; unmanaged surfaces are on none.
+    pub fn window_output(&self, client_id: ClientId, surface_id: ObjectId) -> Option<&str> {
+        let window = self.window(client_id, surface_id)?;
+        self.output_around(Some(self.window_geometry(window)))
+            .map(|(name, _)| name)
+    }
+
+    /// The output showing a global position, or else the first one.
+    pub fn output_at(&self, (x, y): (f64, f64)) -> Option<&str> {
+        self.outputs
+            .iter()
+            .find(|(_, (output_x, output_y, width, height))| {
+                (f64::from(*output_x)..f64::from(output_x + width)).contains(&x)
+                    && (f64::from(*output_y)..f64::from(output_y + height)).contains(&y)
+            })
+            .or(self.outputs.first())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Size toplevels are configured with; `(0, 0)` lets unmanaged surfaces
+    /// and windows without a zone pick their own size.
+    pub fn size(&self, client_id: ClientId, surface_id: ObjectId) -> (i32, i32) {
+        let (_, _, width, height) = self.geometry(client_id, surface_id);
+        (width, height)
+    }
+
+    /// Top-left corner of a main surface in the global layout.
+    pub fn location(&self, client_id: ClientId, surface_id: ObjectId) -> (i32, i32) {
+        let (x, y, _, _) = self.geometry(client_id, surface_id);
+        (x, y)
+    }
+
+    /// Opacity in percent a main surface is drawn with.
+    pub fn opacity(&self, client_id: ClientId, surface_id: ObjectId) -> u32 {
+        self.window(client_id, surface_id)
+            .map_or(100, |window| window.opacity)
+    }
+
+    fn geometry(&self, client_id: ClientId, surface_id: ObjectId) -> Geometry {
+        self.window(client_id, surface_id)
+            .map(|window| self.window_geometry(window))
+            .unwrap_or_default()
+    }
+
+    /// Geometry of a window, moved onto the output of its workspace when
+    /// its zone lies on another output.
+    fn window_geometry(&self, window: &Window) -> Geometry {
+        let (x, y, width, height) = self.area_geometry(window);
+        let workspace_output = window
+            .workspace
+            .as_deref()
+            .and_then(|name| self.workspaces.output(name))
+            .and_then(|name| self.output_geometry(name));
+        match (
+            workspace_output,
+            self.output_around(Some((x, y, width, height))),
+        ) {
+            (Some((to_x, to_y, _, _)), Some((_, (from_x, from_y, _, _)))) => {
+                (x + to_x - from_x, y + to_y - from_y, width, height)
+            }
+            _ => (x, y, width, height),
+        }
+    }
+
+    /// Geometry of a window in its zone or on its output.
+    fn area_geometry(&self, window: &Window) -> Geometry {
+        let output = window
+            .output
+            .as_deref()
+            .and_then(|name| self.output_geometry(name));
+        let area = output.or_else(|| self.zone_geometry(window.zone.as_deref()));
+        if window.fullscreen
+            && let Some(geometry) =
+                output.or_else(|| self.output_around(area).map(|(_, geometry)| geometry))
+        {
+            return geometry;
+        }
+        match (area, window.floating.then_some(window.size)) {
+            (Some(area), None) => area,
+            (Some((x, y, _, _)), Some(None)) => (x, y, 0, 0),
+            (Some((x, y, width, height)), Some(Some((own_width, own_height)))) => (
+                x + (width - own_width) / 2,
+                y + (height - own_height) / 2,
+                own_width,
+                own_height,
+            ),
+            (None, _) => {
+                let (width, height) = window.size.unwrap_or_default();
+                (0, 0, width, height)
+            }
+        }
+    }
+
+    fn zone_geometry(&self, name: Option<&str>) -> Option<Geometry> {
+        let name = name?;
+        self.zones
+            .iter()
+            .find(|zone| zone.name == name)
+            .map(|zone| zone.geometry)
+    }
+
+    fn output_geometry(&self, name: &str) -> Option<Geometry> {
+        self.outputs
+            .iter()
+            .find(|(output, _)| output == name)
+            .map(|(_, geometry)| *geometry)
+    }
+
+    /// The output showing the center of `area`, or else the first one.
+    fn output_around(&self, area: Option<Geometry>) -> Option<(&str, Geometry)> {
+        let outputs = || {
+            self.outputs
+                .iter()
+                .map(|(name, geometry)| (name.as_str(), *geometry))
+        };
+        area.and_then(|(x, y, width, height)| {
+            let (center_x, center_y) = (x + width / 2, y + height / 2);
+            outputs().find(|&(_, (x, y, width, height))| {
+                (x..x + width).contains(&center_x) && (y..y + height).contains(&center_y)
+            })
+        })
+        .or_else(|| outputs().next())
+    }
+
+    /// The workspace shown on the output around `area`.
+    fn shown_workspace_around(&self, area: Option<Geometry>) -> Option<String> {
+        let (output, _) = self.output_around(area)?;
+        self.workspaces.active(output).map(str::to_string)
+    }
+
+    /// Moves a window to the workspace shown where its zone or output is.
+    fn settle_workspace(&mut self, client_id: ClientId, surface_id: ObjectId) {
+        let Some(window) = self.window(client_id, surface_id) else {
+            return;
+        };
+        let workspace = self.shown_workspace_around(Some(self.area_geometry(window)));
+        if let (Some(workspace), Some(window)) = (workspace, self.window_mut(client_id, surface_id))
+        {
+            window.workspace = Some(workspace);
+        }
+    }
+
+    /// Attaches a window to the workspace `name`, creating it on the output
+    /// the window is on. Windows keep the name while no output is connected.
+    fn set_workspace(&mut self, client_id: ClientId, surface_id: ObjectId, name: &str) {
+        let Some(window) = self.window(client_id, surface_id) else {
+            return;
+        };
+        if let Some((output, _)) = self.output_around(Some(self.window_geometry(window))) {
+            let output = output.to_string();
+            self.workspaces.ensure(name, &output);
+        }
+        if let Some(window) = self.window_mut(client_id, surface_id) {
+            window.workspace = Some(name.to_string());
+        }
+    }
+
+    /// Whether the output of a window's workspace shows it. Workspaces not
+    /// created yet are shown.
+    fn is_window_visible(&self, window: &Window) -> bool {
+        window.workspace.as_deref().is_none_or(|name| {
+            self.workspaces.is_active(name) || self.workspaces.output(name).is_none()
+        })
+    }
+
+    /// The zone marked as default, or else the first one.
+    fn default_zone(&self) -> Option<String> {
+        self.zones
+            .iter()
+            .find(|zone| zone.default)
+            .or(self.zones.first())
+            .map(|zone| zone.name.clone())
+    }
+
+    /// Runs `update` and returns the windows whose geometry it changed.
+    fn track_geometry(&mut self, update: impl FnOnce(&mut Self)) -> Vec<(ClientId, ObjectId)> {
+        self.track_changes(update).moved
+    }
+
+    /// Runs `update`, which must not add or remove windows, and returns the
+    /// windows it moved, showed or hid.
+    fn track_changes(&mut self, update: impl FnOnce(&mut Self)) -> WindowChanges {
+        let previous: Vec<_> = self
+            .windows
+            .iter()
+            .map(|window| (self.window_geometry(window), self.is_window_visible(window)))
+            .collect();
+        update(self);
+        let mut changes = WindowChanges::default();
+        for (window, (geometry, visible)) in self.windows.iter().zip(previous) {
+            let key = (window.client_id, window.surface_id);
+            if self.window_geometry(window) != geometry {
+                changes.moved.push(key);
+            }
+            let now_visible = self.is_window_visible(window);
+            if now_visible != visible {
+                changes.visibility.push((key, now_visible));
+            }
+        }
+        changes
+    }
+
+    fn window(&self, client_id: ClientId, surface_id: ObjectId) -> Option<&Window> {
+        self.windows
+            .iter()
+            .find(|window| window.client_id == client_id && window.surface_id == surface_id)
+    }
+
+    fn window_mut(&mut self, client_id: ClientId, surface_id: ObjectId) -> Option<&mut Window> {
+        self.windows
+            .iter_mut()
+            .find(|window| window.client_id == client_id && window.surface_id == surface_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_ids::{client, object};
+
+    fn zones() -> Vec<Zone> {
+        vec![
+            Zone::new(String::from("left"), 0, 0, 960, 1080, false),
+            Zone::new(String::from("right"), 960, 0, 960, 1080, true),
+        ]
+    }
+
+    #[test]
+    fn new_windows_fill_the_default_zone() {
+        let mut manager = WindowManager::default();
+        manager.add_window(client(1), object(2));
+        assert_eq!(manager.size(client(1), object(2)), (0, 0));
+
+        assert_eq!(manager.set_zones(zones()), [(client(1), object(2))]);
+        manager.add_window(client(1), object(3));
+        assert_eq!(manager.size(client(1), object(3)), (960, 1080));
+        assert_eq!(manager.window_mapped(client(1), object(3)), Some((960, 0)));
+        assert_eq!(manager.window_mapped(client(1), object(3)), None);
+        assert_eq!(manager.location(client(1), object(2)), (960, 0));
+        assert_eq!(manager.location(client(1), object(4)), (0, 0));
+    }
+
+    #[test]
+    fn moves_windows_between_zones() {
+        let mut manager = WindowManager::default();
+        manager.set_zones(zones());
+        manager.add_window(client(1), object(2));
+        manager.add_window(client(2), object(2));
+        assert!(!manager.move_window(client(3), object(2), "left"));
+        assert!(!manager.move_window(client(2), object(2), "top"));
+
+        manager.window_mapped(client(1), object(2));
+        manager.window_mapped(client(2), object(2));
+        assert!(manager.move_window(client(2), object(2), "left"));
+        assert_eq!(manager.location(client(2), object(2)), (0, 0));
+
+        let changed = manager.set_zones(vec![Zone::new(
+            String::from("right"),
+            960,
+            0,
+            960,
+            1080,
+            false,
+        )]);
+        assert_eq!(changed, [(client(2), object(2))]);
+        assert_eq!(manager.location(client(2), object(2)), (960, 0));
+    }
+
+    #[test]
+    fn windows_are_on_the_output_of_their_zone() {
+        let mut manager = WindowManager::default();
+        manager.set_zones(zones());
+        manager.set_outputs(&side_by_side_outputs());
+        manager.add_window(client(1), object(2));
+        assert_eq!(manager.window_output(client(1), object(2)), Some("DP-1"));
+        assert_eq!(manager.window_output(client(1), object(3)), None);
+
+        assert!(manager.move_window(client(1), object(2), "left"));
+        assert_eq!(manager.window_output(client(1), object(2)), Some("eDP-1"));
+    }
+
+    fn side_by_side_outputs() -> [Output; 2] {
+        let output = |name: &str, x| Output {
+            name: String::from(name),
+            description: String::new(),
+            location: (x, 0),
+            size: (960, 1080),
+            scale: 100,
+            transform: lumalla_shared::Transform::Normal,
+        };
+        [output("eDP-1", 0), output("DP-1", 960)]
+    }
+
+    #[test]
+    fn switching_workspaces_hides_the_windows_of_the_previous_one() {
+        let mut manager = WindowManager::default();
+        manager.set_zones(zones());
+        manager.set_outputs(&side_by_side_outputs());
+        manager.add_window(client(1), object(2));
+        manager.window_mapped(client(1), object(2));
+
+        let changes = manager.switch_workspace("web", "DP-1");
+        assert_eq!(changes.visibility, [((client(1), object(2)), false)]);
+        assert!(!manager.is_visible(client(1), object(2)));
+        assert_eq!(
+            manager.shown_workspaces(),
+            [
+                (String::from("DP-1"), String::from("web")),
+                (String::from("eDP-1"), String::from("1")),
+            ]
+        );
+
+        let changes = manager.show_workspace_of(client(1), object(2));
+        assert_eq!(changes.visibility, [((client(1), object(2)), true)]);
+        assert_eq!(
+            manager.shown_workspaces(),
+            [
+                (String::from("DP-1"), String::from("2")),
+                (String::from("eDP-1"), String::from("1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_move_to_workspaces_on_other_outputs() {
+        let mut manager = WindowManager::default();
+        manager.set_zones(zones());
+        manager.set_outputs(&side_by_side_outputs());
+        manager.add_window(client(1), object(2));
+        manager.window_mapped(client(1), object(2));
+        assert!(
+            manager
+                .move_window_to_workspace(client(3), object(2), "mail")
+                .is_none()
+        );
+
+        let changes = manager
+            .move_window_to_workspace(client(1), object(2), "mail")
+            .unwrap();
+        assert_eq!(changes.visibility, [((client(1), object(2)), false)]);
+
+        // The workspace takes its windows along, keeping their zone layout.
+        let changes = manager.move_workspace_to_output("mail", "eDP-1").unwrap();
+        assert_eq!(changes.moved, [(client(1), object(2))]);
+        assert_eq!(changes.visibility, [((client(1), object(2)), true)]);
+        assert_eq!(manager.location(client(1), object(2)), (0, 0));
+        assert_eq!(manager.window_output(client(1), object(2)), Some("eDP-1"));
+        assert_eq!(
+            manager.shown_workspaces(),
+            [
+                (String::from("DP-1"), String::from("2")),
+                (String::from("eDP-1"), String::from("mail")),
+            ]
+        );
+        assert!(
+            manager
+                .move_workspace_to_output("mail", "HDMI-A-1")
+                .is_none()
+        );
+        assert!(manager.move_workspace_to_output("music", "DP-1").is_none());
+    }
+
+    #[test]
+    fn rules_place_windows_matching_their_properties() {
+        let mut manager = WindowManager::default();
+        manager.set_zones(zones());
+        let output = Output {
+            name: String::from("eDP-1"),
+            description: String::new(),
+            location: (0, 0),
+            size: (3840, 2160),
+            scale: 200,
+            transform: lumalla_shared::Transform::Normal,
+        };
+        assert_eq!(manager.set_outputs(&[output]), WindowChanges::default());
+        manager.set_rules(vec![
+            WindowRule {
+                app_id: Some(String::from("mpv")),
+                floating: Some(true),
+                size: Some((640, 360)),
+                opacity: Some(80),
+                ..Default::default()
+            },
+            WindowRule {
+                title: Some(String::from("presentation")),
+                fullscreen: Some(true),
+                ..Default::default()
+            },
+        ]);
+        manager.add_window(client(1), object(2));
+        let mut properties = WindowProperties {
+            app_id: "mpv",
+            title: "",
+            transient: false,
+        };
+        assert!(manager.apply_rules(client(1), object(2), &properties));
+        assert!(!manager.apply_rules(client(1), object(2), &properties));
+        assert_eq!(manager.location(client(1), object(2)), (1120, 360));
+        assert_eq!(manager.size(client(1), object(2)), (640, 360));
+        assert_eq!(manager.opacity(client(1), object(2)), 80);
+
+        properties.title = "presentation.mkv";
+        assert!(manager.apply_rules(client(1), object(2), &properties));
+        assert!(manager.is_fullscreen(client(1), object(2)));
+        assert_eq!(manager.location(client(1), object(2)), (0, 0));
+        assert_eq!(manager.size(client(1), object(2)), (1920, 1080));
+    }
+}
@@ -0,0 +1,125 @@
+use log::warn;
+use lumalla_shared::WindowRule;
+use regex::Regex;
+
+/// What window rules match toplevels by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowProperties<'a> {
+    pub app_id: &'a str,
+    pub title: &'a str,
+    /// Whether the toplevel has a parent.
+    pub transient: bool,
+}
+
+/// A rule with its title pattern compiled.
+#[derive(Debug)]
+struct CompiledRule {
+    rule: WindowRule,
+    title: Option<Regex>,
+}
+
+impl CompiledRule {
+    fn matches(&self, properties: &WindowProperties) -> bool {
+        self.rule
+            .app_id
+            .as_deref()
+            .is_none_or(|app_id| app_id == properties.app_id)
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| title.is_match(properties.title))
+            && self
+                .rule
+                .transient
+                .is_none_or(|transient| transient == properties.transient)
+    }
+}
+
+/// Rules toplevels are matched against, in the order they apply.
+#[derive(Debug, Default)]
+pub struct WindowRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl WindowRules {
+    /// Replaces the rules. Rules with an invalid title pattern are skipped.
+    pub fn set(&mut self, rules: Vec<WindowRule>) {
+        self.rules = rules
+            .into_iter()
+            .filter_map(|rule| {
+                let title = rule
+                    .title
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .inspect_err(|err| warn!("Skipping window rule with invalid title: {err}"))
+                    .ok()?;
+                Some(CompiledRule { rule, title })
+            })
+            .collect();
+    }
+
+    /// Effects of every rule matching a toplevel, later rules overriding
+    /// earlier ones.
+    pub fn effects(&self, properties: &WindowProperties) -> WindowRule {
+        let mut effects = WindowRule::default();
+        for compiled in self.rules.iter().filter(|rule| rule.matches(properties)) {
+            effects.merge(&compiled.rule);
+        }
+        effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_matching_rules_override_earlier_ones() {
+        let mut rules = WindowRules::default();
+        rules.set(vec![
+            WindowRule {
+                app_id: Some(String::from("firefox")),
+                zone: Some(String::from("left")),
+                opacity: Some(90),
+                ..Default::default()
+            },
+            WindowRule {
+                title: Some(String::from("^Picture-in-Picture$")),
+                transient: Some(false),
+                floating: Some(true),
+                zone: Some(String::from("right")),
+                ..Default::default()
+            },
+            WindowRule {
+                title: Some(String::from("(unclosed")),
+                fullscreen: Some(true),
+                ..Default::default()
+            },
+        ]);
+
+        let browser = WindowProperties {
+            app_id: "firefox",
+            title: "Mozilla Firefox",
+            transient: false,
+        };
+        let effects = rules.effects(&browser);
+        assert_eq!(effects.zone.as_deref(), Some("left"));
+        assert_eq!((effects.opacity, effects.floating), (Some(90), None));
+
+        let video = WindowProperties {
+            title: "Picture-in-Picture",
+            ..browser
+        };
+        let effects = rules.effects(&video);
+        assert_eq!(effects.zone.as_deref(), Some("right"));
+        assert_eq!((effects.opacity, effects.floating), (Some(90), Some(true)));
+        assert_eq!(effects.fullscreen, None);
+
+        let dialog = WindowProperties {
+            transient: true,
+            ..video
+        };
+        assert_eq!(rules.effects(&dialog).floating, None);
+    }
+}
@@ -0,0 +1,182 @@
+/// A group of windows shown together on one output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Workspace {
+    name: String,
+    output: String,
+    /// Whether its output shows it.
+    active: bool,
+}
+
+/// Workspaces of the outputs; every output shows one of its workspaces.
+///
+/// Workspaces are created on demand, either named by the config or
+/// numbered when an output needs one.
+#[derive(Debug, Default)]
+pub struct Workspaces {
+    workspaces: Vec<Workspace>,
+}
+
+impl Workspaces {
+    /// Gives outputs without a workspace a numbered one, and moves the
+    /// workspaces of disconnected outputs to the first remaining output.
+    ///
+    /// Workspaces stay where they are while no output is connected.
+    pub fn set_outputs(&mut self, outputs: &[&str]) {
+        let Some(first) = outputs.first() else {
+            return;
+        };
+        for workspace in &mut self.workspaces {
+            if !outputs.contains(&workspace.output.as_str()) {
+                workspace.output = first.to_string();
+                workspace.active = false;
+            }
+        }
+        for output in outputs {
+            if self.active(output).is_none() {
+                self.show_any(output);
+            }
+        }
+    }
+
+    /// The workspace an output shows.
+    pub fn active(&self, output: &str) -> Option<&str> {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.active && workspace.output == output)
+            .map(|workspace| workspace.name.as_str())
+    }
+
+    /// The output a workspace is attached to.
+    pub fn output(&self, name: &str) -> Option<&str> {
+        self.workspace(name)
+            .map(|workspace| workspace.output.as_str())
+    }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.workspace(name)
+            .is_some_and(|workspace| workspace.active)
+    }
+
+    /// Creates the workspace `name` on `output` unless it exists already.
+    pub fn ensure(&mut self, name: &str, output: &str) {
+        if self.workspace(name).is_none() {
+            self.workspaces.push(Workspace {
+                name: name.to_string(),
+                output: output.to_string(),
+                active: false,
+            });
+        }
+    }
+
+    /// Shows the workspace `name` on its output, creating it on `output` if
+    /// it does not exist yet.
+    pub fn activate(&mut self, name: &str, output: &str) {
+        self.ensure(name, output);
+        let Some(output) = self.output(name).map(str::to_string) else {
+            return;
+        };
+        for workspace in &mut self.workspaces {
+            if workspace.output == output {
+                workspace.active = workspace.name == name;
+            }
+        }
+    }
+
+    /// Attaches the workspace `name` to `output` and shows it there. The
+    /// output it leaves shows another of its workspaces instead.
+    ///
+    /// Returns `false` if there is no such workspace.
+    pub fn move_to_output(&mut self, name: &str, output: &str) -> bool {
+        let Some(previous) = self.output(name).map(str::to_string) else {
+            return false;
+        };
+        if let Some(workspace) = self
+            .workspaces
+            .iter_mut()
+            .find(|workspace| workspace.name == name)
+        {
+            workspace.output = output.to_string();
+            workspace.active = false;
+        }
+        self.activate(name, output);
+        if self.active(&previous).is_none() {
+            self.show_any(&previous);
+        }
+        true
+    }
+
+    /// `(output, workspace)` of every shown workspace, sorted by output.
+    pub fn shown(&self) -> Vec<(String, String)> {
+        let mut shown: Vec<_> = self
+            .workspaces
+            .iter()
+            .filter(|workspace| workspace.active)
+            .map(|workspace| (workspace.output.clone(), workspace.name.clone()))
+            .collect();
+        shown.sort();
+        shown
+    }
+
+    /// Shows the first workspace of an output, or a new numbered one.
+    fn show_any(&mut self, output: &str) {
+        let name = self
+            .workspaces
+            .iter()
+            .find(|workspace| workspace.output == output)
+            .map(|workspace| workspace.name.clone())
+            .unwrap_or_else(|| {
+                (1..)
+                    .map(|number: u32| number.to_string())
+                    .find(|name| self.workspace(name).is_none())
+                    .unwrap_or_default()
+            });
+        self.activate(&name, output);
+    }
+
+    fn workspace(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(workspaces: &Workspaces) -> Vec<(&str, &str)> {
+        workspaces
+            .workspaces
+            .iter()
+            .filter(|workspace| workspace.active)
+            .map(|workspace| (workspace.output.as_str(), workspace.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn outputs_show_one_workspace_each() {
+        let mut workspaces = Workspaces::default();
+        workspaces.set_outputs(&["eDP-1", "DP-1"]);
+        assert_eq!(shown(&workspaces), [("eDP-1", "1"), ("DP-1", "2")]);
+
+        workspaces.activate("web", "eDP-1");
+        assert_eq!(shown(&workspaces), [("DP-1", "2"), ("eDP-1", "web")]);
+        workspaces.activate("1", "DP-1");
+        assert_eq!(workspaces.active("eDP-1"), Some("1"));
+        assert!(!workspaces.is_active("web"));
+
+        assert!(workspaces.move_to_output("2", "eDP-1"));
+        assert_eq!(workspaces.active("eDP-1"), Some("2"));
+        assert_eq!(workspaces.active("DP-1"), Some("3"));
+        assert!(!workspaces.move_to_output("mail", "DP-1"));
+
+        workspaces.set_outputs(&["DP-1"]);
+        assert_eq!(workspaces.output("web"), Some("DP-1"));
+        assert_eq!(
+            workspaces.shown(),
+            [(String::from("DP-1"), String::from("3"))]
+        );
+        workspaces.set_outputs(&[]);
+        assert_eq!(workspaces.active("DP-1"), Some("3"));
+    }
+}
@@ -10,9 +10,13 @@ use lumalla_seat::SeatState;
 use lumalla_shared::{Comms, DbusMessage, KeymapMemfd, MainMessage, Mods};
 use mio::{Interest, Registry, Token, event::Source};
 
-use crate::libinput::{InputEvent, KEY_STATE_PRESSED, LibInput, is_modifier_key, update_modifier};
+use crate::libinput::{
+    BUTTON_STATE_PRESSED, KEY_STATE_PRESSED, LibInput, LibinputEvent, is_modifier_key,
+    update_modifier,
+};
 use crate::xkb::Xkb;
 
+pub use libinput::{ScrollSource, ScrollValue};
 pub use xkb::XkbModifiers as KeyboardModifiers;
 
 struct KeyBinding {
@@ -33,6 +37,62 @@ pub enum KeyboardEvent {
     Modifiers(KeyboardModifiers),
 }
 
+/// Pointer updates for the Wayland seat after libinput dispatch.
+#[derive(Debug, Clone, Copy)]
+pub enum PointerEvent {
+    /// Relative motion in accelerated pointer units.
+    Motion { time_msec: u32, dx: f64, dy: f64 },
+    /// Absolute position normalized to `0.0..=1.0` of the output layout.
+    MotionAbsolute { time_msec: u32, x: f64, y: f64 },
+    Button {
+        time_msec: u32,
+        /// Linux/evdev button code (`BTN_LEFT`, ...).
+        button: u32,
+        pressed: bool,
+    },
+    Axis {
+        time_msec: u32,
+        source: ScrollSource,
+        horizontal: Option<ScrollValue>,
+        vertical: Option<ScrollValue>,
+    },
+}
+
+/// Touchscreen updates for the Wayland seat after libinput dispatch.
+#[derive(Debug, Clone, Copy)]
+pub enum TouchEvent {
+    /// New touch point; the position is normalized to `0.0..=1.0` of the output layout.
+    Down {
+        time_msec: u32,
+        /// Seat-wide slot identifying the touch point until it is lifted.
+        id: i32,
+        x: f64,
+        y: f64,
+    },
+    Motion {
+        time_msec: u32,
+        id: i32,
+        x: f64,
+        y: f64,
+    },
+    Up {
+        time_msec: u32,
+        id: i32,
+    },
+    Cancel {
+        id: i32,
+    },
+    /// End of a set of touch events that belong together.
+    Frame,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputEvent {
+    Keyboard(KeyboardEvent),
+    Pointer(PointerEvent),
+    Touch(TouchEvent),
+}
+
 pub struct InputState {
     comms: Comms,
     libinput: LibInput,
@@ -92,27 +152,71 @@ impl InputState {
         self.keymaps.clear();
     }
 
-    pub fn dispatch(
-        &mut self,
-        mut on_keyboard_event: impl FnMut(KeyboardEvent),
-    ) -> anyhow::Result<()> {
+    pub fn dispatch(&mut self, mut on_input_event: impl FnMut(InputEvent)) -> anyhow::Result<()> {
         self.libinput.dispatch()?;
         while let Some(event) = self.libinput.next_event() {
-            match event {
-                InputEvent::KeyboardKey { key, state } => {
-                    self.handle_key(key, state, &mut on_keyboard_event);
+            let time_msec = self.time_msec();
+            let input_event = match event {
+                LibinputEvent::KeyboardKey { key, state } => {
+                    self.handle_key(key, state, &mut on_input_event);
+                    continue;
                 }
-            }
+                LibinputEvent::PointerMotion { dx, dy } => {
+                    InputEvent::Pointer(PointerEvent::Motion { time_msec, dx, dy })
+                }
+                LibinputEvent::PointerMotionAbsolute { x, y } => {
+                    InputEvent::Pointer(PointerEvent::MotionAbsolute { time_msec, x, y })
+                }
+                LibinputEvent::PointerButton { button, state } => {
+                    InputEvent::Pointer(PointerEvent::Button {
+                        time_msec,
+                        button,
+                        pressed: state == BUTTON_STATE_PRESSED,
+                    })
+                }
+                LibinputEvent::PointerScroll {
+                    source,
+                    horizontal,
+                    vertical,
+                } => InputEvent::Pointer(PointerEvent::Axis {
+                    time_msec,
+                    source,
+                    horizontal,
+                    vertical,
+                }),
+                LibinputEvent::TouchDown { slot, x, y } => InputEvent::Touch(TouchEvent::Down {
+                    time_msec,
+                    id: slot,
+                    x,
+                    y,
+                }),
+                LibinputEvent::TouchMotion { slot, x, y } => {
+                    InputEvent::Touch(TouchEvent::Motion {
+                        time_msec,
+                        id: slot,
+                        x,
+                        y,
+                    })
+                }
+                LibinputEvent::TouchUp { slot } => InputEvent::Touch(TouchEvent::Up {
+                    time_msec,
+                    id: slot,
+                }),
+                LibinputEvent::TouchCancel { slot } => {
+                    InputEvent::Touch(TouchEvent::Cancel { id: slot })
+                }
+                LibinputEvent::TouchFrame => InputEvent::Touch(TouchEvent::Frame),
+            };
+            on_input_event(input_event);
         }
         Ok(())
     }
 
-    fn handle_key(
-        &mut self,
-        key: u32,
-        state: u32,
-        on_keyboard_event: &mut impl FnMut(KeyboardEvent),
-    ) {
+    fn time_msec(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn handle_key(&mut self, key: u32, state: u32, on_input_event: &mut impl FnMut(InputEvent)) {
         let pressed = state == KEY_STATE_PRESSED;
         if pressed {
             // Hardcoded: Ctrl+Alt+F1..F12 switches VT; bare F1 exits.
@@ -136,16 +240,15 @@ impl InputState {
             }
         }
 
-        let time_msec = self.start.elapsed().as_millis() as u32;
-        on_keyboard_event(KeyboardEvent::Key {
-            time_msec,
+        on_input_event(InputEvent::Keyboard(KeyboardEvent::Key {
+            time_msec: self.time_msec(),
             key,
             pressed,
-        });
+        }));
         if mods_changed {
             let modifiers = self.xkb.modifiers();
             debug!("xkb modifiers: {modifiers:?}");
-            on_keyboard_event(KeyboardEvent::Modifiers(modifiers));
+            on_input_event(InputEvent::Keyboard(KeyboardEvent::Modifiers(modifiers)));
         }
 
         if is_modifier_key(key) {
@@ -39,6 +39,19 @@ pub(crate) mod bindings {
     pub const LIBINPUT_EVENT_POINTER_BUTTON: u32 = 402;
     pub const LIBINPUT_EVENT_POINTER_AXIS: u32 = 403; // Event is deprecated and should be ignored
     pub const LIBINPUT_EVENT_POINTER_SCROLL_WHEEL: u32 = 404;
+    pub const LIBINPUT_EVENT_POINTER_SCROLL_FINGER: u32 = 405;
+    pub const LIBINPUT_EVENT_POINTER_SCROLL_CONTINUOUS: u32 = 406;
+    pub const LIBINPUT_EVENT_TOUCH_DOWN: u32 = 500;
+    pub const LIBINPUT_EVENT_TOUCH_UP: u32 = 501;
+    pub const LIBINPUT_EVENT_TOUCH_MOTION: u32 = 502;
+    pub const LIBINPUT_EVENT_TOUCH_CANCEL: u32 = 503;
+    pub const LIBINPUT_EVENT_TOUCH_FRAME: u32 = 504;
+
+    pub const LIBINPUT_BUTTON_STATE_RELEASED: u32 = 0;
+    pub const LIBINPUT_BUTTON_STATE_PRESSED: u32 = 1;
+
+    pub const LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL: u32 = 0;
+    pub const LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL: u32 = 1;
 
     pub const LIBINPUT_KEY_STATE_RELEASED: u32 = 0;
     pub const LIBINPUT_KEY_STATE_PRESSED: u32 = 1;
@@ -70,6 +83,16 @@ pub(crate) mod bindings {
         _private: [u8; 0],
     }
 
+    #[repr(C)]
+    pub struct libinput_event_pointer {
+        _private: [u8; 0],
+    }
+
+    #[repr(C)]
+    pub struct libinput_event_touch {
+        _private: [u8; 0],
+    }
+
     #[repr(C)]
     pub struct libinput_interface {
         pub open_restricted:
@@ -97,6 +120,45 @@ pub(crate) mod bindings {
         ) -> *mut libinput_event_keyboard;
         pub fn libinput_event_keyboard_get_key(event: *mut libinput_event_keyboard) -> u32;
         pub fn libinput_event_keyboard_get_key_state(event: *mut libinput_event_keyboard) -> u32;
+        pub fn libinput_event_get_pointer_event(
+            event: *mut libinput_event,
+        ) -> *mut libinput_event_pointer;
+        pub fn libinput_event_pointer_get_dx(event: *mut libinput_event_pointer) -> f64;
+        pub fn libinput_event_pointer_get_dy(event: *mut libinput_event_pointer) -> f64;
+        pub fn libinput_event_pointer_get_absolute_x_transformed(
+            event: *mut libinput_event_pointer,
+            width: u32,
+        ) -> f64;
+        pub fn libinput_event_pointer_get_absolute_y_transformed(
+            event: *mut libinput_event_pointer,
+            height: u32,
+        ) -> f64;
+        pub fn libinput_event_pointer_get_button(event: *mut libinput_event_pointer) -> u32;
+        pub fn libinput_event_pointer_get_button_state(event: *mut libinput_event_pointer) -> u32;
+        pub fn libinput_event_pointer_has_axis(
+            event: *mut libinput_event_pointer,
+            axis: u32,
+        ) -> c_int;
+        pub fn libinput_event_pointer_get_scroll_value(
+            event: *mut libinput_event_pointer,
+            axis: u32,
+        ) -> f64;
+        pub fn libinput_event_pointer_get_scroll_value_v120(
+            event: *mut libinput_event_pointer,
+            axis: u32,
+        ) -> f64;
+        pub fn libinput_event_get_touch_event(
+            event: *mut libinput_event,
+        ) -> *mut libinput_event_touch;
+        pub fn libinput_event_touch_get_seat_slot(event: *mut libinput_event_touch) -> i32;
+        pub fn libinput_event_touch_get_x_transformed(
+            event: *mut libinput_event_touch,
+            width: u32,
+        ) -> f64;
+        pub fn libinput_event_touch_get_y_transformed(
+            event: *mut libinput_event_touch,
+            height: u32,
+        ) -> f64;
     }
 }
 
@@ -295,8 +357,64 @@ impl Source for LibInput {
 pub(crate) const KEY_STATE_PRESSED: u32 = bindings::LIBINPUT_KEY_STATE_PRESSED;
 pub(crate) const KEY_STATE_RELEASED: u32 = bindings::LIBINPUT_KEY_STATE_RELEASED;
 
-pub(crate) enum InputEvent {
-    KeyboardKey { key: u32, state: u32 },
+pub(crate) const BUTTON_STATE_PRESSED: u32 = bindings::LIBINPUT_BUTTON_STATE_PRESSED;
+
+pub(crate) enum LibinputEvent {
+    KeyboardKey {
+        key: u32,
+        state: u32,
+    },
+    PointerMotion {
+        dx: f64,
+        dy: f64,
+    },
+    /// Absolute position normalized to `0.0..=1.0` on both axes.
+    PointerMotionAbsolute {
+        x: f64,
+        y: f64,
+    },
+    PointerButton {
+        button: u32,
+        state: u32,
+    },
+    PointerScroll {
+        source: ScrollSource,
+        horizontal: Option<ScrollValue>,
+        vertical: Option<ScrollValue>,
+    },
+    /// Touch positions are normalized to `0.0..=1.0` on both axes.
+    TouchDown {
+        slot: i32,
+        x: f64,
+        y: f64,
+    },
+    TouchMotion {
+        slot: i32,
+        x: f64,
+        y: f64,
+    },
+    TouchUp {
+        slot: i32,
+    },
+    TouchCancel {
+        slot: i32,
+    },
+    TouchFrame,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollSource {
+    Wheel,
+    Finger,
+    Continuous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScrollValue {
+    /// Scroll distance in pointer motion units.
+    pub value: f64,
+    /// High-resolution wheel value where 120 is one detent; wheel scrolling only.
+    pub value120: Option<f64>,
 }
 
 pub(crate) fn is_modifier_key(key: u32) -> bool {
@@ -329,7 +447,7 @@ pub(crate) fn update_modifier(key: u32, pressed: bool, mods: &mut lumalla_shared
 }
 
 impl LibInput {
-    pub(crate) fn next_event(&self) -> Option<InputEvent> {
+    pub(crate) fn next_event(&self) -> Option<LibinputEvent> {
         loop {
             let event = unsafe { bindings::libinput_get_event(self.libinput.as_ptr()) };
             if event.is_null() {
@@ -360,9 +478,70 @@ impl LibInput {
                         let state = unsafe {
                             bindings::libinput_event_keyboard_get_key_state(keyboard_event)
                         };
-                        Some(InputEvent::KeyboardKey { key, state })
+                        Some(LibinputEvent::KeyboardKey { key, state })
+                    }
+                }
+                bindings::LIBINPUT_EVENT_POINTER_MOTION => {
+                    pointer_event(event).map(|pointer| LibinputEvent::PointerMotion {
+                        dx: unsafe { bindings::libinput_event_pointer_get_dx(pointer) },
+                        dy: unsafe { bindings::libinput_event_pointer_get_dy(pointer) },
+                    })
+                }
+                bindings::LIBINPUT_EVENT_POINTER_MOTION_ABSOLUTE => {
+                    pointer_event(event).map(|pointer| LibinputEvent::PointerMotionAbsolute {
+                        x: unsafe {
+                            bindings::libinput_event_pointer_get_absolute_x_transformed(pointer, 1)
+                        },
+                        y: unsafe {
+                            bindings::libinput_event_pointer_get_absolute_y_transformed(pointer, 1)
+                        },
+                    })
+                }
+                bindings::LIBINPUT_EVENT_POINTER_BUTTON => {
+                    pointer_event(event).map(|pointer| LibinputEvent::PointerButton {
+                        button: unsafe { bindings::libinput_event_pointer_get_button(pointer) },
+                        state: unsafe {
+                            bindings::libinput_event_pointer_get_button_state(pointer)
+                        },
+                    })
+                }
+                // The legacy POINTER_AXIS event duplicates the scroll events below.
+                bindings::LIBINPUT_EVENT_POINTER_AXIS => None,
+                bindings::LIBINPUT_EVENT_POINTER_SCROLL_WHEEL => {
+                    pointer_event(event).map(|pointer| scroll_event(pointer, ScrollSource::Wheel))
+                }
+                bindings::LIBINPUT_EVENT_POINTER_SCROLL_FINGER => {
+                    pointer_event(event).map(|pointer| scroll_event(pointer, ScrollSource::Finger))
+                }
+                bindings::LIBINPUT_EVENT_POINTER_SCROLL_CONTINUOUS => pointer_event(event)
+                    .map(|pointer| scroll_event(pointer, ScrollSource::Continuous)),
+                bindings::LIBINPUT_EVENT_TOUCH_DOWN => touch_event(event).map(|touch| {
+                    let (x, y) = touch_position(touch);
+                    LibinputEvent::TouchDown {
+                        slot: touch_slot(touch),
+                        x,
+                        y,
+                    }
+                }),
+                bindings::LIBINPUT_EVENT_TOUCH_MOTION => touch_event(event).map(|touch| {
+                    let (x, y) = touch_position(touch);
+                    LibinputEvent::TouchMotion {
+                        slot: touch_slot(touch),
+                        x,
+                        y,
                     }
+                }),
+                bindings::LIBINPUT_EVENT_TOUCH_UP => {
+                    touch_event(event).map(|touch| LibinputEvent::TouchUp {
+                        slot: touch_slot(touch),
+                    })
                 }
+                bindings::LIBINPUT_EVENT_TOUCH_CANCEL => {
+                    touch_event(event).map(|touch| LibinputEvent::TouchCancel {
+                        slot: touch_slot(touch),
+                    })
+                }
+                bindings::LIBINPUT_EVENT_TOUCH_FRAME => Some(LibinputEvent::TouchFrame),
                 event_type => {
                     debug!("Unhandled libinput event type: {event_type}");
                     None
@@ -375,3 +554,52 @@ impl LibInput {
         }
     }
 }
+
+fn pointer_event(
+    event: *mut bindings::libinput_event,
+) -> Option<*mut bindings::libinput_event_pointer> {
+    let pointer = unsafe { bindings::libinput_event_get_pointer_event(event) };
+    (!pointer.is_null()).then_some(pointer)
+}
+
+fn scroll_event(
+    pointer: *mut bindings::libinput_event_pointer,
+    source: ScrollSource,
+) -> LibinputEvent {
+    let axis_value = |axis| {
+        if unsafe { bindings::libinput_event_pointer_has_axis(pointer, axis) } == 0 {
+            return None;
+        }
+        let value = unsafe { bindings::libinput_event_pointer_get_scroll_value(pointer, axis) };
+        let value120 = (source == ScrollSource::Wheel).then(|| unsafe {
+            bindings::libinput_event_pointer_get_scroll_value_v120(pointer, axis)
+        });
+        Some(ScrollValue { value, value120 })
+    };
+    LibinputEvent::PointerScroll {
+        source,
+        horizontal: axis_value(bindings::LIBINPUT_POINTER_AXIS_SCROLL_HORIZONTAL),
+        vertical: axis_value(bindings::LIBINPUT_POINTER_AXIS_SCROLL_VERTICAL),
+    }
+}
+
+fn touch_event(
+    event: *mut bindings::libinput_event,
+) -> Option<*mut bindings::libinput_event_touch> {
+    let touch = unsafe { bindings::libinput_event_get_touch_event(event) };
+    (!touch.is_null()).then_some(touch)
+}
+
+/// Seat-wide slot of the touch point, unique across all touch devices.
+fn touch_slot(touch: *mut bindings::libinput_event_touch) -> i32 {
+    unsafe { bindings::libinput_event_touch_get_seat_slot(touch) }
+}
+
+fn touch_position(touch: *mut bindings::libinput_event_touch) -> (f64, f64) {
+    unsafe {
+        (
+            bindings::libinput_event_touch_get_x_transformed(touch, 1),
+            bindings::libinput_event_touch_get_y_transformed(touch, 1),
+        )
+    }
+}
@@ -2,7 +2,9 @@
 
 use std::collections::HashMap;
 
-use lumalla_shared::{DrmConnector, DrmDeviceState, DrmMode, Mods, Output, WindowRule, Zone};
+use lumalla_shared::{
+    DrmConnector, DrmDeviceState, DrmMode, LayoutOutput, Mods, Output, Transform, WindowRule, Zone,
+};
 use serde::{Deserialize, Serialize};
 use zbus::zvariant::Type;
 
@@ -102,6 +104,10 @@ pub struct OutputConfigInfo {
     pub enabled: bool,
     /// Kernel mode name; empty string means preferred/first mode.
     pub mode_name: String,
+    /// Scale in percent; zero means unscaled.
+    pub scale: u32,
+    /// `wl_output.transform` value the output is shown with.
+    pub transform: i32,
 }
 
 impl From<&lumalla_shared::OutputConfig> for OutputConfigInfo {
@@ -110,6 +116,8 @@ impl From<&lumalla_shared::OutputConfig> for OutputConfigInfo {
             name: config.name.clone(),
             enabled: config.enabled,
             mode_name: config.mode_name.clone().unwrap_or_default(),
+            scale: config.scale.unwrap_or_default(),
+            transform: config.transform.to_wayland(),
         }
     }
 }
@@ -124,6 +132,8 @@ impl From<OutputConfigInfo> for lumalla_shared::OutputConfig {
             } else {
                 Some(info.mode_name)
             },
+            scale: (info.scale != 0).then_some(info.scale),
+            transform: Transform::from_wayland(info.transform).unwrap_or_default(),
         }
     }
 }
@@ -143,6 +153,10 @@ pub struct OutputInfo {
     pub width: i32,
     /// Height in pixels.
     pub height: i32,
+    /// Scale in percent.
+    pub scale: u32,
+    /// `wl_output.transform` value the output is shown with.
+    pub transform: i32,
 }
 
 impl From<&Output> for OutputInfo {
@@ -154,6 +168,8 @@ impl From<&Output> for OutputInfo {
             y: output.location.1,
             width: output.size.0,
             height: output.size.1,
+            scale: output.scale,
+            transform: output.transform.to_wayland(),
         }
     }
 }
@@ -171,6 +187,8 @@ impl From<&OutputInfo> for Output {
             description: info.description.clone(),
             location: (info.x, info.y),
             size: (info.width, info.height),
+            scale: info.scale,
+            transform: Transform::from_wayland(info.transform).unwrap_or_default(),
         }
     }
 }
@@ -253,20 +271,67 @@ impl From<ZoneInfo> for Zone {
     }
 }
 
-/// Window placement rule.
+/// Window rule exposed over D-Bus.
+///
+/// Empty strings and zero values leave a condition or effect unset.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
 pub struct WindowRuleInfo {
     /// Application id to match.
     pub app_id: String,
+    /// Regular expression the title must match.
+    pub title: String,
+    /// Positive to match only windows with a parent, negative to match only
+    /// windows without one.
+    pub transient: i32,
     /// Target zone name.
     pub zone: String,
+    /// Target output name.
+    pub output: String,
+    /// Target workspace name.
+    pub workspace: String,
+    /// Positive to float the window, negative to tile it.
+    pub floating: i32,
+    /// Positive to show the window fullscreen, negative to keep it windowed.
+    pub fullscreen: i32,
+    /// Initial width; used together with `height`.
+    pub width: i32,
+    /// Initial height; used together with `width`.
+    pub height: i32,
+    /// Opacity in percent.
+    pub opacity: u32,
+}
+
+fn flag(value: Option<bool>) -> i32 {
+    match value {
+        Some(true) => 1,
+        Some(false) => -1,
+        None => 0,
+    }
+}
+
+fn from_flag(value: i32) -> Option<bool> {
+    (value != 0).then_some(value > 0)
+}
+
+fn from_name(name: String) -> Option<String> {
+    (!name.is_empty()).then_some(name)
 }
 
 impl From<WindowRule> for WindowRuleInfo {
     fn from(rule: WindowRule) -> Self {
+        let (width, height) = rule.size.unwrap_or_default();
         Self {
-            app_id: rule.app_id,
-            zone: rule.zone,
+            app_id: rule.app_id.unwrap_or_default(),
+            title: rule.title.unwrap_or_default(),
+            transient: flag(rule.transient),
+            zone: rule.zone.unwrap_or_default(),
+            output: rule.output.unwrap_or_default(),
+            workspace: rule.workspace.unwrap_or_default(),
+            floating: flag(rule.floating),
+            fullscreen: flag(rule.fullscreen),
+            width,
+            height,
+            opacity: rule.opacity.unwrap_or_default(),
         }
     }
 }
@@ -274,8 +339,16 @@ impl From<WindowRule> for WindowRuleInfo {
 impl From<WindowRuleInfo> for WindowRule {
     fn from(rule: WindowRuleInfo) -> Self {
         Self {
-            app_id: rule.app_id,
-            zone: rule.zone,
+            app_id: from_name(rule.app_id),
+            title: from_name(rule.title),
+            transient: from_flag(rule.transient),
+            zone: from_name(rule.zone),
+            output: from_name(rule.output),
+            workspace: from_name(rule.workspace),
+            floating: from_flag(rule.floating),
+            fullscreen: from_flag(rule.fullscreen),
+            size: (rule.width > 0 && rule.height > 0).then_some((rule.width, rule.height)),
+            opacity: (rule.opacity != 0).then_some(rule.opacity),
         }
     }
 }
@@ -291,6 +364,15 @@ pub struct LayoutOutputInfo {
     pub y: i32,
 }
 
+impl From<LayoutOutputInfo> for LayoutOutput {
+    fn from(info: LayoutOutputInfo) -> Self {
+        Self {
+            name: info.name,
+            location: (info.x, info.y),
+        }
+    }
+}
+
 /// A registered key binding.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
 pub struct KeyBindingInfo {
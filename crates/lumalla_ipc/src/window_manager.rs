@@ -1,5 +1,7 @@
 //! Window manager D-Bus interface and client proxy.
 
+use std::collections::HashMap;
+
 use zbus::{interface, object_server::SignalEmitter};
 
 use crate::types::{
@@ -32,15 +34,44 @@ pub trait WindowManagerHandler: Send + Sync {
     /// Replace workspace layout.
     fn set_layout(&mut self, spaces: LayoutSpacesInfo) -> zbus::fdo::Result<()>;
 
-    /// Add a window placement rule.
-    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<()>;
+    /// Add a window rule; returns its id.
+    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<u32>;
+
+    /// Return the window rules by id, in the order they apply.
+    fn get_window_rules(&self) -> zbus::fdo::Result<Vec<(u32, WindowRuleInfo)>>;
+
+    /// Remove the window rule with the given id.
+    fn remove_window_rule(&mut self, id: u32) -> zbus::fdo::Result<()>;
 
     /// Close the focused window.
     fn close_current_window(&mut self) -> zbus::fdo::Result<()>;
 
+    /// Kill the client of a window that did not close within `timeout_ms`
+    /// after `close_current_window`; `0` never kills clients.
+    fn set_kill_timeout(&mut self, timeout_ms: u32) -> zbus::fdo::Result<()>;
+
     /// Move the focused window to a zone.
     fn move_current_window_to_zone(&mut self, zone: &str) -> zbus::fdo::Result<()>;
 
+    /// Set how pointer input moves keyboard focus (`click` or `follows-mouse`).
+    fn set_focus_mode(&mut self, mode: &str) -> zbus::fdo::Result<()>;
+
+    /// Focus the window `index` places down the focus stack.
+    fn tab(&mut self, index: u32) -> zbus::fdo::Result<()>;
+
+    /// Show a workspace, creating it on the output under the pointer.
+    fn switch_workspace(&mut self, name: &str) -> zbus::fdo::Result<()>;
+
+    /// Move the focused window to a workspace.
+    fn move_current_window_to_workspace(&mut self, name: &str) -> zbus::fdo::Result<()>;
+
+    /// Attach a workspace to an output and show it there.
+    fn move_workspace_to_output(&mut self, workspace: &str, output: &str)
+    -> zbus::fdo::Result<()>;
+
+    /// Return the workspace every output shows, by output name.
+    fn get_active_workspaces(&self) -> zbus::fdo::Result<HashMap<String, String>>;
+
     /// Spawn a child process.
     fn spawn(&mut self, command: &str, args: Vec<String>) -> zbus::fdo::Result<()>;
 
@@ -95,6 +126,10 @@ pub mod signals {
     pub const DRM_DEVICES_CHANGED: &str = "DrmDevicesChanged";
     /// A configured key binding was activated.
     pub const BINDING_ACTIVATED: &str = "BindingActivated";
+    /// The outputs were placed with another layout space.
+    pub const LAYOUT_SPACE_ACTIVATED: &str = "LayoutSpaceActivated";
+    /// An output shows another workspace.
+    pub const WORKSPACE_ACTIVATED: &str = "WorkspaceActivated";
 }
 
 #[interface(
@@ -135,18 +170,58 @@ impl WindowManager {
         self.handler.set_layout(spaces)
     }
 
-    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<()> {
+    fn add_window_rule(&mut self, rule: WindowRuleInfo) -> zbus::fdo::Result<u32> {
         self.handler.add_window_rule(rule)
     }
 
+    fn get_window_rules(&self) -> zbus::fdo::Result<Vec<(u32, WindowRuleInfo)>> {
+        self.handler.get_window_rules()
+    }
+
+    fn remove_window_rule(&mut self, id: u32) -> zbus::fdo::Result<()> {
+        self.handler.remove_window_rule(id)
+    }
+
     fn close_current_window(&mut self) -> zbus::fdo::Result<()> {
         self.handler.close_current_window()
     }
 
+    fn set_kill_timeout(&mut self, timeout_ms: u32) -> zbus::fdo::Result<()> {
+        self.handler.set_kill_timeout(timeout_ms)
+    }
+
     fn move_current_window_to_zone(&mut self, zone: &str) -> zbus::fdo::Result<()> {
         self.handler.move_current_window_to_zone(zone)
     }
 
+    fn set_focus_mode(&mut self, mode: &str) -> zbus::fdo::Result<()> {
+        self.handler.set_focus_mode(mode)
+    }
+
+    fn tab(&mut self, index: u32) -> zbus::fdo::Result<()> {
+        self.handler.tab(index)
+    }
+
+    fn switch_workspace(&mut self, name: &str) -> zbus::fdo::Result<()> {
+        self.handler.switch_workspace(name)
+    }
+
+    fn move_current_window_to_workspace(&mut self, name: &str) -> zbus::fdo::Result<()> {
+        self.handler.move_current_window_to_workspace(name)
+    }
+
+    fn move_workspace_to_output(
+        &mut self,
+        workspace: &str,
+        output: &str,
+    ) -> zbus::fdo::Result<()> {
+        self.handler.move_workspace_to_output(workspace, output)
+    }
+
+    fn get_active_workspaces(&self) -> zbus::fdo::Result<HashMap<String, String>> {
+        self.handler.get_active_workspaces()
+    }
+
     fn spawn(&mut self, command: &str, args: Vec<String>) -> zbus::fdo::Result<()> {
         self.handler.spawn(command, args)
     }
@@ -204,4 +279,17 @@ impl WindowManager {
         emitter: &SignalEmitter<'_>,
         binding_id: &str,
     ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn layout_space_activated(
+        emitter: &SignalEmitter<'_>,
+        name: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn workspace_activated(
+        emitter: &SignalEmitter<'_>,
+        output: &str,
+        workspace: &str,
+    ) -> zbus::Result<()>;
 }
@@ -0,0 +1,107 @@
+//! The image shown at the pointer: a client's cursor surface or the default
+//! image of the xcursor theme.
+
+use anyhow::Context;
+use lumalla_shared::{Transform, Viewport};
+use xcursor::{
+    CursorTheme,
+    parser::{Image, parse_xcursor},
+};
+
+use crate::{SurfaceFrame, WL_SHM_FORMAT_ARGB8888};
+
+/// Nominal size of the default cursor when `XCURSOR_SIZE` is not set.
+const DEFAULT_CURSOR_SIZE: u32 = 24;
+/// Icon names of the default cursor, most specific first.
+const DEFAULT_CURSOR_NAMES: [&str; 2] = ["default", "left_ptr"];
+
+/// What the pointer looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cursor {
+    /// The default image of the xcursor theme.
+    Default,
+    Hidden,
+    /// A client's cursor surface, with the hotspot relative to its top-left corner.
+    Surface {
+        owner_id: u32,
+        surface_id: u32,
+        hotspot: (i32, i32),
+    },
+}
+
+/// Loads the default cursor of the theme named by `XCURSOR_THEME` at the
+/// size in `XCURSOR_SIZE`, with its hotspot.
+pub(crate) fn load_default_cursor() -> anyhow::Result<(SurfaceFrame, (i32, i32))> {
+    let theme_name = std::env::var("XCURSOR_THEME").unwrap_or_else(|_| String::from("default"));
+    let size = std::env::var("XCURSOR_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_CURSOR_SIZE);
+    let theme = CursorTheme::load(&theme_name);
+    let path = DEFAULT_CURSOR_NAMES
+        .iter()
+        .find_map(|name| theme.load_icon(name))
+        .with_context(|| format!("Cursor theme {theme_name} has no default cursor"))?;
+    let content = std::fs::read(&path)
+        .with_context(|| format!("Failed to read cursor {}", path.display()))?;
+    let images = parse_xcursor(&content)
+        .with_context(|| format!("Invalid xcursor file {}", path.display()))?;
+    let image = closest_image(images, size).context("Cursor file has no images")?;
+
+    let frame = SurfaceFrame {
+        // Not owned by any client.
+        owner_id: 0,
+        surface_id: 0,
+        root_id: 0,
+        // The file stores premultiplied little-endian ARGB, like wl_shm.
+        pixels: image.pixels_rgba,
+        width: image.width as usize,
+        height: image.height as usize,
+        stride: image.width as usize * 4,
+        format: WL_SHM_FORMAT_ARGB8888,
+        damage: Vec::new(),
+        scale: 1,
+        transform: Transform::Normal,
+        viewport: Viewport::default(),
+    };
+    Ok((frame, (image.xhot as i32, image.yhot as i32)))
+}
+
+/// The image whose nominal size is closest to `size`; the first frame of
+/// animated cursors.
+fn closest_image(images: Vec<Image>, size: u32) -> Option<Image> {
+    images
+        .into_iter()
+        .min_by_key(|image| image.size.abs_diff(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: u32, delay: u32) -> Image {
+        Image {
+            size,
+            width: size,
+            height: size,
+            xhot: 0,
+            yhot: 0,
+            delay,
+            pixels_rgba: Vec::new(),
+            pixels_argb: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn picks_first_frame_of_closest_size() {
+        let images = vec![image(16, 1), image(32, 1), image(32, 2), image(48, 1)];
+        let picked = closest_image(images, 24).unwrap();
+        assert_eq!((picked.size, picked.delay), (16, 1));
+
+        let images = vec![image(16, 1), image(32, 1), image(32, 2), image(48, 1)];
+        let picked = closest_image(images, 30).unwrap();
+        assert_eq!((picked.size, picked.delay), (32, 1));
+
+        assert!(closest_image(Vec::new(), 24).is_none());
+    }
+}
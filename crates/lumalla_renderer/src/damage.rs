@@ -0,0 +1,309 @@
+//! Regions that changed and have to be uploaded or drawn again.
+
+use std::collections::VecDeque;
+
+use lumalla_shared::Transform;
+
+/// Rectangles kept per damage before they are merged into their bounding box.
+const MAX_DAMAGE_RECTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    /// A rectangle at the origin.
+    pub(crate) fn from_size((width, height): (u32, u32)) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: width as i32,
+            height: height as i32,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub(crate) fn translate(self, dx: i32, dy: i32) -> Self {
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..self
+        }
+    }
+
+    pub(crate) fn scale(self, factor: i32) -> Self {
+        Self {
+            x: self.x * factor,
+            y: self.y * factor,
+            width: self.width * factor,
+            height: self.height * factor,
+        }
+    }
+
+    /// The pixels a rectangle of the layout covers on an output showing it
+    /// at `scale` percent.
+    pub(crate) fn to_device(self, scale: u32) -> Self {
+        let scale = scale as i32;
+        let left = (self.x * scale).div_euclid(100);
+        let top = (self.y * scale).div_euclid(100);
+        Self {
+            x: left,
+            y: top,
+            width: ((self.x + self.width) * scale + 99).div_euclid(100) - left,
+            height: ((self.y + self.height) * scale + 99).div_euclid(100) - top,
+        }
+    }
+
+    /// Where the rectangle lands once an image of `size` containing it is
+    /// transformed.
+    pub(crate) fn transform(self, transform: Transform, (width, height): (u32, u32)) -> Self {
+        let (x, y, width, height) = transform.apply_rect(
+            (self.x, self.y, self.width, self.height),
+            (width as i32, height as i32),
+        );
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// `None` if the rectangles do not overlap.
+    pub(crate) fn intersection(self, other: Self) -> Option<Self> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        let rect = Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        };
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    pub(crate) fn bounding_box(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+/// Parts of a window or output that changed since some earlier point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Damage {
+    /// Everything changed.
+    Full,
+    Rects(Vec<Rect>),
+}
+
+impl Default for Damage {
+    fn default() -> Self {
+        Self::Rects(Vec::new())
+    }
+}
+
+impl Damage {
+    pub fn add(&mut self, rect: Rect) {
+        let Self::Rects(rects) = self else {
+            return;
+        };
+        if rect.is_empty() {
+            return;
+        }
+        rects.push(rect);
+        if rects.len() > MAX_DAMAGE_RECTS {
+            let bounds = rects.iter().copied().reduce(Rect::bounding_box);
+            *rects = bounds.into_iter().collect();
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        match other {
+            Self::Full => *self = Self::Full,
+            Self::Rects(rects) => {
+                for &rect in rects {
+                    self.add(rect);
+                }
+            }
+        }
+    }
+
+    /// The damage on an output showing the layout at `scale` percent.
+    pub fn to_device(&self, scale: u32) -> Self {
+        match self {
+            Self::Full => Self::Full,
+            Self::Rects(rects) => {
+                Self::Rects(rects.iter().map(|rect| rect.to_device(scale)).collect())
+            }
+        }
+    }
+
+    /// The damage once an image of `size` is transformed.
+    pub fn transformed(&self, transform: Transform, size: (u32, u32)) -> Self {
+        match self {
+            Self::Full => Self::Full,
+            Self::Rects(rects) => Self::Rects(
+                rects
+                    .iter()
+                    .map(|rect| rect.transform(transform, size))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The damaged parts of `bounds`; full damage covers all of it.
+    pub fn clipped(&self, bounds: Rect) -> Vec<Rect> {
+        match self {
+            Self::Full => (!bounds.is_empty()).then_some(bounds).into_iter().collect(),
+            Self::Rects(rects) => rects
+                .iter()
+                .filter_map(|rect| rect.intersection(bounds))
+                .collect(),
+        }
+    }
+}
+
+/// Damage of the frames drawn into a ring of buffers, to repaint a buffer
+/// by what changed since it was last drawn into (its age).
+#[derive(Debug)]
+pub(crate) struct DamageHistory {
+    /// Frames drawn so far.
+    frame: u64,
+    /// Frame each buffer was last drawn for; `None` for undrawn buffers.
+    drawn: Vec<Option<u64>>,
+    /// Damage of the latest frames, newest first.
+    frames: VecDeque<Damage>,
+}
+
+impl DamageHistory {
+    pub fn new(buffers: usize) -> Self {
+        Self {
+            frame: 0,
+            drawn: vec![None; buffers],
+            frames: VecDeque::with_capacity(buffers),
+        }
+    }
+
+    /// Records the damage of a new frame drawn into `buffer` and returns
+    /// what has to be repainted in it.
+    pub fn draw(&mut self, buffer: usize, damage: Damage) -> Damage {
+        self.frame += 1;
+        self.frames.push_front(damage);
+        self.frames.truncate(self.drawn.len());
+        let age = self.drawn[buffer].map(|drawn| self.frame - drawn);
+        self.drawn[buffer] = Some(self.frame);
+        match age {
+            Some(age) if age as usize <= self.frames.len() => {
+                let mut damage = Damage::default();
+                for frame in self.frames.iter().take(age as usize) {
+                    damage.merge(frame);
+                }
+                damage
+            }
+            _ => Damage::Full,
+        }
+    }
+
+    /// Forgets the contents of every buffer, so they are repainted entirely.
+    pub fn reset(&mut self) {
+        self.drawn.fill(None);
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn clips_damage_to_bounds() {
+        let mut damage = Damage::default();
+        damage.add(rect(-5, -5, 10, 10));
+        damage.add(rect(8, 8, 0, 4));
+        damage.add(rect(20, 20, 5, 5));
+        assert_eq!(damage.clipped(rect(0, 0, 10, 10)), [rect(0, 0, 5, 5)]);
+        assert_eq!(
+            Damage::Full.clipped(rect(0, 0, 10, 10)),
+            [rect(0, 0, 10, 10)]
+        );
+    }
+
+    #[test]
+    fn scales_damage_to_every_covered_pixel() {
+        let damage = Damage::Rects(vec![rect(1, 1, 2, 2), rect(10, 0, 4, 4)]);
+        assert_eq!(
+            damage.to_device(150),
+            Damage::Rects(vec![rect(1, 1, 4, 4), rect(15, 0, 6, 6)])
+        );
+        assert_eq!(Damage::Full.to_device(125), Damage::Full);
+    }
+
+    #[test]
+    fn rotates_damage_with_the_output() {
+        // The top-right corner of a 20x10 image ends up at the top left.
+        let damage = Damage::Rects(vec![rect(18, 0, 2, 3)]);
+        assert_eq!(
+            damage.transformed(Transform::Rotate90, (20, 10)),
+            Damage::Rects(vec![rect(0, 0, 3, 2)])
+        );
+        assert_eq!(
+            Damage::Full.transformed(Transform::Flipped, (20, 10)),
+            Damage::Full
+        );
+    }
+
+    #[test]
+    fn merges_excess_rects_into_their_bounds() {
+        let mut damage = Damage::default();
+        for index in 0..=MAX_DAMAGE_RECTS as i32 {
+            damage.add(rect(index * 10, index, 1, 1));
+        }
+        assert_eq!(
+            damage,
+            Damage::Rects(vec![rect(0, 0, MAX_DAMAGE_RECTS as i32 * 10 + 1, 17)])
+        );
+    }
+
+    #[test]
+    fn repaints_buffers_by_their_age() {
+        let mut history = DamageHistory::new(2);
+        let frame = |x| Damage::Rects(vec![rect(x, 0, 1, 1)]);
+        assert_eq!(history.draw(0, frame(0)), Damage::Full);
+        assert_eq!(history.draw(1, frame(1)), Damage::Full);
+        assert_eq!(
+            history.draw(0, frame(2)),
+            Damage::Rects(vec![rect(2, 0, 1, 1), rect(1, 0, 1, 1)])
+        );
+        // The same buffer twice in a row only misses the latest frame.
+        assert_eq!(history.draw(0, frame(3)), frame(3));
+
+        history.reset();
+        assert_eq!(history.draw(1, frame(4)), Damage::Full);
+    }
+}
@@ -0,0 +1,190 @@
+//! Cursor images for hardware cursor planes, kept in CPU-mapped dumb buffers.
+
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+use std::ptr;
+
+use log::debug;
+
+use super::sys;
+
+/// DRM fourcc: ARGB8888 ('AR24'), premultiplied like Wayland cursors.
+const DRM_FORMAT_ARGB8888: u32 = u32::from_le_bytes(*b"AR24");
+/// Cursor size drivers accept when they do not report one.
+const DEFAULT_CURSOR_SIZE: u64 = 64;
+
+/// Size of the buffers the cursor planes of a DRM device scan out.
+pub fn cursor_plane_size(drm_fd: BorrowedFd<'_>) -> (u32, u32) {
+    let cap = |capability| {
+        let mut value = 0u64;
+        let result = unsafe { sys::drmGetCap(drm_fd.as_raw_fd(), capability, &mut value) };
+        if result != 0 || value == 0 {
+            DEFAULT_CURSOR_SIZE
+        } else {
+            value
+        }
+    };
+    (
+        cap(sys::DRM_CAP_CURSOR_WIDTH) as u32,
+        cap(sys::DRM_CAP_CURSOR_HEIGHT) as u32,
+    )
+}
+
+/// An ARGB8888 dumb buffer mapped for CPU writes and added as a framebuffer.
+pub struct CursorBuffer {
+    fd: RawFd,
+    handle: u32,
+    fb_id: u32,
+    map: *mut u8,
+    map_len: usize,
+    pitch: u32,
+    width: u32,
+    height: u32,
+}
+
+impl CursorBuffer {
+    /// Allocate a transparent buffer of the cursor plane size.
+    pub fn create(drm_fd: BorrowedFd<'_>, (width, height): (u32, u32)) -> anyhow::Result<Self> {
+        let fd = drm_fd.as_raw_fd();
+        let mut handle = 0u32;
+        let mut pitch = 0u32;
+        let mut size = 0u64;
+        let result = unsafe {
+            sys::drmModeCreateDumbBuffer(
+                fd,
+                width,
+                height,
+                32,
+                0,
+                &mut handle,
+                &mut pitch,
+                &mut size,
+            )
+        };
+        if result != 0 {
+            anyhow::bail!(
+                "drmModeCreateDumbBuffer failed: {}",
+                io::Error::last_os_error()
+            );
+        }
+        // Owns the handle from here on, so failures below clean up in Drop.
+        let mut buffer = Self {
+            fd,
+            handle,
+            fb_id: 0,
+            map: ptr::null_mut(),
+            map_len: 0,
+            pitch,
+            width,
+            height,
+        };
+
+        let mut offset = 0u64;
+        let result = unsafe { sys::drmModeMapDumbBuffer(fd, handle, &mut offset) };
+        if result != 0 {
+            anyhow::bail!(
+                "drmModeMapDumbBuffer failed: {}",
+                io::Error::last_os_error()
+            );
+        }
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                offset as libc::off_t,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            anyhow::bail!("mmap of dumb buffer failed: {}", io::Error::last_os_error());
+        }
+        buffer.map = map.cast();
+        buffer.map_len = size as usize;
+        buffer.mapped_mut().fill(0);
+
+        let handles = [handle, 0, 0, 0];
+        let pitches = [pitch, 0, 0, 0];
+        let offsets = [0u32; 4];
+        let result = unsafe {
+            sys::drmModeAddFB2(
+                fd,
+                width,
+                height,
+                DRM_FORMAT_ARGB8888,
+                handles.as_ptr(),
+                pitches.as_ptr(),
+                offsets.as_ptr(),
+                &mut buffer.fb_id,
+                0,
+            )
+        };
+        if result != 0 {
+            anyhow::bail!("drmModeAddFB2 failed: {}", io::Error::last_os_error());
+        }
+
+        debug!(
+            "Created cursor FB {} ({width}x{height}, pitch={pitch})",
+            buffer.fb_id
+        );
+        Ok(buffer)
+    }
+
+    pub fn id(&self) -> u32 {
+        self.fb_id
+    }
+
+    /// Copy an ARGB8888 image into the top-left corner and clear the rest.
+    /// Parts beyond the buffer are cut off. `opaque` images (XRGB8888) get
+    /// their padding byte set to full alpha.
+    pub fn write(&mut self, pixels: &[u8], width: u32, height: u32, stride: usize, opaque: bool) {
+        let pitch = self.pitch as usize;
+        let row_len = width.min(self.width) as usize * 4;
+        let rows = height.min(self.height) as usize;
+        let mapped = self.mapped_mut();
+        mapped.fill(0);
+        for (row, source) in pixels.chunks(stride).take(rows).enumerate() {
+            let len = row_len.min(source.len());
+            let target = &mut mapped[row * pitch..][..len];
+            target.copy_from_slice(&source[..len]);
+            if opaque {
+                for pixel in target.chunks_exact_mut(4) {
+                    pixel[3] = 0xff;
+                }
+            }
+        }
+    }
+
+    fn mapped_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `map` is a live shared mapping of `map_len` bytes owned by
+        // this buffer.
+        unsafe { std::slice::from_raw_parts_mut(self.map, self.map_len) }
+    }
+}
+
+impl Drop for CursorBuffer {
+    fn drop(&mut self) {
+        if self.fb_id != 0 {
+            let result = unsafe { sys::drmModeRmFB(self.fd, self.fb_id) };
+            if result != 0 {
+                log::warn!(
+                    "drmModeRmFB({}) failed: {}",
+                    self.fb_id,
+                    io::Error::last_os_error()
+                );
+            }
+        }
+        if !self.map.is_null() {
+            unsafe { libc::munmap(self.map.cast(), self.map_len) };
+        }
+        let result = unsafe { sys::drmModeDestroyDumbBuffer(self.fd, self.handle) };
+        if result != 0 {
+            log::warn!(
+                "drmModeDestroyDumbBuffer({}) failed: {}",
+                self.handle,
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
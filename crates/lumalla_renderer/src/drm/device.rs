@@ -3,19 +3,20 @@
 use std::collections::HashMap;
 use std::ffi::{CStr, OsStr, c_int};
 use std::io;
-use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::ptr;
+use std::time::Duration;
 
 use anyhow::Context;
 use log::{info, warn};
 use lumalla_seat::{SeatDevice, SeatState};
 use lumalla_shared::{DrmConnector, DrmDeviceState, Udev, UdevMonitor};
-use mio::{Interest, Registry, Token, event::Source, unix::SourceFd};
+use mio::{Events, Interest, Poll, Registry, Token, event::Source, unix::SourceFd};
 
 use super::connector::probe_connectors;
-use super::modeset::enable_atomic_client_caps;
+use super::modeset::{PageFlip, dispatch_drm_events, enable_atomic_client_caps};
 
 #[allow(non_camel_case_types, dead_code)]
 mod bindings {
@@ -174,6 +175,10 @@ pub struct DrmDevices {
     /// Kept alive so the monitor's udev context remains valid.
     _udev: Udev,
     monitor: UdevMonitor,
+    /// Watches opened devices for page-flip events; see [`Self::events_fd`].
+    events: Poll,
+    event_tokens: HashMap<Token, PathBuf>,
+    next_event_token: usize,
 }
 
 impl DrmDevices {
@@ -191,6 +196,9 @@ impl DrmDevices {
             opened: HashMap::new(),
             _udev: udev,
             monitor,
+            events: Poll::new().context("Failed to create DRM event poll")?,
+            event_tokens: HashMap::new(),
+            next_event_token: 0,
         })
     }
 
@@ -253,6 +261,43 @@ impl DrmDevices {
         Ok(result)
     }
 
+    /// Pollable fd that becomes readable when an opened device has pending
+    /// DRM events; drain them with [`Self::dispatch_page_flips`].
+    pub fn events_fd(&self) -> RawFd {
+        self.events.as_raw_fd()
+    }
+
+    /// Read the DRM events of every device that has some pending, without blocking.
+    pub fn dispatch_page_flips(&mut self) -> anyhow::Result<Vec<(PathBuf, PageFlip)>> {
+        let mut flips = Vec::new();
+        let mut events = Events::with_capacity(16);
+        loop {
+            self.events
+                .poll(&mut events, Some(Duration::ZERO))
+                .context("Failed to poll DRM devices for events")?;
+            if events.is_empty() {
+                return Ok(flips);
+            }
+            for event in &events {
+                let Some((path, device)) = self
+                    .event_tokens
+                    .get(&event.token())
+                    .and_then(|path| self.opened.get_key_value(path))
+                else {
+                    continue;
+                };
+                match dispatch_drm_events(device.fd().as_raw_fd()) {
+                    Ok(completed) => {
+                        flips.extend(completed.into_iter().map(|flip| (path.clone(), flip)));
+                    }
+                    Err(err) => {
+                        warn!("Failed to read DRM events on {}: {err:#}", path.display());
+                    }
+                }
+            }
+        }
+    }
+
     /// Open any missing primary nodes via the seat.
     ///
     /// Fresh opens through libseat already have DRM master. After a VT switch,
@@ -271,6 +316,7 @@ impl DrmDevices {
         let opened = std::mem::take(&mut self.opened);
         for (path, device) in opened {
             info!("Closing DRM device {} for session disable", path.display());
+            self.unwatch_events(&path, &device);
             let seat_device = device.into_seat_device();
             if let Err(err) = seat.close_device(seat_device) {
                 warn!("Failed to close DRM device {}: {err:#}", path.display());
@@ -328,11 +374,42 @@ impl DrmDevices {
                     path.display()
                 );
             }
+            let token = Token(self.next_event_token);
+            self.next_event_token += 1;
+            match self.events.registry().register(
+                &mut SourceFd(&device.fd().as_raw_fd()),
+                token,
+                Interest::READABLE,
+            ) {
+                Ok(()) => {
+                    self.event_tokens.insert(token, path.clone());
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to watch {} for page-flip events: {err}",
+                        path.display()
+                    );
+                }
+            }
             self.opened.insert(path.clone(), device);
         }
         Ok(())
     }
 
+    fn unwatch_events(&mut self, path: &Path, device: &DrmDevice) {
+        if let Err(err) = self
+            .events
+            .registry()
+            .deregister(&mut SourceFd(&device.fd().as_raw_fd()))
+        {
+            warn!(
+                "Failed to stop watching {} for page-flip events: {err}",
+                path.display()
+            );
+        }
+        self.event_tokens.retain(|_, watched| watched != path);
+    }
+
     fn close_removed(&mut self, seat: &SeatState) -> anyhow::Result<()> {
         let to_close: Vec<PathBuf> = self
             .opened
@@ -344,6 +421,7 @@ impl DrmDevices {
         for path in to_close {
             if let Some(device) = self.opened.remove(&path) {
                 info!("Closing removed DRM device {}", path.display());
+                self.unwatch_events(&path, &device);
                 let seat_device = device.into_seat_device();
                 if let Err(err) = seat.close_device(seat_device) {
                     warn!("Failed to close DRM device {}: {err:#}", path.display());
@@ -4,13 +4,16 @@
 //! allocated in Vulkan and exported as DMA-BUFs for KMS.
 
 mod connector;
+mod cursor;
 mod device;
 mod modeset;
 mod sys;
 
+pub use cursor::{CursorBuffer, cursor_plane_size};
 pub use device::{DrmDevice, DrmDevices, DrmDispatchResult, find_drm_devices};
 pub use modeset::{
-    ConnectedOutput, DrmFramebuffer, ModeBlob, ModeInfo, atomic_modeset, atomic_page_flip,
-    dispatch_drm_events, enable_atomic_client_caps, find_first_connected_output,
-    resolve_connected_output,
+    ConnectedOutput, CursorState, DrmFramebuffer, ModeBlob, ModeInfo, PageFlip,
+    atomic_cursor_update, atomic_modeset, atomic_page_flip, dispatch_drm_events, drm_rotation,
+    enable_atomic_client_caps, find_first_connected_output, framebuffer_size,
+    resolve_connected_output, test_page_flip,
 };
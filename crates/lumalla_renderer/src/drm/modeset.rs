@@ -1,18 +1,20 @@
 //! DRM modesetting via the atomic API: planes, property blobs, and page-flips.
 
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, c_void};
 use std::io;
 use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, Ordering};
 
 use log::debug;
+use lumalla_shared::Transform;
 
 use super::sys;
+use crate::damage::Rect;
 
 /// Full kernel mode info required for atomic MODE_ID blobs.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct ModeInfo {
     raw: sys::drmModeModeInfo,
 }
@@ -42,6 +44,17 @@ impl ModeInfo {
         self.raw.vrefresh
     }
 
+    /// Duration of one refresh cycle derived from the pixel clock; zero
+    /// when the timings are unknown.
+    pub fn refresh_nsec(&self) -> u32 {
+        let pixels = u64::from(self.raw.htotal) * u64::from(self.raw.vtotal);
+        let clock_khz = u64::from(self.raw.clock);
+        if pixels == 0 || clock_khz == 0 {
+            return 0;
+        }
+        u32::try_from(pixels * 1_000_000 / clock_khz).unwrap_or(0)
+    }
+
     pub fn name(&self) -> String {
         unsafe { CStr::from_ptr(self.raw.name.as_ptr()) }
             .to_string_lossy()
@@ -69,6 +82,34 @@ pub struct AtomicProps {
     pub plane_crtc_y: u32,
     pub plane_crtc_w: u32,
     pub plane_crtc_h: u32,
+    /// `None` if the plane takes no damage clips.
+    pub plane_fb_damage_clips: Option<u32>,
+    /// `None` if the plane cannot be rotated.
+    pub plane_rotation: Option<u32>,
+    /// `None` if the plane cannot wait for a fence.
+    pub plane_in_fence_fd: Option<u32>,
+}
+
+/// Cached property IDs of a cursor plane.
+#[derive(Debug, Clone)]
+pub struct CursorPlaneProps {
+    pub fb_id: u32,
+    pub crtc_id: u32,
+    pub src_x: u32,
+    pub src_y: u32,
+    pub src_w: u32,
+    pub src_h: u32,
+    pub crtc_x: u32,
+    pub crtc_y: u32,
+    pub crtc_w: u32,
+    pub crtc_h: u32,
+}
+
+/// The cursor plane of a CRTC.
+#[derive(Debug, Clone)]
+pub struct CursorPlane {
+    pub plane_id: u32,
+    pub props: CursorPlaneProps,
 }
 
 /// A connected connector with CRTC, primary plane, and mode — ready for atomic modeset.
@@ -79,8 +120,34 @@ pub struct ConnectedOutput {
     pub crtc_id: u32,
     pub crtc_index: u32,
     pub plane_id: u32,
+    /// `None` if the CRTC has no cursor plane.
+    pub cursor_plane: Option<CursorPlane>,
     pub mode: ModeInfo,
     pub props: AtomicProps,
+    /// `rotation` of the primary plane, see [`drm_rotation`]; framebuffers
+    /// are rotated into the mode by it.
+    pub rotation: u64,
+}
+
+/// What the cursor plane of an output shows, relative to the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorState {
+    /// Framebuffer of the cursor image; 0 hides the cursor plane.
+    pub fb_id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CursorState {
+    pub const HIDDEN: Self = Self {
+        fb_id: 0,
+        x: 0,
+        y: 0,
+        width: 0,
+        height: 0,
+    };
 }
 
 /// A DRM framebuffer imported from a DMA-BUF.
@@ -231,6 +298,55 @@ impl Drop for ModeBlob {
     }
 }
 
+/// An FB_DAMAGE_CLIPS blob, destroyed once its commit was made.
+struct DamageClipsBlob {
+    fd: RawFd,
+    id: u32,
+}
+
+impl DamageClipsBlob {
+    fn create(fd: RawFd, damage: &[Rect]) -> anyhow::Result<Self> {
+        let clips: Vec<sys::drm_mode_rect> = damage
+            .iter()
+            .map(|rect| sys::drm_mode_rect {
+                x1: rect.x,
+                y1: rect.y,
+                x2: rect.x + rect.width,
+                y2: rect.y + rect.height,
+            })
+            .collect();
+        let mut id = 0u32;
+        let result = unsafe {
+            sys::drmModeCreatePropertyBlob(
+                fd,
+                clips.as_ptr().cast(),
+                std::mem::size_of_val(clips.as_slice()),
+                &mut id,
+            )
+        };
+        if result != 0 {
+            anyhow::bail!(
+                "drmModeCreatePropertyBlob failed: {}",
+                io::Error::last_os_error()
+            );
+        }
+        Ok(Self { fd, id })
+    }
+}
+
+impl Drop for DamageClipsBlob {
+    fn drop(&mut self) {
+        let result = unsafe { sys::drmModeDestroyPropertyBlob(self.fd, self.id) };
+        if result != 0 {
+            log::warn!(
+                "drmModeDestroyPropertyBlob({}) failed: {}",
+                self.id,
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
+
 /// Enable atomic + universal planes on a freshly opened DRM primary node.
 pub fn enable_atomic_client_caps(fd: RawFd) -> anyhow::Result<()> {
     let result =
@@ -276,7 +392,37 @@ pub fn find_first_connected_output(fd: RawFd) -> anyhow::Result<Option<Connected
     Ok(None)
 }
 
+/// The `rotation` plane property value showing framebuffers with
+/// `transform` applied.
+///
+/// Both rotate counter-clockwise and mirror before rotating.
+pub fn drm_rotation(transform: Transform) -> u64 {
+    let rotation = match transform.quarter_turns() {
+        0 => sys::DRM_MODE_ROTATE_0,
+        1 => sys::DRM_MODE_ROTATE_90,
+        2 => sys::DRM_MODE_ROTATE_180,
+        _ => sys::DRM_MODE_ROTATE_270,
+    };
+    match transform.is_flipped() {
+        true => rotation | sys::DRM_MODE_REFLECT_X,
+        false => rotation,
+    }
+}
+
+/// Size of the framebuffers an output scans out, before its plane rotates
+/// them into the mode.
+pub fn framebuffer_size(output: &ConnectedOutput) -> (u32, u32) {
+    let (width, height) = (output.mode.width(), output.mode.height());
+    match output.rotation & (sys::DRM_MODE_ROTATE_90 | sys::DRM_MODE_ROTATE_270) {
+        0 => (width, height),
+        _ => (height, width),
+    }
+}
+
 /// Initial modeset: enable CRTC, attach connector, set mode, and assign primary plane FB.
+///
+/// Blocks until the mode is set; the CRTC then reports a flip event like
+/// [`atomic_page_flip`] does.
 pub fn atomic_modeset(
     drm_fd: BorrowedFd<'_>,
     output: &ConnectedOutput,
@@ -284,19 +430,17 @@ pub fn atomic_modeset(
     fb_id: u32,
 ) -> anyhow::Result<()> {
     let fd = drm_fd.as_raw_fd();
-    let width = output.mode.width();
-    let height = output.mode.height();
     let props = &output.props;
 
     let req = AtomicRequest::new()?;
     req.add(output.connector_id, props.connector_crtc_id, u64::from(output.crtc_id))?;
     req.add(output.crtc_id, props.crtc_active, 1u64)?;
     req.add(output.crtc_id, props.crtc_mode_id, u64::from(mode_blob_id))?;
-    add_plane_fb_props(&req, output, props, fb_id, width, height)?;
+    add_plane_fb_props(&req, output, fb_id)?;
 
     req.commit(
         fd,
-        sys::DRM_MODE_ATOMIC_ALLOW_MODESET,
+        sys::DRM_MODE_ATOMIC_ALLOW_MODESET | sys::DRM_MODE_PAGE_FLIP_EVENT,
         ptr::null_mut(),
     )?;
 
@@ -311,29 +455,70 @@ pub fn atomic_modeset(
     Ok(())
 }
 
+/// A page flip completed by the kernel, reported by [`dispatch_drm_events`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFlip {
+    pub crtc_id: u32,
+    /// Vblank counter of the CRTC when the flip completed.
+    pub sequence: u32,
+    pub tv_sec: u32,
+    pub tv_usec: u32,
+}
+
+thread_local! {
+    /// Flips reported by the handler during the current `drmHandleEvent` call.
+    static COMPLETED_FLIPS: RefCell<Vec<PageFlip>> = const { RefCell::new(Vec::new()) };
+}
+
 /// Non-blocking page-flip of the primary plane FB, requesting a flip event.
+/// The cursor plane is updated along with it, if the output has one.
+///
+/// `damage` lists the parts of the FB that differ from the one shown, for
+/// drivers that only scan out changed regions; `None` means all of it.
+///
+/// With `in_fence`, a sync file, the FB is only scanned out once it is
+/// signaled. The output's plane has to support that, see
+/// [`AtomicProps::plane_in_fence_fd`].
 ///
-/// `flip_done` is set to `true` by the DRM page-flip handler when the flip completes.
+/// Completion is reported by [`dispatch_drm_events`] for the output's CRTC.
+/// Fails with `EBUSY` while a previous flip on the CRTC is still pending.
 pub fn atomic_page_flip(
     drm_fd: BorrowedFd<'_>,
     output: &ConnectedOutput,
     fb_id: u32,
-    flip_done: &AtomicBool,
+    damage: Option<&[Rect]>,
+    in_fence: Option<BorrowedFd<'_>>,
+    cursor: Option<CursorState>,
 ) -> anyhow::Result<()> {
     let fd = drm_fd.as_raw_fd();
-    let width = output.mode.width();
-    let height = output.mode.height();
     let props = &output.props;
 
-    flip_done.store(false, Ordering::SeqCst);
-
     let req = AtomicRequest::new()?;
-    add_plane_fb_props(&req, output, props, fb_id, width, height)?;
+    add_plane_fb_props(&req, output, fb_id)?;
+    if let Some(in_fence) = in_fence {
+        let Some(prop_id) = props.plane_in_fence_fd else {
+            anyhow::bail!("Plane {} cannot wait for an IN_FENCE_FD", output.plane_id);
+        };
+        // The kernel takes its own reference during the commit.
+        req.add(output.plane_id, prop_id, in_fence.as_raw_fd() as u64)?;
+    }
+    // The kernel holds on to the blob once it was committed.
+    let _damage_blob = match (props.plane_fb_damage_clips, damage) {
+        (Some(prop_id), Some(damage)) if !damage.is_empty() => {
+            let blob = DamageClipsBlob::create(fd, damage)?;
+            req.add(output.plane_id, prop_id, u64::from(blob.id))?;
+            Some(blob)
+        }
+        _ => None,
+    };
+    if let Some(cursor) = cursor {
+        add_cursor_props(&req, output, cursor)?;
+    }
 
     req.commit(
         fd,
         sys::DRM_MODE_PAGE_FLIP_EVENT | sys::DRM_MODE_ATOMIC_NONBLOCK,
-        flip_done as *const AtomicBool as *mut c_void,
+        ptr::null_mut(),
     )?;
 
     debug!(
@@ -343,61 +528,132 @@ pub fn atomic_page_flip(
     Ok(())
 }
 
-/// Drain pending DRM events on `fd` (page-flip completions, etc.).
-pub fn dispatch_drm_events(fd: RawFd) -> anyhow::Result<()> {
+/// Check whether the primary plane can scan out `fb_id` without changing
+/// anything, using a test-only atomic commit.
+pub fn test_page_flip(
+    drm_fd: BorrowedFd<'_>,
+    output: &ConnectedOutput,
+    fb_id: u32,
+    cursor: Option<CursorState>,
+) -> anyhow::Result<()> {
+    let req = AtomicRequest::new()?;
+    add_plane_fb_props(&req, output, fb_id)?;
+    if let Some(cursor) = cursor {
+        add_cursor_props(&req, output, cursor)?;
+    }
+    req.commit(
+        drm_fd.as_raw_fd(),
+        sys::DRM_MODE_ATOMIC_TEST_ONLY,
+        ptr::null_mut(),
+    )
+}
+
+/// Non-blocking update of the cursor plane alone, requesting a flip event
+/// like [`atomic_page_flip`]. Does nothing for outputs without a cursor plane.
+pub fn atomic_cursor_update(
+    drm_fd: BorrowedFd<'_>,
+    output: &ConnectedOutput,
+    cursor: CursorState,
+) -> anyhow::Result<()> {
+    let req = AtomicRequest::new()?;
+    if !add_cursor_props(&req, output, cursor)? {
+        return Ok(());
+    }
+    req.commit(
+        drm_fd.as_raw_fd(),
+        sys::DRM_MODE_PAGE_FLIP_EVENT | sys::DRM_MODE_ATOMIC_NONBLOCK,
+        ptr::null_mut(),
+    )
+}
+
+/// Drain pending DRM events on `fd` and return the completed page flips.
+///
+/// Reads from `fd`, so it blocks unless `fd` is readable.
+pub fn dispatch_drm_events(fd: RawFd) -> anyhow::Result<Vec<PageFlip>> {
     let mut ctx = sys::drmEventContext {
         version: sys::DRM_EVENT_CONTEXT_VERSION,
         vblank_handler: ptr::null_mut(),
-        page_flip_handler: Some(page_flip_handler),
-        page_flip_handler2: None,
+        page_flip_handler: None,
+        page_flip_handler2: Some(page_flip_handler),
         sequence_handler: ptr::null_mut(),
     };
 
     let result = unsafe { sys::drmHandleEvent(fd, &mut ctx) };
+    let flips = COMPLETED_FLIPS.with_borrow_mut(std::mem::take);
     if result != 0 {
-        anyhow::bail!(
-            "drmHandleEvent failed: {}",
-            io::Error::last_os_error()
-        );
+        anyhow::bail!("drmHandleEvent failed: {}", io::Error::last_os_error());
     }
-    Ok(())
+    Ok(flips)
 }
 
 unsafe extern "C" fn page_flip_handler(
     _fd: std::ffi::c_int,
-    _sequence: u32,
-    _tv_sec: u32,
-    _tv_usec: u32,
-    user_data: *mut c_void,
+    sequence: u32,
+    tv_sec: u32,
+    tv_usec: u32,
+    crtc_id: u32,
+    _user_data: *mut c_void,
 ) {
-    if user_data.is_null() {
-        return;
-    }
-    let flag = unsafe { &*(user_data as *const AtomicBool) };
-    flag.store(true, Ordering::SeqCst);
+    COMPLETED_FLIPS.with_borrow_mut(|flips| {
+        flips.push(PageFlip {
+            crtc_id,
+            sequence,
+            tv_sec,
+            tv_usec,
+        })
+    });
 }
 
+/// Show `fb_id`, of [`framebuffer_size`], on the whole mode of the output.
 fn add_plane_fb_props(
     req: &AtomicRequest,
     output: &ConnectedOutput,
-    props: &AtomicProps,
     fb_id: u32,
-    width: u32,
-    height: u32,
 ) -> anyhow::Result<()> {
+    let props = &output.props;
+    let (src_width, src_height) = framebuffer_size(output);
     req.add(output.plane_id, props.plane_fb_id, u64::from(fb_id))?;
     req.add(output.plane_id, props.plane_crtc_id, u64::from(output.crtc_id))?;
     req.add(output.plane_id, props.plane_src_x, 0u64)?;
     req.add(output.plane_id, props.plane_src_y, 0u64)?;
-    req.add(output.plane_id, props.plane_src_w, u64::from(width) << 16)?;
-    req.add(output.plane_id, props.plane_src_h, u64::from(height) << 16)?;
+    req.add(output.plane_id, props.plane_src_w, u64::from(src_width) << 16)?;
+    req.add(output.plane_id, props.plane_src_h, u64::from(src_height) << 16)?;
     req.add(output.plane_id, props.plane_crtc_x, 0u64)?;
     req.add(output.plane_id, props.plane_crtc_y, 0u64)?;
-    req.add(output.plane_id, props.plane_crtc_w, u64::from(width))?;
-    req.add(output.plane_id, props.plane_crtc_h, u64::from(height))?;
+    req.add(output.plane_id, props.plane_crtc_w, u64::from(output.mode.width()))?;
+    req.add(output.plane_id, props.plane_crtc_h, u64::from(output.mode.height()))?;
+    // Resets rotations left behind by other DRM masters, too.
+    if let Some(prop_id) = props.plane_rotation {
+        req.add(output.plane_id, prop_id, output.rotation)?;
+    }
     Ok(())
 }
 
+/// Returns `false` if the output has no cursor plane.
+fn add_cursor_props(
+    req: &AtomicRequest,
+    output: &ConnectedOutput,
+    cursor: CursorState,
+) -> anyhow::Result<bool> {
+    let Some(plane) = &output.cursor_plane else {
+        return Ok(false);
+    };
+    let props = &plane.props;
+    let crtc_id = if cursor.fb_id == 0 { 0 } else { output.crtc_id };
+    req.add(plane.plane_id, props.fb_id, u64::from(cursor.fb_id))?;
+    req.add(plane.plane_id, props.crtc_id, u64::from(crtc_id))?;
+    req.add(plane.plane_id, props.src_x, 0u64)?;
+    req.add(plane.plane_id, props.src_y, 0u64)?;
+    req.add(plane.plane_id, props.src_w, u64::from(cursor.width) << 16)?;
+    req.add(plane.plane_id, props.src_h, u64::from(cursor.height) << 16)?;
+    // CRTC_X and CRTC_Y are signed, so the cursor can leave the output.
+    req.add(plane.plane_id, props.crtc_x, i64::from(cursor.x) as u64)?;
+    req.add(plane.plane_id, props.crtc_y, i64::from(cursor.y) as u64)?;
+    req.add(plane.plane_id, props.crtc_w, u64::from(cursor.width))?;
+    req.add(plane.plane_id, props.crtc_h, u64::from(cursor.height))?;
+    Ok(true)
+}
+
 struct AtomicRequest {
     ptr: sys::drmModeAtomicReqPtr,
 }
@@ -471,13 +727,21 @@ fn probe_connected_output(
         );
     };
 
-    let Some(plane_id) = find_primary_plane(fd, crtc_index)? else {
+    let Some(plane_id) = find_plane(fd, crtc_index, sys::DRM_PLANE_TYPE_PRIMARY)? else {
         anyhow::bail!(
             "No primary plane for CRTC {crtc_id} (index {crtc_index}) on {}",
             connector_name(raw)
         );
     };
 
+    let cursor_plane = match find_plane(fd, crtc_index, sys::DRM_PLANE_TYPE_CURSOR)? {
+        Some(plane_id) => Some(CursorPlane {
+            plane_id,
+            props: cursor_plane_props(fd, plane_id)?,
+        }),
+        None => None,
+    };
+
     used_crtcs.insert(crtc_id);
 
     let props = AtomicProps {
@@ -494,6 +758,9 @@ fn probe_connected_output(
         plane_crtc_y: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "CRTC_Y")?,
         plane_crtc_w: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "CRTC_W")?,
         plane_crtc_h: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "CRTC_H")?,
+        plane_fb_damage_clips: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "FB_DAMAGE_CLIPS").ok(),
+        plane_rotation: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "rotation").ok(),
+        plane_in_fence_fd: find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, "IN_FENCE_FD").ok(),
     };
 
     Ok(Some(ConnectedOutput {
@@ -502,11 +769,29 @@ fn probe_connected_output(
         crtc_id,
         crtc_index,
         plane_id,
+        cursor_plane,
         mode,
         props,
+        rotation: sys::DRM_MODE_ROTATE_0,
     }))
 }
 
+fn cursor_plane_props(fd: RawFd, plane_id: u32) -> anyhow::Result<CursorPlaneProps> {
+    let prop = |name| find_prop_id(fd, plane_id, sys::DRM_MODE_OBJECT_PLANE, name);
+    Ok(CursorPlaneProps {
+        fb_id: prop("FB_ID")?,
+        crtc_id: prop("CRTC_ID")?,
+        src_x: prop("SRC_X")?,
+        src_y: prop("SRC_Y")?,
+        src_w: prop("SRC_W")?,
+        src_h: prop("SRC_H")?,
+        crtc_x: prop("CRTC_X")?,
+        crtc_y: prop("CRTC_Y")?,
+        crtc_w: prop("CRTC_W")?,
+        crtc_h: prop("CRTC_H")?,
+    })
+}
+
 fn find_crtc_for_connector(
     fd: RawFd,
     resources: &DrmModeResources,
@@ -566,7 +851,8 @@ fn crtc_from_possible_mask(
     None
 }
 
-fn find_primary_plane(fd: RawFd, crtc_index: u32) -> anyhow::Result<Option<u32>> {
+/// First plane of `plane_type` that can be used with the CRTC.
+fn find_plane(fd: RawFd, crtc_index: u32, plane_type: u64) -> anyhow::Result<Option<u32>> {
     let planes = get_plane_resources(fd)?;
     for &plane_id in planes.plane_ids() {
         let plane = get_plane(fd, plane_id)?;
@@ -577,7 +863,7 @@ fn find_primary_plane(fd: RawFd, crtc_index: u32) -> anyhow::Result<Option<u32>>
         let Some(type_prop) = props.get("type").copied() else {
             continue;
         };
-        if type_prop == sys::DRM_PLANE_TYPE_PRIMARY {
+        if type_prop == plane_type {
             return Ok(Some(plane_id));
         }
     }
@@ -925,8 +1211,23 @@ impl Drop for DrmProperty {
 
 #[cfg(test)]
 mod tests {
+    use super::ModeInfo;
     use crate::drm::sys;
 
+    #[test]
+    fn refresh_interval_follows_the_pixel_clock() {
+        // SAFETY: drmModeModeInfo is plain old data.
+        let mut raw: sys::drmModeModeInfo = unsafe { std::mem::zeroed() };
+        let mode = ModeInfo { raw };
+        assert_eq!(mode.refresh_nsec(), 0);
+
+        raw.clock = 148_500;
+        raw.htotal = 2200;
+        raw.vtotal = 1125;
+        let mode = ModeInfo { raw };
+        assert_eq!(mode.refresh_nsec(), 16_666_666);
+    }
+
     #[test]
     fn drm_mode_encoder_layout() {
         assert_eq!(std::mem::size_of::<sys::drmModeEncoder>(), 20);
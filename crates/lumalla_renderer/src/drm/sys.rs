@@ -13,6 +13,9 @@ pub const DRM_PROP_NAME_LEN: usize = 32;
 pub const DRM_CLIENT_CAP_UNIVERSAL_PLANES: u64 = 2;
 pub const DRM_CLIENT_CAP_ATOMIC: u64 = 3;
 
+pub const DRM_CAP_CURSOR_WIDTH: u64 = 0x8;
+pub const DRM_CAP_CURSOR_HEIGHT: u64 = 0x9;
+
 pub const DRM_MODE_OBJECT_CRTC: u32 = 0xcccccccc;
 pub const DRM_MODE_OBJECT_CONNECTOR: u32 = 0xc0c0c0c0;
 pub const DRM_MODE_OBJECT_PLANE: u32 = 0xeeeeeeee;
@@ -21,7 +24,14 @@ pub const DRM_PLANE_TYPE_OVERLAY: u64 = 0;
 pub const DRM_PLANE_TYPE_PRIMARY: u64 = 1;
 pub const DRM_PLANE_TYPE_CURSOR: u64 = 2;
 
+pub const DRM_MODE_ROTATE_0: u64 = 1 << 0;
+pub const DRM_MODE_ROTATE_90: u64 = 1 << 1;
+pub const DRM_MODE_ROTATE_180: u64 = 1 << 2;
+pub const DRM_MODE_ROTATE_270: u64 = 1 << 3;
+pub const DRM_MODE_REFLECT_X: u64 = 1 << 4;
+
 pub const DRM_MODE_PAGE_FLIP_EVENT: u32 = 0x01;
+pub const DRM_MODE_ATOMIC_TEST_ONLY: u32 = 0x0100;
 pub const DRM_MODE_ATOMIC_NONBLOCK: u32 = 0x0200;
 pub const DRM_MODE_ATOMIC_ALLOW_MODESET: u32 = 0x0400;
 
@@ -46,7 +56,7 @@ pub struct drmModeRes {
 pub type drmModeResPtr = *mut drmModeRes;
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct drmModeModeInfo {
     pub clock: u32,
     pub hdisplay: u16,
@@ -66,6 +76,16 @@ pub struct drmModeModeInfo {
 }
 
 /// Partial `drmModeConnector` — fields through `encoders`.
+/// `struct drm_mode_rect`, as stored in FB_DAMAGE_CLIPS blobs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct drm_mode_rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
 #[repr(C)]
 pub struct drmModeConnector {
     pub connector_id: u32,
@@ -181,6 +201,7 @@ pub struct drmEventContext {
 
 unsafe extern "C" {
     pub fn drmSetClientCap(fd: c_int, capability: u64, value: u64) -> c_int;
+    pub fn drmGetCap(fd: c_int, capability: u64, value: *mut u64) -> c_int;
 
     pub fn drmModeGetResources(fd: c_int) -> drmModeResPtr;
     pub fn drmModeFreeResources(ptr: drmModeResPtr);
@@ -216,8 +237,32 @@ unsafe extern "C" {
         buf_id: *mut u32,
         flags: u32,
     ) -> c_int;
+    pub fn drmModeAddFB2(
+        fd: c_int,
+        width: u32,
+        height: u32,
+        pixel_format: u32,
+        bo_handles: *const u32,
+        pitches: *const u32,
+        offsets: *const u32,
+        buf_id: *mut u32,
+        flags: u32,
+    ) -> c_int;
     pub fn drmModeRmFB(fd: c_int, buffer_id: u32) -> c_int;
 
+    pub fn drmModeCreateDumbBuffer(
+        fd: c_int,
+        width: u32,
+        height: u32,
+        bpp: u32,
+        flags: u32,
+        handle: *mut u32,
+        pitch: *mut u32,
+        size: *mut u64,
+    ) -> c_int;
+    pub fn drmModeMapDumbBuffer(fd: c_int, handle: u32, offset: *mut u64) -> c_int;
+    pub fn drmModeDestroyDumbBuffer(fd: c_int, handle: u32) -> c_int;
+
     pub fn drmModeAtomicAlloc() -> drmModeAtomicReqPtr;
     pub fn drmModeAtomicFree(req: drmModeAtomicReqPtr);
     pub fn drmModeAtomicAddProperty(
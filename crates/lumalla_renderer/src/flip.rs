@@ -0,0 +1,241 @@
+//! Page flip bookkeeping of the outputs, apart from the DRM calls that
+//! queue the flips.
+
+use std::path::{Path, PathBuf};
+
+/// What the primary plane of an output shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScanoutSource {
+    /// A buffer of the output's ring, by index.
+    Composed(usize),
+    /// A client dmabuf shown without composition, by `(owner_id, buffer_id)`.
+    Client((u32, u32)),
+}
+
+/// What an output does once its pending page flip completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlipFollowUp {
+    None,
+    /// The scene changed while the flip was pending.
+    Redraw,
+    /// Only the cursor changed while the flip was pending.
+    UpdateCursor,
+}
+
+/// A page flip that reached the screen.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct CompletedFlip {
+    /// What the primary plane shows now.
+    pub source: ScanoutSource,
+    /// Windows drawn into the flipped buffer whose contents did not change since.
+    pub windows: Vec<(u32, u32)>,
+    pub follow_up: FlipFollowUp,
+}
+
+/// The ring of scanout buffers of an output and the page flips between them.
+///
+/// At most one page flip is pending per CRTC, so redraws and cursor updates
+/// requested meanwhile wait until it completes.
+#[derive(Debug)]
+pub(crate) struct FlipState {
+    drm_path: PathBuf,
+    crtc_id: u32,
+    buffer_count: usize,
+    /// What is being scanned out.
+    front: ScanoutSource,
+    /// What a page flip is queued for.
+    pending: Option<ScanoutSource>,
+    /// Windows drawn into the pending buffer whose contents did not change since.
+    pending_windows: Vec<(u32, u32)>,
+    /// The scene changed while a page flip was pending.
+    needs_redraw: bool,
+    /// The cursor changed while a page flip was pending.
+    cursor_moved: bool,
+}
+
+impl FlipState {
+    /// State of an output modeset to the first of `buffer_count` buffers,
+    /// showing `windows`. The modeset reports a flip event once the buffer
+    /// is shown, and the cursor plane is first committed after that.
+    pub fn modeset(
+        drm_path: PathBuf,
+        crtc_id: u32,
+        buffer_count: usize,
+        windows: Vec<(u32, u32)>,
+    ) -> Self {
+        Self {
+            drm_path,
+            crtc_id,
+            buffer_count,
+            front: ScanoutSource::Composed(0),
+            pending: Some(ScanoutSource::Composed(0)),
+            pending_windows: windows,
+            needs_redraw: false,
+            cursor_moved: true,
+        }
+    }
+
+    /// Whether a flip event of `crtc_id` on the DRM device `drm_path` is
+    /// for this output.
+    pub fn reports(&self, drm_path: &Path, crtc_id: u32) -> bool {
+        self.drm_path == drm_path && self.crtc_id == crtc_id
+    }
+
+    pub fn front(&self) -> ScanoutSource {
+        self.front
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Index of the ring buffer to draw the next frame into: the one after
+    /// the buffer being scanned out, which is not the pending one since
+    /// nothing is drawn while a flip is pending.
+    pub fn back(&self) -> usize {
+        match self.front {
+            ScanoutSource::Composed(index) => (index + 1) % self.buffer_count,
+            ScanoutSource::Client(_) => 0,
+        }
+    }
+
+    /// Whether a client dmabuf is scanned out or queued to be.
+    pub fn shows_client_buffer(&self, key: (u32, u32)) -> bool {
+        self.front == ScanoutSource::Client(key) || self.pending == Some(ScanoutSource::Client(key))
+    }
+
+    /// Starts drawing a frame. Returns `false` while a flip is pending; the
+    /// redraw follows once it completes then.
+    pub fn begin_redraw(&mut self) -> bool {
+        if self.pending.is_some() {
+            self.needs_redraw = true;
+            return false;
+        }
+        self.needs_redraw = false;
+        self.cursor_moved = false;
+        true
+    }
+
+    /// Starts a cursor plane update. Returns `false` while a flip is
+    /// pending; the update follows once it completes then.
+    pub fn begin_cursor_update(&mut self) -> bool {
+        if self.pending.is_some() {
+            self.cursor_moved = true;
+            return false;
+        }
+        self.cursor_moved = false;
+        true
+    }
+
+    /// Records a page flip queued to `source`, showing `windows`.
+    pub fn queued(&mut self, source: ScanoutSource, windows: Vec<(u32, u32)>) {
+        self.pending = Some(source);
+        self.pending_windows = windows;
+    }
+
+    /// Adds windows to the pending flip, e.g. a cursor surface drawn with it.
+    pub fn add_pending_windows(&mut self, windows: impl IntoIterator<Item = (u32, u32)>) {
+        self.pending_windows.extend(windows);
+    }
+
+    /// Keeps a window's frame callbacks from completing with the pending
+    /// flip after its contents changed.
+    pub fn outdate_window(&mut self, key: (u32, u32)) {
+        self.pending_windows.retain(|window| *window != key);
+    }
+
+    /// Completes the pending flip. Returns `None` for stray flip events.
+    pub fn completed(&mut self) -> Option<CompletedFlip> {
+        let source = self.pending.take()?;
+        self.front = source;
+        let follow_up = if self.needs_redraw {
+            FlipFollowUp::Redraw
+        } else if self.cursor_moved {
+            FlipFollowUp::UpdateCursor
+        } else {
+            FlipFollowUp::None
+        };
+        Some(CompletedFlip {
+            source,
+            windows: std::mem::take(&mut self.pending_windows),
+            follow_up,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modeset(crtc_id: u32) -> FlipState {
+        FlipState::modeset(PathBuf::from("/dev/dri/card0"), crtc_id, 2, vec![(1, 2)])
+    }
+
+    #[test]
+    fn draws_into_the_buffer_after_the_front_one() {
+        let mut flip = modeset(40);
+        assert!(flip.is_pending());
+        assert!(!flip.begin_redraw());
+        assert_eq!(
+            flip.completed(),
+            Some(CompletedFlip {
+                source: ScanoutSource::Composed(0),
+                windows: vec![(1, 2)],
+                follow_up: FlipFollowUp::Redraw,
+            })
+        );
+        assert_eq!(flip.completed(), None);
+
+        assert!(flip.begin_redraw());
+        assert_eq!(flip.back(), 1);
+        flip.queued(ScanoutSource::Composed(1), vec![(1, 2), (3, 2)]);
+        // The pending buffer is not drawn into again before it is shown.
+        assert!(!flip.begin_redraw());
+        assert_eq!(flip.back(), 1);
+        flip.outdate_window((3, 2));
+        let completed = flip.completed().unwrap();
+        assert_eq!(completed.windows, [(1, 2)]);
+        assert_eq!(completed.follow_up, FlipFollowUp::Redraw);
+        assert_eq!(flip.back(), 0);
+    }
+
+    #[test]
+    fn deferred_cursor_updates_follow_the_flip() {
+        let mut flip = modeset(40);
+        assert_eq!(
+            flip.completed().unwrap().follow_up,
+            FlipFollowUp::UpdateCursor
+        );
+        assert!(flip.begin_cursor_update());
+        flip.queued(flip.front(), Vec::new());
+        assert!(!flip.begin_cursor_update());
+        assert_eq!(
+            flip.completed().unwrap().follow_up,
+            FlipFollowUp::UpdateCursor
+        );
+
+        assert!(flip.begin_redraw());
+        flip.queued(ScanoutSource::Client((5, 9)), Vec::new());
+        assert!(flip.shows_client_buffer((5, 9)));
+        assert!(!flip.begin_cursor_update());
+        assert!(!flip.begin_redraw());
+        assert_eq!(flip.completed().unwrap().follow_up, FlipFollowUp::Redraw);
+        assert!(flip.shows_client_buffer((5, 9)));
+        assert_eq!(flip.back(), 0);
+        assert_eq!(flip.completed(), None);
+    }
+
+    #[test]
+    fn flip_events_match_the_output_of_their_crtc() {
+        let laptop = modeset(40);
+        let monitor = modeset(41);
+        let other_card = FlipState::modeset(PathBuf::from("/dev/dri/card1"), 40, 2, Vec::new());
+        let card0 = Path::new("/dev/dri/card0");
+
+        assert!(laptop.reports(card0, 40));
+        assert!(!monitor.reports(card0, 40));
+        assert!(monitor.reports(card0, 41));
+        assert!(!other_card.reports(card0, 40));
+        assert!(other_card.reports(Path::new("/dev/dri/card1"), 40));
+    }
+}
@@ -1,44 +1,88 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::io;
-use std::os::fd::{AsFd, AsRawFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Context;
 use ash::vk;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 use lumalla_seat::SeatState;
-use lumalla_shared::{DrmDeviceState, OutputConfig};
-use mio::{Interest, Registry, Token, event::Source};
+use lumalla_shared::{
+    DmabufAttributes, DmabufFormat, DmabufFormats, DrmDeviceState, Output, OutputConfig,
+    PresentedFrame, Transform, Viewport, arrange_outputs, logical_size,
+};
+use mio::{Interest, Registry, Token, event::Source, unix::SourceFd};
 
+mod cursor;
+mod damage;
 pub mod drm;
+mod flip;
+mod scene;
 pub mod vulkan;
 
+pub use crate::cursor::Cursor;
+use crate::cursor::load_default_cursor;
+pub use crate::damage::Rect;
+use crate::damage::{Damage, DamageHistory};
 use crate::drm::{
-    ConnectedOutput, DrmDevices, DrmDispatchResult, DrmFramebuffer, ModeBlob, atomic_modeset,
-    resolve_connected_output,
+    ConnectedOutput, CursorBuffer, CursorState, DrmDevices, DrmDispatchResult, DrmFramebuffer,
+    ModeBlob, atomic_cursor_update, atomic_modeset, atomic_page_flip, cursor_plane_size,
+    drm_rotation, framebuffer_size, resolve_connected_output, test_page_flip,
 };
+use crate::flip::{FlipFollowUp, FlipState, ScanoutSource};
+use crate::scene::{Placement, Scene};
 use crate::vulkan::{
-    DmaBufImage, Framebuffer, RenderPass, VulkanContext, clear_framebuffer_to_color,
-    upload_bgra_to_image,
+    DmaBufImage, DmaBufPlane, Framebuffer, ImportedDmaBuf, QuadFrame, QuadRenderer, Texture,
+    TexturedQuad, VulkanContext, drm_fourcc_to_vulkan, importable_dma_buf_formats,
+    upload_bgra_to_texture, wait_for_dma_buf_writes,
 };
 
 /// Default clear color for enabled outputs (teal).
 pub const SOLID_CLEAR_COLOR: [f32; 4] = [0.0, 0.55, 0.65, 1.0];
 const WL_SHM_FORMAT_ARGB8888: u32 = 0;
 const WL_SHM_FORMAT_XRGB8888: u32 = 1;
-
-#[derive(Debug)]
+const SCANOUT_FORMAT: vk::Format = vk::Format::B8G8R8A8_UNORM;
+/// Buffers per output: one being scanned out while the next frame is drawn
+/// into the other and flipped to.
+const SCANOUT_BUFFER_COUNT: usize = 2;
+/// Cursor buffers per output, flipped between like the scanout buffers.
+const CURSOR_BUFFER_COUNT: usize = 2;
+
+#[derive(Debug, Clone)]
 pub struct SurfaceFrame {
     pub owner_id: u32,
     pub surface_id: u32,
+    /// Main surface of the sub-surface tree; equal to `surface_id` for main surfaces.
+    pub root_id: u32,
     pub pixels: Vec<u8>,
     pub width: usize,
     pub height: usize,
     pub stride: usize,
     pub format: u32,
+    /// Parts of the buffer that changed since the surface's previous frame.
+    pub damage: Vec<Rect>,
+    /// Scale the buffer is drawn at; at least 1.
+    pub scale: u32,
+    /// Transform the client drew the buffer with.
+    pub transform: Transform,
+    /// How the surface crops and scales the buffer.
+    pub viewport: Viewport,
 }
 
 impl SurfaceFrame {
+    fn geometry(&self) -> BufferGeometry {
+        BufferGeometry {
+            size: (self.width as u32, self.height as u32),
+            scale: self.scale.max(1),
+            transform: self.transform,
+            viewport: self.viewport,
+        }
+    }
+
     fn validate(&self) -> anyhow::Result<()> {
         anyhow::ensure!(
             self.width > 0 && self.height > 0,
@@ -69,35 +113,303 @@ impl SurfaceFrame {
     }
 }
 
+/// A client buffer backed by dmabufs, committed to a surface.
+#[derive(Debug, Clone)]
+pub struct DmabufFrame {
+    pub owner_id: u32,
+    pub surface_id: u32,
+    /// Main surface of the sub-surface tree; equal to `surface_id` for main surfaces.
+    pub root_id: u32,
+    /// The `wl_buffer` of the owner the frame was committed with.
+    pub buffer_id: u32,
+    pub attributes: Arc<DmabufAttributes>,
+    /// Parts of the buffer that changed since the surface's previous frame.
+    pub damage: Vec<Rect>,
+    /// Scale the buffer is drawn at; at least 1.
+    pub scale: u32,
+    /// Transform the client drew the buffer with.
+    pub transform: Transform,
+    /// How the surface crops and scales the buffer.
+    pub viewport: Viewport,
+}
+
+/// How a buffer is shown on its surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BufferGeometry {
+    size: (u32, u32),
+    scale: u32,
+    transform: Transform,
+    viewport: Viewport,
+}
+
+impl BufferGeometry {
+    /// Size of the surface before the viewport crops and scales the buffer.
+    fn unviewported_size(&self) -> (u32, u32) {
+        let (width, height) = self.transform.apply_size(self.size);
+        (width / self.scale, height / self.scale)
+    }
+
+    /// Size of the surface showing the buffer.
+    fn surface_size(&self) -> (u32, u32) {
+        let (width, height) = self.unviewported_size();
+        let size = (width as i32, height as i32);
+        let (width, height) = self.viewport.surface_size(size).unwrap_or(size);
+        (width as u32, height as u32)
+    }
+
+    /// The part of the buffer shown, `[x, y, width, height]` as fractions
+    /// of the surface before the viewport.
+    fn source(&self) -> [f32; 4] {
+        let (width, height) = self.unviewported_size();
+        let (x, y, source_width, source_height) =
+            self.viewport.source_rect((width as i32, height as i32));
+        [
+            x / width as f32,
+            y / height as f32,
+            source_width / width as f32,
+            source_height / height as f32,
+        ]
+    }
+
+    /// The part of the surface showing a rectangle of the buffer, rounded
+    /// out to whole surface pixels.
+    fn surface_rect(&self, rect: Rect) -> Rect {
+        let (x, y, width, height) = self.transform.invert().apply_rect(
+            (rect.x, rect.y, rect.width, rect.height),
+            (self.size.0 as i32, self.size.1 as i32),
+        );
+        let scale = self.scale as i32;
+        let left = x.div_euclid(scale);
+        let top = y.div_euclid(scale);
+        let (unviewported_width, unviewported_height) = self.unviewported_size();
+        let (x, y, width, height) = self.viewport.destination_of(
+            (
+                left,
+                top,
+                (x + width + scale - 1).div_euclid(scale) - left,
+                (y + height + scale - 1).div_euclid(scale) - top,
+            ),
+            (unviewported_width as i32, unviewported_height as i32),
+        );
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// A client dmabuf shown on a surface.
+#[derive(Debug, Clone, Copy)]
+struct SurfaceDmabuf {
+    /// Main surface of the sub-surface tree.
+    root_id: u32,
+    buffer_id: u32,
+    scale: u32,
+    transform: Transform,
+    viewport: Viewport,
+}
+
+/// The image a window is drawn from.
+struct WindowImage {
+    view: vk::ImageView,
+    /// Size of the window.
+    size: (u32, u32),
+    /// The part of the image shown, as fractions of the whole.
+    source: [f32; 4],
+    transform: Transform,
+    /// The image is stored bottom to top.
+    y_invert: bool,
+}
+
+/// A client dmabuf shown on a surface, imported on first use.
+struct ClientDmabuf {
+    attributes: Arc<DmabufAttributes>,
+    image: Option<ImportedDmaBuf>,
+    /// The client committed new contents since the image was last acquired.
+    needs_acquire: bool,
+    /// The client destroyed the buffer; it is dropped once no surface or
+    /// output shows it.
+    destroyed: bool,
+    /// The buffer imported on the DRM cards it was scanned out on; `None`
+    /// where the card cannot import it.
+    scanout_fbs: HashMap<PathBuf, Option<DrmFramebuffer>>,
+}
+
+/// One surface of a sub-surface tree, relative to the main surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceLayer {
+    pub surface_id: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A render target imported as a DRM framebuffer on its output's card.
+struct ScanoutBuffer {
+    /// Dropped first, which waits for the GPU to finish drawing the buffer.
+    frame: RefCell<QuadFrame>,
+    drm_fb: DrmFramebuffer,
+    framebuffer: Framebuffer,
+    _dma_image: DmaBufImage,
+}
+
+/// What [`RendererState::draw_scene`] drew into a scanout buffer.
+struct DrawnScene {
+    windows: Vec<(u32, u32)>,
+    /// Signaled once drawing finishes, `None` if it has already.
+    in_fence: Option<OwnedFd>,
+}
+
+/// The cursor plane of an output and the images it flips between.
+struct OutputCursor {
+    buffers: Vec<CursorBuffer>,
+    /// Index of the buffer holding the latest image.
+    front: usize,
+    /// `cursor_serial` of the image in the front buffer.
+    serial: Option<u64>,
+    /// What the cursor plane was last committed to show.
+    committed: Option<CursorState>,
+    size: (u32, u32),
+}
+
+impl OutputCursor {
+    fn new(drm_fd: BorrowedFd<'_>) -> anyhow::Result<Self> {
+        let size = cursor_plane_size(drm_fd);
+        let buffers = (0..CURSOR_BUFFER_COUNT)
+            .map(|_| CursorBuffer::create(drm_fd, size))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            buffers,
+            front: 0,
+            serial: None,
+            committed: None,
+            size,
+        })
+    }
+}
+
 struct OutputScanout {
     drm_path: PathBuf,
     output: ConnectedOutput,
+    /// Scale in percent the layout is shown at.
+    scale: u32,
+    /// Transform the layout is shown with.
+    transform: Transform,
+    /// Location of the output in the layout.
+    location: (i32, i32),
     _mode_blob: ModeBlob,
-    _drm_fb: DrmFramebuffer,
-    _dma_image: DmaBufImage,
+    buffers: Vec<ScanoutBuffer>,
+    /// `None` if the output has no cursor plane; no cursor is shown on it then.
+    cursor: Option<OutputCursor>,
+    flip: FlipState,
+    /// Damage of the frames composed into `buffers`.
+    damage_history: DamageHistory,
+    /// Windows of the last composed frame, bottom to top, in layout
+    /// coordinates.
+    drawn_windows: Vec<Placement>,
+    /// Changed contents of windows since the last composed frame, in window
+    /// coordinates.
+    window_damage: HashMap<(u32, u32), Damage>,
+}
+
+impl OutputScanout {
+    /// Whether this scanout drives `target` without another modeset.
+    fn matches(&self, target: &PresentTarget) -> bool {
+        self.drm_path == target.drm_path
+            && self.output.connector_id == target.output.connector_id
+            && self.output.crtc_id == target.output.crtc_id
+            && self.output.plane_id == target.output.plane_id
+            && self.output.mode == target.output.mode
+            && self.transform == target.transform
+    }
+
+    /// Size in pixels of the part of the layout shown, at the scale of the
+    /// output.
+    fn layout_size(&self) -> (u32, u32) {
+        layout_size(&self.output, self.transform)
+    }
+
+    /// Transform to draw the layout into the framebuffers with; see
+    /// [`render_transform`].
+    fn render_transform(&self) -> Transform {
+        render_transform(&self.output, self.transform)
+    }
 }
 
 pub struct RendererState {
-    // Drop order: scanouts → vulkan → drm_devices.
-    drm_devices: DrmDevices,
+    // Drop order: scanouts → scene GPU resources → vulkan → drm_devices.
+    scanouts: HashMap<String, OutputScanout>,
+    quad_renderer: Option<QuadRenderer>,
+    /// Composed contents of every window in the scene, except for windows
+    /// drawn straight from a lone client dmabuf.
+    surface_textures: HashMap<(u32, u32), Texture>,
+    /// Client dmabufs keyed by `(owner_id, buffer_id)`.
+    client_dmabufs: HashMap<(u32, u32), ClientDmabuf>,
     vulkan: Option<VulkanContext>,
+    /// Formats and modifiers the render device imports client dmabufs in.
+    dmabuf_formats: Option<DmabufFormats>,
+    drm_devices: DrmDevices,
     /// Configured render device (`None` = auto).
     render_device: Option<PathBuf>,
     /// Per-connector overrides; missing names use defaults (enabled if connected).
     output_configs: HashMap<String, OutputConfig>,
-    scanouts: HashMap<String, OutputScanout>,
-    active_surface_frame: Option<SurfaceFrame>,
+    /// Configured locations of outputs in the layout; the others are placed
+    /// by [`arrange_outputs`].
+    output_locations: HashMap<String, (i32, i32)>,
+    clear_color: [f32; 4],
+    /// An output changed while a page flip was pending on it; it is set up
+    /// again once the flip completed.
+    needs_modeset: bool,
+    /// Latest SHM frame of every surface, keyed by `(owner_id, surface_id)`.
+    surface_frames: HashMap<(u32, u32), SurfaceFrame>,
+    /// Surfaces showing a client dmabuf instead of an SHM frame.
+    surface_dmabufs: HashMap<(u32, u32), SurfaceDmabuf>,
+    /// Sub-surface stacking per main surface, bottom to top.
+    surface_trees: HashMap<(u32, u32), Vec<SurfaceLayer>>,
+    scene: Scene,
+    /// Windows whose texture no longer matches their frames, with the parts
+    /// that changed in texture coordinates.
+    stale_textures: HashMap<(u32, u32), Damage>,
+    cursor: Cursor,
+    /// Pointer position in the global layout.
+    cursor_position: (i32, i32),
+    /// Latest frame of every cursor surface, keyed by `(owner_id, surface_id)`.
+    cursor_frames: HashMap<(u32, u32), SurfaceFrame>,
+    /// Image and hotspot of [`Cursor::Default`]; `None` if the theme has none.
+    default_cursor: Option<(SurfaceFrame, (i32, i32))>,
+    /// Bumped whenever the cursor image changes, so that every output
+    /// writes it into its cursor buffers again.
+    cursor_serial: u64,
 }
 
 impl RendererState {
     pub fn new() -> anyhow::Result<Self> {
         Ok(Self {
-            drm_devices: DrmDevices::new()?,
+            scanouts: HashMap::new(),
+            quad_renderer: None,
+            surface_textures: HashMap::new(),
+            client_dmabufs: HashMap::new(),
             vulkan: None,
+            dmabuf_formats: None,
+            drm_devices: DrmDevices::new()?,
             render_device: None,
             output_configs: HashMap::new(),
-            scanouts: HashMap::new(),
-            active_surface_frame: None,
+            output_locations: HashMap::new(),
+            clear_color: SOLID_CLEAR_COLOR,
+            needs_modeset: false,
+            surface_frames: HashMap::new(),
+            surface_dmabufs: HashMap::new(),
+            surface_trees: HashMap::new(),
+            scene: Scene::default(),
+            stale_textures: HashMap::new(),
+            cursor: Cursor::Default,
+            cursor_position: (0, 0),
+            cursor_frames: HashMap::new(),
+            default_cursor: load_default_cursor()
+                .inspect_err(|err| warn!("No default cursor: {err:#}"))
+                .ok(),
+            cursor_serial: 0,
         })
     }
 
@@ -115,51 +427,292 @@ impl RendererState {
             .collect()
     }
 
+    /// Outputs that are currently scanned out, by name.
+    pub fn outputs(&self) -> Vec<Output> {
+        let mut outputs: Vec<Output> = self
+            .scanouts
+            .values()
+            .map(|scanout| Output {
+                name: scanout.output.connector_name.clone(),
+                description: scanout.drm_path.display().to_string(),
+                location: scanout.location,
+                size: (
+                    scanout.output.mode.width() as i32,
+                    scanout.output.mode.height() as i32,
+                ),
+                scale: scanout.scale,
+                transform: scanout.transform,
+            })
+            .collect();
+        outputs.sort_by(|a, b| a.name.cmp(&b.name));
+        outputs
+    }
+
     /// Drain pending udev DRM events; update device paths and/or connectors.
     pub fn dispatch(&mut self) -> anyhow::Result<DrmDispatchResult> {
         self.drm_devices.dispatch()
     }
 
-    /// Replace and immediately present the current single-surface scene.
+    /// Store a surface's latest frame and redraw the scene.
+    ///
+    /// Main surfaces are added on top of the scene when their first frame
+    /// arrives; sub-surfaces are drawn as part of their main surface's tree.
     pub fn set_surface_frame(&mut self, frame: SurfaceFrame) -> anyhow::Result<()> {
         frame.validate()?;
-        self.active_surface_frame = Some(frame);
+        let key = (frame.owner_id, frame.surface_id);
+        if frame.root_id == frame.surface_id {
+            self.scene.map(key);
+        }
+        let (texture_damage, window_damage) =
+            self.surface_damage(key, frame.root_id, frame.geometry(), &frame.damage);
+        self.damage_window(
+            (frame.owner_id, frame.root_id),
+            &texture_damage,
+            &window_damage,
+        );
+        self.outdate_pending_frames((frame.owner_id, frame.root_id));
+        self.surface_frames.insert(key, frame);
+        if self.surface_dmabufs.remove(&key).is_some() {
+            self.release_unused_dmabufs();
+        }
         if self.drm_devices.opened().is_empty() {
             return Ok(());
         }
-        self.present_enabled_outputs(SOLID_CLEAR_COLOR)
+        self.redraw_outputs()
     }
 
-    pub fn remove_surface_frame(&mut self, owner_id: u32, surface_id: u32) {
-        if self
-            .active_surface_frame
-            .as_ref()
-            .is_some_and(|frame| frame.owner_id == owner_id && frame.surface_id == surface_id)
+    /// Show a client dmabuf on a surface and redraw the scene.
+    ///
+    /// Like [`Self::set_surface_frame`], but the buffer is imported and
+    /// sampled by the GPU instead of being copied. The buffer stays imported
+    /// until [`Self::remove_dmabuf_buffer`] and no surface shows it anymore.
+    pub fn set_surface_dmabuf(&mut self, frame: DmabufFrame) -> anyhow::Result<()> {
+        let key = (frame.owner_id, frame.surface_id);
+        if frame.root_id == frame.surface_id {
+            self.scene.map(key);
+        }
+        let geometry = BufferGeometry {
+            size: (frame.attributes.width, frame.attributes.height),
+            scale: frame.scale.max(1),
+            transform: frame.transform,
+            viewport: frame.viewport,
+        };
+        let (texture_damage, window_damage) =
+            self.surface_damage(key, frame.root_id, geometry, &frame.damage);
+        self.damage_window(
+            (frame.owner_id, frame.root_id),
+            &texture_damage,
+            &window_damage,
+        );
+        self.outdate_pending_frames((frame.owner_id, frame.root_id));
+        self.surface_frames.remove(&key);
+        self.client_dmabufs
+            .entry((frame.owner_id, frame.buffer_id))
+            .or_insert_with(|| ClientDmabuf {
+                attributes: frame.attributes,
+                image: None,
+                needs_acquire: true,
+                destroyed: false,
+                scanout_fbs: HashMap::new(),
+            })
+            .needs_acquire = true;
+        self.surface_dmabufs.insert(
+            key,
+            SurfaceDmabuf {
+                root_id: frame.root_id,
+                buffer_id: frame.buffer_id,
+                scale: geometry.scale,
+                transform: frame.transform,
+                viewport: frame.viewport,
+            },
+        );
+        self.release_unused_dmabufs();
+        if self.drm_devices.opened().is_empty() {
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    /// Forget a client dmabuf once no surface or output shows it anymore.
+    pub fn remove_dmabuf_buffer(&mut self, owner_id: u32, buffer_id: u32) {
+        if let Some(dmabuf) = self.client_dmabufs.get_mut(&(owner_id, buffer_id)) {
+            dmabuf.destroyed = true;
+            self.release_unused_dmabufs();
+        }
+    }
+
+    /// Formats and modifiers client dmabufs can be imported in, once a
+    /// render device is in use.
+    pub fn dmabuf_formats(&self) -> Option<DmabufFormats> {
+        self.dmabuf_formats.clone()
+    }
+
+    /// Replace the sub-surface stacking of a main surface.
+    pub fn set_surface_tree(
+        &mut self,
+        owner_id: u32,
+        root_id: u32,
+        layers: Vec<SurfaceLayer>,
+    ) -> anyhow::Result<()> {
+        let key = (owner_id, root_id);
+        self.surface_trees.insert(key, layers);
+        self.damage_window(key, &Damage::Full, &Damage::Full);
+        self.outdate_pending_frames(key);
+        if !self.scene.contains(key) || self.drm_devices.opened().is_empty() {
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    /// Move a main surface to `(x, y)` in the global layout.
+    pub fn set_surface_position(
+        &mut self,
+        owner_id: u32,
+        root_id: u32,
+        x: i32,
+        y: i32,
+    ) -> anyhow::Result<()> {
+        if !self.scene.set_position((owner_id, root_id), (x, y))
+            || self.drm_devices.opened().is_empty()
         {
-            self.active_surface_frame = None;
-            if !self.drm_devices.opened().is_empty() {
-                if let Err(error) = self.present_enabled_outputs(SOLID_CLEAR_COLOR) {
-                    error!("Failed to clear removed Wayland surface: {error:#}");
-                }
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    /// Draw a main surface with `opacity` percent opacity.
+    pub fn set_surface_opacity(
+        &mut self,
+        owner_id: u32,
+        root_id: u32,
+        opacity: u32,
+    ) -> anyhow::Result<()> {
+        if !self
+            .scene
+            .set_opacity((owner_id, root_id), opacity.min(100))
+            || self.drm_devices.opened().is_empty()
+        {
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    /// Show or hide a main surface, e.g. when its workspace is switched.
+    ///
+    /// Hidden surfaces keep their contents but are not drawn, so their
+    /// frames are not reported by [`Self::dispatch_page_flips`].
+    pub fn set_surface_visible(
+        &mut self,
+        owner_id: u32,
+        root_id: u32,
+        visible: bool,
+    ) -> anyhow::Result<()> {
+        if !self.scene.set_visible((owner_id, root_id), visible)
+            || self.drm_devices.opened().is_empty()
+        {
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    /// Draw a new frame for a visible main surface whose contents did not
+    /// change, so that [`Self::dispatch_page_flips`] reports it.
+    pub fn request_frame(&mut self, owner_id: u32, root_id: u32) -> anyhow::Result<()> {
+        let key = (owner_id, root_id);
+        if !self.scene.is_visible(key) || self.drm_devices.opened().is_empty() {
+            return Ok(());
+        }
+        self.outdate_pending_frames(key);
+        self.redraw_outputs()
+    }
+
+    /// Stack a main surface above all others.
+    pub fn raise_surface(&mut self, owner_id: u32, root_id: u32) -> anyhow::Result<()> {
+        if !self.scene.raise((owner_id, root_id)) || self.drm_devices.opened().is_empty() {
+            return Ok(());
+        }
+        self.redraw_outputs()
+    }
+
+    pub fn remove_surface_frame(&mut self, owner_id: u32, surface_id: u32) {
+        let key = (owner_id, surface_id);
+        if self.cursor_frames.remove(&key).is_some() {
+            if self.shows_cursor_surface(key) {
+                self.cursor_changed();
+            }
+            return;
+        }
+        self.surface_trees.remove(&key);
+        let root_id = if let Some(frame) = self.surface_frames.remove(&key) {
+            frame.root_id
+        } else if let Some(dmabuf) = self.surface_dmabufs.remove(&key) {
+            self.release_unused_dmabufs();
+            dmabuf.root_id
+        } else {
+            return;
+        };
+        self.scene.unmap(key);
+        self.damage_window((owner_id, root_id), &Damage::Full, &Damage::Full);
+        if !self.drm_devices.opened().is_empty() {
+            if let Err(error) = self.redraw_outputs() {
+                error!("Failed to clear removed Wayland surface: {error:#}");
             }
         }
     }
 
     pub fn remove_client_frames(&mut self, owner_id: u32) {
-        if self
-            .active_surface_frame
-            .as_ref()
-            .is_some_and(|frame| frame.owner_id == owner_id)
-        {
-            self.active_surface_frame = None;
-            if !self.drm_devices.opened().is_empty() {
-                if let Err(error) = self.present_enabled_outputs(SOLID_CLEAR_COLOR) {
-                    error!("Failed to clear disconnected Wayland surface: {error:#}");
-                }
+        self.surface_trees
+            .retain(|(owner, _), _| *owner != owner_id);
+        self.surface_frames
+            .retain(|(owner, _), _| *owner != owner_id);
+        self.surface_dmabufs
+            .retain(|(owner, _), _| *owner != owner_id);
+        self.cursor_frames
+            .retain(|(owner, _), _| *owner != owner_id);
+        // Buffers still scanned out are dropped once flipped away from.
+        for ((owner, _), dmabuf) in &mut self.client_dmabufs {
+            dmabuf.destroyed |= *owner == owner_id;
+        }
+        self.release_unused_dmabufs();
+        self.stale_textures
+            .retain(|(owner, _), _| *owner != owner_id);
+        if self.scene.remove_owner(owner_id) && !self.drm_devices.opened().is_empty() {
+            if let Err(error) = self.redraw_outputs() {
+                error!("Failed to clear disconnected Wayland surface: {error:#}");
             }
         }
     }
 
+    /// Store the latest frame of a cursor surface, updating the cursor
+    /// planes if it is the current cursor.
+    pub fn set_cursor_frame(&mut self, frame: SurfaceFrame) -> anyhow::Result<()> {
+        frame.validate()?;
+        let key = (frame.owner_id, frame.surface_id);
+        self.cursor_frames.insert(key, frame);
+        if self.shows_cursor_surface(key) {
+            self.cursor_changed();
+        }
+        Ok(())
+    }
+
+    /// Change what the pointer looks like. Only the cursor planes are
+    /// updated; the scene is not redrawn.
+    pub fn set_cursor(&mut self, cursor: Cursor) {
+        if self.cursor != cursor {
+            self.cursor = cursor;
+            self.cursor_changed();
+        }
+    }
+
+    /// Move the cursor to `(x, y)` in the global layout. Only the cursor
+    /// planes are updated; the scene is not redrawn.
+    pub fn move_cursor(&mut self, x: i32, y: i32) {
+        if self.cursor_position != (x, y) {
+            self.cursor_position = (x, y);
+            self.update_cursor_planes();
+        }
+    }
+
     /// Open missing DRM devices via the seat (fresh open after VT resume).
     pub fn activate_drm(&mut self, seat: &SeatState) -> anyhow::Result<()> {
         self.drm_devices.activate(seat)
@@ -167,13 +720,13 @@ impl RendererState {
 
     /// Close seat-opened DRM devices after session disable was acknowledged.
     pub fn deactivate_drm(&mut self, seat: &SeatState) {
-        self.scanouts.clear();
+        self.clear_scanouts();
         self.drm_devices.deactivate(seat);
     }
 
     /// Close removed / open newly discovered DRM devices while the seat is active.
     pub fn reconcile_drm(&mut self, seat: &SeatState) -> anyhow::Result<()> {
-        self.scanouts.clear();
+        self.clear_scanouts();
         self.drm_devices.reconcile(seat)
     }
 
@@ -182,7 +735,7 @@ impl RendererState {
         info!("Render device config: {path:?}");
         self.render_device = path;
         if !self.drm_devices.opened().is_empty() {
-            self.present_enabled_outputs(SOLID_CLEAR_COLOR)?;
+            self.present_enabled_outputs(self.clear_color)?;
         }
         Ok(())
     }
@@ -191,34 +744,119 @@ impl RendererState {
     pub fn set_output_configs(&mut self, configs: Vec<OutputConfig>) -> anyhow::Result<()> {
         for config in configs {
             info!(
-                "Output config: {} enabled={} mode={:?}",
-                config.name, config.enabled, config.mode_name
+                "Output config: {} enabled={} mode={:?} scale={:?} transform={}",
+                config.name,
+                config.enabled,
+                config.mode_name,
+                config.scale,
+                config.transform.name()
             );
             self.output_configs.insert(config.name.clone(), config);
         }
         if !self.drm_devices.opened().is_empty() {
-            self.present_enabled_outputs(SOLID_CLEAR_COLOR)?;
+            self.present_enabled_outputs(self.clear_color)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the configured output locations. Re-presents if the seat is
+    /// active and they changed.
+    pub fn set_output_locations(
+        &mut self,
+        locations: HashMap<String, (i32, i32)>,
+    ) -> anyhow::Result<()> {
+        if self.output_locations == locations {
+            return Ok(());
+        }
+        info!("Output locations: {locations:?}");
+        self.output_locations = locations;
+        if !self.drm_devices.opened().is_empty() {
+            self.present_enabled_outputs(self.clear_color)?;
         }
         Ok(())
     }
 
-    /// Present a solid clear on every enabled connected output (any card).
+    /// Register the fd that becomes readable when page flips complete; see
+    /// [`Self::dispatch_page_flips`].
+    pub fn register_page_flips(&self, registry: &Registry, token: Token) -> io::Result<()> {
+        registry.register(
+            &mut SourceFd(&self.drm_devices.events_fd()),
+            token,
+            Interest::READABLE,
+        )
+    }
+
+    /// Complete the page flips reported by the DRM devices, then draw the
+    /// outputs whose scene changed while their flip was pending.
     ///
-    /// Buffers are allocated on the selected render GPU and imported on each
-    /// output's DRM card (same- or cross-device). Failures are logged per output.
-    pub fn present_enabled_outputs(&mut self, color: [f32; 4]) -> anyhow::Result<()> {
-        let frame = self.active_surface_frame.take();
-        let result = self.present_enabled_outputs_with_frame(color, frame.as_ref());
-        self.active_surface_frame = frame;
-        result
+    /// Returns the windows that reached the screen with each flip.
+    pub fn dispatch_page_flips(&mut self) -> anyhow::Result<Vec<PresentedFrame>> {
+        let mut presented = Vec::new();
+        let mut redraw = Vec::new();
+        let mut cursor_updates = Vec::new();
+        for (drm_path, flip) in self.drm_devices.dispatch_page_flips()? {
+            let Some((name, scanout)) = self
+                .scanouts
+                .iter_mut()
+                .find(|(_, scanout)| scanout.flip.reports(&drm_path, flip.crtc_id))
+            else {
+                continue;
+            };
+            let Some(completed) = scanout.flip.completed() else {
+                continue;
+            };
+            let client_buffer = match completed.source {
+                ScanoutSource::Composed(_) => None,
+                ScanoutSource::Client(key) => Some(key),
+            };
+            presented.push(PresentedFrame {
+                output: name.clone(),
+                tv_sec: u64::from(flip.tv_sec),
+                tv_nsec: flip.tv_usec * 1000,
+                refresh_nsec: scanout.output.mode.refresh_nsec(),
+                sequence: u64::from(flip.sequence),
+                windows: completed.windows,
+                client_buffer,
+            });
+            match completed.follow_up {
+                FlipFollowUp::Redraw => redraw.push(name.clone()),
+                FlipFollowUp::UpdateCursor => cursor_updates.push(name.clone()),
+                FlipFollowUp::None => {}
+            }
+        }
+        // Client buffers that were flipped away from may be dropped now.
+        self.release_unused_dmabufs();
+
+        if std::mem::take(&mut self.needs_modeset) {
+            self.present_enabled_outputs(self.clear_color)?;
+            return Ok(presented);
+        }
+        for name in redraw {
+            if let Err(err) = self.redraw_output(&name) {
+                error!("Failed to redraw {name}: {err:#}");
+            }
+        }
+        for name in cursor_updates {
+            if let Err(err) = self.update_cursor_plane(&name) {
+                error!("Failed to update cursor on {name}: {err:#}");
+            }
+        }
+        Ok(presented)
     }
 
-    fn present_enabled_outputs_with_frame(
-        &mut self,
-        color: [f32; 4],
-        frame: Option<&SurfaceFrame>,
-    ) -> anyhow::Result<()> {
-        self.scanouts.clear();
+    /// Present the scene over a solid clear on every enabled connected output (any card).
+    ///
+    /// Buffers are allocated on the selected render GPU and imported on each
+    /// output's DRM card (same- or cross-device). Outputs are only modeset when
+    /// they are new or their mode changed; the others flip to a redrawn buffer.
+    /// Failures are logged per output.
+    pub fn present_enabled_outputs(&mut self, color: [f32; 4]) -> anyhow::Result<()> {
+        if self.clear_color != color {
+            for scanout in self.scanouts.values_mut() {
+                scanout.damage_history.reset();
+            }
+        }
+        self.clear_color = color;
 
         let Some(render_path) = self.resolved_render_device_path() else {
             warn!("No render device available; skipping presentation");
@@ -229,14 +867,33 @@ impl RendererState {
         self.ensure_vulkan(&render_path)?;
 
         let targets = self.collect_present_targets();
+        self.scanouts
+            .retain(|name, _| targets.iter().any(|target| target.connector_name == *name));
         if targets.is_empty() {
             warn!("No enabled connected outputs to present");
             return Ok(());
         }
 
+        self.update_surface_textures()?;
+
         let mut presented = 0usize;
         for target in targets {
-            match self.present_one_output(&target, color, frame) {
+            match self.scanouts.get(&target.connector_name) {
+                Some(scanout) if scanout.matches(&target) => {
+                    self.set_output_scale(&target.connector_name, target.scale);
+                    self.set_output_location(&target.connector_name, target.location);
+                    if let Err(err) = self.redraw_output(&target.connector_name) {
+                        error!("Failed to redraw {}: {err:#}", target.connector_name);
+                    }
+                    continue;
+                }
+                Some(scanout) if scanout.flip.is_pending() => {
+                    self.needs_modeset = true;
+                    continue;
+                }
+                _ => {}
+            }
+            match self.modeset_output(&target) {
                 Ok(scanout) => {
                     info!(
                         "Presented {} on {} (CRTC {}, {}x{}@{}Hz)",
@@ -261,10 +918,552 @@ impl RendererState {
             }
         }
 
-        info!("Presented {presented} output(s)");
+        if presented > 0 {
+            info!("Presented {presented} output(s)");
+        }
+        Ok(())
+    }
+
+    /// Show the layout on an output at another scale, repainting it entirely.
+    fn set_output_scale(&mut self, name: &str, scale: u32) {
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return;
+        };
+        if scanout.scale == scale {
+            return;
+        }
+        scanout.scale = scale;
+        scanout.damage_history.reset();
+        if let Some(output_cursor) = &mut scanout.cursor {
+            output_cursor.serial = None;
+        }
+    }
+
+    /// Show another part of the layout on an output, repainting it entirely.
+    fn set_output_location(&mut self, name: &str, location: (i32, i32)) {
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return;
+        };
+        if scanout.location == location {
+            return;
+        }
+        scanout.location = location;
+        scanout.damage_history.reset();
+    }
+
+    /// Drop the destroyed client dmabufs that no surface or output shows anymore.
+    fn release_unused_dmabufs(&mut self) {
+        self.client_dmabufs.retain(|&key, dmabuf| {
+            !dmabuf.destroyed
+                || self
+                    .surface_dmabufs
+                    .iter()
+                    .any(|((owner, _), dmabuf)| (*owner, dmabuf.buffer_id) == key)
+                || self
+                    .scanouts
+                    .values()
+                    .any(|scanout| scanout.flip.shows_client_buffer(key))
+        });
+    }
+
+    /// Stop scanning out before the DRM devices are closed, together with
+    /// the client dmabufs imported on them.
+    fn clear_scanouts(&mut self) {
+        self.scanouts.clear();
+        for dmabuf in self.client_dmabufs.values_mut() {
+            dmabuf.scanout_fbs.clear();
+        }
+        self.release_unused_dmabufs();
+    }
+
+    /// Keep a window's frame callbacks from completing with flips queued
+    /// before its contents changed.
+    fn outdate_pending_frames(&mut self, key: (u32, u32)) {
+        for scanout in self.scanouts.values_mut() {
+            scanout.flip.outdate_window(key);
+        }
+    }
+
+    /// Damage of a new buffer of a surface, in the coordinates of its
+    /// window's texture and of the window: the changed parts, or all of the
+    /// surface if its size, scale or transform changed.
+    fn surface_damage(
+        &self,
+        key: (u32, u32),
+        root_id: u32,
+        geometry: BufferGeometry,
+        damage: &[Rect],
+    ) -> (Damage, Damage) {
+        let root = (key.0, root_id);
+        let (x, y) = self
+            .surface_trees
+            .get(&root)
+            .and_then(|layers| layers.iter().find(|layer| layer.surface_id == key.1))
+            .map_or((0, 0), |layer| (layer.x, layer.y));
+        let mut window_damage = Damage::default();
+        let previous = self.surface_geometry(key);
+        if previous != Some(geometry) {
+            // The previous extent may be uncovered.
+            for geometry in previous.into_iter().chain([geometry]) {
+                window_damage.add(Rect::from_size(geometry.surface_size()).translate(x, y));
+            }
+            return (Damage::Full, window_damage);
+        }
+
+        let mut texture_damage = Damage::default();
+        let composed = self.has_subsurfaces(root);
+        let root_scale = match key.1 == root_id {
+            true => geometry.scale,
+            false => self.surface_geometry(root).map_or(1, |root| root.scale),
+        };
+        for &rect in damage {
+            let window_rect = geometry.surface_rect(rect).translate(x, y);
+            window_damage.add(window_rect);
+            // Lone buffers are their window's texture as they are.
+            texture_damage.add(match composed {
+                true => window_rect.scale(root_scale as i32),
+                false => rect,
+            });
+        }
+        (texture_damage, window_damage)
+    }
+
+    /// Mark parts of a window as changed, for its texture and every output.
+    fn damage_window(&mut self, key: (u32, u32), texture_damage: &Damage, window_damage: &Damage) {
+        self.stale_textures
+            .entry(key)
+            .or_default()
+            .merge(texture_damage);
+        for scanout in self.scanouts.values_mut() {
+            scanout
+                .window_damage
+                .entry(key)
+                .or_default()
+                .merge(window_damage);
+        }
+    }
+
+    /// Draw the scene on every output, setting them up first if there are none.
+    fn redraw_outputs(&mut self) -> anyhow::Result<()> {
+        if self.scanouts.is_empty() {
+            return self.present_enabled_outputs(self.clear_color);
+        }
+        self.update_surface_textures()?;
+        let names: Vec<String> = self.scanouts.keys().cloned().collect();
+        for name in names {
+            if let Err(err) = self.redraw_output(&name) {
+                error!("Failed to redraw {name}: {err:#}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the scene into the back buffer of an output and queue a page flip
+    /// to it. While a flip is pending the redraw is deferred until it completes.
+    ///
+    /// A client dmabuf filling the output is flipped to directly instead,
+    /// when the output's card accepts it.
+    fn redraw_output(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return Ok(());
+        };
+        if !scanout.flip.begin_redraw() {
+            return Ok(());
+        }
+
+        let mut cursor_windows = Vec::new();
+        let cursor = self.prepare_cursor(name, &mut cursor_windows);
+        if self.flip_to_client_buffer(name, cursor)? {
+            if let Some(scanout) = self.scanouts.get_mut(name) {
+                scanout.flip.add_pending_windows(cursor_windows);
+            }
+            return Ok(());
+        }
+
+        let scanout = &self.scanouts[name];
+        let back = scanout.flip.back();
+        let size = scanout.layout_size();
+        let scale = scanout.scale;
+        let transform = scanout.render_transform();
+        let framebuffer_size = framebuffer_size(&scanout.output);
+        let location = scanout.location;
+        let placements = self.output_placements(location, scaled_size(size, scale));
+        let frame_damage =
+            output_damage(&scanout.drawn_windows, &placements, &scanout.window_damage)
+                .to_device(scale)
+                .transformed(transform, size);
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return Ok(());
+        };
+        let damage = scanout.damage_history.draw(back, frame_damage);
+        scanout.drawn_windows = placements;
+        scanout.window_damage.clear();
+        let clips = match damage {
+            Damage::Full => None,
+            damage => Some(damage.clipped(Rect::from_size(framebuffer_size))),
+        };
+
+        let scanout = &self.scanouts[name];
+        let drawn = self.draw_scene(
+            &scanout.buffers[back],
+            location,
+            size,
+            scale,
+            transform,
+            clips.as_deref(),
+        );
+        let DrawnScene {
+            mut windows,
+            in_fence,
+        } = match drawn {
+            Ok(drawn) => drawn,
+            Err(err) => {
+                // The buffer may be partly drawn.
+                if let Some(scanout) = self.scanouts.get_mut(name) {
+                    scanout.damage_history.reset();
+                }
+                return Err(err);
+            }
+        };
+        windows.extend(cursor_windows);
+        let scanout = &self.scanouts[name];
+        let buffer = &scanout.buffers[back];
+        let drm_device = self
+            .drm_devices
+            .opened()
+            .get(&scanout.drm_path)
+            .with_context(|| {
+                format!(
+                    "DRM device {} is no longer open",
+                    scanout.drm_path.display()
+                )
+            })?;
+        // Planes that cannot wait for drawing to finish get the buffer once it has.
+        let in_fence = match in_fence {
+            Some(_) if scanout.output.props.plane_in_fence_fd.is_none() => {
+                buffer.frame.borrow().wait()?;
+                None
+            }
+            in_fence => in_fence,
+        };
+        atomic_page_flip(
+            drm_device.fd(),
+            &scanout.output,
+            buffer.drm_fb.id(),
+            clips.as_deref(),
+            in_fence.as_ref().map(AsFd::as_fd),
+            cursor,
+        )
+        .context("Failed to queue page flip")?;
+
+        self.flip_queued(name, ScanoutSource::Composed(back), windows, cursor);
+        Ok(())
+    }
+
+    /// Queue a page flip straight to the client dmabuf of the topmost window
+    /// when it fills the output on its own.
+    ///
+    /// Returns `false` if the output has to be composed, including when a
+    /// test-only commit shows that its plane cannot scan the buffer out.
+    fn flip_to_client_buffer(
+        &mut self,
+        name: &str,
+        cursor: Option<CursorState>,
+    ) -> anyhow::Result<bool> {
+        let scanout = &self.scanouts[name];
+        let size = scanout.layout_size();
+        let placements = self.output_placements(scanout.location, scaled_size(size, scanout.scale));
+        let Some(top) = placements.last() else {
+            return Ok(false);
+        };
+        let shown = top.scaled(scanout.scale);
+        if (shown.x, shown.y, (shown.width, shown.height)) != (0, 0, size)
+            || top.opacity < 100
+            || self.surface_textures.contains_key(&top.key)
+        {
+            return Ok(false);
+        }
+        let Some(&SurfaceDmabuf {
+            buffer_id,
+            transform,
+            viewport: Viewport { source: None, .. },
+            ..
+        }) = self.surface_dmabufs.get(&top.key)
+        else {
+            return Ok(false);
+        };
+        // A buffer drawn with the transform of the output needs none.
+        if transform != scanout.render_transform() {
+            return Ok(false);
+        }
+        let key = (top.key.0, buffer_id);
+        let Some(dmabuf) = self.client_dmabufs.get_mut(&key) else {
+            return Ok(false);
+        };
+        let attributes = &dmabuf.attributes;
+        let opaque = drm_fourcc_to_vulkan(attributes.format).is_some_and(|(_, opaque)| opaque);
+        // The buffer has to match the pixels of the output, whatever its
+        // scale or viewport.
+        if !opaque
+            || attributes.y_invert
+            || attributes.planes.len() != 1
+            || (attributes.width, attributes.height) != framebuffer_size(&scanout.output)
+        {
+            return Ok(false);
+        }
+        let Some(drm_device) = self.drm_devices.opened().get(&scanout.drm_path) else {
+            return Ok(false);
+        };
+
+        let drm_fb = dmabuf
+            .scanout_fbs
+            .entry(scanout.drm_path.clone())
+            .or_insert_with(|| {
+                let plane = &attributes.planes[0];
+                DrmFramebuffer::from_dma_buf(
+                    drm_device.fd(),
+                    plane.fd.as_fd(),
+                    attributes.width,
+                    attributes.height,
+                    plane.stride,
+                    plane.offset,
+                    attributes.modifier,
+                    attributes.format,
+                )
+                .inspect_err(|err| debug!("Client dmabuf cannot be scanned out: {err:#}"))
+                .ok()
+            });
+        let Some(fb_id) = drm_fb.as_ref().map(DrmFramebuffer::id) else {
+            return Ok(false);
+        };
+        if let Err(err) = test_page_flip(drm_device.fd(), &scanout.output, fb_id, cursor) {
+            debug!("{name} cannot scan out client dmabuf directly: {err:#}");
+            return Ok(false);
+        }
+        atomic_page_flip(drm_device.fd(), &scanout.output, fb_id, None, None, cursor)
+            .context("Failed to queue page flip")?;
+
+        let windows = placements.iter().map(|placement| placement.key).collect();
+        self.flip_queued(name, ScanoutSource::Client(key), windows, cursor);
+        Ok(true)
+    }
+
+    /// Record a page flip queued on an output.
+    fn flip_queued(
+        &mut self,
+        name: &str,
+        source: ScanoutSource,
+        windows: Vec<(u32, u32)>,
+        cursor: Option<CursorState>,
+    ) {
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return;
+        };
+        scanout.flip.queued(source, windows);
+        if let Some(output_cursor) = &mut scanout.cursor {
+            output_cursor.committed = cursor;
+        }
+    }
+
+    /// Whether the cursor surface `key` is what the pointer shows.
+    fn shows_cursor_surface(&self, key: (u32, u32)) -> bool {
+        matches!(
+            self.cursor,
+            Cursor::Surface { owner_id, surface_id, .. } if (owner_id, surface_id) == key
+        )
+    }
+
+    /// Have every output write the cursor image again.
+    fn cursor_changed(&mut self) {
+        self.cursor_serial += 1;
+        self.update_cursor_planes();
+    }
+
+    /// Commit the cursor on every output with a cursor plane.
+    fn update_cursor_planes(&mut self) {
+        let names: Vec<String> = self
+            .scanouts
+            .iter()
+            .filter(|(_, scanout)| scanout.cursor.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            if let Err(err) = self.update_cursor_plane(&name) {
+                error!("Failed to update cursor on {name}: {err:#}");
+            }
+        }
+    }
+
+    /// Commit the cursor plane of an output on its own. While a page flip is
+    /// pending the update is deferred until it completes.
+    fn update_cursor_plane(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(scanout) = self.scanouts.get_mut(name) else {
+            return Ok(());
+        };
+        if !scanout.flip.begin_cursor_update() {
+            return Ok(());
+        }
+
+        let mut windows = Vec::new();
+        let Some(cursor) = self.prepare_cursor(name, &mut windows) else {
+            return Ok(());
+        };
+        let scanout = &self.scanouts[name];
+        if scanout
+            .cursor
+            .as_ref()
+            .is_some_and(|output_cursor| output_cursor.committed == Some(cursor))
+        {
+            return Ok(());
+        }
+        let drm_device = self
+            .drm_devices
+            .opened()
+            .get(&scanout.drm_path)
+            .with_context(|| {
+                format!(
+                    "DRM device {} is no longer open",
+                    scanout.drm_path.display()
+                )
+            })?;
+        atomic_cursor_update(drm_device.fd(), &scanout.output, cursor)
+            .context("Failed to queue cursor update")?;
+
+        // Completes with a flip event like any page flip, which keeps the
+        // primary plane where it is.
+        self.flip_queued(name, scanout.flip.front(), windows, Some(cursor));
         Ok(())
     }
 
+    /// Write the current cursor image into the cursor buffers of an output
+    /// if it changed, and return what its cursor plane should show.
+    ///
+    /// A cursor surface whose new contents were written is added to
+    /// `windows`, so its frame callbacks complete with the flip. Returns
+    /// `None` for outputs without a cursor plane.
+    fn prepare_cursor(&mut self, name: &str, windows: &mut Vec<(u32, u32)>) -> Option<CursorState> {
+        let scanout = self.scanouts.get_mut(name)?;
+        let scale = f64::from(scanout.scale) / 100.0;
+        // The cursor plane is not rotated with the primary plane.
+        let transform = scanout.transform;
+        let (layout_width, layout_height) = scanout.layout_size();
+        let location = scanout.location;
+        let output_cursor = scanout.cursor.as_mut()?;
+        let image = match self.cursor {
+            Cursor::Default => self
+                .default_cursor
+                .as_ref()
+                .map(|(frame, hotspot)| (frame, *hotspot)),
+            Cursor::Hidden => None,
+            Cursor::Surface {
+                owner_id,
+                surface_id,
+                hotspot,
+            } => self
+                .cursor_frames
+                .get(&(owner_id, surface_id))
+                .map(|frame| (frame, hotspot)),
+        };
+        let Some((frame, (hotspot_x, hotspot_y))) = image else {
+            return Some(CursorState::HIDDEN);
+        };
+        let frame = scaled_frame(frame, scale);
+        let (frame_width, frame_height) = (frame.width as i32, frame.height as i32);
+
+        if output_cursor.serial != Some(self.cursor_serial) {
+            let back = (output_cursor.front + 1) % output_cursor.buffers.len();
+            let frame = transformed_frame(&frame, transform);
+            output_cursor.buffers[back].write(
+                &frame.pixels,
+                frame.width as u32,
+                frame.height as u32,
+                frame.stride,
+                frame.format == WL_SHM_FORMAT_XRGB8888,
+            );
+            output_cursor.front = back;
+            output_cursor.serial = Some(self.cursor_serial);
+            if let Cursor::Surface {
+                owner_id,
+                surface_id,
+                ..
+            } = self.cursor
+            {
+                windows.push((owner_id, surface_id));
+            }
+        }
+
+        let (x, y) = self.cursor_position;
+        let to_device = |value: i32| (f64::from(value) * scale).round() as i32;
+        let (x, y, width, height) = transform.apply_rect(
+            (
+                to_device(x - location.0) - to_device(hotspot_x),
+                to_device(y - location.1) - to_device(hotspot_y),
+                frame_width,
+                frame_height,
+            ),
+            (layout_width as i32, layout_height as i32),
+        );
+        // The pointer is on another output.
+        let (mode_width, mode_height) = transform.apply_size((layout_width, layout_height));
+        if x >= mode_width as i32 || y >= mode_height as i32 || x + width <= 0 || y + height <= 0 {
+            return Some(CursorState::HIDDEN);
+        }
+        Some(CursorState {
+            fb_id: output_cursor.buffers[output_cursor.front].id(),
+            x,
+            y,
+            width: output_cursor.size.0,
+            height: output_cursor.size.1,
+        })
+    }
+
+    /// Draws the scene from `location` at `scale` percent with `transform`
+    /// applied into `buffer`, or just the `damage` rectangles of it into a
+    /// buffer drawn before.
+    ///
+    /// Returns the windows that were drawn.
+    fn draw_scene(
+        &self,
+        buffer: &ScanoutBuffer,
+        location: (i32, i32),
+        size: (u32, u32),
+        scale: u32,
+        transform: Transform,
+        damage: Option<&[Rect]>,
+    ) -> anyhow::Result<DrawnScene> {
+        let vulkan = self
+            .vulkan
+            .as_ref()
+            .context("VulkanContext missing during present")?;
+        let quad_renderer = self
+            .quad_renderer
+            .as_ref()
+            .context("Quad renderer missing during present")?;
+        let (windows, quads): (Vec<_>, Vec<_>) = self
+            .output_quads(location, size, scale, transform)
+            .into_iter()
+            .unzip();
+        let scissors: Option<Vec<_>> =
+            damage.map(|damage| damage.iter().copied().map(vk_rect).collect());
+        if scissors.as_ref().is_some_and(Vec::is_empty) {
+            return Ok(DrawnScene {
+                windows,
+                in_fence: None,
+            });
+        }
+        let in_fence = quad_renderer
+            .draw(
+                vulkan.device(),
+                &mut buffer.frame.borrow_mut(),
+                &buffer.framebuffer,
+                self.clear_color,
+                &quads,
+                scissors.as_deref(),
+            )
+            .context("Failed to draw scene")?;
+        Ok(DrawnScene { windows, in_fence })
+    }
+
     fn collect_present_targets(&self) -> Vec<PresentTarget> {
         let mut targets = Vec::new();
 
@@ -294,6 +1493,9 @@ impl RendererState {
                             drm_path: drm_path.clone(),
                             connector_name: connector.name.clone(),
                             output,
+                            scale: config.and_then(|c| c.scale).unwrap_or(100),
+                            transform: config.map(|c| c.transform).unwrap_or_default(),
+                            location: (0, 0),
                         });
                     }
                     Ok(None) => {}
@@ -309,73 +1511,426 @@ impl RendererState {
         }
 
         targets.sort_by(|a, b| a.connector_name.cmp(&b.connector_name));
+        let mut outputs: Vec<Output> = targets.iter().map(PresentTarget::output).collect();
+        arrange_outputs(&mut outputs, &self.output_locations);
+        for (target, output) in targets.iter_mut().zip(outputs) {
+            target.location = output.location;
+        }
         targets
     }
 
-    fn present_one_output(
+    /// Uploads the windows whose frames changed since they were last drawn.
+    ///
+    /// Failed uploads are logged and retried on the next present.
+    fn update_surface_textures(&mut self) -> anyhow::Result<()> {
+        let vulkan = self
+            .vulkan
+            .as_ref()
+            .context("VulkanContext missing during present")?;
+        if self.quad_renderer.is_none() {
+            self.quad_renderer = Some(QuadRenderer::new(vulkan.device(), SCANOUT_FORMAT)?);
+        }
+
+        let max_size = vulkan
+            .physical_device()
+            .properties()
+            .limits
+            .max_image_dimension2_d;
+        self.surface_textures
+            .retain(|key, _| self.scene.contains(*key));
+        let windows: Vec<_> = self.scene.windows().iter().map(|w| w.key).collect();
+        for key in windows {
+            let damage = match self.stale_textures.remove(&key) {
+                Some(damage) => damage,
+                None if self.window_image(key).is_some() => continue,
+                None => Damage::Full,
+            };
+            if let Err(err) = self.update_window_texture(key, max_size, &damage) {
+                error!("Failed to update Wayland surface {key:?}: {err:#}");
+            }
+        }
+        self.stale_textures.clear();
+        Ok(())
+    }
+
+    /// Brings the texture of a window up to date with its surfaces, given
+    /// the parts of it that changed.
+    ///
+    /// SHM trees are composed on the CPU and their damage is uploaded. Trees
+    /// with client dmabufs are composed on the GPU instead, and a lone dmabuf
+    /// is drawn as it is, without a texture.
+    fn update_window_texture(
         &mut self,
-        target: &PresentTarget,
-        color: [f32; 4],
-        frame: Option<&SurfaceFrame>,
-    ) -> anyhow::Result<OutputScanout> {
-        let width = target.output.mode.width();
-        let height = target.output.mode.height();
-        let format = vk::Format::B8G8R8A8_UNORM;
+        key: (u32, u32),
+        max_size: u32,
+        damage: &Damage,
+    ) -> anyhow::Result<()> {
+        let vulkan = self
+            .vulkan
+            .as_ref()
+            .context("VulkanContext missing during present")?;
+        let (owner_id, root_id) = key;
+        let root_layer = [SurfaceLayer {
+            surface_id: root_id,
+            x: 0,
+            y: 0,
+        }];
+        let layers = self
+            .surface_trees
+            .get(&key)
+            .map_or(&root_layer[..], Vec::as_slice);
+        let dmabuf_buffers: Vec<u32> = layers
+            .iter()
+            .filter_map(|layer| self.surface_dmabufs.get(&(owner_id, layer.surface_id)))
+            .map(|dmabuf| dmabuf.buffer_id)
+            .collect();
+
+        if dmabuf_buffers.is_empty() {
+            let Some(frame) = compose_surface_tree(
+                &self.surface_frames,
+                key,
+                self.surface_trees.get(&key).map(Vec::as_slice),
+            ) else {
+                return Ok(());
+            };
+            let reusable = self.surface_textures.remove(&key);
+            let texture = upload_surface_texture(vulkan, reusable, &frame, max_size, damage)?;
+            self.surface_textures.insert(key, texture);
+            return Ok(());
+        }
 
-        let (dma_image, fourcc) = {
-            let vulkan = self
-                .vulkan
-                .as_ref()
-                .context("VulkanContext missing during present")?;
+        for buffer_id in dmabuf_buffers {
+            if let Some(dmabuf) = self.client_dmabufs.get_mut(&(owner_id, buffer_id)) {
+                prepare_client_dmabuf(vulkan, dmabuf)?;
+            }
+        }
+        if layers.iter().all(|layer| layer.surface_id == root_id) {
+            self.surface_textures.remove(&key);
+            return Ok(());
+        }
+
+        // The tree is composed at the scale of the main surface.
+        let Some(root) = self.surface_geometry(key) else {
+            return Ok(());
+        };
+        let scale = root.scale;
+        let (root_width, root_height) = root.surface_size();
+        let target_size = (root_width * scale, root_height * scale);
+        // SHM layers are uploaded just for this composition.
+        let mut uploads = Vec::new();
+        let mut quads = Vec::new();
+        for layer in layers {
+            let surface_key = (owner_id, layer.surface_id);
+            let Some(geometry) = self.surface_geometry(surface_key) else {
+                continue;
+            };
+            let (view, y_invert) = if let Some((image, y_invert)) =
+                self.imported_dmabuf(surface_key)
+            {
+                (image.view(), y_invert)
+            } else if let Some(frame) = self.surface_frames.get(&surface_key) {
+                let texture = upload_surface_texture(vulkan, None, frame, max_size, &Damage::Full)?;
+                let view = texture.view();
+                uploads.push(texture);
+                (view, false)
+            } else {
+                continue;
+            };
+            let (width, height) = geometry.surface_size();
+            let placement = Placement {
+                key: surface_key,
+                x: layer.x * scale as i32,
+                y: layer.y * scale as i32,
+                width: width * scale,
+                height: height * scale,
+                opacity: 100,
+            };
+            quads.push(TexturedQuad {
+                view,
+                rect: placement.ndc_rect(target_size),
+                source: geometry.source(),
+                transform: geometry.transform,
+                y_invert,
+                output_transform: Transform::Normal,
+                opacity: 1.0,
+            });
+        }
 
-            let dma_image = DmaBufImage::allocate(
+        let extent = vk::Extent2D {
+            width: target_size.0.min(max_size),
+            height: target_size.1.min(max_size),
+        };
+        let texture = match self
+            .surface_textures
+            .remove(&key)
+            .filter(|texture| texture.extent() == extent)
+        {
+            Some(texture) => texture,
+            None => Texture::new(
                 vulkan.device(),
                 vulkan.physical_device(),
-                width,
-                height,
-                format,
+                extent.width,
+                extent.height,
+            )?,
+        };
+        let quad_renderer = self
+            .quad_renderer
+            .as_ref()
+            .context("Quad renderer missing during present")?;
+        quad_renderer
+            .compose(
+                vulkan.device(),
+                vulkan.graphics_command_pool(),
+                &texture,
+                &quads,
             )
-            .context("Failed to allocate exportable scanout image")?;
+            .context("Failed to compose Wayland surface tree")?;
+        self.surface_textures.insert(key, texture);
+        Ok(())
+    }
 
-            let fourcc = dma_image
-                .drm_fourcc()
-                .context("Vulkan format has no DRM fourcc mapping")?;
+    /// The imported client dmabuf a surface shows and whether it is stored
+    /// bottom to top, once it is ready for sampling.
+    fn imported_dmabuf(&self, key: (u32, u32)) -> Option<(&ImportedDmaBuf, bool)> {
+        let buffer_id = self.surface_dmabufs.get(&key)?.buffer_id;
+        let dmabuf = self.client_dmabufs.get(&(key.0, buffer_id))?;
+        let image = dmabuf.image.as_ref().filter(|_| !dmabuf.needs_acquire)?;
+        Some((image, dmabuf.attributes.y_invert))
+    }
 
-            let render_pass = RenderPass::new_for_scanout(vulkan.device(), format)?;
-            let framebuffer = Framebuffer::from_view(
-                vulkan.device(),
-                &render_pass,
-                dma_image.view(),
-                dma_image.extent(),
-            )?;
+    /// How the buffer a surface shows is sized, scaled and transformed.
+    fn surface_geometry(&self, key: (u32, u32)) -> Option<BufferGeometry> {
+        if let Some(frame) = self.surface_frames.get(&key) {
+            return Some(frame.geometry());
+        }
+        let dmabuf = self.surface_dmabufs.get(&key)?;
+        let attributes = &self
+            .client_dmabufs
+            .get(&(key.0, dmabuf.buffer_id))?
+            .attributes;
+        Some(BufferGeometry {
+            size: (attributes.width, attributes.height),
+            scale: dmabuf.scale,
+            transform: dmabuf.transform,
+            viewport: dmabuf.viewport,
+        })
+    }
 
-            clear_framebuffer_to_color(
-                vulkan.device(),
-                vulkan.graphics_command_pool(),
-                &render_pass,
-                &framebuffer,
-                color,
-            )
-            .context("Failed to clear scanout image")?;
-
-            if let Some(frame) = frame {
-                let upload = prepare_surface_upload(frame, width, height)?;
-                upload_bgra_to_image(
-                    vulkan.device(),
-                    vulkan.physical_device(),
-                    vulkan.graphics_command_pool(),
-                    &dma_image,
-                    &upload.pixels,
-                    upload.width,
-                    upload.height,
-                )
-                .context("Failed to upload Wayland SHM frame")?;
+    /// Whether a window is composed from more than its main surface.
+    fn has_subsurfaces(&self, (owner_id, root_id): (u32, u32)) -> bool {
+        self.surface_trees
+            .get(&(owner_id, root_id))
+            .is_some_and(|layers| layers.iter().any(|layer| layer.surface_id != root_id))
+    }
+
+    /// The image a window is drawn from.
+    fn window_image(&self, key: (u32, u32)) -> Option<WindowImage> {
+        let geometry = self.surface_geometry(key)?;
+        let size = geometry.surface_size();
+        if let Some(texture) = self.surface_textures.get(&key) {
+            // Composed trees are drawn untransformed and cropped into their
+            // texture.
+            let (source, transform) = match self.has_subsurfaces(key) {
+                true => ([0.0, 0.0, 1.0, 1.0], Transform::Normal),
+                false => (geometry.source(), geometry.transform),
+            };
+            return Some(WindowImage {
+                view: texture.view(),
+                size,
+                source,
+                transform,
+                y_invert: false,
+            });
+        }
+        let (image, y_invert) = self.imported_dmabuf(key)?;
+        Some(WindowImage {
+            view: image.view(),
+            size,
+            source: geometry.source(),
+            transform: geometry.transform,
+            y_invert,
+        })
+    }
+
+    /// Windows visible on an output showing `size` of the layout from
+    /// `location`, bottom to top, relative to the output.
+    fn output_placements(&self, location: (i32, i32), size: (u32, u32)) -> Vec<Placement> {
+        let window_size = |key| self.window_image(key).map(|image| image.size);
+        self.scene.output_placements(location, size, window_size)
+    }
+
+    /// Quads of the windows visible on an output of `size` pixels showing
+    /// the layout from `location` at `scale` percent with `transform`
+    /// applied, with the window they show, bottom to top.
+    fn output_quads(
+        &self,
+        location: (i32, i32),
+        size: (u32, u32),
+        scale: u32,
+        transform: Transform,
+    ) -> Vec<((u32, u32), TexturedQuad)> {
+        let framebuffer_size = transform.apply_size(size);
+        self.output_placements(location, scaled_size(size, scale))
+            .into_iter()
+            .filter_map(|placement| {
+                let image = self.window_image(placement.key)?;
+                let quad = TexturedQuad {
+                    view: image.view,
+                    rect: placement
+                        .scaled(scale)
+                        .transformed(transform, size)
+                        .ndc_rect(framebuffer_size),
+                    source: image.source,
+                    transform: image.transform,
+                    y_invert: image.y_invert,
+                    output_transform: transform,
+                    opacity: placement.opacity as f32 / 100.0,
+                };
+                Some((placement.key, quad))
+            })
+            .collect()
+    }
+
+    /// Allocate the buffer ring of an output, draw the first buffer and
+    /// modeset the output to it.
+    ///
+    /// A transformed output has its primary plane rotate the framebuffers
+    /// when the card can; otherwise the scene is drawn transformed.
+    fn modeset_output(&self, target: &PresentTarget) -> anyhow::Result<OutputScanout> {
+        if target.transform != Transform::Normal && target.output.props.plane_rotation.is_some() {
+            let output = ConnectedOutput {
+                rotation: drm_rotation(target.transform),
+                ..target.output.clone()
+            };
+            match self.modeset_output_as(target, output) {
+                Ok(scanout) => return Ok(scanout),
+                Err(err) => debug!(
+                    "{} cannot rotate its plane, drawing it transformed: {err:#}",
+                    target.connector_name
+                ),
             }
+        }
+        self.modeset_output_as(target, target.output.clone())
+    }
+
+    /// [`Self::modeset_output`] with the primary plane set up as in `output`.
+    fn modeset_output_as(
+        &self,
+        target: &PresentTarget,
+        output: ConnectedOutput,
+    ) -> anyhow::Result<OutputScanout> {
+        let buffers = (0..SCANOUT_BUFFER_COUNT)
+            .map(|_| self.import_scanout_buffer(&target.drm_path, framebuffer_size(&output)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let size = layout_size(&output, target.transform);
+        let DrawnScene { windows, .. } = self.draw_scene(
+            &buffers[0],
+            target.location,
+            size,
+            target.scale,
+            render_transform(&output, target.transform),
+            None,
+        )?;
+        // The modeset shows the buffer right away.
+        buffers[0].frame.borrow().wait()?;
+        let mut damage_history = DamageHistory::new(buffers.len());
+        damage_history.draw(0, Damage::Full);
+
+        let drm_device = self
+            .drm_devices
+            .opened()
+            .get(&target.drm_path)
+            .with_context(|| {
+                format!("DRM device {} is no longer open", target.drm_path.display())
+            })?;
+
+        let mode_blob = ModeBlob::create(drm_device.fd(), &output.mode)
+            .context("Failed to create MODE_ID property blob")?;
 
-            vulkan.device().wait_idle()?;
-            (dma_image, fourcc)
+        atomic_modeset(
+            drm_device.fd(),
+            &output,
+            mode_blob.id(),
+            buffers[0].drm_fb.id(),
+        )
+        .context("Failed atomic modeset")?;
+
+        let cursor = match output.cursor_plane {
+            Some(_) => OutputCursor::new(drm_device.fd())
+                .inspect_err(|err| {
+                    warn!(
+                        "No cursor on {}: failed to allocate cursor buffers: {err:#}",
+                        target.connector_name
+                    )
+                })
+                .ok(),
+            None => {
+                warn!(
+                    "No cursor on {}: CRTC has no cursor plane",
+                    target.connector_name
+                );
+                None
+            }
         };
 
+        let flip = FlipState::modeset(
+            target.drm_path.clone(),
+            output.crtc_id,
+            buffers.len(),
+            windows,
+        );
+        Ok(OutputScanout {
+            drm_path: target.drm_path.clone(),
+            output,
+            scale: target.scale,
+            transform: target.transform,
+            location: target.location,
+            _mode_blob: mode_blob,
+            buffers,
+            cursor,
+            flip,
+            damage_history,
+            drawn_windows: self.output_placements(target.location, scaled_size(size, target.scale)),
+            window_damage: HashMap::new(),
+        })
+    }
+
+    /// Allocate a render target of `width` by `height` pixels on the render
+    /// GPU and import it on the output's DRM card at `drm_path`.
+    fn import_scanout_buffer(
+        &self,
+        drm_path: &Path,
+        (width, height): (u32, u32),
+    ) -> anyhow::Result<ScanoutBuffer> {
+        let vulkan = self
+            .vulkan
+            .as_ref()
+            .context("VulkanContext missing during present")?;
+        let quad_renderer = self
+            .quad_renderer
+            .as_ref()
+            .context("Quad renderer missing during present")?;
+
+        let dma_image = DmaBufImage::allocate(
+            vulkan.device(),
+            vulkan.physical_device(),
+            width,
+            height,
+            SCANOUT_FORMAT,
+        )
+        .context("Failed to allocate exportable scanout image")?;
+
+        let fourcc = dma_image
+            .drm_fourcc()
+            .context("Vulkan format has no DRM fourcc mapping")?;
+
+        let framebuffer = Framebuffer::from_view(
+            vulkan.device(),
+            quad_renderer.render_pass(),
+            dma_image.view(),
+            dma_image.extent(),
+        )?;
+
         let dma_buf = dma_image
             .export_dma_buf()
             .context("Failed to export DMA-BUF for scanout")?;
@@ -383,10 +1938,8 @@ impl RendererState {
         let drm_device = self
             .drm_devices
             .opened()
-            .get(&target.drm_path)
-            .with_context(|| {
-                format!("DRM device {} is no longer open", target.drm_path.display())
-            })?;
+            .get(drm_path)
+            .with_context(|| format!("DRM device {} is no longer open", drm_path.display()))?;
 
         let drm_fb = DrmFramebuffer::from_dma_buf(
             drm_device.fd(),
@@ -400,17 +1953,12 @@ impl RendererState {
         )
         .context("Failed to import DMA-BUF as DRM framebuffer")?;
 
-        let mode_blob = ModeBlob::create(drm_device.fd(), &target.output.mode)
-            .context("Failed to create MODE_ID property blob")?;
-
-        atomic_modeset(drm_device.fd(), &target.output, mode_blob.id(), drm_fb.id())
-            .context("Failed atomic modeset")?;
+        let frame = QuadFrame::new(vulkan.device(), vulkan.graphics_command_pool())?;
 
-        Ok(OutputScanout {
-            drm_path: target.drm_path.clone(),
-            output: target.output.clone(),
-            _mode_blob: mode_blob,
-            _drm_fb: drm_fb,
+        Ok(ScanoutBuffer {
+            frame: RefCell::new(frame),
+            drm_fb,
+            framebuffer,
             _dma_image: dma_image,
         })
     }
@@ -463,11 +2011,41 @@ impl RendererState {
 
         if needs_recreate {
             self.scanouts.clear();
+            self.quad_renderer = None;
+            self.surface_textures.clear();
+            for dmabuf in self.client_dmabufs.values_mut() {
+                dmabuf.image = None;
+                dmabuf.needs_acquire = true;
+            }
             info!(
                 "Initializing Vulkan for DRM device {}",
                 preferred_drm_path.display()
             );
-            self.vulkan = Some(VulkanContext::new(Some(preferred_drm_path))?);
+            let vulkan = VulkanContext::new(Some(preferred_drm_path))?;
+            self.dmabuf_formats = match std::fs::metadata(preferred_drm_path) {
+                Ok(metadata) => Some(DmabufFormats {
+                    main_device: metadata.rdev(),
+                    formats: importable_dma_buf_formats(
+                        vulkan.instance(),
+                        vulkan.physical_device(),
+                    )
+                    .into_iter()
+                    .map(|format| DmabufFormat {
+                        format: format.fourcc,
+                        modifier: format.modifier,
+                        planes: format.plane_count,
+                    })
+                    .collect(),
+                }),
+                Err(err) => {
+                    warn!(
+                        "Not importing client dmabufs, {} is unavailable: {err}",
+                        preferred_drm_path.display()
+                    );
+                    None
+                }
+            };
+            self.vulkan = Some(vulkan);
         }
 
         Ok(())
@@ -478,6 +2056,50 @@ struct PresentTarget {
     drm_path: PathBuf,
     connector_name: String,
     output: ConnectedOutput,
+    /// Scale in percent the layout is shown at.
+    scale: u32,
+    /// Transform the layout is shown with.
+    transform: Transform,
+    /// Location of the output in the layout.
+    location: (i32, i32),
+}
+
+impl PresentTarget {
+    /// The output as it is shown once presented.
+    fn output(&self) -> Output {
+        Output {
+            name: self.connector_name.clone(),
+            description: self.drm_path.display().to_string(),
+            location: self.location,
+            size: (
+                self.output.mode.width() as i32,
+                self.output.mode.height() as i32,
+            ),
+            scale: self.scale,
+            transform: self.transform,
+        }
+    }
+}
+
+/// Size in pixels of the part of the layout `output` shows with
+/// `transform`, at the scale of the output.
+fn layout_size(output: &ConnectedOutput, transform: Transform) -> (u32, u32) {
+    transform.apply_size((output.mode.width(), output.mode.height()))
+}
+
+/// Transform to draw the layout into the framebuffers of `output` with, to
+/// show it with `transform`; none if the primary plane rotates them.
+fn render_transform(output: &ConnectedOutput, transform: Transform) -> Transform {
+    match output.rotation == drm_rotation(Transform::Normal) {
+        true => transform,
+        false => Transform::Normal,
+    }
+}
+
+/// Part of the layout an output of `size` pixels shows at `scale` percent.
+fn scaled_size((width, height): (u32, u32), scale: u32) -> (u32, u32) {
+    let (width, height) = logical_size((width as i32, height as i32), scale);
+    (width as u32, height as u32)
 }
 
 struct PreparedSurfaceUpload {
@@ -523,6 +2145,299 @@ fn prepare_surface_upload(
     })
 }
 
+/// Uploads a composed window. Its previous texture is reused if the size
+/// matches; only the `damage` is uploaded then.
+fn upload_surface_texture(
+    vulkan: &VulkanContext,
+    reusable: Option<Texture>,
+    frame: &SurfaceFrame,
+    max_size: u32,
+    damage: &Damage,
+) -> anyhow::Result<Texture> {
+    let extent = vk::Extent2D {
+        width: (frame.width as u32).min(max_size),
+        height: (frame.height as u32).min(max_size),
+    };
+    let (texture, regions) = match reusable.filter(|texture| texture.extent() == extent) {
+        Some(texture) => {
+            let bounds = Rect::from_size((extent.width, extent.height));
+            let regions: Vec<_> = damage.clipped(bounds).into_iter().map(vk_rect).collect();
+            if regions.is_empty() {
+                return Ok(texture);
+            }
+            (Some(texture), Some(regions))
+        }
+        None => (None, None),
+    };
+    let upload = prepare_surface_upload(frame, max_size, max_size)?;
+    let texture = match texture {
+        Some(texture) => texture,
+        None => Texture::new(
+            vulkan.device(),
+            vulkan.physical_device(),
+            upload.width,
+            upload.height,
+        )?,
+    };
+    upload_bgra_to_texture(
+        vulkan.device(),
+        vulkan.physical_device(),
+        vulkan.graphics_command_pool(),
+        &texture,
+        &upload.pixels,
+        regions.as_deref(),
+    )
+    .context("Failed to upload Wayland SHM frame")?;
+    Ok(texture)
+}
+
+/// Damage an output has to repaint between two frames: where windows
+/// appeared, disappeared, moved or were restacked, and the changed contents
+/// of the others. `window_damage` is in window coordinates.
+fn output_damage(
+    previous: &[Placement],
+    current: &[Placement],
+    window_damage: &HashMap<(u32, u32), Damage>,
+) -> Damage {
+    let bounds = |placement: &Placement| Rect {
+        x: placement.x,
+        y: placement.y,
+        width: placement.width as i32,
+        height: placement.height as i32,
+    };
+    let mut damage = Damage::default();
+    for index in 0..previous.len().max(current.len()) {
+        match (previous.get(index), current.get(index)) {
+            (Some(previous), Some(current)) if previous == current => {
+                let Some(changed) = window_damage.get(&current.key) else {
+                    continue;
+                };
+                let window = Rect::from_size((current.width, current.height));
+                for rect in changed.clipped(window) {
+                    damage.add(rect.translate(current.x, current.y));
+                }
+            }
+            (previous, current) => {
+                for placement in previous.into_iter().chain(current) {
+                    damage.add(bounds(placement));
+                }
+            }
+        }
+    }
+    damage
+}
+
+fn vk_rect(rect: Rect) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: rect.x,
+            y: rect.y,
+        },
+        extent: vk::Extent2D {
+            width: rect.width.max(0) as u32,
+            height: rect.height.max(0) as u32,
+        },
+    }
+}
+
+/// Imports a client dmabuf on first use and takes it over from the client
+/// once new contents were committed to it.
+fn prepare_client_dmabuf(vulkan: &VulkanContext, dmabuf: &mut ClientDmabuf) -> anyhow::Result<()> {
+    let attributes = &dmabuf.attributes;
+    let planes: Vec<DmaBufPlane<'_>> = attributes
+        .planes
+        .iter()
+        .map(|plane| DmaBufPlane {
+            fd: plane.fd.as_fd(),
+            offset: plane.offset,
+            stride: plane.stride,
+        })
+        .collect();
+    if dmabuf.image.is_none() {
+        let image = ImportedDmaBuf::import(
+            vulkan.device(),
+            vulkan.physical_device(),
+            (attributes.width, attributes.height),
+            attributes.format,
+            attributes.modifier,
+            &planes,
+        )
+        .context("Failed to import client dmabuf")?;
+        dmabuf.image = Some(image);
+        dmabuf.needs_acquire = true;
+    }
+    if let Some(image) = dmabuf.image.as_mut().filter(|_| dmabuf.needs_acquire) {
+        let fds: Vec<_> = planes.iter().map(|plane| plane.fd).collect();
+        wait_for_dma_buf_writes(&fds);
+        image.acquire(vulkan.device(), vulkan.graphics_command_pool())?;
+        dmabuf.needs_acquire = false;
+    }
+    Ok(())
+}
+
+/// Flattens a main surface and its sub-surfaces into a single frame, at the
+/// scale of the main surface and without a transform.
+///
+/// Sub-surfaces are clipped to the main surface. A lone main surface is
+/// returned as it is.
+fn compose_surface_tree<'a>(
+    frames: &'a HashMap<(u32, u32), SurfaceFrame>,
+    (owner_id, root_id): (u32, u32),
+    layers: Option<&[SurfaceLayer]>,
+) -> Option<Cow<'a, SurfaceFrame>> {
+    let root = frames.get(&(owner_id, root_id))?;
+    let Some(layers) = layers.filter(|layers| layers.iter().any(|l| l.surface_id != root_id))
+    else {
+        return Some(Cow::Borrowed(root));
+    };
+    let geometry = root.geometry();
+    let scale = f64::from(geometry.scale);
+    let mut composed = blank_frame(root, scale);
+    for layer in layers {
+        if let Some(frame) = frames.get(&(owner_id, layer.surface_id)) {
+            blend_frame(
+                &mut composed,
+                scale,
+                frame,
+                layer.x * geometry.scale as i32,
+                layer.y * geometry.scale as i32,
+            );
+        }
+    }
+    Some(Cow::Owned(composed))
+}
+
+/// A frame drawn at `scale` without a transform or viewport, for where the
+/// buffer of a surface cannot be scaled or transformed while drawn.
+fn scaled_frame(frame: &SurfaceFrame, scale: f64) -> Cow<'_, SurfaceFrame> {
+    let geometry = frame.geometry();
+    if f64::from(geometry.scale) == scale
+        && geometry.transform == Transform::Normal
+        && geometry.viewport == Viewport::default()
+    {
+        return Cow::Borrowed(frame);
+    }
+    let mut scaled = blank_frame(frame, scale);
+    blend_frame(&mut scaled, scale, frame, 0, 0);
+    Cow::Owned(scaled)
+}
+
+/// A frame showing `frame` with `transform` applied, pixel by pixel.
+fn transformed_frame(frame: &SurfaceFrame, transform: Transform) -> Cow<'_, SurfaceFrame> {
+    if transform == Transform::Normal {
+        return Cow::Borrowed(frame);
+    }
+    let size = (frame.width as i32, frame.height as i32);
+    let (width, height) = transform.apply_size((frame.width, frame.height));
+    let mut pixels = vec![0; width * height * 4];
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let (to_x, to_y, _, _) = transform.apply_rect((x as i32, y as i32, 1, 1), size);
+            let from = y * frame.stride + x * 4;
+            let to = to_y as usize * width * 4 + to_x as usize * 4;
+            pixels[to..to + 4].copy_from_slice(&frame.pixels[from..from + 4]);
+        }
+    }
+    Cow::Owned(SurfaceFrame {
+        owner_id: frame.owner_id,
+        surface_id: frame.surface_id,
+        root_id: frame.root_id,
+        pixels,
+        width,
+        height,
+        stride: width * 4,
+        format: frame.format,
+        damage: Vec::new(),
+        scale: frame.scale,
+        transform: Transform::Normal,
+        viewport: Viewport::default(),
+    })
+}
+
+/// A transparent, untransformed frame the size of the surface showing
+/// `frame`, at `scale`; its own scale is rounded to a whole number.
+fn blank_frame(frame: &SurfaceFrame, scale: f64) -> SurfaceFrame {
+    let (width, height) = frame.geometry().surface_size();
+    let width = (f64::from(width) * scale).round().max(1.0) as usize;
+    let height = (f64::from(height) * scale).round().max(1.0) as usize;
+    SurfaceFrame {
+        owner_id: frame.owner_id,
+        surface_id: frame.surface_id,
+        root_id: frame.root_id,
+        pixels: vec![0; width * height * 4],
+        width,
+        height,
+        stride: width * 4,
+        format: WL_SHM_FORMAT_ARGB8888,
+        damage: Vec::new(),
+        scale: scale.round().max(1.0) as u32,
+        transform: Transform::Normal,
+        viewport: Viewport::default(),
+    }
+}
+
+/// Blends a premultiplied frame over an untransformed ARGB target drawn at
+/// `target_scale`, at `(x, y)`, cropped, scaled and transformed to the
+/// target's space.
+///
+/// Pixels are sampled from the nearest buffer pixel.
+fn blend_frame(target: &mut SurfaceFrame, target_scale: f64, frame: &SurfaceFrame, x: i32, y: i32) {
+    let geometry = frame.geometry();
+    let (surface_width, surface_height) = geometry.surface_size();
+    let (unviewported_width, unviewported_height) = geometry.unviewported_size();
+    let (crop_x, crop_y, crop_width, crop_height) = geometry
+        .viewport
+        .source_rect((unviewported_width as i32, unviewported_height as i32));
+    let buffer_scale = f64::from(geometry.scale);
+    let transformed_size = geometry
+        .transform
+        .apply_size((frame.width as i32, frame.height as i32));
+    // Source coordinate of the center of a target pixel.
+    let sample = |index: usize, crop_offset: f32, crop_length: f32, surface_length: u32| {
+        let surface = (index as f64 + 0.5) / target_scale;
+        let cropped =
+            f64::from(crop_offset) + surface * f64::from(crop_length) / f64::from(surface_length);
+        (cropped * buffer_scale) as i32
+    };
+    let span = |length: u32| (f64::from(length) * target_scale).round() as usize;
+    let columns = clip_span(x, span(surface_width), target.width);
+    for row in clip_span(y, span(surface_height), target.height) {
+        let target_row = (row as isize + y as isize) as usize;
+        let sample_y = sample(row, crop_y, crop_height, surface_height);
+        for column in columns.clone() {
+            let target_column = (column as isize + x as isize) as usize;
+            let sample_x = sample(column, crop_x, crop_width, surface_width);
+            let (source_x, source_y, _, _) = geometry
+                .transform
+                .apply_rect((sample_x, sample_y, 1, 1), transformed_size);
+            let source_x = (source_x as usize).min(frame.width - 1);
+            let source_y = (source_y as usize).min(frame.height - 1);
+            let source = &frame.pixels[source_y * frame.stride + source_x * 4..][..4];
+            let destination =
+                &mut target.pixels[target_row * target.stride + target_column * 4..][..4];
+            let alpha = if frame.format == WL_SHM_FORMAT_XRGB8888 {
+                u8::MAX
+            } else {
+                source[3]
+            };
+            let inverse = u32::from(u8::MAX - alpha);
+            for channel in 0..3 {
+                let blended =
+                    u32::from(source[channel]) + u32::from(destination[channel]) * inverse / 255;
+                destination[channel] = blended.min(255) as u8;
+            }
+            destination[3] = (u32::from(alpha) + u32::from(destination[3]) * inverse / 255) as u8;
+        }
+    }
+}
+
+/// Range of source indices that land inside `0..limit` when offset by `offset`.
+fn clip_span(offset: i32, len: usize, limit: usize) -> std::ops::Range<usize> {
+    let start = (-(offset as isize)).clamp(0, len as isize) as usize;
+    let end = (limit as isize - offset as isize).clamp(0, len as isize) as usize;
+    start..end.max(start)
+}
+
 impl Source for RendererState {
     fn register(
         &mut self,
@@ -555,11 +2470,16 @@ mod tests {
         SurfaceFrame {
             owner_id: 1,
             surface_id: 2,
+            root_id: 2,
             pixels: vec![0; 16],
             width: 2,
             height: 2,
             stride: 8,
             format: 0,
+            damage: Vec::new(),
+            scale: 1,
+            transform: Transform::Normal,
+            viewport: Viewport::default(),
         }
     }
 
@@ -585,6 +2505,7 @@ mod tests {
         let frame = SurfaceFrame {
             owner_id: 1,
             surface_id: 2,
+            root_id: 2,
             pixels: vec![
                 1, 2, 3, 0, 4, 5, 6, 0, 90, 91, 92, 93, 7, 8, 9, 0, 10, 11, 12, 0, 94, 95, 96, 97,
             ],
@@ -592,6 +2513,10 @@ mod tests {
             height: 2,
             stride: 12,
             format: WL_SHM_FORMAT_XRGB8888,
+            damage: Vec::new(),
+            scale: 1,
+            transform: Transform::Normal,
+            viewport: Viewport::default(),
         };
 
         let upload = prepare_surface_upload(&frame, 1, 2).unwrap();
@@ -613,4 +2538,189 @@ mod tests {
         let upload = prepare_surface_upload(&frame, 4, 4).unwrap();
         assert_eq!(upload.pixels, vec![1, 2, 3, 4]);
     }
+
+    #[test]
+    fn composes_clipped_subsurfaces_over_main_surface() {
+        let root = SurfaceFrame {
+            pixels: vec![10, 20, 30, 0, 10, 20, 30, 0, 10, 20, 30, 0, 10, 20, 30, 0],
+            format: WL_SHM_FORMAT_XRGB8888,
+            ..frame()
+        };
+        let child = SurfaceFrame {
+            surface_id: 3,
+            pixels: vec![0, 0, 100, 128, 50, 50, 50, 255],
+            width: 2,
+            height: 1,
+            stride: 8,
+            ..frame()
+        };
+        let frames = HashMap::from([((1, 2), root), ((1, 3), child)]);
+        let layers = [
+            SurfaceLayer {
+                surface_id: 2,
+                x: 0,
+                y: 0,
+            },
+            SurfaceLayer {
+                surface_id: 3,
+                x: 1,
+                y: 1,
+            },
+        ];
+
+        let composed = compose_surface_tree(&frames, (1, 2), Some(&layers)).unwrap();
+        assert_eq!(composed.format, WL_SHM_FORMAT_ARGB8888);
+        assert_eq!(
+            composed.pixels,
+            vec![
+                10, 20, 30, 255, 10, 20, 30, 255, 10, 20, 30, 255, 4, 9, 114, 255
+            ]
+        );
+
+        let alone = compose_surface_tree(&frames, (1, 2), None).unwrap();
+        assert!(matches!(alone, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn composes_subsurfaces_at_the_main_surface_scale() {
+        let root = SurfaceFrame {
+            pixels: vec![0; 32],
+            width: 4,
+            height: 2,
+            stride: 16,
+            scale: 2,
+            ..frame()
+        };
+        // A 2x1 surface drawn rotated, so its right pixel is on top.
+        let child = SurfaceFrame {
+            surface_id: 3,
+            pixels: vec![1, 2, 3, 255, 4, 5, 6, 255],
+            width: 1,
+            height: 2,
+            stride: 4,
+            transform: Transform::Rotate90,
+            ..frame()
+        };
+        let frames = HashMap::from([((1, 2), root), ((1, 3), child)]);
+        let layers = [
+            SurfaceLayer {
+                surface_id: 2,
+                x: 0,
+                y: 0,
+            },
+            SurfaceLayer {
+                surface_id: 3,
+                x: 0,
+                y: 0,
+            },
+        ];
+
+        let composed = compose_surface_tree(&frames, (1, 2), Some(&layers)).unwrap();
+        assert_eq!((composed.width, composed.height, composed.scale), (4, 2, 2));
+        let (left, right) = ([4, 5, 6, 255], [1, 2, 3, 255]);
+        let row = [left, left, right, right].concat();
+        assert_eq!(composed.pixels, [row.clone(), row].concat());
+    }
+
+    #[test]
+    fn maps_buffer_damage_to_the_surface() {
+        let geometry = BufferGeometry {
+            size: (4, 2),
+            scale: 2,
+            transform: Transform::Rotate90,
+            viewport: Viewport::default(),
+        };
+        assert_eq!(geometry.surface_size(), (1, 2));
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(geometry.surface_rect(rect(2, 0, 2, 1)), rect(0, 1, 1, 1));
+        assert_eq!(geometry.surface_rect(rect(0, 0, 4, 2)), rect(0, 0, 1, 2));
+    }
+
+    #[test]
+    fn crops_and_scales_buffers_through_the_viewport() {
+        // The right half of a 4x1 buffer, stretched to 4x2.
+        let frame = SurfaceFrame {
+            pixels: [
+                [1, 1, 1, 255],
+                [2, 2, 2, 255],
+                [3, 3, 3, 255],
+                [4, 4, 4, 255],
+            ]
+            .concat(),
+            width: 4,
+            height: 1,
+            stride: 16,
+            viewport: Viewport {
+                source: Some((2.0, 0.0, 2.0, 1.0)),
+                destination: Some((4, 2)),
+            },
+            ..frame()
+        };
+        let geometry = frame.geometry();
+        assert_eq!(geometry.surface_size(), (4, 2));
+        assert_eq!(geometry.source(), [0.5, 0.0, 0.5, 1.0]);
+        let rect = Rect {
+            x: 3,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        assert_eq!(
+            geometry.surface_rect(rect),
+            Rect {
+                x: 2,
+                y: 0,
+                width: 2,
+                height: 2,
+            }
+        );
+
+        let scaled = scaled_frame(&frame, 1.0);
+        assert_eq!((scaled.width, scaled.height), (4, 2));
+        let (third, fourth) = ([3, 3, 3, 255], [4, 4, 4, 255]);
+        let row = [third, third, fourth, fourth].concat();
+        assert_eq!(scaled.pixels, [row.clone(), row].concat());
+    }
+
+    #[test]
+    fn damages_moved_windows_and_changed_contents() {
+        let placement = |key, x| Placement {
+            key,
+            x,
+            y: 0,
+            width: 10,
+            height: 10,
+            opacity: 100,
+        };
+        let rect = |x, width| Rect {
+            x,
+            y: 0,
+            width,
+            height: 10,
+        };
+        let previous = [placement((1, 1), 0), placement((1, 2), 20)];
+        let current = [placement((1, 1), 0), placement((1, 2), 30)];
+        let window_damage = HashMap::from([
+            ((1, 1), Damage::Rects(vec![rect(5, 20)])),
+            ((1, 2), Damage::Full),
+        ]);
+        assert_eq!(
+            output_damage(&previous, &current, &window_damage),
+            Damage::Rects(vec![rect(5, 5), rect(20, 10), rect(30, 10)])
+        );
+
+        assert_eq!(
+            output_damage(&current, &current, &HashMap::new()),
+            Damage::default()
+        );
+        assert_eq!(
+            output_damage(&current, &current[..1], &HashMap::new()),
+            Damage::Rects(vec![rect(30, 10)])
+        );
+    }
 }
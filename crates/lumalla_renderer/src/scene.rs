@@ -0,0 +1,309 @@
+//! Placement and stacking of the windows shown on the outputs.
+
+use lumalla_shared::Transform;
+
+/// A main surface, drawn together with its sub-surfaces as one texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SceneWindow {
+    /// `(owner_id, root_id)` of the main surface.
+    pub key: (u32, u32),
+    /// Top-left corner in the global layout.
+    pub x: i32,
+    pub y: i32,
+    /// Opacity in percent.
+    pub opacity: u32,
+    /// Hidden windows keep their contents and stacking but are not drawn.
+    pub visible: bool,
+}
+
+/// A window overlapping an output, relative to the output's top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Placement {
+    pub key: (u32, u32),
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Opacity in percent.
+    pub opacity: u32,
+}
+
+impl Placement {
+    /// Offset and size in normalized device coordinates of an output of the
+    /// given size, `[x, y, width, height]`.
+    pub fn ndc_rect(&self, (output_width, output_height): (u32, u32)) -> [f32; 4] {
+        let scale_x = 2.0 / output_width as f32;
+        let scale_y = 2.0 / output_height as f32;
+        [
+            self.x as f32 * scale_x - 1.0,
+            self.y as f32 * scale_y - 1.0,
+            self.width as f32 * scale_x,
+            self.height as f32 * scale_y,
+        ]
+    }
+
+    /// The placement once an output of `size` showing it is transformed.
+    pub fn transformed(&self, transform: Transform, (width, height): (u32, u32)) -> Self {
+        let (x, y, width, height) = transform.apply_rect(
+            (self.x, self.y, self.width as i32, self.height as i32),
+            (width as i32, height as i32),
+        );
+        Self {
+            key: self.key,
+            x,
+            y,
+            width: width as u32,
+            height: height as u32,
+            opacity: self.opacity,
+        }
+    }
+
+    /// The placement on an output showing the layout at `scale` percent,
+    /// with its edges rounded to the nearest pixel.
+    pub fn scaled(&self, scale: u32) -> Self {
+        let scale = scale as i32;
+        let to_device = |value: i32| (value * scale + 50).div_euclid(100);
+        let (left, top) = (to_device(self.x), to_device(self.y));
+        Self {
+            key: self.key,
+            x: left,
+            y: top,
+            width: (to_device(self.x + self.width as i32) - left) as u32,
+            height: (to_device(self.y + self.height as i32) - top) as u32,
+            opacity: self.opacity,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Scene {
+    /// Bottom to top.
+    windows: Vec<SceneWindow>,
+}
+
+impl Scene {
+    /// Adds a fully opaque, visible window on top of the stack at the
+    /// origin of the layout.
+    ///
+    /// Returns `false` if the window is already shown; it keeps its position
+    /// and stacking then.
+    pub fn map(&mut self, key: (u32, u32)) -> bool {
+        if self.contains(key) {
+            return false;
+        }
+        self.windows.push(SceneWindow {
+            key,
+            x: 0,
+            y: 0,
+            opacity: 100,
+            visible: true,
+        });
+        true
+    }
+
+    pub fn unmap(&mut self, key: (u32, u32)) -> bool {
+        let len = self.windows.len();
+        self.windows.retain(|window| window.key != key);
+        self.windows.len() != len
+    }
+
+    pub fn remove_owner(&mut self, owner_id: u32) -> bool {
+        let len = self.windows.len();
+        self.windows.retain(|window| window.key.0 != owner_id);
+        self.windows.len() != len
+    }
+
+    /// Moves a window within the layout. Returns `false` for unknown windows.
+    pub fn set_position(&mut self, key: (u32, u32), (x, y): (i32, i32)) -> bool {
+        let Some(window) = self.windows.iter_mut().find(|window| window.key == key) else {
+            return false;
+        };
+        window.x = x;
+        window.y = y;
+        true
+    }
+
+    /// Changes how opaque a window is drawn. Returns `false` for unknown
+    /// windows and unchanged opacities.
+    pub fn set_opacity(&mut self, key: (u32, u32), opacity: u32) -> bool {
+        let Some(window) = self.windows.iter_mut().find(|window| window.key == key) else {
+            return false;
+        };
+        let changed = window.opacity != opacity;
+        window.opacity = opacity;
+        changed
+    }
+
+    /// Shows or hides a window. Returns `false` for unknown windows and
+    /// unchanged visibility.
+    pub fn set_visible(&mut self, key: (u32, u32), visible: bool) -> bool {
+        let Some(window) = self.windows.iter_mut().find(|window| window.key == key) else {
+            return false;
+        };
+        let changed = window.visible != visible;
+        window.visible = visible;
+        changed
+    }
+
+    /// Whether a window is in the scene and drawn.
+    pub fn is_visible(&self, key: (u32, u32)) -> bool {
+        self.windows
+            .iter()
+            .any(|window| window.key == key && window.visible)
+    }
+
+    /// Moves a window to the top of the stack. Returns `false` for unknown windows.
+    pub fn raise(&mut self, key: (u32, u32)) -> bool {
+        let Some(index) = self.windows.iter().position(|window| window.key == key) else {
+            return false;
+        };
+        let window = self.windows.remove(index);
+        self.windows.push(window);
+        true
+    }
+
+    pub fn contains(&self, key: (u32, u32)) -> bool {
+        self.windows.iter().any(|window| window.key == key)
+    }
+
+    /// Windows from bottom to top.
+    pub fn windows(&self) -> &[SceneWindow] {
+        &self.windows
+    }
+
+    /// Visible windows overlapping the output at `location` with `size`,
+    /// bottom to top.
+    ///
+    /// `window_size` yields the size of a window's contents; windows without
+    /// contents are skipped.
+    pub fn output_placements(
+        &self,
+        location: (i32, i32),
+        (output_width, output_height): (u32, u32),
+        window_size: impl Fn((u32, u32)) -> Option<(u32, u32)>,
+    ) -> Vec<Placement> {
+        self.windows
+            .iter()
+            .filter(|window| window.visible)
+            .filter_map(|window| {
+                let (width, height) = window_size(window.key)?;
+                let x = i64::from(window.x) - i64::from(location.0);
+                let y = i64::from(window.y) - i64::from(location.1);
+                let overlaps = x < i64::from(output_width)
+                    && y < i64::from(output_height)
+                    && x + i64::from(width) > 0
+                    && y + i64::from(height) > 0;
+                overlaps.then_some(Placement {
+                    key: window.key,
+                    x: x as i32,
+                    y: y as i32,
+                    width,
+                    height,
+                    opacity: window.opacity,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_stack_in_map_order_until_raised() {
+        let mut scene = Scene::default();
+        assert!(scene.map((1, 2)));
+        assert!(scene.map((3, 4)));
+        assert!(!scene.map((1, 2)));
+        let keys =
+            |scene: &Scene| -> Vec<_> { scene.windows().iter().map(|window| window.key).collect() };
+        assert_eq!(keys(&scene), vec![(1, 2), (3, 4)]);
+
+        assert!(scene.raise((1, 2)));
+        assert_eq!(keys(&scene), vec![(3, 4), (1, 2)]);
+
+        assert!(scene.remove_owner(1));
+        assert!(!scene.unmap((1, 2)));
+        assert_eq!(keys(&scene), vec![(3, 4)]);
+    }
+
+    #[test]
+    fn places_overlapping_windows_relative_to_the_output() {
+        let mut scene = Scene::default();
+        scene.map((1, 1));
+        scene.map((1, 2));
+        scene.map((1, 3));
+        scene.map((1, 4));
+        scene.set_position((1, 1), (1900, 100));
+        scene.set_position((1, 2), (2000, 0));
+        scene.set_position((1, 3), (-100, -100));
+        scene.set_position((1, 4), (-100, 0));
+        scene.map((1, 5));
+        scene.set_position((1, 5), (1920, 0));
+        assert!(scene.set_visible((1, 5), false));
+        assert!(!scene.set_visible((1, 5), false));
+        assert!(!scene.is_visible((1, 5)));
+
+        let placements = scene.output_placements((1920, 0), (1280, 1024), |key| {
+            (key.1 != 4).then_some((100, 100))
+        });
+        assert_eq!(
+            placements,
+            vec![
+                Placement {
+                    key: (1, 1),
+                    x: -20,
+                    y: 100,
+                    width: 100,
+                    height: 100,
+                    opacity: 100,
+                },
+                Placement {
+                    key: (1, 2),
+                    x: 80,
+                    y: 0,
+                    width: 100,
+                    height: 100,
+                    opacity: 100,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_placements_to_normalized_device_coordinates() {
+        let placement = Placement {
+            key: (1, 2),
+            x: -50,
+            y: 100,
+            width: 100,
+            height: 200,
+            opacity: 100,
+        };
+        assert_eq!(placement.ndc_rect((200, 400)), [-1.5, -0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn scales_placements_to_whole_pixels() {
+        let placement = Placement {
+            key: (1, 1),
+            x: 1,
+            y: -1,
+            width: 3,
+            height: 3,
+            opacity: 100,
+        };
+        assert_eq!(
+            placement.scaled(150),
+            Placement {
+                key: (1, 1),
+                x: 2,
+                y: -1,
+                width: 4,
+                height: 4,
+                opacity: 100,
+            }
+        );
+    }
+}
@@ -307,6 +307,25 @@ impl<'a> CommandBufferRecorder<'a> {
         }
     }
 
+    /// Updates push constants of the bound pipeline's layout.
+    pub fn push_constants(
+        &mut self,
+        pipeline_layout: vk::PipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &[u8],
+    ) {
+        unsafe {
+            self.device.handle().cmd_push_constants(
+                self.command_buffer,
+                pipeline_layout,
+                stage_flags,
+                offset,
+                constants,
+            );
+        }
+    }
+
     /// Draws a fullscreen quad using vertex shader generation.
     ///
     /// This uses `vkCmdDraw` with 3 vertices (one triangle) and relies on
@@ -343,6 +362,27 @@ impl<'a> CommandBufferRecorder<'a> {
         }
     }
 
+    /// Clears a rectangle of the first color attachment inside a render pass.
+    pub fn clear_color_attachment(&mut self, color: [f32; 4], rect: vk::Rect2D) {
+        let attachment = vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            color_attachment: 0,
+            clear_value: vk::ClearValue {
+                color: vk::ClearColorValue { float32: color },
+            },
+        };
+        let rect = vk::ClearRect {
+            rect,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        unsafe {
+            self.device
+                .handle()
+                .cmd_clear_attachments(self.command_buffer, &[attachment], &[rect]);
+        }
+    }
+
     /// Ends recording and returns the command buffer.
     pub fn end(self) -> anyhow::Result<vk::CommandBuffer> {
         unsafe { self.device.handle().end_command_buffer(self.command_buffer) }
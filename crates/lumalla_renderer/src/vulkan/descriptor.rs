@@ -70,3 +70,112 @@ impl Drop for DescriptorSetLayout {
         debug!("Destroyed descriptor set layout");
     }
 }
+
+/// Represents a descriptor pool.
+///
+/// Descriptor sets allocated from the pool are freed together when the pool
+/// is dropped.
+pub struct DescriptorPool {
+    /// The Vulkan descriptor pool handle
+    handle: vk::DescriptorPool,
+    /// The device that owns this pool
+    device: ash::Device,
+}
+
+impl DescriptorPool {
+    /// Creates a new descriptor pool holding up to `max_sets` sets.
+    pub fn new(
+        device: &Device,
+        max_sets: u32,
+        pool_sizes: &[vk::DescriptorPoolSize],
+    ) -> anyhow::Result<Self> {
+        let create_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(max_sets)
+            .pool_sizes(pool_sizes);
+
+        let handle = unsafe { device.handle().create_descriptor_pool(&create_info, None) }
+            .context("Failed to create descriptor pool")?;
+
+        debug!("Created descriptor pool for {max_sets} sets");
+
+        Ok(Self {
+            handle,
+            device: device.handle().clone(),
+        })
+    }
+
+    /// Creates a descriptor pool for `count` single combined image sampler sets.
+    pub fn new_samplers(device: &Device, count: u32) -> anyhow::Result<Self> {
+        let pool_size = vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(count);
+
+        Self::new(device, count, &[pool_size])
+    }
+
+    /// Allocates a descriptor set with the given layout.
+    pub fn allocate(&self, layout: &DescriptorSetLayout) -> anyhow::Result<vk::DescriptorSet> {
+        let layouts = [layout.handle()];
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.handle)
+            .set_layouts(&layouts);
+
+        let sets = unsafe { self.device.allocate_descriptor_sets(&allocate_info) }
+            .context("Failed to allocate descriptor set")?;
+
+        Ok(sets[0])
+    }
+
+    /// Allocates a descriptor set for a single combined image sampler and
+    /// points it at `view`, which must be in `SHADER_READ_ONLY_OPTIMAL` layout.
+    pub fn allocate_sampler(
+        &self,
+        layout: &DescriptorSetLayout,
+        binding: u32,
+        view: vk::ImageView,
+        sampler: vk::Sampler,
+    ) -> anyhow::Result<vk::DescriptorSet> {
+        let set = self.allocate(layout)?;
+        let image_info = [vk::DescriptorImageInfo::default()
+            .sampler(sampler)
+            .image_view(view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+
+        unsafe {
+            self.device.update_descriptor_sets(&[write], &[]);
+        }
+
+        Ok(set)
+    }
+
+    /// Frees every descriptor set allocated from the pool.
+    ///
+    /// None of them may still be in use by the GPU.
+    pub fn reset(&self) -> anyhow::Result<()> {
+        unsafe {
+            self.device
+                .reset_descriptor_pool(self.handle, vk::DescriptorPoolResetFlags::empty())
+        }
+        .context("Failed to reset descriptor pool")?;
+        Ok(())
+    }
+
+    /// Returns the descriptor pool handle.
+    pub fn handle(&self) -> vk::DescriptorPool {
+        self.handle
+    }
+}
+
+impl Drop for DescriptorPool {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_descriptor_pool(self.handle, None);
+        }
+        debug!("Destroyed descriptor pool");
+    }
+}
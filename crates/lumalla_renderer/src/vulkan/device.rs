@@ -19,10 +19,15 @@ pub struct Device {
     external_memory_fd: ash::khr::external_memory_fd::Device,
     /// DRM modifier queries via `VK_EXT_image_drm_format_modifier`
     image_drm_format_modifier: ash::ext::image_drm_format_modifier::Device,
+    /// Sync file export via `VK_KHR_external_semaphore_fd`, `None` where
+    /// semaphores cannot be exported as sync files
+    external_semaphore_fd: Option<ash::khr::external_semaphore_fd::Device>,
     /// The graphics queue
     graphics_queue: vk::Queue,
     /// The graphics queue family index
     graphics_queue_family: u32,
+    /// Queue family that client dmabufs are acquired from
+    foreign_queue_family: u32,
 }
 
 impl Device {
@@ -79,6 +84,7 @@ impl Device {
             ash::khr::external_memory::NAME,
             ash::khr::external_semaphore::NAME,
             ash::khr::external_semaphore_fd::NAME,
+            ash::ext::queue_family_foreign::NAME,
         ];
 
         for &ext in optional_extensions {
@@ -94,6 +100,15 @@ impl Device {
             debug!("Enabling required device extension: {:?}", ext);
         }
 
+        // Without VK_EXT_queue_family_foreign, dmabufs written by other drivers
+        // are acquired as if they came from the same driver.
+        let foreign_queue_family =
+            if extensions_to_enable.contains(&ash::ext::queue_family_foreign::NAME) {
+                vk::QUEUE_FAMILY_FOREIGN_EXT
+            } else {
+                vk::QUEUE_FAMILY_EXTERNAL
+            };
+
         let extension_ptrs: Vec<*const i8> = extensions_to_enable
             .iter()
             .map(|ext| ext.as_ptr())
@@ -126,6 +141,14 @@ impl Device {
         let external_memory_fd = ash::khr::external_memory_fd::Device::new(instance, &device);
         let image_drm_format_modifier =
             ash::ext::image_drm_format_modifier::Device::new(instance, &device);
+        let external_semaphore_fd = (extensions_to_enable
+            .contains(&ash::khr::external_semaphore_fd::NAME)
+            && exports_sync_files(instance, physical_device))
+        .then(|| ash::khr::external_semaphore_fd::Device::new(instance, &device));
+        debug!(
+            "Semaphores exportable as sync files: {}",
+            external_semaphore_fd.is_some()
+        );
 
         // Get the graphics queue
         let graphics_queue = unsafe { device.get_device_queue(graphics_queue_family, 0) };
@@ -135,8 +158,10 @@ impl Device {
             handle: device,
             external_memory_fd,
             image_drm_format_modifier,
+            external_semaphore_fd,
             graphics_queue,
             graphics_queue_family,
+            foreign_queue_family,
         })
     }
 
@@ -155,6 +180,12 @@ impl Device {
         &self.image_drm_format_modifier
     }
 
+    /// Returns the external semaphore FD device extension loader, if
+    /// semaphores can be exported as sync files.
+    pub fn external_semaphore_fd(&self) -> Option<&ash::khr::external_semaphore_fd::Device> {
+        self.external_semaphore_fd.as_ref()
+    }
+
     /// Returns the graphics queue.
     pub fn graphics_queue(&self) -> vk::Queue {
         self.graphics_queue
@@ -165,6 +196,11 @@ impl Device {
         self.graphics_queue_family
     }
 
+    /// Returns the queue family that imported dmabufs are acquired from.
+    pub fn foreign_queue_family(&self) -> u32 {
+        self.foreign_queue_family
+    }
+
     /// Waits for the device to become idle.
     ///
     /// This is useful for cleanup and synchronization.
@@ -213,3 +249,20 @@ impl Drop for Device {
         }
     }
 }
+
+/// Whether binary semaphores of `physical_device` can be exported as sync files.
+fn exports_sync_files(instance: &ash::Instance, physical_device: &PhysicalDevice) -> bool {
+    let info = vk::PhysicalDeviceExternalSemaphoreInfo::default()
+        .handle_type(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+    let mut properties = vk::ExternalSemaphoreProperties::default();
+    unsafe {
+        instance.get_physical_device_external_semaphore_properties(
+            physical_device.handle(),
+            &info,
+            &mut properties,
+        );
+    }
+    properties
+        .external_semaphore_features
+        .contains(vk::ExternalSemaphoreFeatureFlags::EXPORTABLE)
+}
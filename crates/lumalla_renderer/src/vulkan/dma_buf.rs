@@ -1,12 +1,13 @@
-//! Vulkan-allocated images exported as DMA-BUFs for KMS scanout.
+//! DMA-BUF interop: Vulkan-allocated images exported for KMS scanout, and
+//! client buffers imported for sampling.
 
-use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd};
 
 use anyhow::Context;
 use ash::vk;
-use log::debug;
+use log::{debug, warn};
 
-use super::{Device, PhysicalDevice};
+use super::{CommandBufferRecorder, CommandPool, Device, Fence, PhysicalDevice};
 
 /// The DRM_FORMAT_MOD_LINEAR modifier value.
 pub const DRM_FORMAT_MOD_LINEAR: u64 = 0;
@@ -26,6 +27,25 @@ pub const DRM_FORMAT_XBGR8888: u32 = u32::from_le_bytes(*b"XB24");
 /// DRM fourcc: ABGR8888 ('AB24').
 pub const DRM_FORMAT_ABGR8888: u32 = u32::from_le_bytes(*b"AB24");
 
+/// Formats clients may share buffers in, in order of preference.
+const CLIENT_FOURCCS: [u32; 4] = [
+    DRM_FORMAT_ARGB8888,
+    DRM_FORMAT_XRGB8888,
+    DRM_FORMAT_ABGR8888,
+    DRM_FORMAT_XBGR8888,
+];
+
+/// Aspects of the memory planes of an image with a DRM format modifier.
+const MEMORY_PLANE_ASPECTS: [vk::ImageAspectFlags; 4] = [
+    vk::ImageAspectFlags::MEMORY_PLANE_0_EXT,
+    vk::ImageAspectFlags::MEMORY_PLANE_1_EXT,
+    vk::ImageAspectFlags::MEMORY_PLANE_2_EXT,
+    vk::ImageAspectFlags::MEMORY_PLANE_3_EXT,
+];
+
+/// How long to wait for a client to finish rendering into a buffer.
+const IMPLICIT_FENCE_TIMEOUT_MS: i32 = 100;
+
 /// A Vulkan image allocated for DMA-BUF export (and later KMS scanout).
 pub struct DmaBufImage {
     image: vk::Image,
@@ -238,6 +258,494 @@ impl Drop for DmaBufImage {
     }
 }
 
+/// A format and modifier pair that client DMA-BUFs can be imported with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaBufFormat {
+    pub fourcc: u32,
+    pub modifier: u64,
+    /// Memory planes of an image with this modifier.
+    pub plane_count: u32,
+}
+
+/// Lists the format and modifier pairs for which client DMA-BUFs can be
+/// imported and sampled on `physical_device`.
+///
+/// Implicit modifiers cannot be imported, so `DRM_FORMAT_MOD_INVALID` is
+/// never listed.
+pub fn importable_dma_buf_formats(
+    instance: &ash::Instance,
+    physical_device: &PhysicalDevice,
+) -> Vec<DmaBufFormat> {
+    let mut formats = Vec::new();
+    for fourcc in CLIENT_FOURCCS {
+        let Some((format, _)) = drm_fourcc_to_vulkan(fourcc) else {
+            continue;
+        };
+        for properties in format_modifier_properties(instance, physical_device.handle(), format) {
+            let sampleable = properties
+                .drm_format_modifier_tiling_features
+                .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE);
+            let modifier = properties.drm_format_modifier;
+            if sampleable
+                && modifier != DRM_FORMAT_MOD_INVALID
+                && can_import(instance, physical_device.handle(), format, modifier)
+            {
+                formats.push(DmaBufFormat {
+                    fourcc,
+                    modifier,
+                    plane_count: properties.drm_format_modifier_plane_count,
+                });
+            }
+        }
+    }
+    formats
+}
+
+fn format_modifier_properties(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+) -> Vec<vk::DrmFormatModifierPropertiesEXT> {
+    let mut count_list = vk::DrmFormatModifierPropertiesListEXT::default();
+    let mut count_properties = vk::FormatProperties2::default().push_next(&mut count_list);
+    unsafe {
+        instance.get_physical_device_format_properties2(
+            physical_device,
+            format,
+            &mut count_properties,
+        );
+    }
+    let count = count_list.drm_format_modifier_count as usize;
+
+    let mut modifiers = vec![vk::DrmFormatModifierPropertiesEXT::default(); count];
+    let mut list = vk::DrmFormatModifierPropertiesListEXT::default()
+        .drm_format_modifier_properties(&mut modifiers);
+    let mut properties = vk::FormatProperties2::default().push_next(&mut list);
+    unsafe {
+        instance.get_physical_device_format_properties2(physical_device, format, &mut properties);
+    }
+    let count = list.drm_format_modifier_count as usize;
+    modifiers.truncate(count);
+    modifiers
+}
+
+fn can_import(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+    modifier: u64,
+) -> bool {
+    let mut modifier_info = vk::PhysicalDeviceImageDrmFormatModifierInfoEXT::default()
+        .drm_format_modifier(modifier)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let mut external_info = vk::PhysicalDeviceExternalImageFormatInfo::default()
+        .handle_type(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+    let format_info = vk::PhysicalDeviceImageFormatInfo2::default()
+        .format(format)
+        .ty(vk::ImageType::TYPE_2D)
+        .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+        .usage(vk::ImageUsageFlags::SAMPLED)
+        .push_next(&mut external_info)
+        .push_next(&mut modifier_info);
+    let mut external_properties = vk::ExternalImageFormatProperties::default();
+    let mut properties = vk::ImageFormatProperties2::default().push_next(&mut external_properties);
+    let supported = unsafe {
+        instance.get_physical_device_image_format_properties2(
+            physical_device,
+            &format_info,
+            &mut properties,
+        )
+    }
+    .is_ok();
+    supported
+        && external_properties
+            .external_memory_properties
+            .external_memory_features
+            .contains(vk::ExternalMemoryFeatureFlags::IMPORTABLE)
+}
+
+/// One plane of a client DMA-BUF.
+#[derive(Debug, Clone, Copy)]
+pub struct DmaBufPlane<'a> {
+    pub fd: BorrowedFd<'a>,
+    pub offset: u32,
+    pub stride: u32,
+}
+
+/// A client DMA-BUF imported as a sampled Vulkan image, without copying it.
+pub struct ImportedDmaBuf {
+    image: vk::Image,
+    /// One allocation, or one per plane for disjoint images.
+    memories: Vec<vk::DeviceMemory>,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+    /// The image has been acquired from the client at least once.
+    acquired: bool,
+    device: ash::Device,
+}
+
+impl ImportedDmaBuf {
+    /// Imports the planes of a client buffer.
+    ///
+    /// Planes in separate DMA-BUFs are bound to separate allocations of a
+    /// disjoint image. The image must be acquired before it is sampled; see
+    /// [`Self::acquire`].
+    pub fn import(
+        device: &Device,
+        physical_device: &PhysicalDevice,
+        (width, height): (u32, u32),
+        fourcc: u32,
+        modifier: u64,
+        planes: &[DmaBufPlane<'_>],
+    ) -> anyhow::Result<Self> {
+        let (format, opaque) = drm_fourcc_to_vulkan(fourcc)
+            .with_context(|| format!("Unsupported DRM format {fourcc:#x}"))?;
+        anyhow::ensure!(width > 0 && height > 0, "Image dimensions must be non-zero");
+        anyhow::ensure!(
+            (1..=MEMORY_PLANE_ASPECTS.len()).contains(&planes.len()),
+            "DMA-BUF has {} planes",
+            planes.len()
+        );
+        let disjoint = planes[1..]
+            .iter()
+            .any(|plane| !is_same_file(plane.fd, planes[0].fd));
+
+        let plane_layouts: Vec<vk::SubresourceLayout> = planes
+            .iter()
+            .map(|plane| vk::SubresourceLayout {
+                offset: u64::from(plane.offset),
+                size: 0,
+                row_pitch: u64::from(plane.stride),
+                array_pitch: 0,
+                depth_pitch: 0,
+            })
+            .collect();
+        let mut modifier_info = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+            .drm_format_modifier(modifier)
+            .plane_layouts(&plane_layouts);
+        let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+        let flags = if disjoint {
+            vk::ImageCreateFlags::DISJOINT
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+        let extent = vk::Extent2D { width, height };
+        let image_info = vk::ImageCreateInfo::default()
+            .flags(flags)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .usage(vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::PREINITIALIZED)
+            .push_next(&mut external_memory_info)
+            .push_next(&mut modifier_info);
+
+        let image = unsafe { device.handle().create_image(&image_info, None) }
+            .context("Failed to create image for client DMA-BUF")?;
+        // Dropping a partially imported buffer releases what was created so far.
+        let mut imported = Self {
+            image,
+            memories: Vec::new(),
+            view: vk::ImageView::null(),
+            extent,
+            acquired: false,
+            device: device.handle().clone(),
+        };
+
+        let memory_planes = if disjoint { planes.len() } else { 1 };
+        for (plane, aspect) in planes[..memory_planes].iter().zip(MEMORY_PLANE_ASPECTS) {
+            let memory =
+                import_plane_memory(device, physical_device, image, plane, disjoint, aspect)?;
+            imported.memories.push(memory);
+        }
+
+        let mut plane_infos: Vec<_> = MEMORY_PLANE_ASPECTS[..memory_planes]
+            .iter()
+            .map(|&aspect| vk::BindImagePlaneMemoryInfo::default().plane_aspect(aspect))
+            .collect();
+        let bind_infos: Vec<_> = imported
+            .memories
+            .iter()
+            .zip(&mut plane_infos)
+            .map(|(&memory, plane_info)| {
+                let info = vk::BindImageMemoryInfo::default()
+                    .image(image)
+                    .memory(memory);
+                if disjoint {
+                    info.push_next(plane_info)
+                } else {
+                    info
+                }
+            })
+            .collect();
+        unsafe { device.handle().bind_image_memory2(&bind_infos) }
+            .context("Failed to bind client DMA-BUF memory")?;
+
+        // Opaque formats are sampled as if their padding byte were alpha 1.
+        let alpha = if opaque {
+            vk::ComponentSwizzle::ONE
+        } else {
+            vk::ComponentSwizzle::IDENTITY
+        };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .components(vk::ComponentMapping {
+                a: alpha,
+                ..Default::default()
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        imported.view = unsafe { device.handle().create_image_view(&view_info, None) }
+            .context("Failed to create image view for client DMA-BUF")?;
+
+        debug!(
+            "Imported client DMA-BUF: {width}x{height} fourcc={fourcc:#x} modifier={modifier:#x} planes={} disjoint={disjoint}",
+            planes.len()
+        );
+        Ok(imported)
+    }
+
+    /// Takes the image over from the client after it committed new contents,
+    /// leaving it ready for sampling in fragment shaders.
+    ///
+    /// An image acquired before is first handed back, since the client wrote
+    /// to it in the meantime.
+    pub fn acquire(&mut self, device: &Device, command_pool: &CommandPool) -> anyhow::Result<()> {
+        let barrier = |old_layout, new_layout, src_queue, dst_queue| {
+            vk::ImageMemoryBarrier::default()
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(src_queue)
+                .dst_queue_family_index(dst_queue)
+                .image(self.image)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+        };
+        let foreign = device.foreign_queue_family();
+        let graphics = device.graphics_queue_family();
+        let release = barrier(
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::GENERAL,
+            graphics,
+            foreign,
+        )
+        .src_access_mask(vk::AccessFlags::SHADER_READ);
+        // The first acquire must keep the contents the client imported the
+        // buffer with.
+        let old_layout = if self.acquired {
+            vk::ImageLayout::GENERAL
+        } else {
+            vk::ImageLayout::PREINITIALIZED
+        };
+        let acquire = barrier(
+            old_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            foreign,
+            graphics,
+        )
+        .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+        let command_buffer = command_pool
+            .allocate_command_buffer(device)
+            .context("Failed to allocate DMA-BUF acquire command buffer")?;
+        let result = (|| -> anyhow::Result<()> {
+            let recorder = CommandBufferRecorder::begin_one_time(device, command_buffer)?;
+            unsafe {
+                if self.acquired {
+                    device.handle().cmd_pipeline_barrier(
+                        recorder.command_buffer(),
+                        vk::PipelineStageFlags::FRAGMENT_SHADER,
+                        vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &[release],
+                    );
+                }
+                device.handle().cmd_pipeline_barrier(
+                    recorder.command_buffer(),
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[acquire],
+                );
+            }
+            recorder.end()?;
+            let fence = Fence::new(device, false)?;
+            device.submit_graphics(&[command_buffer], &[], &[], &[], fence.handle())?;
+            fence
+                .wait_default()
+                .context("Timed out acquiring client DMA-BUF")
+        })();
+        if result.is_err() {
+            // Submitted work may still reference the command buffer.
+            let _ = device.wait_idle();
+        }
+        command_pool.free_command_buffers(device, &[command_buffer]);
+        result?;
+        self.acquired = true;
+        Ok(())
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+}
+
+impl Drop for ImportedDmaBuf {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            for &memory in &self.memories {
+                self.device.free_memory(memory, None);
+            }
+        }
+        debug!("Destroyed imported client DMA-BUF");
+    }
+}
+
+/// Imports the DMA-BUF of one memory plane, or of the whole image if it is
+/// not disjoint.
+fn import_plane_memory(
+    device: &Device,
+    physical_device: &PhysicalDevice,
+    image: vk::Image,
+    plane: &DmaBufPlane<'_>,
+    disjoint: bool,
+    aspect: vk::ImageAspectFlags,
+) -> anyhow::Result<vk::DeviceMemory> {
+    let mut plane_requirements_info =
+        vk::ImagePlaneMemoryRequirementsInfo::default().plane_aspect(aspect);
+    let mut requirements_info = vk::ImageMemoryRequirementsInfo2::default().image(image);
+    if disjoint {
+        requirements_info = requirements_info.push_next(&mut plane_requirements_info);
+    }
+    let mut requirements = vk::MemoryRequirements2::default();
+    unsafe {
+        device
+            .handle()
+            .get_image_memory_requirements2(&requirements_info, &mut requirements);
+    }
+    let requirements = requirements.memory_requirements;
+
+    // Vulkan takes ownership of the descriptor once the import succeeded.
+    let fd = plane
+        .fd
+        .try_clone_to_owned()
+        .context("Failed to duplicate DMA-BUF descriptor")?;
+    let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+    unsafe {
+        device.external_memory_fd().get_memory_fd_properties(
+            vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+            fd.as_raw_fd(),
+            &mut fd_properties,
+        )
+    }
+    .context("Failed to query DMA-BUF memory properties")?;
+    let memory_type_index = find_memory_type_index(
+        physical_device.memory_properties(),
+        requirements.memory_type_bits & fd_properties.memory_type_bits,
+        vk::MemoryPropertyFlags::empty(),
+    )
+    .context("No memory type can import the DMA-BUF")?;
+
+    let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+        .handle_type(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
+        .fd(fd.as_raw_fd());
+    let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+    let mut allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type_index)
+        .push_next(&mut import_info);
+    if !disjoint {
+        allocate_info = allocate_info.push_next(&mut dedicated_info);
+    }
+    let memory = unsafe { device.handle().allocate_memory(&allocate_info, None) }
+        .context("Failed to import DMA-BUF memory")?;
+    let _ = fd.into_raw_fd();
+    Ok(memory)
+}
+
+/// Whether two descriptors refer to the same DMA-BUF.
+fn is_same_file(a: BorrowedFd<'_>, b: BorrowedFd<'_>) -> bool {
+    let identity = |fd: BorrowedFd<'_>| {
+        let mut metadata = std::mem::MaybeUninit::<libc::stat>::zeroed();
+        if unsafe { libc::fstat(fd.as_raw_fd(), metadata.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        let metadata = unsafe { metadata.assume_init() };
+        Some((metadata.st_dev, metadata.st_ino))
+    };
+    identity(a).is_some_and(|identity_a| Some(identity_a) == identity(b))
+}
+
+/// Waits until the GPU finished writing the client's latest contents into
+/// the DMA-BUFs, as signaled by their implicit fences.
+///
+/// Gives up after a short timeout, so a stuck client cannot stall
+/// presentation; its buffer is then sampled as it is.
+pub fn wait_for_dma_buf_writes(fds: &[BorrowedFd<'_>]) {
+    let mut poll_fds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|fd| libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    let ready = unsafe {
+        libc::poll(
+            poll_fds.as_mut_ptr(),
+            poll_fds.len() as libc::nfds_t,
+            IMPLICIT_FENCE_TIMEOUT_MS,
+        )
+    };
+    if ready >= 0 && (ready as usize) < poll_fds.len() {
+        warn!("Client DMA-BUF was not ready after {IMPLICIT_FENCE_TIMEOUT_MS}ms");
+    }
+}
+
+/// Maps a DRM fourcc to the Vulkan format its bytes are sampled with, and
+/// whether its alpha channel is padding.
+pub fn drm_fourcc_to_vulkan(fourcc: u32) -> Option<(vk::Format, bool)> {
+    match fourcc {
+        DRM_FORMAT_ARGB8888 => Some((vk::Format::B8G8R8A8_UNORM, false)),
+        DRM_FORMAT_XRGB8888 => Some((vk::Format::B8G8R8A8_UNORM, true)),
+        DRM_FORMAT_ABGR8888 => Some((vk::Format::R8G8B8A8_UNORM, false)),
+        DRM_FORMAT_XBGR8888 => Some((vk::Format::R8G8B8A8_UNORM, true)),
+        _ => None,
+    }
+}
+
 /// Maps a Vulkan format to a DRM fourcc.
 pub fn vulkan_to_drm_fourcc(format: vk::Format) -> Option<u32> {
     match format {
@@ -247,7 +755,7 @@ pub fn vulkan_to_drm_fourcc(format: vk::Format) -> Option<u32> {
     }
 }
 
-fn find_memory_type_index(
+pub(super) fn find_memory_type_index(
     memory_properties: &vk::PhysicalDeviceMemoryProperties,
     type_bits: u32,
     required_properties: vk::MemoryPropertyFlags,
@@ -13,18 +13,22 @@ mod instance;
 mod memory;
 mod physical_device;
 mod pipeline;
+mod quad;
 mod render_pass;
 pub mod shaders;
 mod sync;
+mod texture;
 mod upload;
 
 pub use clear::clear_framebuffer_to_color;
 pub use command::{CommandBufferRecorder, CommandPool};
-pub use descriptor::DescriptorSetLayout;
+pub use descriptor::{DescriptorPool, DescriptorSetLayout};
 pub use device::Device;
 pub use dma_buf::{
     DRM_FORMAT_ABGR8888, DRM_FORMAT_ARGB8888, DRM_FORMAT_MOD_INVALID, DRM_FORMAT_MOD_LINEAR,
-    DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888, DmaBufImage, vulkan_to_drm_fourcc,
+    DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888, DmaBufFormat, DmaBufImage, DmaBufPlane,
+    ImportedDmaBuf, drm_fourcc_to_vulkan, importable_dma_buf_formats, vulkan_to_drm_fourcc,
+    wait_for_dma_buf_writes,
 };
 pub use framebuffer::Framebuffer;
 pub use image::Image;
@@ -32,6 +36,8 @@ pub use instance::VulkanContext;
 pub use memory::MemoryAllocator;
 pub use physical_device::PhysicalDevice;
 pub use pipeline::{GraphicsPipeline, GraphicsPipelineBuilder, ShaderModule};
+pub use quad::{QuadFrame, QuadRenderer, TexturedQuad};
 pub use render_pass::RenderPass;
 pub use sync::{Fence, Semaphore};
-pub use upload::upload_bgra_to_image;
+pub use texture::{Sampler, Texture};
+pub use upload::{upload_bgra_to_image, upload_bgra_to_texture};
@@ -66,6 +66,7 @@ pub struct GraphicsPipelineBuilder<'a> {
     fragment_shader: Option<&'a ShaderModule>,
     descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
     push_constant_ranges: Vec<vk::PushConstantRange>,
+    premultiplied_alpha: bool,
 }
 
 impl<'a> GraphicsPipelineBuilder<'a> {
@@ -78,6 +79,7 @@ impl<'a> GraphicsPipelineBuilder<'a> {
             fragment_shader: None,
             descriptor_set_layouts: Vec::new(),
             push_constant_ranges: Vec::new(),
+            premultiplied_alpha: false,
         }
     }
 
@@ -105,6 +107,12 @@ impl<'a> GraphicsPipelineBuilder<'a> {
         self
     }
 
+    /// Blends fragment colors that are already multiplied by their alpha.
+    pub fn premultiplied_alpha(mut self) -> Self {
+        self.premultiplied_alpha = true;
+        self
+    }
+
     /// Builds the graphics pipeline.
     pub fn build(self) -> anyhow::Result<GraphicsPipeline> {
         // Create pipeline layout
@@ -174,6 +182,11 @@ impl<'a> GraphicsPipelineBuilder<'a> {
             .rasterization_samples(vk::SampleCountFlags::TYPE_1);
 
         // Color blend attachment state
+        let src_color_blend_factor = if self.premultiplied_alpha {
+            vk::BlendFactor::ONE
+        } else {
+            vk::BlendFactor::SRC_ALPHA
+        };
         let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
             .color_write_mask(
                 vk::ColorComponentFlags::R
@@ -182,7 +195,7 @@ impl<'a> GraphicsPipelineBuilder<'a> {
                     | vk::ColorComponentFlags::A,
             )
             .blend_enable(true)
-            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .src_color_blend_factor(src_color_blend_factor)
             .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
             .color_blend_op(vk::BlendOp::ADD)
             .src_alpha_blend_factor(vk::BlendFactor::ONE)
@@ -0,0 +1,445 @@
+//! Textured quad drawing for composing surfaces onto an output.
+
+use std::os::fd::OwnedFd;
+
+use anyhow::Context;
+use ash::vk;
+use lumalla_shared::Transform;
+
+use super::{
+    CommandBufferRecorder, CommandPool, DescriptorPool, DescriptorSetLayout, Device, Fence,
+    Framebuffer, GraphicsPipeline, GraphicsPipelineBuilder, RenderPass, Sampler, Semaphore,
+    Texture, shaders,
+};
+
+/// A texture drawn as an axis-aligned rectangle.
+#[derive(Debug, Clone, Copy)]
+pub struct TexturedQuad {
+    pub view: vk::ImageView,
+    /// Offset and size in normalized device coordinates, `[x, y, width, height]`.
+    pub rect: [f32; 4],
+    /// The part of the contents shown, `[x, y, width, height]` as fractions
+    /// of the whole.
+    pub source: [f32; 4],
+    /// The texture holds the contents of the quad with this transform applied.
+    pub transform: Transform,
+    /// The texture rows are stored bottom to top.
+    pub y_invert: bool,
+    /// The output shows the quad's contents with this transform applied.
+    pub output_transform: Transform,
+    /// How opaque the quad is drawn, from `0.0` to `1.0`.
+    pub opacity: f32,
+}
+
+impl TexturedQuad {
+    /// Push constants of the quad: its rectangle, the rows of the affine map
+    /// from its corners to texture coordinates, then its opacity.
+    fn push_constants(&self) -> [[f32; 4]; 4] {
+        let uv = |corner| {
+            let (u, v) = self.transform.apply_point(corner, (1, 1));
+            let v = if self.y_invert { 1 - v } else { v };
+            [u as f32, v as f32]
+        };
+        let origin = uv((0, 0));
+        let right = uv((1, 0));
+        let down = uv((0, 1));
+        let [x, y, width, height] = self.source;
+        // Texture coordinates of a point of the contents.
+        let texture = |(cx, cy): (f32, f32), axis: usize| {
+            let across = right[axis] - origin[axis];
+            let along = down[axis] - origin[axis];
+            origin[axis] + across * (x + cx * width) + along * (y + cy * height)
+        };
+        // Points of the contents at the corners of the quad as drawn.
+        let contents = |corner| {
+            let (cx, cy) = self.output_transform.invert().apply_point(corner, (1, 1));
+            (cx as f32, cy as f32)
+        };
+        let (top_left, top_right, bottom_left) =
+            (contents((0, 0)), contents((1, 0)), contents((0, 1)));
+        let row = |axis: usize| {
+            let origin = texture(top_left, axis);
+            [
+                texture(top_right, axis) - origin,
+                texture(bottom_left, axis) - origin,
+                origin,
+                0.0,
+            ]
+        };
+        [self.rect, row(0), row(1), [self.opacity, 0.0, 0.0, 0.0]]
+    }
+}
+
+/// Pipeline and state shared by every textured quad draw.
+pub struct QuadRenderer {
+    // Drop order: pipeline → descriptor set layout.
+    pipeline: GraphicsPipeline,
+    descriptor_set_layout: DescriptorSetLayout,
+    sampler: Sampler,
+    render_pass: RenderPass,
+    /// Compatible with `render_pass`, but keeps the previous contents.
+    update_render_pass: RenderPass,
+    /// Compatible with `render_pass`, but leaves the target ready for sampling.
+    compose_render_pass: RenderPass,
+}
+
+impl QuadRenderer {
+    /// Creates the textured quad pipeline for scanout images of `format`.
+    ///
+    /// Textures are expected to hold premultiplied alpha, like Wayland buffers.
+    pub fn new(device: &Device, format: vk::Format) -> anyhow::Result<Self> {
+        let render_pass = RenderPass::new_for_scanout(device, format)?;
+        let update_render_pass = RenderPass::new_for_scanout_update(device, format)?;
+        let compose_render_pass = RenderPass::new_simple_color(device, format)?;
+        let descriptor_set_layout = DescriptorSetLayout::new_sampler(device, 0)?;
+        let sampler = Sampler::new_nearest(device)?;
+        let vertex_shader = shaders::create_textured_quad_vert(device)?;
+        let fragment_shader = shaders::create_textured_quad_frag(device)?;
+        let pipeline = GraphicsPipelineBuilder::new(device, &render_pass)
+            .vertex_shader(&vertex_shader)
+            .fragment_shader(&fragment_shader)
+            .descriptor_set_layout(descriptor_set_layout.handle())
+            .push_constant_range(
+                vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .offset(0)
+                    .size(size_of::<[[f32; 4]; 4]>() as u32),
+            )
+            .premultiplied_alpha()
+            .build()
+            .context("Failed to create textured quad pipeline")?;
+
+        Ok(Self {
+            pipeline,
+            descriptor_set_layout,
+            sampler,
+            render_pass,
+            update_render_pass,
+            compose_render_pass,
+        })
+    }
+
+    /// Render pass that framebuffers passed to [`Self::draw`] must be created with.
+    pub fn render_pass(&self) -> &RenderPass {
+        &self.render_pass
+    }
+
+    /// Clears `framebuffer` to `color` (RGBA float) and draws `quads` bottom to top.
+    ///
+    /// With `damage`, only those rectangles are redrawn and the rest of a
+    /// framebuffer drawn before is kept.
+    ///
+    /// Returns a sync file signaled once drawing finishes. Without one,
+    /// drawing has finished already. Every texture must be in
+    /// `SHADER_READ_ONLY_OPTIMAL` layout.
+    pub fn draw(
+        &self,
+        device: &Device,
+        frame: &mut QuadFrame,
+        framebuffer: &Framebuffer,
+        color: [f32; 4],
+        quads: &[TexturedQuad],
+        damage: Option<&[vk::Rect2D]>,
+    ) -> anyhow::Result<Option<OwnedFd>> {
+        let render_pass = match damage {
+            Some(_) => &self.update_render_pass,
+            None => &self.render_pass,
+        };
+        self.record_and_submit(
+            device,
+            frame,
+            render_pass,
+            framebuffer,
+            color,
+            quads,
+            damage,
+        )?;
+        match &frame.finished {
+            Some(finished) => finished.export_sync_file(device),
+            None => frame.wait().map(|()| None),
+        }
+    }
+
+    /// Draws `quads` bottom to top into `target`, cleared to transparent,
+    /// and leaves it in `SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// Blocks until the GPU finishes.
+    pub fn compose(
+        &self,
+        device: &Device,
+        command_pool: &CommandPool,
+        target: &Texture,
+        quads: &[TexturedQuad],
+    ) -> anyhow::Result<()> {
+        let framebuffer = Framebuffer::from_view(
+            device,
+            &self.compose_render_pass,
+            target.view(),
+            target.extent(),
+        )?;
+        let mut frame = QuadFrame::create(device, command_pool, None)?;
+        self.record_and_submit(
+            device,
+            &mut frame,
+            &self.compose_render_pass,
+            &framebuffer,
+            [0.0; 4],
+            quads,
+            None,
+        )?;
+        frame.wait()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_and_submit(
+        &self,
+        device: &Device,
+        frame: &mut QuadFrame,
+        render_pass: &RenderPass,
+        framebuffer: &Framebuffer,
+        color: [f32; 4],
+        quads: &[TexturedQuad],
+        damage: Option<&[vk::Rect2D]>,
+    ) -> anyhow::Result<()> {
+        // The GPU has to be done with the previous draw before the command
+        // buffer and descriptor sets are reused.
+        frame.wait()?;
+        let descriptor_sets = frame.descriptor_sets(
+            device,
+            &self.descriptor_set_layout,
+            self.sampler.handle(),
+            quads,
+        )?;
+
+        let clear_value = vk::ClearValue {
+            color: vk::ClearColorValue { float32: color },
+        };
+        let extent = framebuffer.extent();
+
+        {
+            let mut recorder = CommandBufferRecorder::begin_one_time(device, frame.command_buffer)?;
+            recorder.begin_render_pass(render_pass, framebuffer, &[clear_value])?;
+            recorder.bind_pipeline(&self.pipeline);
+            recorder.set_viewport_fullscreen(extent.width, extent.height);
+            let full = [vk::Rect2D {
+                offset: vk::Offset2D::default(),
+                extent,
+            }];
+            for &scissor in damage.unwrap_or(&full) {
+                recorder.set_scissor(&scissor);
+                if damage.is_some() {
+                    recorder.clear_color_attachment(color, scissor);
+                }
+                for (quad, &descriptor_set) in quads.iter().zip(&descriptor_sets) {
+                    let constants: Vec<u8> = quad
+                        .push_constants()
+                        .iter()
+                        .flatten()
+                        .flat_map(|v| v.to_ne_bytes())
+                        .collect();
+                    recorder.bind_descriptor_sets(
+                        self.pipeline.layout(),
+                        0,
+                        &[descriptor_set],
+                        &[],
+                    );
+                    recorder.push_constants(
+                        self.pipeline.layout(),
+                        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                        0,
+                        &constants,
+                    );
+                    recorder.draw(6, 1, 0, 0);
+                }
+            }
+            recorder.end_render_pass();
+            recorder.end()?;
+        }
+
+        let signal: Vec<_> = frame.finished.iter().map(Semaphore::handle).collect();
+        frame.fence.reset()?;
+        device.submit_graphics(
+            &[frame.command_buffer],
+            &[],
+            &[],
+            &signal,
+            frame.fence.handle(),
+        )
+    }
+}
+
+/// Number of quads a descriptor pool is created for at first.
+const INITIAL_DESCRIPTOR_CAPACITY: u32 = 16;
+
+/// The command buffer, descriptor sets and synchronization for drawing into
+/// one framebuffer, reused every time it is drawn.
+pub struct QuadFrame {
+    /// Signaled once the GPU is done with the last draw.
+    fence: Fence,
+    /// Signaled once the last draw finishes, to be exported as a sync file.
+    finished: Option<Semaphore>,
+    descriptor_pool: DescriptorPool,
+    /// Number of quads `descriptor_pool` holds sets for.
+    capacity: u32,
+    command_buffer: vk::CommandBuffer,
+    command_pool: vk::CommandPool,
+    device: ash::Device,
+}
+
+impl QuadFrame {
+    /// Allocates the resources for drawing into a framebuffer from
+    /// `command_pool`, which has to outlive the frame.
+    pub fn new(device: &Device, command_pool: &CommandPool) -> anyhow::Result<Self> {
+        let finished = Semaphore::new_sync_file_exportable(device)?;
+        Self::create(device, command_pool, finished)
+    }
+
+    fn create(
+        device: &Device,
+        command_pool: &CommandPool,
+        finished: Option<Semaphore>,
+    ) -> anyhow::Result<Self> {
+        let command_buffer = command_pool
+            .allocate_command_buffer(device)
+            .context("Failed to allocate draw command buffer")?;
+        Ok(Self {
+            fence: Fence::new(device, true)?,
+            finished,
+            descriptor_pool: DescriptorPool::new_samplers(device, INITIAL_DESCRIPTOR_CAPACITY)?,
+            capacity: INITIAL_DESCRIPTOR_CAPACITY,
+            command_buffer,
+            command_pool: command_pool.handle(),
+            device: device.handle().clone(),
+        })
+    }
+
+    /// Blocks until the GPU finishes the last draw.
+    pub fn wait(&self) -> anyhow::Result<()> {
+        self.fence
+            .wait_default()
+            .context("Timed out waiting for quads to be drawn")
+    }
+
+    /// Allocates a descriptor set for each of `quads`, freeing those of the
+    /// last draw.
+    fn descriptor_sets(
+        &mut self,
+        device: &Device,
+        layout: &DescriptorSetLayout,
+        sampler: vk::Sampler,
+        quads: &[TexturedQuad],
+    ) -> anyhow::Result<Vec<vk::DescriptorSet>> {
+        let capacity = descriptor_capacity(self.capacity, quads.len());
+        if capacity == self.capacity {
+            self.descriptor_pool.reset()?;
+        } else {
+            self.descriptor_pool = DescriptorPool::new_samplers(device, capacity)?;
+            self.capacity = capacity;
+        }
+        quads
+            .iter()
+            .map(|quad| {
+                self.descriptor_pool
+                    .allocate_sampler(layout, 0, quad.view, sampler)
+            })
+            .collect()
+    }
+}
+
+impl Drop for QuadFrame {
+    fn drop(&mut self) {
+        // The command buffer may still be executing.
+        let _ = self.wait();
+        unsafe {
+            self.device
+                .free_command_buffers(self.command_pool, &[self.command_buffer]);
+        }
+    }
+}
+
+/// Number of quads a descriptor pool holding sets for `capacity` of them
+/// has to be replaced with to draw `count`.
+fn descriptor_capacity(capacity: u32, count: usize) -> u32 {
+    match u32::try_from(count) {
+        Ok(count) if count <= capacity => capacity,
+        Ok(count) => count.next_power_of_two(),
+        Err(_) => u32::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(transform: Transform, y_invert: bool) -> TexturedQuad {
+        TexturedQuad {
+            view: vk::ImageView::null(),
+            rect: [-1.0, -1.0, 2.0, 2.0],
+            source: [0.0, 0.0, 1.0, 1.0],
+            transform,
+            y_invert,
+            output_transform: Transform::Normal,
+            opacity: 1.0,
+        }
+    }
+
+    /// Texture coordinates the corner `(x, y)` of a quad samples.
+    fn sample(quad: &TexturedQuad, (x, y): (f32, f32)) -> (f32, f32) {
+        let [_, u, v, _] = quad.push_constants();
+        (u[0] * x + u[1] * y + u[2], v[0] * x + v[1] * y + v[2])
+    }
+
+    #[test]
+    fn maps_corners_through_the_buffer_transform() {
+        let normal = quad(Transform::Normal, false);
+        assert_eq!(sample(&normal, (1.0, 0.0)), (1.0, 0.0));
+        assert_eq!(sample(&normal, (0.0, 1.0)), (0.0, 1.0));
+
+        // Drawn rotated counter-clockwise, so the top-right corner of the
+        // quad is at the top left of the texture.
+        let rotated = quad(Transform::Rotate90, false);
+        assert_eq!(sample(&rotated, (1.0, 0.0)), (0.0, 0.0));
+        assert_eq!(sample(&rotated, (0.0, 0.0)), (0.0, 1.0));
+
+        let inverted = quad(Transform::Rotate90, true);
+        assert_eq!(sample(&inverted, (1.0, 0.0)), (0.0, 1.0));
+        assert_eq!(sample(&inverted, (0.0, 0.0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn crops_to_the_source_before_the_transform() {
+        let mut cropped = quad(Transform::Normal, false);
+        cropped.source = [0.5, 0.25, 0.5, 0.5];
+        assert_eq!(sample(&cropped, (0.0, 0.0)), (0.5, 0.25));
+        assert_eq!(sample(&cropped, (1.0, 1.0)), (1.0, 0.75));
+
+        // The right half of the contents is the top half of the texture.
+        let mut rotated = quad(Transform::Rotate90, false);
+        rotated.source = [0.5, 0.0, 0.5, 1.0];
+        assert_eq!(sample(&rotated, (0.0, 0.0)), (0.0, 0.5));
+        assert_eq!(sample(&rotated, (1.0, 0.0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn counter_rotates_for_the_output_transform() {
+        // The output rotates its contents counter-clockwise, so the top
+        // left of the framebuffer shows the top right of the contents.
+        let mut rotated = quad(Transform::Normal, false);
+        rotated.output_transform = Transform::Rotate90;
+        assert_eq!(sample(&rotated, (0.0, 0.0)), (1.0, 0.0));
+        assert_eq!(sample(&rotated, (0.0, 1.0)), (0.0, 0.0));
+
+        // A buffer drawn with the same transform as the output is copied.
+        rotated.transform = Transform::Rotate90;
+        assert_eq!(sample(&rotated, (0.0, 0.0)), (0.0, 0.0));
+        assert_eq!(sample(&rotated, (1.0, 1.0)), (1.0, 1.0));
+    }
+
+    #[test]
+    fn descriptor_pools_grow_to_fit_the_quads() {
+        assert_eq!(descriptor_capacity(16, 0), 16);
+        assert_eq!(descriptor_capacity(16, 16), 16);
+        assert_eq!(descriptor_capacity(16, 17), 32);
+        assert_eq!(descriptor_capacity(32, 100), 128);
+    }
+}
@@ -83,29 +83,55 @@ impl RenderPass {
     /// This uses PRESENT_SRC_KHR as the final layout, which is appropriate
     /// for images that will be presented directly via WSI.
     pub fn new_for_display(device: &Device, format: vk::Format) -> anyhow::Result<Self> {
-        Self::new_with_final_layout(device, format, vk::ImageLayout::PRESENT_SRC_KHR)
+        Self::new_with_layouts(
+            device,
+            format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )
     }
 
     /// Creates a render pass for clearing a DMA-BUF image destined for KMS scanout.
     ///
     /// Final layout is `GENERAL` so the image can be exported and scanned out.
     pub fn new_for_scanout(device: &Device, format: vk::Format) -> anyhow::Result<Self> {
-        Self::new_with_final_layout(device, format, vk::ImageLayout::GENERAL)
+        Self::new_with_layouts(
+            device,
+            format,
+            vk::AttachmentLoadOp::CLEAR,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        )
     }
 
-    fn new_with_final_layout(
+    /// Like [`Self::new_for_scanout`], but keeps the previous contents of an
+    /// image that was drawn before, so that only parts of it are redrawn.
+    pub fn new_for_scanout_update(device: &Device, format: vk::Format) -> anyhow::Result<Self> {
+        Self::new_with_layouts(
+            device,
+            format,
+            vk::AttachmentLoadOp::LOAD,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+        )
+    }
+
+    fn new_with_layouts(
         device: &Device,
         format: vk::Format,
+        load_op: vk::AttachmentLoadOp,
+        initial_layout: vk::ImageLayout,
         final_layout: vk::ImageLayout,
     ) -> anyhow::Result<Self> {
         let color_attachment = vk::AttachmentDescription::default()
             .format(format)
             .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .load_op(load_op)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .initial_layout(initial_layout)
             .final_layout(final_layout);
 
         let color_attachment_ref = vk::AttachmentReference {
@@ -113,14 +139,20 @@ impl RenderPass {
             layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
         };
 
+        // Kept contents are read by the load op.
+        let dst_access_mask = if load_op == vk::AttachmentLoadOp::LOAD {
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        } else {
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+        };
         // External dependency after the pass so KMS can sample the stored image.
         let dependency_begin = vk::SubpassDependency::default()
             .src_subpass(vk::SUBPASS_EXTERNAL)
             .dst_subpass(0)
             .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::empty())
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
             .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+            .dst_access_mask(dst_access_mask);
 
         let dependency_end = vk::SubpassDependency::default()
             .src_subpass(0)
@@ -148,8 +180,8 @@ impl RenderPass {
             .context("Failed to create render pass")?;
 
         debug!(
-            "Created render pass with format {:?} final_layout={:?}",
-            format, final_layout
+            "Created render pass with format {:?} load_op={:?} final_layout={:?}",
+            format, load_op, final_layout
         );
 
         Ok(Self {
@@ -138,6 +138,196 @@ pub const SOLID_COLOR_FRAG_SPV: &[u32] = &[
     0x00000009, 0x0000000e, 0x000100fd, 0x00010038,
 ];
 
+/// SPIR-V bytecode for a textured quad vertex shader.
+///
+/// This shader generates a quad from the vertex index, without requiring any
+/// vertex buffers. The quad's offset and size in normalized device
+/// coordinates are passed as a push constant, followed by the rows of the
+/// affine map from its corners to texture coordinates. Use with 6 vertices.
+///
+/// Equivalent GLSL:
+/// ```glsl
+/// #version 450
+/// layout(push_constant) uniform Quad { vec4 rect; vec4 uv_u; vec4 uv_v; } quad;
+/// layout(location = 0) out vec2 uv;
+/// void main() {
+///     vec2 corners[6] = vec2[6](
+///         vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(0.0, 1.0),
+///         vec2(0.0, 1.0), vec2(1.0, 0.0), vec2(1.0, 1.0)
+///     );
+///     vec2 corner = corners[gl_VertexIndex];
+///     vec3 point = vec3(corner, 1.0);
+///     uv = vec2(dot(quad.uv_u.xyz, point), dot(quad.uv_v.xyz, point));
+///     gl_Position = vec4(quad.rect.xy + corner * quad.rect.zw, 0.0, 1.0);
+/// }
+/// ```
+#[rustfmt::skip]
+pub const TEXTURED_QUAD_VERT_SPV: &[u32] = &[
+    // SPIR-V magic number and version
+    0x07230203, 0x00010000, 0x0008000b, 0x0000003d,
+    0x00000000, 0x00020011, 0x00000001, 0x0003000e,
+    0x00000000, 0x00000001, 0x0008000f, 0x00000000,
+    0x00000001, 0x6e69616d, 0x00000000, 0x00000002,
+    0x00000003, 0x00000004, 0x00030003, 0x00000002,
+    0x000001c2, 0x00040005, 0x00000001, 0x6e69616d,
+    0x00000000, 0x00040005, 0x00000005, 0x64617551,
+    0x00000000, 0x00050006, 0x00000005, 0x00000000,
+    0x74636572, 0x00000000, 0x00050006, 0x00000005,
+    0x00000001, 0x755f7675, 0x00000000, 0x00050006,
+    0x00000005, 0x00000002, 0x765f7675, 0x00000000,
+    0x00040005, 0x00000006, 0x64617571, 0x00000000,
+    0x00030005, 0x00000003, 0x00007675, 0x00040047,
+    0x00000002, 0x0000000b, 0x0000002a, 0x00040047,
+    0x00000003, 0x0000001e, 0x00000000, 0x00050048,
+    0x00000007, 0x00000000, 0x0000000b, 0x00000000,
+    0x00030047, 0x00000007, 0x00000002, 0x00050048,
+    0x00000005, 0x00000000, 0x00000023, 0x00000000,
+    0x00050048, 0x00000005, 0x00000001, 0x00000023,
+    0x00000010, 0x00050048, 0x00000005, 0x00000002,
+    0x00000023, 0x00000020, 0x00030047, 0x00000005,
+    0x00000002, 0x00020013, 0x00000008, 0x00030021,
+    0x00000009, 0x00000008, 0x00030016, 0x0000000a,
+    0x00000020, 0x00040017, 0x0000000b, 0x0000000a,
+    0x00000002, 0x00040017, 0x00000030, 0x0000000a,
+    0x00000003, 0x00040017, 0x0000000c, 0x0000000a,
+    0x00000004, 0x00040015, 0x0000000d, 0x00000020,
+    0x00000000, 0x00040015, 0x0000000e, 0x00000020,
+    0x00000001, 0x0004002b, 0x0000000d, 0x0000000f,
+    0x00000006, 0x0004001c, 0x00000010, 0x0000000b,
+    0x0000000f, 0x00040020, 0x00000011, 0x00000007,
+    0x00000010, 0x00040020, 0x00000012, 0x00000007,
+    0x0000000b, 0x00040020, 0x00000013, 0x00000001,
+    0x0000000e, 0x0004003b, 0x00000013, 0x00000002,
+    0x00000001, 0x00040020, 0x00000014, 0x00000003,
+    0x0000000b, 0x0004003b, 0x00000014, 0x00000003,
+    0x00000003, 0x0003001e, 0x00000007, 0x0000000c,
+    0x00040020, 0x00000015, 0x00000003, 0x00000007,
+    0x0004003b, 0x00000015, 0x00000004, 0x00000003,
+    0x0005001e, 0x00000005, 0x0000000c, 0x0000000c,
+    0x0000000c, 0x00040020, 0x00000016, 0x00000009,
+    0x00000005, 0x0004003b, 0x00000016, 0x00000006,
+    0x00000009, 0x00040020, 0x00000017, 0x00000009,
+    0x0000000c, 0x00040020, 0x00000018, 0x00000003,
+    0x0000000c, 0x0004002b, 0x0000000e, 0x00000019,
+    0x00000000, 0x0004002b, 0x0000000e, 0x00000031,
+    0x00000001, 0x0004002b, 0x0000000e, 0x00000032,
+    0x00000002, 0x0004002b, 0x0000000a, 0x0000001a,
+    0x00000000, 0x0004002b, 0x0000000a, 0x0000001b,
+    0x3f800000, 0x0005002c, 0x0000000b, 0x0000001c,
+    0x0000001a, 0x0000001a, 0x0005002c, 0x0000000b,
+    0x0000001d, 0x0000001b, 0x0000001a, 0x0005002c,
+    0x0000000b, 0x0000001e, 0x0000001a, 0x0000001b,
+    0x0005002c, 0x0000000b, 0x0000001f, 0x0000001b,
+    0x0000001b, 0x0009002c, 0x00000010, 0x00000020,
+    0x0000001c, 0x0000001d, 0x0000001e, 0x0000001e,
+    0x0000001d, 0x0000001f, 0x00050036, 0x00000008,
+    0x00000001, 0x00000000, 0x00000009, 0x000200f8,
+    0x00000021, 0x0004003b, 0x00000011, 0x00000022,
+    0x00000007, 0x0003003e, 0x00000022, 0x00000020,
+    0x0004003d, 0x0000000e, 0x00000023, 0x00000002,
+    0x00050041, 0x00000012, 0x00000024, 0x00000022,
+    0x00000023, 0x0004003d, 0x0000000b, 0x00000025,
+    0x00000024, 0x00050050, 0x00000030, 0x00000033,
+    0x00000025, 0x0000001b, 0x00050041, 0x00000017,
+    0x00000034, 0x00000006, 0x00000031, 0x0004003d,
+    0x0000000c, 0x00000035, 0x00000034, 0x0008004f,
+    0x00000030, 0x00000036, 0x00000035, 0x00000035,
+    0x00000000, 0x00000001, 0x00000002, 0x00050094,
+    0x0000000a, 0x00000037, 0x00000036, 0x00000033,
+    0x00050041, 0x00000017, 0x00000038, 0x00000006,
+    0x00000032, 0x0004003d, 0x0000000c, 0x00000039,
+    0x00000038, 0x0008004f, 0x00000030, 0x0000003a,
+    0x00000039, 0x00000039, 0x00000000, 0x00000001,
+    0x00000002, 0x00050094, 0x0000000a, 0x0000003b,
+    0x0000003a, 0x00000033, 0x00050050, 0x0000000b,
+    0x0000003c, 0x00000037, 0x0000003b, 0x0003003e,
+    0x00000003, 0x0000003c, 0x00050041, 0x00000017,
+    0x00000026, 0x00000006, 0x00000019, 0x0004003d,
+    0x0000000c, 0x00000027, 0x00000026, 0x0007004f,
+    0x0000000b, 0x00000028, 0x00000027, 0x00000027,
+    0x00000000, 0x00000001, 0x0007004f, 0x0000000b,
+    0x00000029, 0x00000027, 0x00000027, 0x00000002,
+    0x00000003, 0x00050085, 0x0000000b, 0x0000002a,
+    0x00000025, 0x00000029, 0x00050081, 0x0000000b,
+    0x0000002b, 0x00000028, 0x0000002a, 0x00050051,
+    0x0000000a, 0x0000002c, 0x0000002b, 0x00000000,
+    0x00050051, 0x0000000a, 0x0000002d, 0x0000002b,
+    0x00000001, 0x00070050, 0x0000000c, 0x0000002e,
+    0x0000002c, 0x0000002d, 0x0000001a, 0x0000001b,
+    0x00050041, 0x00000018, 0x0000002f, 0x00000004,
+    0x00000019, 0x0003003e, 0x0000002f, 0x0000002e,
+    0x000100fd, 0x00010038,
+];
+
+/// SPIR-V bytecode for a textured quad fragment shader.
+///
+/// This shader samples the texture bound at set 0, binding 0 and scales the
+/// premultiplied color by the opacity following the vertex push constants.
+///
+/// Equivalent GLSL:
+/// ```glsl
+/// #version 450
+/// layout(set = 0, binding = 0) uniform sampler2D tex;
+/// layout(push_constant) uniform Opacity { layout(offset = 48) float opacity; } fragment;
+/// layout(location = 0) in vec2 uv;
+/// layout(location = 0) out vec4 color;
+/// void main() {
+///     color = texture(tex, uv) * fragment.opacity;
+/// }
+/// ```
+#[rustfmt::skip]
+pub const TEXTURED_QUAD_FRAG_SPV: &[u32] = &[
+    // SPIR-V magic number and version
+    0x07230203, 0x00010000, 0x0008000b, 0x0000001c,
+    0x00000000, 0x00020011, 0x00000001, 0x0003000e,
+    0x00000000, 0x00000001, 0x0007000f, 0x00000004,
+    0x00000001, 0x6e69616d, 0x00000000, 0x00000002,
+    0x00000003, 0x00030010, 0x00000001, 0x00000007,
+    0x00030003, 0x00000002, 0x000001c2, 0x00040005,
+    0x00000001, 0x6e69616d, 0x00000000, 0x00030005,
+    0x00000004, 0x00786574, 0x00030005, 0x00000003,
+    0x00007675, 0x00040005, 0x00000002, 0x6f6c6f63,
+    0x00000072, 0x00040005, 0x00000013, 0x6361704f,
+    0x00797469, 0x00050006, 0x00000013, 0x00000000,
+    0x6361706f, 0x00797469, 0x00050005, 0x00000015,
+    0x67617266, 0x746e656d, 0x00000000, 0x00040047,
+    0x00000004, 0x00000022, 0x00000000, 0x00040047,
+    0x00000004, 0x00000021, 0x00000000, 0x00040047,
+    0x00000003, 0x0000001e, 0x00000000, 0x00040047,
+    0x00000002, 0x0000001e, 0x00000000, 0x00050048,
+    0x00000013, 0x00000000, 0x00000023, 0x00000030,
+    0x00030047, 0x00000013, 0x00000002, 0x00020013,
+    0x00000005, 0x00030021, 0x00000006, 0x00000005,
+    0x00030016, 0x00000007, 0x00000020, 0x00040017,
+    0x00000008, 0x00000007, 0x00000002, 0x00040017,
+    0x00000009, 0x00000007, 0x00000004, 0x00090019,
+    0x0000000a, 0x00000007, 0x00000001, 0x00000000,
+    0x00000000, 0x00000000, 0x00000001, 0x00000000,
+    0x0003001b, 0x0000000b, 0x0000000a, 0x00040020,
+    0x0000000c, 0x00000000, 0x0000000b, 0x0004003b,
+    0x0000000c, 0x00000004, 0x00000000, 0x00040020,
+    0x0000000d, 0x00000001, 0x00000008, 0x0004003b,
+    0x0000000d, 0x00000003, 0x00000001, 0x00040020,
+    0x0000000e, 0x00000003, 0x00000009, 0x0004003b,
+    0x0000000e, 0x00000002, 0x00000003, 0x0003001e,
+    0x00000013, 0x00000007, 0x00040020, 0x00000014,
+    0x00000009, 0x00000013, 0x0004003b, 0x00000014,
+    0x00000015, 0x00000009, 0x00040015, 0x00000016,
+    0x00000020, 0x00000001, 0x0004002b, 0x00000016,
+    0x00000017, 0x00000000, 0x00040020, 0x00000018,
+    0x00000009, 0x00000007, 0x00050036, 0x00000005,
+    0x00000001, 0x00000000, 0x00000006, 0x000200f8,
+    0x0000000f, 0x0004003d, 0x0000000b, 0x00000010,
+    0x00000004, 0x0004003d, 0x00000008, 0x00000011,
+    0x00000003, 0x00050057, 0x00000009, 0x00000012,
+    0x00000010, 0x00000011, 0x00050041, 0x00000018,
+    0x00000019, 0x00000015, 0x00000017, 0x0004003d,
+    0x00000007, 0x0000001a, 0x00000019, 0x0005008e,
+    0x00000009, 0x0000001b, 0x00000012, 0x0000001a,
+    0x0003003e, 0x00000002, 0x0000001b, 0x000100fd,
+    0x00010038,
+];
+
 /// Creates the fullscreen triangle vertex shader module.
 pub fn create_fullscreen_triangle_vert(device: &Device) -> anyhow::Result<ShaderModule> {
     ShaderModule::from_spirv(device, FULLSCREEN_TRIANGLE_VERT_SPV)
@@ -149,3 +339,15 @@ pub fn create_solid_color_frag(device: &Device) -> anyhow::Result<ShaderModule>
     ShaderModule::from_spirv(device, SOLID_COLOR_FRAG_SPV)
         .context("Failed to create solid color fragment shader")
 }
+
+/// Creates the textured quad vertex shader module.
+pub fn create_textured_quad_vert(device: &Device) -> anyhow::Result<ShaderModule> {
+    ShaderModule::from_spirv(device, TEXTURED_QUAD_VERT_SPV)
+        .context("Failed to create textured quad vertex shader")
+}
+
+/// Creates the textured quad fragment shader module.
+pub fn create_textured_quad_frag(device: &Device) -> anyhow::Result<ShaderModule> {
+    ShaderModule::from_spirv(device, TEXTURED_QUAD_FRAG_SPV)
+        .context("Failed to create textured quad fragment shader")
+}
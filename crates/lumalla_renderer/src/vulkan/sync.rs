@@ -1,5 +1,7 @@
 //! Synchronization primitives (fences and semaphores)
 
+use std::os::fd::{FromRawFd, OwnedFd};
+
 use anyhow::Context;
 use ash::vk;
 use log::debug;
@@ -107,6 +109,46 @@ impl Semaphore {
         })
     }
 
+    /// Creates a semaphore that can be exported with [`Self::export_sync_file`].
+    ///
+    /// Returns `None` if the device cannot export semaphores as sync files.
+    pub fn new_sync_file_exportable(device: &Device) -> anyhow::Result<Option<Self>> {
+        if device.external_semaphore_fd().is_none() {
+            return Ok(None);
+        }
+        let mut export_info = vk::ExportSemaphoreCreateInfo::default()
+            .handle_types(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+        let create_info = vk::SemaphoreCreateInfo::default().push_next(&mut export_info);
+
+        let handle = unsafe { device.handle().create_semaphore(&create_info, None) }
+            .context("Failed to create exportable semaphore")?;
+
+        debug!("Created semaphore exportable as sync file");
+
+        Ok(Some(Self {
+            handle,
+            device: device.handle().clone(),
+        }))
+    }
+
+    /// Exports the pending signal of the semaphore as a sync file, which
+    /// leaves the semaphore unsignaled.
+    ///
+    /// Must be called after submitting work that signals the semaphore.
+    /// Returns `None` if the signal already happened.
+    pub fn export_sync_file(&self, device: &Device) -> anyhow::Result<Option<OwnedFd>> {
+        let loader = device
+            .external_semaphore_fd()
+            .context("Semaphores cannot be exported as sync files")?;
+        let info = vk::SemaphoreGetFdInfoKHR::default()
+            .semaphore(self.handle)
+            .handle_type(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+        let fd = unsafe { loader.get_semaphore_fd(&info) }
+            .context("Failed to export semaphore as sync file")?;
+        // -1 stands for a signal that already happened.
+        Ok((fd >= 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
     /// Returns the semaphore handle.
     pub fn handle(&self) -> vk::Semaphore {
         self.handle
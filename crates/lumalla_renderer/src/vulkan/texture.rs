@@ -0,0 +1,184 @@
+//! Sampled textures holding client surface contents.
+
+use anyhow::Context;
+use ash::vk;
+use log::debug;
+
+use super::{Device, PhysicalDevice, dma_buf::find_memory_type_index};
+
+/// A device-local BGRA image that is filled by uploads or drawn into, and
+/// sampled by shaders.
+pub struct Texture {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+    device: ash::Device,
+}
+
+impl Texture {
+    /// Allocates a texture in `B8G8R8A8_UNORM`, the byte order of Wayland's
+    /// ARGB8888 and XRGB8888 SHM formats.
+    pub fn new(
+        device: &Device,
+        physical_device: &PhysicalDevice,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            width > 0 && height > 0,
+            "Texture dimensions must be non-zero"
+        );
+
+        let format = vk::Format::B8G8R8A8_UNORM;
+        let extent = vk::Extent2D { width, height };
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_DST
+                    | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        let image = unsafe { device.handle().create_image(&image_info, None) }
+            .context("Failed to create texture image")?;
+
+        let requirements = unsafe { device.handle().get_image_memory_requirements(image) };
+        let Some(memory_type_index) = find_memory_type_index(
+            physical_device.memory_properties(),
+            requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        ) else {
+            unsafe {
+                device.handle().destroy_image(image, None);
+            }
+            anyhow::bail!("No DEVICE_LOCAL memory type for texture");
+        };
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        let memory = match unsafe { device.handle().allocate_memory(&allocate_info, None) } {
+            Ok(memory) => memory,
+            Err(error) => {
+                unsafe {
+                    device.handle().destroy_image(image, None);
+                }
+                return Err(error).context("Failed to allocate texture memory");
+            }
+        };
+        if let Err(error) = unsafe { device.handle().bind_image_memory(image, memory, 0) } {
+            unsafe {
+                device.handle().free_memory(memory, None);
+                device.handle().destroy_image(image, None);
+            }
+            return Err(error).context("Failed to bind texture memory");
+        }
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .components(vk::ComponentMapping::default())
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = match unsafe { device.handle().create_image_view(&view_info, None) } {
+            Ok(view) => view,
+            Err(error) => {
+                unsafe {
+                    device.handle().free_memory(memory, None);
+                    device.handle().destroy_image(image, None);
+                }
+                return Err(error).context("Failed to create texture image view");
+            }
+        };
+
+        debug!("Allocated texture: {width}x{height}");
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+            extent,
+            device: device.handle().clone(),
+        })
+    }
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            self.device.free_memory(self.memory, None);
+        }
+        debug!("Destroyed texture");
+    }
+}
+
+/// Samples textures without filtering between texels, clamped to the edge.
+pub struct Sampler {
+    handle: vk::Sampler,
+    device: ash::Device,
+}
+
+impl Sampler {
+    pub fn new_nearest(device: &Device) -> anyhow::Result<Self> {
+        let create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+
+        let handle = unsafe { device.handle().create_sampler(&create_info, None) }
+            .context("Failed to create sampler")?;
+
+        Ok(Self {
+            handle,
+            device: device.handle().clone(),
+        })
+    }
+
+    pub fn handle(&self) -> vk::Sampler {
+        self.handle
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_sampler(self.handle, None);
+        }
+    }
+}
@@ -1,12 +1,18 @@
-//! One-shot CPU upload into a Vulkan scanout image.
+//! One-shot CPU upload into a Vulkan scanout image or texture.
 
+use std::borrow::Cow;
 use std::ptr;
 
 use anyhow::Context;
 use ash::vk;
 
-use super::{CommandBufferRecorder, CommandPool, Device, DmaBufImage, Fence, PhysicalDevice};
+use super::{
+    CommandBufferRecorder, CommandPool, Device, DmaBufImage, Fence, PhysicalDevice, Texture,
+};
 
+/// Copies tightly packed `pixels` of `width` x `height` into the top-left
+/// corner of `image`; only the `damage` rectangles of them if given.
+#[allow(clippy::too_many_arguments)]
 pub fn upload_bgra_to_image(
     device: &Device,
     physical_device: &PhysicalDevice,
@@ -15,13 +21,101 @@ pub fn upload_bgra_to_image(
     pixels: &[u8],
     width: u32,
     height: u32,
+    damage: Option<&[vk::Rect2D]>,
+) -> anyhow::Result<()> {
+    let transition = LayoutTransition {
+        old_layout: vk::ImageLayout::GENERAL,
+        src_access_mask: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+        src_stage_mask: vk::PipelineStageFlags::ALL_COMMANDS,
+        new_layout: vk::ImageLayout::GENERAL,
+        dst_access_mask: vk::AccessFlags::MEMORY_READ,
+        dst_stage_mask: vk::PipelineStageFlags::ALL_COMMANDS,
+    };
+    upload_bgra(
+        device,
+        physical_device,
+        command_pool,
+        (image.image(), image.extent()),
+        pixels,
+        width,
+        height,
+        damage,
+        &transition,
+    )
+}
+
+/// Replaces the contents of `texture`, or just the `damage` rectangles of
+/// a texture uploaded before, and leaves it ready for sampling in fragment
+/// shaders.
+pub fn upload_bgra_to_texture(
+    device: &Device,
+    physical_device: &PhysicalDevice,
+    command_pool: &CommandPool,
+    texture: &Texture,
+    pixels: &[u8],
+    damage: Option<&[vk::Rect2D]>,
+) -> anyhow::Result<()> {
+    let transition = match damage {
+        Some(_) => LayoutTransition {
+            old_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        },
+        // The previous contents are overwritten entirely, so they need not be kept.
+        None => LayoutTransition {
+            old_layout: vk::ImageLayout::UNDEFINED,
+            src_access_mask: vk::AccessFlags::empty(),
+            src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        },
+    };
+    let extent = texture.extent();
+    upload_bgra(
+        device,
+        physical_device,
+        command_pool,
+        (texture.image(), extent),
+        pixels,
+        extent.width,
+        extent.height,
+        damage,
+        &transition,
+    )
+}
+
+/// Layouts of the destination image before and after the copy.
+struct LayoutTransition {
+    old_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags,
+    src_stage_mask: vk::PipelineStageFlags,
+    new_layout: vk::ImageLayout,
+    dst_access_mask: vk::AccessFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn upload_bgra(
+    device: &Device,
+    physical_device: &PhysicalDevice,
+    command_pool: &CommandPool,
+    (image, extent): (vk::Image, vk::Extent2D),
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    damage: Option<&[vk::Rect2D]>,
+    transition: &LayoutTransition,
 ) -> anyhow::Result<()> {
     anyhow::ensure!(
         width > 0 && height > 0,
         "Upload dimensions must be non-zero"
     );
     anyhow::ensure!(
-        width <= image.extent().width && height <= image.extent().height,
+        width <= extent.width && height <= extent.height,
         "Upload exceeds destination image"
     );
     let required_size = u64::from(width)
@@ -33,7 +127,21 @@ pub fn upload_bgra_to_image(
         "Upload pixel data is truncated"
     );
 
-    let staging = StagingBuffer::new(device, physical_device, &pixels[..required_size as usize])?;
+    let full = [vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent: vk::Extent2D { width, height },
+    }];
+    let (bytes, copies) = pack_regions(
+        &pixels[..required_size as usize],
+        width,
+        height,
+        damage.unwrap_or(&full),
+    )?;
+    if copies.is_empty() {
+        return Ok(());
+    }
+
+    let staging = StagingBuffer::new(device, physical_device, &bytes)?;
     let command_buffer = command_pool
         .allocate_command_buffer(device)
         .context("Failed to allocate upload command buffer")?;
@@ -41,18 +149,18 @@ pub fn upload_bgra_to_image(
     let record_result = (|| -> anyhow::Result<()> {
         let recorder = CommandBufferRecorder::begin_one_time(device, command_buffer)?;
         let to_transfer = vk::ImageMemoryBarrier::default()
-            .src_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .src_access_mask(transition.src_access_mask)
             .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .old_layout(vk::ImageLayout::GENERAL)
+            .old_layout(transition.old_layout)
             .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
             .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .image(image.image())
+            .image(image)
             .subresource_range(color_subresource_range());
         unsafe {
             device.handle().cmd_pipeline_barrier(
                 recorder.command_buffer(),
-                vk::PipelineStageFlags::ALL_COMMANDS,
+                transition.src_stage_mask,
                 vk::PipelineStageFlags::TRANSFER,
                 vk::DependencyFlags::empty(),
                 &[],
@@ -61,50 +169,34 @@ pub fn upload_bgra_to_image(
             );
         }
 
-        let copy = vk::BufferImageCopy::default()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer: 0,
-                layer_count: 1,
-            })
-            .image_offset(vk::Offset3D::default())
-            .image_extent(vk::Extent3D {
-                width,
-                height,
-                depth: 1,
-            });
         unsafe {
             device.handle().cmd_copy_buffer_to_image(
                 recorder.command_buffer(),
                 staging.buffer,
-                image.image(),
+                image,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[copy],
+                &copies,
             );
         }
 
-        let to_general = vk::ImageMemoryBarrier::default()
+        let to_final = vk::ImageMemoryBarrier::default()
             .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-            .dst_access_mask(vk::AccessFlags::MEMORY_READ)
+            .dst_access_mask(transition.dst_access_mask)
             .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-            .new_layout(vk::ImageLayout::GENERAL)
+            .new_layout(transition.new_layout)
             .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
             .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-            .image(image.image())
+            .image(image)
             .subresource_range(color_subresource_range());
         unsafe {
             device.handle().cmd_pipeline_barrier(
                 recorder.command_buffer(),
                 vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::ALL_COMMANDS,
+                transition.dst_stage_mask,
                 vk::DependencyFlags::empty(),
                 &[],
                 &[],
-                &[to_general],
+                &[to_final],
             );
         }
         recorder.end()?;
@@ -139,6 +231,71 @@ pub fn upload_bgra_to_image(
     Ok(())
 }
 
+/// Gathers the rows of `regions` of tightly packed `pixels` for one staging
+/// buffer, with a copy into the image per region. Empty regions are skipped.
+fn pack_regions<'a>(
+    pixels: &'a [u8],
+    width: u32,
+    height: u32,
+    regions: &[vk::Rect2D],
+) -> anyhow::Result<(Cow<'a, [u8]>, Vec<vk::BufferImageCopy>)> {
+    let copy = |offset: usize, region: &vk::Rect2D| {
+        vk::BufferImageCopy::default()
+            .buffer_offset(offset as vk::DeviceSize)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(vk::Offset3D {
+                x: region.offset.x,
+                y: region.offset.y,
+                z: 0,
+            })
+            .image_extent(vk::Extent3D {
+                width: region.extent.width,
+                height: region.extent.height,
+                depth: 1,
+            })
+    };
+    for region in regions {
+        anyhow::ensure!(
+            region.offset.x >= 0
+                && region.offset.y >= 0
+                && region.offset.x as u32 + region.extent.width <= width
+                && region.offset.y as u32 + region.extent.height <= height,
+            "Upload region exceeds the pixel data"
+        );
+    }
+    if let [region] = regions
+        && region.extent.width == width
+        && region.extent.height == height
+    {
+        return Ok((Cow::Borrowed(pixels), vec![copy(0, region)]));
+    }
+
+    let row_bytes = width as usize * 4;
+    let mut bytes = Vec::new();
+    let mut copies = Vec::with_capacity(regions.len());
+    for region in regions {
+        if region.extent.width == 0 || region.extent.height == 0 {
+            continue;
+        }
+        copies.push(copy(bytes.len(), region));
+        let x = region.offset.x as usize * 4;
+        let len = region.extent.width as usize * 4;
+        let rows =
+            region.offset.y as usize..(region.offset.y as u32 + region.extent.height) as usize;
+        for row in rows {
+            bytes.extend_from_slice(&pixels[row * row_bytes + x..][..len]);
+        }
+    }
+    Ok((Cow::Owned(bytes), copies))
+}
+
 fn color_subresource_range() -> vk::ImageSubresourceRange {
     vk::ImageSubresourceRange {
         aspect_mask: vk::ImageAspectFlags::COLOR,
@@ -280,6 +437,31 @@ mod tests {
     use super::*;
     use crate::vulkan::{Framebuffer, RenderPass, VulkanContext, clear_framebuffer_to_color};
 
+    #[test]
+    fn packs_damaged_rows_only() {
+        // 3x2 image whose pixels hold their index.
+        let pixels: Vec<u8> = (0..6u8).flat_map(|index| [index; 4]).collect();
+        let region = |x, y, width, height| vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: vk::Extent2D { width, height },
+        };
+
+        let (bytes, copies) = pack_regions(&pixels, 3, 2, &[region(0, 0, 3, 2)]).unwrap();
+        assert!(matches!(bytes, Cow::Borrowed(_)));
+        assert_eq!(copies.len(), 1);
+
+        let (bytes, copies) =
+            pack_regions(&pixels, 3, 2, &[region(1, 0, 2, 2), region(0, 1, 0, 1)]).unwrap();
+        assert_eq!(
+            bytes.chunks(4).map(|pixel| pixel[0]).collect::<Vec<_>>(),
+            [1, 2, 4, 5]
+        );
+        assert_eq!(copies.len(), 1);
+        assert_eq!(copies[0].image_offset.x, 1);
+
+        assert!(pack_regions(&pixels, 3, 2, &[region(2, 0, 2, 1)]).is_err());
+    }
+
     #[test]
     #[ignore = "requires a Vulkan GPU with DMA-BUF export support"]
     fn hardware_uploads_bgra_to_exportable_image() {
@@ -315,6 +497,7 @@ mod tests {
             &pixels,
             16,
             16,
+            None,
         )
         .unwrap();
         image.export_dma_buf().unwrap();
@@ -53,6 +53,11 @@ pub enum Action {
         index: usize,
     },
     /// Close current window
+    ///
+    /// Actions are not dispatched anywhere yet. Key bindings close windows
+    /// with the config's `close_current_window`, which sends
+    /// [`MainMessage::CloseCurrentWindow`](crate::MainMessage::CloseCurrentWindow);
+    /// dispatching this action should send the same message.
     Close,
     /// Start pipewire video stream
     StartVideoStream,
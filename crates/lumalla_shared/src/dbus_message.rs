@@ -5,7 +5,7 @@ use crate::{DrmDeviceState, Output};
 pub enum DbusMessage {
     /// Requests the D-Bus thread to shut down.
     Shutdown,
-    /// Replaces the output list returned by `GetOutputs` and used for layout resolution.
+    /// Replaces the output list returned by `GetOutputs`.
     SetOutputs(Vec<Output>),
     /// Replaces the DRM device list returned by `GetDrmDevices`.
     SetDrmDevices(Vec<DrmDeviceState>),
@@ -17,4 +17,16 @@ pub enum DbusMessage {
     EmitDrmDevicesChanged(Vec<DrmDeviceState>),
     /// Broadcast that a custom key binding was activated.
     EmitBindingActivated(String),
+    /// Broadcast the name of the layout space the outputs are now placed with.
+    EmitLayoutSpaceActivated(String),
+    /// Replaces the `(output, workspace)` pairs returned by
+    /// `GetActiveWorkspaces`, broadcasting the outputs whose workspace changed.
+    SetActiveWorkspaces(Vec<(String, String)>),
+    /// Start a program with the environment set over D-Bus.
+    Spawn {
+        /// The program to run.
+        command: String,
+        /// Arguments passed to the program.
+        args: Vec<String>,
+    },
 }
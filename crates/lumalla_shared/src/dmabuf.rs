@@ -0,0 +1,55 @@
+use std::os::fd::OwnedFd;
+
+/// One plane of a client buffer backed by a dmabuf.
+#[derive(Debug)]
+pub struct DmabufPlane {
+    /// The dmabuf holding the plane
+    pub fd: OwnedFd,
+    /// Offset of the plane in the dmabuf, in bytes
+    pub offset: u32,
+    /// Bytes from one row of the plane to the next
+    pub stride: u32,
+}
+
+/// A client buffer made of dmabufs, as described through linux-dmabuf.
+#[derive(Debug)]
+pub struct DmabufAttributes {
+    pub width: u32,
+    pub height: u32,
+    /// DRM fourcc of the buffer
+    pub format: u32,
+    /// DRM format modifier shared by all planes
+    pub modifier: u64,
+    /// The planes in order, starting at plane 0
+    pub planes: Vec<DmabufPlane>,
+    /// The rows are stored bottom to top
+    pub y_invert: bool,
+}
+
+/// A format and modifier pair the render device can sample from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmabufFormat {
+    /// DRM fourcc
+    pub format: u32,
+    /// DRM format modifier
+    pub modifier: u64,
+    /// Number of planes a buffer of this format and modifier is made of
+    pub planes: u32,
+}
+
+/// Buffers the render device can import, advertised through linux-dmabuf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmabufFormats {
+    /// `dev_t` of the render device
+    pub main_device: u64,
+    pub formats: Vec<DmabufFormat>,
+}
+
+impl DmabufFormats {
+    /// The importable pair of `format` and `modifier`, if any
+    pub fn get(&self, format: u32, modifier: u64) -> Option<&DmabufFormat> {
+        self.formats
+            .iter()
+            .find(|entry| entry.format == format && entry.modifier == modifier)
+    }
+}
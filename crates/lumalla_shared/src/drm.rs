@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use crate::Transform;
+
 /// A display mode advertised on a DRM connector.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DrmMode {
@@ -54,4 +56,9 @@ pub struct OutputConfig {
     pub enabled: bool,
     /// Kernel mode name to use; `None` selects the preferred (or first) mode.
     pub mode_name: Option<String>,
+    /// Scale in percent (e.g. `150`); `None` shows the output unscaled.
+    pub scale: Option<u32>,
+    /// How the layout is rotated and flipped on the output, e.g. for
+    /// monitors mounted in portrait orientation.
+    pub transform: Transform,
 }
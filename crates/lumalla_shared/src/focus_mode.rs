@@ -0,0 +1,20 @@
+/// How pointer input moves the keyboard focus between windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FocusMode {
+    /// Windows are focused when clicked
+    #[default]
+    Click,
+    /// The window under the pointer is focused
+    FollowsMouse,
+}
+
+impl FocusMode {
+    /// The focus mode named like in configs: `click` or `follows-mouse`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "click" => Self::Click,
+            "follows-mouse" => Self::FollowsMouse,
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use crate::Output;
+
+/// Where a layout space places an output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutOutput {
+    /// The connector name of the output
+    pub name: String,
+    /// The location of the output in the global layout
+    pub location: (i32, i32),
+}
+
+/// Output placements by the name of their layout space, e.g. `docked` or
+/// `laptop`
+pub type LayoutSpaces = HashMap<String, Vec<LayoutOutput>>;
+
+/// The layout space to place the `connected` outputs with: the one placing
+/// the most of them out of those placing only connected outputs
+///
+/// Ties go to the space whose name sorts first.
+pub fn select_layout_space<'a>(
+    spaces: &'a LayoutSpaces,
+    connected: &[&str],
+) -> Option<(&'a str, &'a [LayoutOutput])> {
+    spaces
+        .iter()
+        .filter(|(_, outputs)| {
+            !outputs.is_empty()
+                && outputs
+                    .iter()
+                    .all(|output| connected.contains(&output.name.as_str()))
+        })
+        .min_by(|(a_name, a), (b_name, b)| b.len().cmp(&a.len()).then(a_name.cmp(b_name)))
+        .map(|(name, outputs)| (name.as_str(), outputs.as_slice()))
+}
+
+/// The layout space activated for a set of connected outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutSpaceActivation {
+    /// Name of the layout space, `None` if none places the connected outputs
+    pub space: Option<String>,
+    /// Where the layout space places outputs, by output name
+    pub locations: HashMap<String, (i32, i32)>,
+    /// Whether `space` differs from the one active before, so its
+    /// activation has to be announced
+    pub changed: bool,
+}
+
+/// Activates the layout space [`select_layout_space`] picks for the
+/// `connected` outputs while the space named `active` is active
+pub fn activate_layout_space(
+    spaces: &LayoutSpaces,
+    connected: &[&str],
+    active: Option<&str>,
+) -> LayoutSpaceActivation {
+    let selected = select_layout_space(spaces, connected);
+    let locations = selected
+        .map(|(_, outputs)| {
+            outputs
+                .iter()
+                .map(|output| (output.name.clone(), output.location))
+                .collect()
+        })
+        .unwrap_or_default();
+    let space = selected.map(|(name, _)| name);
+    LayoutSpaceActivation {
+        space: space.map(String::from),
+        locations,
+        changed: space != active,
+    }
+}
+
+/// Places `outputs` in the global layout: at their location in `locations`,
+/// or else left to right in name order, after the rightmost output placed
+pub fn arrange_outputs(outputs: &mut [Output], locations: &HashMap<String, (i32, i32)>) {
+    let right_edge = |output: &Output| output.location.0 + output.logical_size().0;
+    let mut unplaced = Vec::new();
+    let mut next_x = 0;
+    for output in outputs.iter_mut() {
+        match locations.get(&output.name) {
+            Some(&location) => {
+                output.location = location;
+                next_x = next_x.max(right_edge(output));
+            }
+            None => unplaced.push(output),
+        }
+    }
+    unplaced.sort_by(|a, b| a.name.cmp(&b.name));
+    for output in unplaced {
+        output.location = (next_x, 0);
+        next_x = right_edge(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transform;
+
+    fn output(name: &str, size: (i32, i32), scale: u32) -> Output {
+        Output {
+            name: String::from(name),
+            description: String::new(),
+            location: (0, 0),
+            size,
+            scale,
+            transform: Transform::Normal,
+        }
+    }
+
+    fn placed(name: &str, location: (i32, i32)) -> LayoutOutput {
+        LayoutOutput {
+            name: String::from(name),
+            location,
+        }
+    }
+
+    #[test]
+    fn places_unconfigured_outputs_after_the_configured_ones() {
+        let mut outputs = [
+            output("HDMI-A-1", (1920, 1080), 100),
+            output("eDP-1", (2880, 1800), 200),
+            output("DP-1", (2560, 1440), 100),
+        ];
+        let locations = HashMap::from([(String::from("eDP-1"), (0, 1080))]);
+        arrange_outputs(&mut outputs, &locations);
+
+        let locations: Vec<_> = outputs.iter().map(|output| output.location).collect();
+        assert_eq!(locations, [(4000, 0), (0, 1080), (1440, 0)]);
+    }
+
+    #[test]
+    fn selects_the_largest_space_of_connected_outputs() {
+        let spaces = LayoutSpaces::from([
+            (
+                String::from("docked"),
+                vec![placed("eDP-1", (0, 0)), placed("DP-1", (1440, 0))],
+            ),
+            (String::from("laptop"), vec![placed("eDP-1", (0, 0))]),
+            (String::from("mirror"), vec![placed("eDP-1", (0, 0))]),
+        ]);
+
+        let docked = select_layout_space(&spaces, &["DP-1", "eDP-1"]);
+        assert_eq!(docked.map(|(name, _)| name), Some("docked"));
+        let laptop = select_layout_space(&spaces, &["eDP-1"]);
+        assert_eq!(laptop.map(|(name, _)| name), Some("laptop"));
+        assert!(select_layout_space(&spaces, &["HDMI-A-1"]).is_none());
+    }
+
+    #[test]
+    fn announces_a_layout_space_only_when_it_changes() {
+        let spaces = LayoutSpaces::from([
+            (
+                String::from("docked"),
+                vec![placed("eDP-1", (0, 1440)), placed("DP-1", (0, 0))],
+            ),
+            (String::from("laptop"), vec![placed("eDP-1", (0, 0))]),
+        ]);
+
+        let docked = activate_layout_space(&spaces, &["DP-1", "eDP-1"], Some("laptop"));
+        assert_eq!(docked.space.as_deref(), Some("docked"));
+        assert!(docked.changed);
+        assert_eq!(
+            docked.locations,
+            HashMap::from([
+                (String::from("eDP-1"), (0, 1440)),
+                (String::from("DP-1"), (0, 0)),
+            ])
+        );
+
+        let unchanged = activate_layout_space(&spaces, &["DP-1", "eDP-1"], Some("docked"));
+        assert_eq!(unchanged.space.as_deref(), Some("docked"));
+        assert!(!unchanged.changed);
+        assert_eq!(unchanged.locations, docked.locations);
+
+        let none = activate_layout_space(&spaces, &["HDMI-A-1"], Some("docked"));
+        assert_eq!(none.space, None);
+        assert!(none.changed);
+        assert!(none.locations.is_empty());
+        assert!(!activate_layout_space(&spaces, &["HDMI-A-1"], None).changed);
+    }
+}
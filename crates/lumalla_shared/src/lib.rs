@@ -2,13 +2,18 @@ mod action;
 mod args;
 mod comms;
 mod dbus_message;
+mod dmabuf;
 mod drm;
+mod focus_mode;
 mod keymap_memfd;
+mod layout;
 mod main_message;
 mod message_runner;
 mod mods;
 mod output;
+mod transform;
 pub mod udev;
+mod viewport;
 mod window_rule;
 mod zone;
 
@@ -16,12 +21,20 @@ pub use action::{Action, CallbackRef};
 pub use args::GlobalArgs;
 pub use comms::{Comms, MessageSender, message_loop_with_channel};
 pub use dbus_message::DbusMessage;
+pub use dmabuf::{DmabufAttributes, DmabufFormat, DmabufFormats, DmabufPlane};
 pub use drm::{DrmConnector, DrmDeviceState, DrmMode, OutputConfig};
+pub use focus_mode::FocusMode;
 pub use keymap_memfd::KeymapMemfd;
+pub use layout::{
+    LayoutOutput, LayoutSpaceActivation, LayoutSpaces, activate_layout_space, arrange_outputs,
+    select_layout_space,
+};
 pub use main_message::MainMessage;
 pub use message_runner::{MESSAGE_CHANNEL_TOKEN, MessageRunner};
 pub use mods::Mods;
-pub use output::Output;
+pub use output::{Output, PresentedFrame, logical_size};
+pub use transform::Transform;
 pub use udev::{Udev, UdevDevice, UdevEnumerate, UdevMonitor};
+pub use viewport::Viewport;
 pub use window_rule::WindowRule;
 pub use zone::Zone;
@@ -1,6 +1,11 @@
+use crate::FocusMode;
+use crate::LayoutSpaces;
 use crate::Mods;
 use crate::OutputConfig;
+use crate::WindowRule;
+use crate::Zone;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Represents the messages that can be sent to the main thread
 pub enum MainMessage {
@@ -27,4 +32,41 @@ pub enum MainMessage {
     SetRenderDevice(Option<PathBuf>),
     /// Merge per-connector output configuration (enabled / mode).
     SetOutputConfigs(Vec<OutputConfig>),
+    /// Replace the layout spaces outputs are placed with.
+    SetLayout(LayoutSpaces),
+    /// Replace the zones windows are placed in.
+    SetZones(Vec<Zone>),
+    /// Ask the focused window to close.
+    CloseCurrentWindow,
+    /// Kill clients whose window did not close within this long after being
+    /// asked to (`None` = never).
+    SetKillTimeout(Option<Duration>),
+    /// Move the current window to the named zone.
+    MoveCurrentWindowToZone(String),
+    /// Show the named workspace, creating it on the output under the pointer.
+    SwitchWorkspace(String),
+    /// Move the current window to the named workspace.
+    MoveCurrentWindowToWorkspace(String),
+    /// Attach a workspace with its windows to an output and show it there.
+    MoveWorkspaceToOutput {
+        /// The workspace to move.
+        workspace: String,
+        /// The connector name of the output to move it to.
+        output: String,
+    },
+    /// Replace the rules new windows are matched against, in the order they apply.
+    SetWindowRules(Vec<WindowRule>),
+    /// Change how pointer input moves the keyboard focus.
+    SetFocusMode(FocusMode),
+    /// Focus the window `index` places down the focus stack, wrapping around.
+    Tab(usize),
+    /// Focus the most recently used window of an app, or start it if it shows none.
+    FocusOrSpawn {
+        /// The app_id the app's toplevels have.
+        app_id: String,
+        /// The program started when no window has the app_id.
+        command: String,
+        /// Arguments passed to the program.
+        args: Vec<String>,
+    },
 }
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone)]
+use crate::Transform;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Output {
     /// The name of the output
     pub name: String,
@@ -6,13 +8,90 @@ pub struct Output {
     pub description: String,
     /// The location of the output
     pub location: (i32, i32),
-    /// The size of the output
+    /// The size of the output in pixels
     pub size: (i32, i32),
+    /// The scale of the output in percent
+    pub scale: u32,
+    /// How the layout is rotated and flipped to be shown on the output
+    pub transform: Transform,
 }
 
 impl Output {
+    /// The size of the output in the layout, after transforming and scaling
+    pub fn logical_size(&self) -> (i32, i32) {
+        logical_size(self.transform.apply_size(self.size), self.scale)
+    }
+
     /// Sets the location of the output
     pub fn set_location(&mut self, x: i32, y: i32) {
         self.location = (x, y);
     }
 }
+
+/// Size of `size` pixels shown at `scale` percent, rounded up to cover
+/// every pixel
+pub fn logical_size((width, height): (i32, i32), scale: u32) -> (i32, i32) {
+    let scale = scale.max(1) as i32;
+    (
+        (width * 100 + scale - 1) / scale,
+        (height * 100 + scale - 1) / scale,
+    )
+}
+
+/// Windows shown on an output by a completed page flip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresentedFrame {
+    /// The name of the output the frame was shown on
+    pub output: String,
+    /// `CLOCK_MONOTONIC` time at which the frame was shown
+    pub tv_sec: u64,
+    pub tv_nsec: u32,
+    /// Duration of one refresh cycle of the output; zero when unknown
+    pub refresh_nsec: u32,
+    /// Vertical blank counter of the output when the frame was shown
+    pub sequence: u64,
+    /// `(owner_id, root_id)` of the windows drawn into the frame
+    pub windows: Vec<(u32, u32)>,
+    /// `(owner_id, buffer_id)` of the client dmabuf scanned out without
+    /// composition, if any
+    pub client_buffer: Option<(u32, u32)>,
+}
+
+impl PresentedFrame {
+    /// Presentation time in milliseconds; wraps around like Wayland timestamps
+    pub fn time_msec(&self) -> u32 {
+        self.tv_sec
+            .wrapping_mul(1000)
+            .wrapping_add(u64::from(self.tv_nsec / 1_000_000)) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logical_size_covers_every_pixel() {
+        assert_eq!(logical_size((1920, 1080), 100), (1920, 1080));
+        assert_eq!(logical_size((1920, 1080), 150), (1280, 720));
+        assert_eq!(logical_size((2560, 1600), 175), (1463, 915));
+    }
+
+    #[test]
+    fn presentation_time_wraps_like_wayland_timestamps() {
+        let mut frame = PresentedFrame {
+            output: String::from("eDP-1"),
+            tv_sec: 12,
+            tv_nsec: 345_678_901,
+            refresh_nsec: 16_666_666,
+            sequence: 1,
+            windows: Vec::new(),
+            client_buffer: None,
+        };
+        assert_eq!(frame.time_msec(), 12_345);
+
+        frame.tv_sec = 4_294_968;
+        frame.tv_nsec = 1_000_000;
+        assert_eq!(frame.time_msec(), 705);
+    }
+}
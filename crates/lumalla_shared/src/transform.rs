@@ -0,0 +1,229 @@
+/// How the contents of a buffer or output are rotated and flipped, in the
+/// order of `wl_output.transform`
+///
+/// Rotations are counter-clockwise; flipped transforms mirror around the
+/// vertical axis before rotating.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transform {
+    #[default]
+    Normal,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Flipped,
+    Flipped90,
+    Flipped180,
+    Flipped270,
+}
+
+impl Transform {
+    /// The transform of a `wl_output.transform` value, if it is one
+    pub fn from_wayland(value: i32) -> Option<Self> {
+        Some(match value {
+            0 => Self::Normal,
+            1 => Self::Rotate90,
+            2 => Self::Rotate180,
+            3 => Self::Rotate270,
+            4 => Self::Flipped,
+            5 => Self::Flipped90,
+            6 => Self::Flipped180,
+            7 => Self::Flipped270,
+            _ => return None,
+        })
+    }
+
+    /// The `wl_output.transform` value of the transform
+    pub fn to_wayland(self) -> i32 {
+        self as i32
+    }
+
+    /// The transform named like in output configs: `normal`, `90`, `180`,
+    /// `270`, `flipped`, `flipped-90`, `flipped-180` or `flipped-270`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "normal" => Self::Normal,
+            "90" => Self::Rotate90,
+            "180" => Self::Rotate180,
+            "270" => Self::Rotate270,
+            "flipped" => Self::Flipped,
+            "flipped-90" => Self::Flipped90,
+            "flipped-180" => Self::Flipped180,
+            "flipped-270" => Self::Flipped270,
+            _ => return None,
+        })
+    }
+
+    /// The name of the transform in output configs; see [`Self::from_name`]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Rotate90 => "90",
+            Self::Rotate180 => "180",
+            Self::Rotate270 => "270",
+            Self::Flipped => "flipped",
+            Self::Flipped90 => "flipped-90",
+            Self::Flipped180 => "flipped-180",
+            Self::Flipped270 => "flipped-270",
+        }
+    }
+
+    /// Quarter turns counter-clockwise, after the mirroring if flipped
+    pub fn quarter_turns(self) -> u8 {
+        self as u8 % 4
+    }
+
+    /// Whether the image is mirrored around the vertical axis
+    pub fn is_flipped(self) -> bool {
+        self as u8 >= 4
+    }
+
+    /// The transform applying this one, then `next`
+    pub fn then(self, next: Self) -> Self {
+        // Mirroring reverses the direction of the turns made before it.
+        let turns = match next.is_flipped() {
+            true => next.quarter_turns() + 4 - self.quarter_turns(),
+            false => next.quarter_turns() + self.quarter_turns(),
+        };
+        let flipped = self.is_flipped() != next.is_flipped();
+        Self::from_wayland(i32::from(turns % 4 + if flipped { 4 } else { 0 })).unwrap_or_default()
+    }
+
+    /// The transform that undoes this one
+    pub fn invert(self) -> Self {
+        match self {
+            Self::Rotate90 => Self::Rotate270,
+            Self::Rotate270 => Self::Rotate90,
+            // Half turns and mirrors undo themselves.
+            other => other,
+        }
+    }
+
+    /// Whether width and height trade places
+    pub fn swaps_axes(self) -> bool {
+        matches!(
+            self,
+            Self::Rotate90 | Self::Rotate270 | Self::Flipped90 | Self::Flipped270
+        )
+    }
+
+    /// Size of an image of `size` once transformed
+    pub fn apply_size<T>(self, (width, height): (T, T)) -> (T, T) {
+        if self.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Where a point of an image of `size` lands once the image is
+    /// transformed
+    ///
+    /// Points are on the pixel grid, so `(0, 0)` and `size` are opposite
+    /// corners of the image.
+    pub fn apply_point<T>(self, (x, y): (T, T), (width, height): (T, T)) -> (T, T)
+    where
+        T: Copy + std::ops::Sub<Output = T>,
+    {
+        match self {
+            Self::Normal => (x, y),
+            Self::Rotate90 => (y, width - x),
+            Self::Rotate180 => (width - x, height - y),
+            Self::Rotate270 => (height - y, x),
+            Self::Flipped => (width - x, y),
+            Self::Flipped90 => (y, x),
+            Self::Flipped180 => (x, height - y),
+            Self::Flipped270 => (height - y, width - x),
+        }
+    }
+
+    /// Where the rectangle `(x, y, width, height)` of an image of `size`
+    /// lands once the image is transformed
+    pub fn apply_rect(
+        self,
+        (x, y, width, height): (i32, i32, i32, i32),
+        size: (i32, i32),
+    ) -> (i32, i32, i32, i32) {
+        let (x1, y1) = self.apply_point((x, y), size);
+        let (x2, y2) = self.apply_point((x + width, y + height), size);
+        (
+            x1.min(x2),
+            y1.min(y2),
+            x1.abs_diff(x2) as i32,
+            y1.abs_diff(y2) as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Transform; 8] = [
+        Transform::Normal,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    #[test]
+    fn inverse_transforms_points_back() {
+        let size = (4, 3);
+        for transform in ALL {
+            let transformed_size = transform.apply_size(size);
+            for point in [(0, 0), (1, 2), (4, 3), (3, 0)] {
+                let transformed = transform.apply_point(point, size);
+                assert!(transformed.0 <= transformed_size.0 && transformed.1 <= transformed_size.1);
+                assert_eq!(
+                    transform
+                        .invert()
+                        .apply_point(transformed, transformed_size),
+                    point,
+                    "{transform:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn composes_like_applying_one_after_the_other() {
+        let size = (4, 3);
+        for first in ALL {
+            for next in ALL {
+                let composed = first.then(next);
+                assert_eq!(
+                    composed.apply_size(size),
+                    next.apply_size(first.apply_size(size))
+                );
+                for point in [(0, 0), (1, 2), (4, 3)] {
+                    assert_eq!(
+                        composed.apply_point(point, size),
+                        next.apply_point(first.apply_point(point, size), first.apply_size(size)),
+                        "{first:?} then {next:?}"
+                    );
+                }
+            }
+            assert_eq!(first.then(first.invert()), Transform::Normal);
+            assert_eq!(Transform::from_name(first.name()), Some(first));
+        }
+    }
+
+    #[test]
+    fn rotates_counter_clockwise() {
+        // The top-right corner ends up at the top left.
+        assert_eq!(Transform::Rotate90.apply_point((4, 0), (4, 3)), (0, 0));
+        assert_eq!(
+            Transform::Rotate90.apply_rect((3, 0, 1, 1), (4, 3)),
+            (0, 0, 1, 1)
+        );
+        assert_eq!(
+            Transform::Flipped.apply_rect((0, 0, 1, 2), (4, 3)),
+            (3, 0, 1, 2)
+        );
+        assert_eq!(Transform::from_wayland(5), Some(Transform::Flipped90));
+        assert_eq!(Transform::from_wayland(8), None);
+    }
+}
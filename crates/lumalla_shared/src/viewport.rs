@@ -0,0 +1,145 @@
+/// How a surface crops and scales its buffer, as set through `wp_viewport`
+///
+/// Coordinates are those the surface would have without a viewport, that
+/// is after the buffer transform and scale.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Viewport {
+    /// The part of the buffer shown, `(x, y, width, height)`; all of it when
+    /// unset
+    pub source: Option<(f32, f32, f32, f32)>,
+    /// Size of the surface; the size of the source when unset
+    pub destination: Option<(i32, i32)>,
+}
+
+impl Viewport {
+    /// Whether the source lies within a buffer that makes the surface `size`
+    pub fn fits(&self, (width, height): (i32, i32)) -> bool {
+        self.source
+            .is_none_or(|(x, y, source_width, source_height)| {
+                x + source_width <= width as f32 && y + source_height <= height as f32
+            })
+    }
+
+    /// Size of the surface showing a buffer that makes it `size` without
+    /// the viewport
+    ///
+    /// `None` if the source has a fractional size and no destination is set.
+    pub fn surface_size(&self, size: (i32, i32)) -> Option<(i32, i32)> {
+        if let Some(destination) = self.destination {
+            return Some(destination);
+        }
+        let Some((_, _, width, height)) = self.source else {
+            return Some(size);
+        };
+        (width.fract() == 0.0 && height.fract() == 0.0).then_some((width as i32, height as i32))
+    }
+
+    /// The part shown of a buffer that makes the surface `size` without the
+    /// viewport
+    pub fn source_rect(&self, (width, height): (i32, i32)) -> (f32, f32, f32, f32) {
+        self.source
+            .unwrap_or((0.0, 0.0, width as f32, height as f32))
+    }
+
+    /// Where a rectangle of the surface shows the buffer, rounded out to
+    /// whole pixels, given the `size` the surface has without the viewport
+    pub fn source_of(
+        &self,
+        (x, y, width, height): (i32, i32, i32, i32),
+        size: (i32, i32),
+    ) -> (i32, i32, i32, i32) {
+        let (source_x, source_y, source_width, source_height) = self.source_rect(size);
+        let (surface_width, surface_height) = self.surface_size(size).unwrap_or(size);
+        let scale_x = source_width / surface_width as f32;
+        let scale_y = source_height / surface_height as f32;
+        round_out(
+            source_x + x as f32 * scale_x,
+            source_y + y as f32 * scale_y,
+            width as f32 * scale_x,
+            height as f32 * scale_y,
+        )
+    }
+
+    /// Where a rectangle of the buffer shows up on the surface, rounded out
+    /// to whole pixels, given the `size` the surface has without the
+    /// viewport
+    pub fn destination_of(
+        &self,
+        (x, y, width, height): (i32, i32, i32, i32),
+        size: (i32, i32),
+    ) -> (i32, i32, i32, i32) {
+        let (source_x, source_y, source_width, source_height) = self.source_rect(size);
+        let (surface_width, surface_height) = self.surface_size(size).unwrap_or(size);
+        let scale_x = surface_width as f32 / source_width;
+        let scale_y = surface_height as f32 / source_height;
+        round_out(
+            (x as f32 - source_x) * scale_x,
+            (y as f32 - source_y) * scale_y,
+            width as f32 * scale_x,
+            height as f32 * scale_y,
+        )
+    }
+}
+
+/// The smallest rectangle of whole pixels covering the given one
+fn round_out(x: f32, y: f32, width: f32, height: f32) -> (i32, i32, i32, i32) {
+    let left = x.floor();
+    let top = y.floor();
+    (
+        left as i32,
+        top as i32,
+        ((x + width).ceil() - left) as i32,
+        ((y + height).ceil() - top) as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_the_surface_by_destination_then_source() {
+        let mut viewport = Viewport::default();
+        assert_eq!(viewport.surface_size((300, 150)), Some((300, 150)));
+
+        viewport.source = Some((10.0, 10.0, 100.0, 50.0));
+        assert_eq!(viewport.surface_size((300, 150)), Some((100, 50)));
+        assert!(viewport.fits((300, 150)));
+        assert!(!viewport.fits((100, 50)));
+
+        viewport.source = Some((0.0, 0.0, 100.5, 50.0));
+        assert_eq!(viewport.surface_size((300, 150)), None);
+        viewport.destination = Some((200, 100));
+        assert_eq!(viewport.surface_size((300, 150)), Some((200, 100)));
+    }
+
+    #[test]
+    fn maps_rectangles_between_surface_and_source() {
+        // A 150x75 buffer shown on a 100x50 surface.
+        let viewport = Viewport {
+            source: None,
+            destination: Some((100, 50)),
+        };
+        assert_eq!(
+            viewport.source_of((10, 10, 10, 10), (150, 75)),
+            (15, 15, 15, 15)
+        );
+        assert_eq!(
+            viewport.destination_of((15, 15, 1, 1), (150, 75)),
+            (10, 10, 1, 1)
+        );
+
+        let cropped = Viewport {
+            source: Some((50.0, 25.0, 50.0, 25.0)),
+            destination: None,
+        };
+        assert_eq!(
+            cropped.source_of((0, 0, 10, 10), (150, 75)),
+            (50, 25, 10, 10)
+        );
+        assert_eq!(
+            cropped.destination_of((0, 0, 60, 30), (150, 75)),
+            (-50, -25, 60, 30)
+        );
+    }
+}
@@ -1,8 +1,45 @@
 /// Represents a window rule
-#[derive(Debug)]
+///
+/// A rule applies to toplevels matching all of its conditions; unset
+/// conditions match any window and unset effects leave the window alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct WindowRule {
     /// The app_id of the window to which this rule applies
-    pub app_id: String,
+    pub app_id: Option<String>,
+    /// A regular expression the title of the window must match
+    pub title: Option<String>,
+    /// Whether the window must or must not have a parent
+    pub transient: Option<bool>,
     /// The zone to which the window should be moved to
-    pub zone: String,
+    pub zone: Option<String>,
+    /// The output on which the window should be placed
+    pub output: Option<String>,
+    /// The workspace on which the window should be placed
+    pub workspace: Option<String>,
+    /// Whether the window floats instead of filling its zone
+    pub floating: Option<bool>,
+    /// Whether the window covers its output
+    pub fullscreen: Option<bool>,
+    /// The size the window is opened with
+    pub size: Option<(i32, i32)>,
+    /// The opacity of the window in percent
+    pub opacity: Option<u32>,
+}
+
+impl WindowRule {
+    /// Overrides the effects of this rule with those `other` sets
+    pub fn merge(&mut self, other: &WindowRule) {
+        fn merge<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                value.clone_from(other);
+            }
+        }
+        merge(&mut self.zone, &other.zone);
+        merge(&mut self.output, &other.output);
+        merge(&mut self.workspace, &other.workspace);
+        merge(&mut self.floating, &other.floating);
+        merge(&mut self.fullscreen, &other.fullscreen);
+        merge(&mut self.size, &other.size);
+        merge(&mut self.opacity, &other.opacity);
+    }
 }
@@ -1,4 +1,9 @@
-use std::{collections::VecDeque, io, mem, os::fd::RawFd, ptr};
+use std::{
+    collections::VecDeque,
+    io, mem,
+    os::fd::{OwnedFd, RawFd},
+    ptr,
+};
 
 use libc::{
     CMSG_DATA, CMSG_FIRSTHDR, CMSG_LEN, CMSG_NXTHDR, CMSG_SPACE, EAGAIN, EWOULDBLOCK, MSG_CTRUNC,
@@ -194,6 +199,8 @@ pub struct Writer {
     buffer: Box<Buffer>,
     bytes_in_buffer: usize,
     fds: Vec<RawFd>,
+    /// Forwarded descriptors that must stay open until `fds` has been sent.
+    owned_fds: Vec<OwnedFd>,
     message_start_index: usize,
     message_length_index: usize,
     last_err: Option<anyhow::Error>,
@@ -207,6 +214,7 @@ impl Writer {
             buffer: unsafe { Box::new_uninit().assume_init() },
             bytes_in_buffer: 0,
             fds: Vec::new(),
+            owned_fds: Vec::new(),
             message_start_index: 0,
             message_length_index: 0,
             last_err: None,
@@ -335,6 +343,11 @@ impl Writer {
         self.fds.push(fd);
     }
 
+    /// Keeps a descriptor written with `write_fd` open until it has been sent.
+    pub fn close_fd_after_flush(&mut self, fd: OwnedFd) {
+        self.owned_fds.push(fd);
+    }
+
     #[inline]
     pub fn flush_if_needed(&mut self) -> anyhow::Result<()> {
         if self.bytes_in_buffer >= MAX_MESSAGE_SIZE || self.fds.len() >= 100 {
@@ -401,6 +414,7 @@ impl Writer {
 
             let written = result as usize;
             self.fds.clear();
+            self.owned_fds.clear();
             self.buffer.copy_within(written..self.bytes_in_buffer, 0);
             self.bytes_in_buffer -= written;
         }
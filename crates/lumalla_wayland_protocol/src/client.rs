@@ -70,6 +70,15 @@ impl ClientConnection {
         &mut self.writer
     }
 
+    /// Request context for sending events outside of request handling.
+    pub fn ctx(&mut self) -> Ctx<'_> {
+        Ctx {
+            registry: &mut self.registry,
+            writer: &mut self.writer,
+            client_id: self.client_id,
+        }
+    }
+
     pub fn interest(&self) -> Interest {
         if self.writer.has_pending_output() {
             Interest::READABLE.add(Interest::WRITABLE)
@@ -86,6 +95,31 @@ impl ClientConnection {
         &mut self.stream
     }
 
+    /// Process id of the client, as reported by `SO_PEERCRED` when it connected.
+    pub fn peer_pid(&self) -> io::Result<libc::pid_t> {
+        let mut credentials = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `credentials` and `len` outlive the call and `len` holds the
+        // size of the buffer passed in.
+        let result = unsafe {
+            libc::getsockopt(
+                self.stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&raw mut credentials).cast(),
+                &mut len,
+            )
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(credentials.pid)
+    }
+
     pub fn handle_messages(&mut self, handler: &mut impl RequestHandler) -> anyhow::Result<()> {
         match self.reader.read() {
             ReadResult::EndOfStream => {
@@ -157,6 +191,17 @@ impl ClientConnection {
                 .version(version);
         }
     }
+
+    pub fn broadcast_global_remove(&mut self, global_id: u32) {
+        for registry_object_id in self
+            .registry
+            .iter_object_ids_of_interface(InterfaceIndex::WlRegistry)
+        {
+            self.writer
+                .wl_registry_global_remove(registry_object_id)
+                .name(global_id);
+        }
+    }
 }
 
 impl Source for ClientConnection {
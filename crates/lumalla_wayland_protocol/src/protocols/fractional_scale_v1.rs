@@ -0,0 +1,5 @@
+use lumalla_wayland_protocol_macros::wayland_protocol;
+
+use super::wayland::WL_DISPLAY_ERROR_INVALID_METHOD;
+
+wayland_protocol!("src/protocols/fractional-scale-v1.xml");
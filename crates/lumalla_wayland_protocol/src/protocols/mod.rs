@@ -1,3 +1,13 @@
+pub mod fractional_scale_v1;
+pub mod linux_dmabuf_v1;
+pub mod presentation_time;
+pub mod viewporter;
 pub mod wayland;
+pub mod xdg_shell;
 
+pub use fractional_scale_v1::FractionalScaleV1Protocol;
+pub use linux_dmabuf_v1::LinuxDmabufV1Protocol;
+pub use presentation_time::PresentationTimeProtocol;
+pub use viewporter::ViewporterProtocol;
 pub use wayland::{WaylandProtocol, WlDisplay};
+pub use xdg_shell::XdgShellProtocol;
@@ -8,7 +8,11 @@ use crate::{
     NewObjectId, ObjectId,
     buffer::{MessageHeader, Writer},
     client::Ctx,
-    protocols::{WaylandProtocol, WlDisplay, wayland::*},
+    protocols::{
+        FractionalScaleV1Protocol, LinuxDmabufV1Protocol, PresentationTimeProtocol,
+        ViewporterProtocol, WaylandProtocol, WlDisplay, XdgShellProtocol, fractional_scale_v1::*,
+        linux_dmabuf_v1::*, presentation_time::*, viewporter::*, wayland::*, xdg_shell::*,
+    },
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -36,6 +40,20 @@ pub enum InterfaceIndex {
     WlSubcompositor,
     WlSubsurface,
     WlFixes,
+    XdgWmBase,
+    XdgPositioner,
+    XdgSurface,
+    XdgToplevel,
+    XdgPopup,
+    WpPresentation,
+    WpPresentationFeedback,
+    ZwpLinuxDmabufV1,
+    ZwpLinuxBufferParamsV1,
+    ZwpLinuxDmabufFeedbackV1,
+    WpViewporter,
+    WpViewport,
+    WpFractionalScaleManagerV1,
+    WpFractionalScaleV1,
 }
 
 impl InterfaceIndex {
@@ -64,6 +82,20 @@ impl InterfaceIndex {
             InterfaceIndex::WlSubcompositor => WL_SUBCOMPOSITOR_NAME,
             InterfaceIndex::WlSubsurface => WL_SUBSURFACE_NAME,
             InterfaceIndex::WlFixes => WL_FIXES_NAME,
+            InterfaceIndex::XdgWmBase => XDG_WM_BASE_NAME,
+            InterfaceIndex::XdgPositioner => XDG_POSITIONER_NAME,
+            InterfaceIndex::XdgSurface => XDG_SURFACE_NAME,
+            InterfaceIndex::XdgToplevel => XDG_TOPLEVEL_NAME,
+            InterfaceIndex::XdgPopup => XDG_POPUP_NAME,
+            InterfaceIndex::WpPresentation => WP_PRESENTATION_NAME,
+            InterfaceIndex::WpPresentationFeedback => WP_PRESENTATION_FEEDBACK_NAME,
+            InterfaceIndex::ZwpLinuxDmabufV1 => ZWP_LINUX_DMABUF_V1_NAME,
+            InterfaceIndex::ZwpLinuxBufferParamsV1 => ZWP_LINUX_BUFFER_PARAMS_V1_NAME,
+            InterfaceIndex::ZwpLinuxDmabufFeedbackV1 => ZWP_LINUX_DMABUF_FEEDBACK_V1_NAME,
+            InterfaceIndex::WpViewporter => WP_VIEWPORTER_NAME,
+            InterfaceIndex::WpViewport => WP_VIEWPORT_NAME,
+            InterfaceIndex::WpFractionalScaleManagerV1 => WP_FRACTIONAL_SCALE_MANAGER_V1_NAME,
+            InterfaceIndex::WpFractionalScaleV1 => WP_FRACTIONAL_SCALE_V1_NAME,
         }
     }
 
@@ -92,6 +124,20 @@ impl InterfaceIndex {
             InterfaceIndex::WlSubcompositor => WL_SUBCOMPOSITOR_VERSION,
             InterfaceIndex::WlSubsurface => WL_SUBSURFACE_VERSION,
             InterfaceIndex::WlFixes => WL_FIXES_VERSION,
+            InterfaceIndex::XdgWmBase => XDG_WM_BASE_VERSION,
+            InterfaceIndex::XdgPositioner => XDG_POSITIONER_VERSION,
+            InterfaceIndex::XdgSurface => XDG_SURFACE_VERSION,
+            InterfaceIndex::XdgToplevel => XDG_TOPLEVEL_VERSION,
+            InterfaceIndex::XdgPopup => XDG_POPUP_VERSION,
+            InterfaceIndex::WpPresentation => WP_PRESENTATION_VERSION,
+            InterfaceIndex::WpPresentationFeedback => WP_PRESENTATION_FEEDBACK_VERSION,
+            InterfaceIndex::ZwpLinuxDmabufV1 => ZWP_LINUX_DMABUF_V1_VERSION,
+            InterfaceIndex::ZwpLinuxBufferParamsV1 => ZWP_LINUX_BUFFER_PARAMS_V1_VERSION,
+            InterfaceIndex::ZwpLinuxDmabufFeedbackV1 => ZWP_LINUX_DMABUF_FEEDBACK_V1_VERSION,
+            InterfaceIndex::WpViewporter => WP_VIEWPORTER_VERSION,
+            InterfaceIndex::WpViewport => WP_VIEWPORT_VERSION,
+            InterfaceIndex::WpFractionalScaleManagerV1 => WP_FRACTIONAL_SCALE_MANAGER_V1_VERSION,
+            InterfaceIndex::WpFractionalScaleV1 => WP_FRACTIONAL_SCALE_V1_VERSION,
         }
     }
 }
@@ -256,7 +302,12 @@ pub trait RequestHandler {
 
 impl<T> RequestHandler for T
 where
-    T: WaylandProtocol,
+    T: WaylandProtocol
+        + XdgShellProtocol
+        + PresentationTimeProtocol
+        + LinuxDmabufV1Protocol
+        + ViewporterProtocol
+        + FractionalScaleV1Protocol,
 {
     fn handle_request(
         &mut self,
@@ -337,6 +388,61 @@ where
             InterfaceIndex::WlFixes => {
                 WlFixes::handle_request(self, ctx, header, data, fds, object.version)
             }
+            InterfaceIndex::XdgWmBase => {
+                XdgWmBase::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::XdgPositioner => {
+                XdgPositioner::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::XdgSurface => {
+                XdgSurface::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::XdgToplevel => {
+                XdgToplevel::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::XdgPopup => {
+                XdgPopup::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::WpPresentation => {
+                WpPresentation::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::WpPresentationFeedback => {
+                write_invalid_method_error(ctx, header.object_id);
+                anyhow::bail!("Invalid method");
+            }
+            InterfaceIndex::ZwpLinuxDmabufV1 => {
+                ZwpLinuxDmabufV1::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::ZwpLinuxBufferParamsV1 => {
+                ZwpLinuxBufferParamsV1::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::ZwpLinuxDmabufFeedbackV1 => ZwpLinuxDmabufFeedbackV1::handle_request(
+                self,
+                ctx,
+                header,
+                data,
+                fds,
+                object.version,
+            ),
+            InterfaceIndex::WpViewporter => {
+                WpViewporter::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::WpViewport => {
+                WpViewport::handle_request(self, ctx, header, data, fds, object.version)
+            }
+            InterfaceIndex::WpFractionalScaleManagerV1 => {
+                WpFractionalScaleManagerV1::handle_request(
+                    self,
+                    ctx,
+                    header,
+                    data,
+                    fds,
+                    object.version,
+                )
+            }
+            InterfaceIndex::WpFractionalScaleV1 => {
+                WpFractionalScaleV1::handle_request(self, ctx, header, data, fds, object.version)
+            }
         }
     }
 }
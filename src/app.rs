@@ -12,22 +12,29 @@ use anyhow::Context;
 use log::{debug, error, info, warn};
 use lumalla_dbus::{DbusService, run_thread as run_dbus_thread};
 use lumalla_display::{
-    ClientConnection, ClientId, DisplayState, KeyboardModifiers, SurfaceUpdate, Wayland,
-    create_wayland_display,
+    AxisSource, AxisValue, ClientConnection, ClientId, CommittedFrame, CursorImage, DisplayState,
+    KeyboardModifiers, PointerAxis, Rectangle, SurfaceUpdate, Wayland, create_wayland_display,
+};
+use lumalla_input::{
+    InputEvent, InputState, KeyboardEvent, PointerEvent, ScrollSource, ScrollValue, TouchEvent,
+};
+use lumalla_renderer::{
+    Cursor, DmabufFrame, Rect, RendererState, SOLID_CLEAR_COLOR, SurfaceFrame, SurfaceLayer,
 };
-use lumalla_input::{InputState, KeyboardEvent};
-use lumalla_renderer::{RendererState, SOLID_CLEAR_COLOR, SurfaceFrame};
 use lumalla_seat::SeatState;
 use lumalla_shared::{
-    Comms, DbusMessage, GlobalArgs, MESSAGE_CHANNEL_TOKEN, MainMessage, MessageSender,
-    message_loop_with_channel,
+    Comms, DbusMessage, GlobalArgs, LayoutSpaces, MESSAGE_CHANNEL_TOKEN, MainMessage,
+    MessageSender, Output, activate_layout_space, message_loop_with_channel,
 };
 use mio::{Events, Interest, Poll, Token};
 
+use crate::closing::ClosingWindows;
+
 pub const LIBSEAT_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 1);
 pub const LIBINPUT_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 2);
 pub const UDEV_DRM_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 3);
-pub const WAYLAND_SOCKET_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 4);
+pub const DRM_PAGE_FLIP_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 4);
+pub const WAYLAND_SOCKET_TOKEN: Token = Token(MESSAGE_CHANNEL_TOKEN.0 + 5);
 
 /// Represents the data for the main app thread
 struct AppData {
@@ -43,6 +50,16 @@ struct AppData {
     connected_clients: HashMap<ClientId, ClientConnection>,
     display_state: DisplayState,
     renderer_state: RendererState,
+    /// Layout spaces set by the config, see [`activate_layout_space`].
+    layout_spaces: LayoutSpaces,
+    /// The layout space the outputs are placed with, if any.
+    active_layout_space: Option<String>,
+    /// Outputs last published over D-Bus.
+    outputs: Vec<Output>,
+    /// How long windows asked to close may take before their client is
+    /// killed, if at all.
+    kill_timeout: Option<Duration>,
+    closing_windows: ClosingWindows,
 }
 
 impl AppData {
@@ -68,6 +85,11 @@ impl AppData {
             connected_clients: HashMap::new(),
             display_state,
             renderer_state,
+            layout_spaces: LayoutSpaces::new(),
+            active_layout_space: None,
+            outputs: Vec::new(),
+            kill_timeout: None,
+            closing_windows: ClosingWindows::default(),
         }
     }
 
@@ -78,10 +100,14 @@ impl AppData {
     ) -> anyhow::Result<()> {
         let mut events = Events::with_capacity(1024);
         loop {
-            let (shutdown_now, event_loop_timeout) = self.check_for_shutdown();
+            let (shutdown_now, shutdown_timeout) = self.check_for_shutdown();
             if shutdown_now {
                 break;
             }
+            let event_loop_timeout = match (shutdown_timeout, self.kill_unresponsive_clients()) {
+                (Some(shutdown), Some(kill)) => Some(shutdown.min(kill)),
+                (shutdown, kill) => shutdown.or(kill),
+            };
             if let Err(err) = event_loop.poll(&mut events, event_loop_timeout) {
                 warn!("Unable to poll event loop: {err}");
             }
@@ -123,11 +149,11 @@ impl AppData {
                         ..
                     } = self;
                     if let Err(err) = input_state.dispatch(|event| match event {
-                        KeyboardEvent::Key {
+                        InputEvent::Keyboard(KeyboardEvent::Key {
                             time_msec,
                             key,
                             pressed,
-                        } => {
+                        }) => {
                             display_state.handle_keyboard_key(
                                 connected_clients,
                                 time_msec,
@@ -135,7 +161,7 @@ impl AppData {
                                 pressed,
                             );
                         }
-                        KeyboardEvent::Modifiers(modifiers) => {
+                        InputEvent::Keyboard(KeyboardEvent::Modifiers(modifiers)) => {
                             display_state.handle_keyboard_modifiers(
                                 connected_clients,
                                 KeyboardModifiers {
@@ -146,9 +172,17 @@ impl AppData {
                                 },
                             );
                         }
+                        InputEvent::Pointer(event) => {
+                            handle_pointer_event(display_state, connected_clients, event);
+                        }
+                        InputEvent::Touch(event) => {
+                            handle_touch_event(display_state, connected_clients, event);
+                        }
                     }) {
                         error!("Unable to dispatch libinput events: {err}");
                     }
+                    self.submit_committed_frames();
+                    self.update_cursor();
                 }
                 UDEV_DRM_TOKEN => match self.renderer_state.dispatch() {
                     Ok(result) if result.changed() => {
@@ -173,6 +207,7 @@ impl AppData {
                             {
                                 error!("Unable to present outputs after DRM change: {err:#}");
                             }
+                            self.update_output_layout();
                         }
                         self.comms.dbus(DbusMessage::EmitDrmDevicesChanged(
                             self.renderer_state.drm_device_states(),
@@ -183,6 +218,9 @@ impl AppData {
                         error!("Unable to dispatch DRM udev events: {err}");
                     }
                 },
+                DRM_PAGE_FLIP_TOKEN => {
+                    self.dispatch_page_flips();
+                }
                 WAYLAND_SOCKET_TOKEN => {
                     self.connect_client(event_loop);
                 }
@@ -230,6 +268,7 @@ impl AppData {
                         {
                             error!("Unable to present enabled outputs: {err:#}");
                         }
+                        self.update_output_layout();
                         self.comms.dbus(DbusMessage::EmitReady);
                     }
                 }
@@ -275,10 +314,120 @@ impl AppData {
                     if let Err(err) = self.renderer_state.set_output_configs(configs) {
                         error!("Unable to set output configs: {err:#}");
                     }
+                    self.update_output_layout();
                     self.comms.dbus(DbusMessage::EmitDrmDevicesChanged(
                         self.renderer_state.drm_device_states(),
                     ));
                 }
+                MainMessage::SetLayout(spaces) => {
+                    self.layout_spaces = spaces;
+                    self.update_output_layout();
+                }
+                MainMessage::SetZones(zones) => {
+                    self.display_state
+                        .set_zones(zones, &mut self.connected_clients);
+                    self.submit_committed_frames();
+                }
+                MainMessage::SetWindowRules(rules) => {
+                    self.display_state.set_window_rules(rules);
+                }
+                MainMessage::MoveCurrentWindowToZone(zone) => {
+                    if self
+                        .display_state
+                        .move_current_window_to_zone(&zone, &mut self.connected_clients)
+                    {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                    } else {
+                        warn!("No window to move to zone {zone}");
+                    }
+                }
+                MainMessage::SwitchWorkspace(workspace) => {
+                    if self
+                        .display_state
+                        .switch_workspace(&workspace, &mut self.connected_clients)
+                    {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                        self.publish_workspaces();
+                    } else {
+                        warn!("No output to show workspace {workspace} on");
+                    }
+                }
+                MainMessage::MoveCurrentWindowToWorkspace(workspace) => {
+                    if self
+                        .display_state
+                        .move_current_window_to_workspace(&workspace, &mut self.connected_clients)
+                    {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                        self.publish_workspaces();
+                    } else {
+                        warn!("No window to move to workspace {workspace}");
+                    }
+                }
+                MainMessage::MoveWorkspaceToOutput { workspace, output } => {
+                    if self.display_state.move_workspace_to_output(
+                        &workspace,
+                        &output,
+                        &mut self.connected_clients,
+                    ) {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                        self.publish_workspaces();
+                    } else {
+                        warn!("Unable to move workspace {workspace} to output {output}");
+                    }
+                }
+                MainMessage::FocusOrSpawn {
+                    app_id,
+                    command,
+                    args,
+                } => {
+                    if self
+                        .display_state
+                        .focus_app(&app_id, &mut self.connected_clients)
+                    {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                        self.publish_workspaces();
+                    } else if self.display_state.launch_app(&app_id) {
+                        self.comms.dbus(DbusMessage::Spawn { command, args });
+                    } else {
+                        info!("Not starting {app_id} again, it is still starting");
+                    }
+                }
+                MainMessage::CloseCurrentWindow => {
+                    let Some((client_id, root_id)) = self
+                        .display_state
+                        .close_focused_window(&mut self.connected_clients)
+                    else {
+                        warn!("No window to close");
+                        continue;
+                    };
+                    if let Some(timeout) = self.kill_timeout {
+                        self.closing_windows
+                            .push(client_id, root_id, Instant::now(), timeout);
+                    }
+                }
+                MainMessage::SetKillTimeout(timeout) => {
+                    self.kill_timeout = timeout;
+                }
+                MainMessage::SetFocusMode(mode) => {
+                    self.display_state.set_focus_mode(mode);
+                }
+                MainMessage::Tab(index) => {
+                    if self.display_state.tab(index, &mut self.connected_clients) {
+                        self.submit_committed_frames();
+                        self.display_state
+                            .refresh_pointer_focus(&mut self.connected_clients);
+                    }
+                }
                 MainMessage::Shutdown => {
                     if !self.shutting_down {
                         self.init_shutdown();
@@ -289,6 +438,8 @@ impl AppData {
     }
 
     fn flush_clients(&mut self, event_loop: &mut Poll) {
+        self.display_state
+            .dispatch_data_device_events(&mut self.connected_clients);
         let mut clients_to_remove = Vec::new();
         for (&client_id, client) in self.connected_clients.iter_mut() {
             if let Err(err) = client.flush() {
@@ -310,6 +461,9 @@ impl AppData {
             self.display_state.remove_client(client_id);
             self.renderer_state.remove_client_frames(client_id.get());
             self.connected_clients.remove(&client_id);
+            self.display_state
+                .refresh_keyboard_focus(&mut self.connected_clients);
+            self.update_cursor();
         }
     }
 
@@ -339,41 +493,236 @@ impl AppData {
                 self.connected_clients.remove(&client_id);
             } else {
                 self.submit_committed_frames();
+                self.display_state
+                    .refresh_pointer_focus(&mut self.connected_clients);
             }
+            self.display_state
+                .refresh_keyboard_focus(&mut self.connected_clients);
+            self.update_cursor();
         } else {
             debug!("Received message for unknown client {:?}", client_id);
         }
         Ok(())
     }
 
+    /// Tells the D-Bus service which workspace every output shows.
+    fn publish_workspaces(&mut self) {
+        self.comms.dbus(DbusMessage::SetActiveWorkspaces(
+            self.display_state.shown_workspaces(),
+        ));
+    }
+
+    /// Places the outputs with the layout space of the connected
+    /// connectors, and publishes them to Wayland and D-Bus clients.
+    ///
+    /// Runs whenever connectors or the layout spaces change, so plugging in
+    /// monitors switches spaces by itself.
+    fn update_output_layout(&mut self) {
+        let drm_devices = self.renderer_state.drm_device_states();
+        let connected: Vec<&str> = drm_devices
+            .iter()
+            .flat_map(|device| &device.connectors)
+            .filter(|connector| connector.connected)
+            .map(|connector| connector.name.as_str())
+            .collect();
+        let activation = activate_layout_space(
+            &self.layout_spaces,
+            &connected,
+            self.active_layout_space.as_deref(),
+        );
+        if let Err(err) = self
+            .renderer_state
+            .set_output_locations(activation.locations)
+        {
+            error!("Unable to place outputs: {err:#}");
+        }
+        if activation.changed {
+            match &activation.space {
+                Some(name) => {
+                    info!("Activated layout space {name}");
+                    self.comms
+                        .dbus(DbusMessage::EmitLayoutSpaceActivated(name.clone()));
+                }
+                None => info!("No layout space matches the connected outputs"),
+            }
+            self.active_layout_space = activation.space;
+        }
+
+        let outputs = self.renderer_state.outputs();
+        self.display_state
+            .set_outputs(&outputs, &drm_devices, &mut self.connected_clients);
+        self.publish_workspaces();
+        self.display_state.set_dmabuf_formats(
+            self.renderer_state.dmabuf_formats(),
+            &mut self.connected_clients,
+        );
+        if outputs != self.outputs {
+            self.outputs = outputs.clone();
+            self.comms.dbus(DbusMessage::EmitOutputChanged(outputs));
+        }
+    }
+
+    /// Reports the windows shown by completed page flips to their clients.
+    fn dispatch_page_flips(&mut self) {
+        let frames = match self.renderer_state.dispatch_page_flips() {
+            Ok(frames) => frames,
+            Err(err) => {
+                error!("Unable to dispatch DRM page flips: {err:#}");
+                return;
+            }
+        };
+        for frame in &frames {
+            self.display_state
+                .frame_presented(&mut self.connected_clients, frame);
+        }
+    }
+
     fn submit_committed_frames(&mut self) {
         let updates: Vec<_> = self.display_state.take_surface_updates().collect();
         for update in updates {
             match update {
                 SurfaceUpdate::Frame(frame) => {
-                    let frame = SurfaceFrame {
-                        owner_id: frame.client_id.get(),
-                        surface_id: frame.surface_id.get(),
-                        pixels: frame.pixels,
-                        width: frame.width,
-                        height: frame.height,
-                        stride: frame.stride,
-                        format: frame.format,
-                    };
-                    if let Err(err) = self.renderer_state.set_surface_frame(frame) {
+                    if let Err(err) = self.renderer_state.set_surface_frame(surface_frame(frame)) {
                         error!("Unable to queue committed Wayland surface: {err:#}");
                     }
                 }
+                SurfaceUpdate::CursorFrame(frame) => {
+                    if let Err(err) = self.renderer_state.set_cursor_frame(surface_frame(frame)) {
+                        error!("Unable to update Wayland cursor surface: {err:#}");
+                    }
+                }
+                SurfaceUpdate::Dmabuf(dmabuf) => {
+                    let frame = DmabufFrame {
+                        owner_id: dmabuf.client_id.get(),
+                        surface_id: dmabuf.surface_id.get(),
+                        root_id: dmabuf.root_id.get(),
+                        buffer_id: dmabuf.buffer_id.get(),
+                        attributes: dmabuf.attributes,
+                        damage: renderer_damage(&dmabuf.damage),
+                        scale: dmabuf.scale as u32,
+                        transform: dmabuf.transform,
+                        viewport: dmabuf.viewport,
+                    };
+                    if let Err(err) = self.renderer_state.set_surface_dmabuf(frame) {
+                        error!("Unable to queue committed Wayland dmabuf: {err:#}");
+                    }
+                }
+                SurfaceUpdate::BufferDestroyed {
+                    client_id,
+                    buffer_id,
+                } => self
+                    .renderer_state
+                    .remove_dmabuf_buffer(client_id.get(), buffer_id.get()),
                 SurfaceUpdate::Unmapped {
                     client_id,
                     surface_id,
                 } => self
                     .renderer_state
                     .remove_surface_frame(client_id.get(), surface_id.get()),
+                SurfaceUpdate::Moved {
+                    client_id,
+                    root_id,
+                    x,
+                    y,
+                } => {
+                    if let Err(err) = self.renderer_state.set_surface_position(
+                        client_id.get(),
+                        root_id.get(),
+                        x,
+                        y,
+                    ) {
+                        error!("Unable to move Wayland surface: {err:#}");
+                    }
+                }
+                SurfaceUpdate::Opacity {
+                    client_id,
+                    root_id,
+                    opacity,
+                } => {
+                    if let Err(err) = self.renderer_state.set_surface_opacity(
+                        client_id.get(),
+                        root_id.get(),
+                        opacity,
+                    ) {
+                        error!("Unable to change Wayland surface opacity: {err:#}");
+                    }
+                }
+                SurfaceUpdate::Raised { client_id, root_id } => {
+                    if let Err(err) = self
+                        .renderer_state
+                        .raise_surface(client_id.get(), root_id.get())
+                    {
+                        error!("Unable to raise Wayland surface: {err:#}");
+                    }
+                }
+                SurfaceUpdate::Visibility {
+                    client_id,
+                    root_id,
+                    visible,
+                } => {
+                    if let Err(err) = self.renderer_state.set_surface_visible(
+                        client_id.get(),
+                        root_id.get(),
+                        visible,
+                    ) {
+                        error!("Unable to change Wayland surface visibility: {err:#}");
+                    }
+                }
+                SurfaceUpdate::FrameRequested { client_id, root_id } => {
+                    if let Err(err) = self
+                        .renderer_state
+                        .request_frame(client_id.get(), root_id.get())
+                    {
+                        error!("Unable to draw requested Wayland frame: {err:#}");
+                    }
+                }
+                SurfaceUpdate::Tree {
+                    client_id,
+                    root_id,
+                    layers,
+                } => {
+                    let layers = layers
+                        .into_iter()
+                        .map(|layer| SurfaceLayer {
+                            surface_id: layer.surface_id.get(),
+                            x: layer.x,
+                            y: layer.y,
+                        })
+                        .collect();
+                    if let Err(err) =
+                        self.renderer_state
+                            .set_surface_tree(client_id.get(), root_id.get(), layers)
+                    {
+                        error!("Unable to update Wayland surface tree: {err:#}");
+                    }
+                }
             }
         }
     }
 
+    /// Shows the cursor image of the pointer focus at the pointer position.
+    fn update_cursor(&mut self) {
+        let cursor = match self.display_state.cursor() {
+            None => Cursor::Default,
+            Some(CursorImage {
+                surface_id: None, ..
+            }) => Cursor::Hidden,
+            Some(CursorImage {
+                client_id,
+                surface_id: Some(surface_id),
+                hotspot,
+            }) => Cursor::Surface {
+                owner_id: client_id.get(),
+                surface_id: surface_id.get(),
+                hotspot,
+            },
+        };
+        self.renderer_state.set_cursor(cursor);
+        let (x, y) = self.display_state.cursor_position();
+        self.renderer_state
+            .move_cursor(x.floor() as i32, y.floor() as i32);
+    }
+
     fn connect_client(&mut self, event_loop: &mut Poll) {
         if let Some(mut client) = self.wayland.next_client() {
             let client_id = client.client_id();
@@ -405,6 +754,32 @@ impl AppData {
         self.shutdown_timeout = Some(Instant::now() + Duration::from_millis(1000));
     }
 
+    /// Terminates the clients of windows that did not close within the
+    /// kill timeout, kills those that are still connected one timeout
+    /// later, and returns how long until the next one is due.
+    fn kill_unresponsive_clients(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        let display_state = &self.display_state;
+        let expired = self.closing_windows.expire(now, |client_id, root_id| {
+            display_state.is_window_mapped(client_id, root_id)
+        });
+        for client_id in expired {
+            if let Some(client) = self.connected_clients.get(&client_id) {
+                signal_client(client, libc::SIGTERM);
+            }
+        }
+        let connected_clients = &self.connected_clients;
+        let expired = self
+            .closing_windows
+            .expire_terminated(now, |client_id| connected_clients.contains_key(&client_id));
+        for client_id in expired {
+            if let Some(client) = self.connected_clients.get(&client_id) {
+                signal_client(client, libc::SIGKILL);
+            }
+        }
+        self.closing_windows.next_timeout(now)
+    }
+
     /// Returns whether the app should shut down now and the time until
     /// the next shutdown check should be performed.
     fn check_for_shutdown(&mut self) -> (bool, Option<Duration>) {
@@ -436,6 +811,26 @@ impl AppData {
     }
 }
 
+/// Sends `signal` to the process of a client that did not close its window
+/// in time.
+fn signal_client(client: &ClientConnection, signal: libc::c_int) {
+    let client_id = client.client_id();
+    match client.peer_pid() {
+        Ok(pid) if pid > 0 && pid.cast_unsigned() != std::process::id() => {
+            warn!("Sending signal {signal} to unresponsive client {client_id:?} (pid {pid})");
+            // SAFETY: kill only sends a signal and does not touch our memory.
+            if unsafe { libc::kill(pid, signal) } == -1 {
+                error!(
+                    "Unable to signal client {client_id:?}: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+        Ok(pid) => warn!("Not signalling client {client_id:?} with pid {pid}"),
+        Err(err) => error!("Unable to look up the process of client {client_id:?}: {err}"),
+    }
+}
+
 pub(crate) fn run_app(
     args: &'static GlobalArgs,
     mut main_event_loop: Poll,
@@ -469,6 +864,7 @@ pub(crate) fn run_app(
     comms.dbus(DbusMessage::SetDrmDevices(
         renderer_state.drm_device_states(),
     ));
+    comms.dbus(DbusMessage::SetOutputs(renderer_state.outputs()));
     let mut data = AppData::new(
         comms.clone(),
         config_child,
@@ -488,6 +884,9 @@ fn init_and_register_renderer_state(main_event_loop: &mut Poll) -> anyhow::Resul
         .registry()
         .register(&mut renderer_state, UDEV_DRM_TOKEN, Interest::READABLE)
         .context("Unable to listen on DRM udev monitor")?;
+    renderer_state
+        .register_page_flips(main_event_loop.registry(), DRM_PAGE_FLIP_TOKEN)
+        .context("Unable to listen for DRM page flips")?;
     Ok(renderer_state)
 }
 
@@ -542,3 +941,97 @@ fn start_dbus_service(
     run_dbus_thread(comms, dbus_event_loop, dbus_channel, dbus_service)
         .context("Unable to run D-Bus thread")
 }
+
+fn surface_frame(frame: CommittedFrame) -> SurfaceFrame {
+    SurfaceFrame {
+        owner_id: frame.client_id.get(),
+        surface_id: frame.surface_id.get(),
+        root_id: frame.root_id.get(),
+        pixels: frame.pixels,
+        width: frame.width,
+        height: frame.height,
+        stride: frame.stride,
+        format: frame.format,
+        damage: renderer_damage(&frame.damage),
+        scale: frame.scale as u32,
+        transform: frame.transform,
+        viewport: frame.viewport,
+    }
+}
+
+fn renderer_damage(damage: &[Rectangle]) -> Vec<Rect> {
+    damage
+        .iter()
+        .map(|rect| Rect {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        })
+        .collect()
+}
+
+fn handle_pointer_event(
+    display_state: &mut DisplayState,
+    clients: &mut HashMap<ClientId, ClientConnection>,
+    event: PointerEvent,
+) {
+    match event {
+        PointerEvent::Motion { time_msec, dx, dy } => {
+            display_state.handle_pointer_motion(clients, time_msec, dx, dy);
+        }
+        PointerEvent::MotionAbsolute { time_msec, x, y } => {
+            display_state.handle_pointer_motion_absolute(clients, time_msec, x, y);
+        }
+        PointerEvent::Button {
+            time_msec,
+            button,
+            pressed,
+        } => display_state.handle_pointer_button(clients, time_msec, button, pressed),
+        PointerEvent::Axis {
+            time_msec,
+            source,
+            horizontal,
+            vertical,
+        } => {
+            let axis_value = |value: ScrollValue| AxisValue {
+                value: value.value,
+                value120: value.value120.map(|value120| value120.round() as i32),
+            };
+            let axis = PointerAxis {
+                source: match source {
+                    ScrollSource::Wheel => AxisSource::Wheel,
+                    ScrollSource::Finger => AxisSource::Finger,
+                    ScrollSource::Continuous => AxisSource::Continuous,
+                },
+                horizontal: horizontal.map(axis_value),
+                vertical: vertical.map(axis_value),
+            };
+            display_state.handle_pointer_axis(clients, time_msec, axis);
+        }
+    }
+}
+
+fn handle_touch_event(
+    display_state: &mut DisplayState,
+    clients: &mut HashMap<ClientId, ClientConnection>,
+    event: TouchEvent,
+) {
+    match event {
+        TouchEvent::Down {
+            time_msec,
+            id,
+            x,
+            y,
+        } => display_state.handle_touch_down(clients, time_msec, id, x, y),
+        TouchEvent::Motion {
+            time_msec,
+            id,
+            x,
+            y,
+        } => display_state.handle_touch_motion(clients, time_msec, id, x, y),
+        TouchEvent::Up { time_msec, id } => display_state.handle_touch_up(clients, time_msec, id),
+        TouchEvent::Cancel { id } => display_state.handle_touch_cancel(clients, id),
+        TouchEvent::Frame => display_state.handle_touch_frame(clients),
+    }
+}
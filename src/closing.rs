@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use lumalla_display::{ClientId, ObjectId};
+
+/// Toplevels asked to close, and the clients that did not close them in
+/// time.
+///
+/// A client still mapping its toplevel past the kill timeout is sent
+/// `SIGTERM`, and `SIGKILL` if it is still connected one timeout later.
+#[derive(Debug, Default)]
+pub(crate) struct ClosingWindows {
+    /// Windows with their kill timeout and the time it runs out.
+    windows: Vec<(ClientId, ObjectId, Duration, Instant)>,
+    /// Clients sent `SIGTERM`, with the time they get `SIGKILL`.
+    terminated: Vec<(ClientId, Instant)>,
+}
+
+impl ClosingWindows {
+    pub(crate) fn push(
+        &mut self,
+        client_id: ClientId,
+        surface_id: ObjectId,
+        now: Instant,
+        timeout: Duration,
+    ) {
+        self.windows
+            .push((client_id, surface_id, timeout, now + timeout));
+    }
+
+    /// Forgets the windows that are no longer `mapped`, and returns the
+    /// clients of those still mapped past their deadline at `now`, which
+    /// are due for `SIGTERM`.
+    pub(crate) fn expire(
+        &mut self,
+        now: Instant,
+        mapped: impl Fn(ClientId, ObjectId) -> bool,
+    ) -> Vec<ClientId> {
+        let mut expired = Vec::new();
+        let terminated = &mut self.terminated;
+        self.windows
+            .retain(|&(client_id, surface_id, timeout, deadline)| {
+                if !mapped(client_id, surface_id) {
+                    return false;
+                }
+                if now < deadline {
+                    return true;
+                }
+                if !terminated.iter().any(|&(id, _)| id == client_id) {
+                    terminated.push((client_id, now + timeout));
+                    expired.push(client_id);
+                }
+                false
+            });
+        expired
+    }
+
+    /// Forgets the terminated clients that are no longer `connected`, and
+    /// returns those still connected past their deadline at `now`, which
+    /// are due for `SIGKILL`.
+    pub(crate) fn expire_terminated(
+        &mut self,
+        now: Instant,
+        connected: impl Fn(ClientId) -> bool,
+    ) -> Vec<ClientId> {
+        let mut expired = Vec::new();
+        self.terminated.retain(|&(client_id, deadline)| {
+            if !connected(client_id) {
+                return false;
+            }
+            if now < deadline {
+                return true;
+            }
+            expired.push(client_id);
+            false
+        });
+        expired
+    }
+
+    /// How long after `now` the next deadline is due.
+    pub(crate) fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        let windows = self.windows.iter().map(|&(_, _, _, deadline)| deadline);
+        let terminated = self.terminated.iter().map(|&(_, deadline)| deadline);
+        windows
+            .chain(terminated)
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    fn window(client: u32, surface: u32) -> (ClientId, ObjectId) {
+        (
+            ClientId::new(NonZeroU32::new(client).unwrap()),
+            ObjectId::new(NonZeroU32::new(surface).unwrap()),
+        )
+    }
+
+    #[test]
+    fn terminates_clients_whose_windows_stay_mapped_past_the_deadline() {
+        let mut closing = ClosingWindows::default();
+        let start = Instant::now();
+        let (stuck, stuck_surface) = window(1, 2);
+        let (closed, closed_surface) = window(2, 2);
+        closing.push(stuck, stuck_surface, start, Duration::from_secs(2));
+        closing.push(closed, closed_surface, start, Duration::from_secs(1));
+        assert_eq!(closing.next_timeout(start), Some(Duration::from_secs(1)));
+
+        let mapped = |client_id, _| client_id == stuck;
+        assert!(closing.expire(start, mapped).is_empty());
+        assert_eq!(closing.next_timeout(start), Some(Duration::from_secs(2)));
+
+        let later = start + Duration::from_secs(1);
+        assert!(closing.expire(later, mapped).is_empty());
+        assert_eq!(closing.next_timeout(later), Some(Duration::from_secs(1)));
+
+        let expired_at = start + Duration::from_secs(3);
+        assert_eq!(closing.expire(expired_at, mapped), [stuck]);
+        assert!(closing.expire(expired_at, mapped).is_empty());
+    }
+
+    #[test]
+    fn kills_terminated_clients_only_while_they_stay_connected() {
+        let mut closing = ClosingWindows::default();
+        let start = Instant::now();
+        let (stuck, stuck_surface) = window(1, 2);
+        let (exiting, exiting_surface) = window(2, 2);
+        closing.push(stuck, stuck_surface, start, Duration::from_secs(1));
+        closing.push(exiting, exiting_surface, start, Duration::from_secs(1));
+        let terminated_at = start + Duration::from_secs(1);
+        assert_eq!(closing.expire(terminated_at, |_, _| true), [stuck, exiting]);
+        assert_eq!(
+            closing.next_timeout(terminated_at),
+            Some(Duration::from_secs(1))
+        );
+
+        let connected = |client_id| client_id == stuck;
+        let killed = closing.expire_terminated(terminated_at, connected);
+        assert!(killed.is_empty());
+        let killed_at = terminated_at + Duration::from_secs(1);
+        assert_eq!(closing.expire_terminated(killed_at, connected), [stuck]);
+        assert_eq!(closing.next_timeout(killed_at), None);
+    }
+}
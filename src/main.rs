@@ -13,6 +13,7 @@ use lumalla_shared::{GlobalArgs, MainMessage, message_loop_with_channel};
 use crate::{app::run_app, os_signal::handle_signals};
 
 mod app;
+mod closing;
 mod os_signal;
 
 fn main() -> anyhow::Result<()> {